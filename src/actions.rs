@@ -0,0 +1,676 @@
+use std::collections::HashSet;
+use std::ffi::CString;
+use std::fs;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use globset::{Glob, GlobMatcher};
+
+use crate::config::ActionConfig;
+use crate::events::Event;
+use crate::hooks;
+use crate::reactor::Waker;
+use crate::settling::Settling;
+use crate::watchlist::tokenize;
+
+/// The extended attribute desktop file managers keep tags in, as a comma separated list.
+const TAGS_ATTRIBUTE: &str = "user.xdg.tags";
+/// How many things done can be undone, most recent first
+const JOURNAL_LENGTH: usize = 100;
+
+/// What an action does with a file.
+#[derive(Debug, Clone, PartialEq)]
+enum Task {
+    /// Into a directory, given as a template
+    Move(String),
+    Copy(String),
+    Run(Vec<String>),
+    Tag(String),
+    /// Permanently, or to the trash
+    Delete {
+        permanently: bool,
+    },
+}
+
+#[derive(Debug)]
+struct Rule {
+    name: String,
+    matcher: GlobMatcher,
+    group: Option<String>,
+    stable_for: Duration,
+    task: Task,
+    dry_run: bool,
+}
+
+impl Rule {
+    fn new(config: &ActionConfig, dry_run: bool, delete_permanently: bool) -> Result<Self, String> {
+        let pattern = config.pattern.get_ref();
+        let name = config.name.clone().unwrap_or_else(|| pattern.clone());
+        let matcher = Glob::new(pattern)
+            .map_err(|e| format!("action {}: invalid glob: {}", name, e))?
+            .compile_matcher();
+        let tasks = [
+            config.move_to.clone().map(Task::Move),
+            config.copy_to.clone().map(Task::Copy),
+            config
+                .run
+                .as_deref()
+                .map(|command| match tokenize(command) {
+                    Ok(words) if !words.is_empty() => Ok(Task::Run(words)),
+                    Ok(_) => Err(format!("action {}: run is empty", name)),
+                    Err(e) => Err(format!("action {}: {}", name, e)),
+                })
+                .transpose()?,
+            config.tag.clone().map(Task::Tag),
+            config.delete.then_some(Task::Delete {
+                permanently: delete_permanently,
+            }),
+        ];
+        let mut tasks = tasks.into_iter().flatten();
+        let (Some(task), None) = (tasks.next(), tasks.next()) else {
+            return Err(format!(
+                "action {} needs exactly one of move, copy, run, tag, or delete",
+                name
+            ));
+        };
+
+        Ok(Self {
+            name,
+            matcher,
+            group: config.group.clone(),
+            stable_for: config.stable_for.0,
+            task,
+            dry_run: dry_run || config.dry_run,
+        })
+    }
+
+    fn matches(&self, group: &str, path: &Path) -> bool {
+        self.group.as_ref().is_none_or(|g| g == group)
+            && path.file_name().is_some_and(|n| self.matcher.is_match(n))
+    }
+}
+
+struct Job {
+    rule: String,
+    task: Task,
+    dry_run: bool,
+    event: Event,
+}
+
+/// What a file an action left somewhere looked like, so undoing it can tell whether it's still
+/// the same file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Stamp {
+    device: u64,
+    inode: u64,
+    size: u64,
+    modified: Option<SystemTime>,
+}
+
+impl Stamp {
+    fn of(path: &Path) -> io::Result<Self> {
+        let metadata = fs::symlink_metadata(path)?;
+        Ok(Self {
+            device: metadata.dev(),
+            inode: metadata.ino(),
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
+        })
+    }
+}
+
+/// How to reverse what a task did to a file.
+#[derive(Debug, Clone, PartialEq)]
+enum Undo {
+    /// Move a file back from where it was moved to
+    Move {
+        from: PathBuf,
+        to: PathBuf,
+        stamp: Stamp,
+    },
+    /// Remove a copy that was made
+    Copy { path: PathBuf, stamp: Stamp },
+    /// Remove a tag that was added
+    Tag { path: PathBuf, tag: String },
+    /// Restore a file from the trash
+    Trash(PathBuf),
+}
+
+/// Something done that can be undone.
+struct Entry {
+    rule: String,
+    undo: Undo,
+}
+
+/// Moves, copies, runs a command for, tags, or deletes each new file matching a configured pattern once
+/// its size and modification time have stopped changing. Only the first matching action is
+/// used for a file, and files already there at startup are left alone.
+///
+/// The work is done in the background and reported as action events, for the event log to keep
+/// an audit trail of. Moves, copies, tags, and moves to the trash are kept in a journal so the
+/// most recent ones can be undone.
+pub struct Actions {
+    rules: Vec<Rule>,
+    /// New files waiting to stop changing before they're acted on, with the rule and event
+    pending: Settling<(usize, Event)>,
+    jobs: Sender<Job>,
+    done: Receiver<(Event, Option<Undo>)>,
+    journal: Vec<Entry>,
+    /// Files put back by an undo, which shouldn't be acted on again when they reappear
+    restored: HashSet<PathBuf>,
+    /// Undos done since the last update, to be reported with what else was done
+    undone: Vec<Event>,
+}
+
+impl Actions {
+    pub fn new(
+        configs: &[ActionConfig],
+        dry_run: bool,
+        delete_permanently: bool,
+        waker: Waker,
+    ) -> Result<Self, String> {
+        let rules = configs
+            .iter()
+            .map(|config| Rule::new(config, dry_run, delete_permanently))
+            .collect::<Result<_, _>>()?;
+
+        let (jobs, rx) = channel::<Job>();
+        let (tx, done) = channel();
+        thread::spawn(move || {
+            for job in rx {
+                if tx.send(perform(job)).is_err() {
+                    return;
+                }
+                waker.wake();
+            }
+        });
+
+        Ok(Self {
+            rules,
+            pending: Settling::default(),
+            jobs,
+            done,
+            journal: vec![],
+            restored: HashSet::new(),
+            undone: vec![],
+        })
+    }
+
+    /// Note the files added and removed in a tick's events, start on any that have settled, and
+    /// return what has been done since the last call.
+    pub fn update(&mut self, events: &[Event]) -> Vec<Event> {
+        for event in events {
+            match event {
+                Event::FileAdded { path, .. } if self.restored.remove(path) => {}
+                Event::FileAdded { group, path } => {
+                    if let Some(rule) = self.rules.iter().position(|r| r.matches(group, path)) {
+                        let stable_for = self.rules[rule].stable_for;
+                        let pending = (rule, event.clone());
+                        self.pending.insert(path.clone(), None, stable_for, pending);
+                    }
+                }
+                Event::FileRemoved { path, .. } => {
+                    self.pending.remove(path);
+                }
+                _ => {}
+            }
+        }
+
+        for (_, (rule, event)) in self.pending.settled() {
+            let rule = &self.rules[rule];
+            let _ = self.jobs.send(Job {
+                rule: rule.name.clone(),
+                task: rule.task.clone(),
+                dry_run: rule.dry_run,
+                event,
+            });
+        }
+
+        let mut done = std::mem::take(&mut self.undone);
+        done.extend(self.finished());
+        done
+    }
+
+    /// What the background work has done since this was last called, noting how to undo it.
+    fn finished(&mut self) -> Vec<Event> {
+        let mut done = vec![];
+        for (event, undo) in self.done.try_iter() {
+            if let (Some(undo), Event::Action { rule, .. }) = (undo, &event) {
+                if self.journal.len() == JOURNAL_LENGTH {
+                    self.journal.remove(0);
+                }
+                self.journal.push(Entry {
+                    rule: rule.clone(),
+                    undo,
+                });
+            }
+            done.push(event);
+        }
+        done
+    }
+
+    /// Reverse the most recent thing done that hasn't been undone yet, describing what happened.
+    pub fn undo(&mut self) -> String {
+        // anything finished in the meantime is more recent
+        let finished = self.finished();
+        self.undone.extend(finished);
+        let Some(Entry { rule, undo }) = self.journal.pop() else {
+            return "nothing to undo".to_string();
+        };
+        let (path, result) = match &undo {
+            Undo::Move { from, to, stamp } => (from, undo_move(from, to, *stamp)),
+            Undo::Copy { path, stamp } => (path, remove_copy(path, *stamp)),
+            Undo::Tag { path, tag } => (path, remove_tag(path, tag).map_err(|e| e.to_string())),
+            Undo::Trash(path) => (path, restore_from_trash(path)),
+        };
+        let message = match result {
+            Ok(()) => {
+                if matches!(undo, Undo::Move { .. } | Undo::Trash(_)) {
+                    self.restored.insert(path.clone());
+                }
+                format!("undid {} for {}", rule, path.display())
+            }
+            Err(e) => format!("could not undo {} for {}: {}", rule, path.display(), e),
+        };
+        self.undone.push(Event::Action {
+            rule,
+            path: path.clone(),
+            message: message.clone(),
+        });
+        message
+    }
+
+    /// When the soonest pending file could have settled, to check on it then.
+    pub fn next_check(&self) -> Option<Instant> {
+        self.pending.next_check()
+    }
+}
+
+fn perform(job: Job) -> (Event, Option<Undo>) {
+    let path = job.event.path().map(Path::to_path_buf).unwrap_or_default();
+    let (message, undo) = match do_task(&job.task, &job.event, &path, job.dry_run) {
+        Ok((done, undo)) => (done, undo.filter(|_| !job.dry_run)),
+        Err(e) => (format!("could not {}", e), None),
+    };
+    let event = Event::Action {
+        rule: job.rule,
+        path,
+        message,
+    };
+    (event, undo)
+}
+
+/// Do a task, or only work out what it would do, describing it either way along with how it
+/// could be undone.
+fn do_task(
+    task: &Task,
+    event: &Event,
+    path: &Path,
+    dry_run: bool,
+) -> Result<(String, Option<Undo>), String> {
+    let done = |verb: &str, past: &str, rest: String| {
+        if dry_run {
+            format!("would {} {}", verb, rest)
+        } else {
+            format!("{} {}", past, rest)
+        }
+    };
+    match task {
+        Task::Move(dir) | Task::Copy(dir) => {
+            let (verb, past) = if matches!(task, Task::Move(_)) {
+                ("move", "moved")
+            } else {
+                ("copy", "copied")
+            };
+            let dir = PathBuf::from(event.expand(dir));
+            let target = dir.join(path.file_name().unwrap_or_default());
+            let describe = |e: io::Error| format!("{} to {}: {}", verb, target.display(), e);
+            let message = done(verb, past, format!("to {}", target.display()));
+            if dry_run {
+                return Ok((message, None));
+            }
+            if target.exists() {
+                return Err(describe(io::ErrorKind::AlreadyExists.into()));
+            }
+            fs::create_dir_all(&dir).map_err(describe)?;
+            match task {
+                Task::Move(_) => move_file(path, &target),
+                _ => fs::copy(path, &target).map(|_| ()),
+            }
+            .map_err(describe)?;
+            let stamp = Stamp::of(&target).map_err(describe)?;
+            let undo = match task {
+                Task::Move(_) => Undo::Move {
+                    from: path.to_path_buf(),
+                    to: target,
+                    stamp,
+                },
+                _ => Undo::Copy {
+                    path: target,
+                    stamp,
+                },
+            };
+            Ok((message, Some(undo)))
+        }
+        Task::Run(command) => {
+            let command = command.iter().map(|w| event.expand(w)).collect::<Vec<_>>();
+            if !dry_run {
+                hooks::run(&command).map_err(|e| format!("run {}: {}", command[0], e))?;
+            }
+            Ok((done("run", "ran", command.join(" ")), None))
+        }
+        Task::Tag(tag) => {
+            // a tag that was already there isn't taken off again by undoing
+            let added =
+                !dry_run && add_tag(path, tag).map_err(|e| format!("tag {}: {}", tag, e))?;
+            let undo = added.then(|| Undo::Tag {
+                path: path.to_path_buf(),
+                tag: tag.clone(),
+            });
+            Ok((done("tag", "tagged", tag.clone()), undo))
+        }
+        Task::Delete { permanently: true } => {
+            if !dry_run {
+                fs::remove_file(path).map_err(|e| format!("delete: {}", e))?;
+            }
+            Ok((done("delete", "deleted", "permanently".to_string()), None))
+        }
+        Task::Delete { permanently: false } => {
+            if !dry_run {
+                trash::delete(path).map_err(|e| format!("move to the trash: {}", e))?;
+            }
+            let undo = Undo::Trash(path.to_path_buf());
+            Ok((
+                done("move", "moved", "to the trash".to_string()),
+                Some(undo),
+            ))
+        }
+    }
+}
+
+/// Rename, or copy and remove when the target is on another filesystem.
+fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    match fs::rename(from, to) {
+        Err(e) if e.raw_os_error() == Some(libc::EXDEV) => {
+            fs::copy(from, to)?;
+            fs::remove_file(from)
+        }
+        result => result,
+    }
+}
+
+/// Move a file back to where it was, unless something else has taken its place since or it's
+/// been changed where it was moved to.
+fn undo_move(from: &Path, to: &Path, stamp: Stamp) -> Result<(), String> {
+    if from.exists() {
+        return Err(format!("{} exists again", from.display()));
+    }
+    unchanged(to, stamp)?;
+    move_file(to, from).map_err(|e| e.to_string())
+}
+
+/// Remove a copy that was made, unless it's been changed or replaced since.
+fn remove_copy(path: &Path, stamp: Stamp) -> Result<(), String> {
+    unchanged(path, stamp)?;
+    fs::remove_file(path).map_err(|e| e.to_string())
+}
+
+/// Whether a file is still the one an action left there.
+fn unchanged(path: &Path, stamp: Stamp) -> Result<(), String> {
+    match Stamp::of(path) {
+        Ok(now) if now == stamp => Ok(()),
+        Ok(_) => Err(format!("{} has changed since", path.display())),
+        Err(e) => Err(format!("{}: {}", path.display(), e)),
+    }
+}
+
+/// Put back the file most recently moved to the trash from a path.
+#[cfg(not(target_os = "macos"))]
+fn restore_from_trash(path: &Path) -> Result<(), String> {
+    let item = trash::os_limited::list()
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .filter(|item| item.original_path() == path)
+        .max_by_key(|item| item.time_deleted)
+        .ok_or("it's no longer in the trash")?;
+    trash::os_limited::restore_all([item]).map_err(|e| e.to_string())
+}
+
+#[cfg(target_os = "macos")]
+fn restore_from_trash(_: &Path) -> Result<(), String> {
+    Err("files can't be restored from the trash on macOS".to_string())
+}
+
+/// Add a tag to a file's tags attribute, unless it's already there, returning whether it was
+/// added.
+fn add_tag(path: &Path, tag: &str) -> io::Result<bool> {
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    let name = CString::new(TAGS_ATTRIBUTE)?;
+    let mut tags = get_attribute(&path, &name)?;
+    if tags.split(',').any(|t| t == tag) {
+        return Ok(false);
+    }
+    if !tags.is_empty() {
+        tags.push(',');
+    }
+    tags.push_str(tag);
+    set_attribute(&path, &name, &tags)?;
+    Ok(true)
+}
+
+/// Take a tag out of a file's tags attribute.
+fn remove_tag(path: &Path, tag: &str) -> io::Result<()> {
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    let name = CString::new(TAGS_ATTRIBUTE)?;
+    let tags = get_attribute(&path, &name)?;
+    let kept = tags.split(',').filter(|t| *t != tag).collect::<Vec<_>>();
+    set_attribute(&path, &name, &kept.join(","))
+}
+
+fn set_attribute(path: &CString, name: &CString, value: &str) -> io::Result<()> {
+    // SAFETY: both strings are NUL terminated and the value is valid for its length
+    let result = unsafe {
+        libc::setxattr(
+            path.as_ptr(),
+            name.as_ptr(),
+            value.as_ptr().cast(),
+            value.len(),
+            0,
+        )
+    };
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+/// The value of an extended attribute, or nothing if it isn't set.
+fn get_attribute(path: &CString, name: &CString) -> io::Result<String> {
+    // SAFETY: a null buffer of size 0 only asks for the size
+    let size = unsafe { libc::getxattr(path.as_ptr(), name.as_ptr(), std::ptr::null_mut(), 0) };
+    if size < 0 {
+        let error = io::Error::last_os_error();
+        return match error.raw_os_error() {
+            Some(libc::ENODATA) => Ok(String::new()),
+            _ => Err(error),
+        };
+    }
+    let mut value = vec![0u8; size as usize];
+    // SAFETY: the buffer is valid for writes of its length
+    let size = unsafe {
+        libc::getxattr(
+            path.as_ptr(),
+            name.as_ptr(),
+            value.as_mut_ptr().cast(),
+            value.len(),
+        )
+    };
+    if size < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    value.truncate(size as usize);
+    Ok(String::from_utf8_lossy(&value).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reactor::Reactor;
+
+    fn waker() -> Waker {
+        Reactor::new(Duration::from_secs(60)).unwrap().waker()
+    }
+
+    fn actions(config: &str, dry_run: bool) -> Actions {
+        let configs: Vec<ActionConfig> = vec![toml::from_str(config).unwrap()];
+        Actions::new(&configs, dry_run, false, waker()).unwrap()
+    }
+
+    fn wait(actions: &mut Actions, events: &[Event]) -> Vec<Event> {
+        let mut done = actions.update(events);
+        for _ in 0..100 {
+            if !done.is_empty() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+            done = actions.update(&[]);
+        }
+        done
+    }
+
+    #[test]
+    fn move_settled_files() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let path = dir.join("a.pdf");
+        fs::write(&path, "a").unwrap();
+        let added = |path: &Path| Event::FileAdded {
+            group: "In".to_string(),
+            path: path.to_path_buf(),
+        };
+        let config = format!(
+            r#"
+            name = "file pdfs"
+            match = "*.pdf"
+            stable_for = "0s"
+            move = "{}/{{group}}"
+            "#,
+            dir.display()
+        );
+
+        let done = wait(&mut actions(&config, true), &[added(&path)]);
+        let target = dir.join("In/a.pdf");
+        assert_eq!(
+            done,
+            vec![Event::Action {
+                rule: "file pdfs".to_string(),
+                path: path.clone(),
+                message: format!("would move to {}", target.display()),
+            }]
+        );
+        assert!(path.exists());
+
+        let mut actions = actions(&config, false);
+        // other names are left alone
+        assert!(wait(&mut actions, &[added(&dir.join("b.txt"))]).is_empty());
+        let done = wait(&mut actions, &[added(&path)]);
+        assert_eq!(
+            done,
+            vec![Event::Action {
+                rule: "file pdfs".to_string(),
+                path: path.clone(),
+                message: format!("moved to {}", target.display()),
+            }]
+        );
+        assert!(!path.exists());
+        assert!(target.exists());
+    }
+
+    #[test]
+    fn undo_moves() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let path = dir.join("a.pdf");
+        fs::write(&path, "a").unwrap();
+        let added = Event::FileAdded {
+            group: "In".to_string(),
+            path: path.clone(),
+        };
+        let config = format!(
+            "name = \"file pdfs\"\nmatch = \"*.pdf\"\nstable_for = \"0s\"\nmove = \"{}/done\"",
+            dir.display()
+        );
+        let mut actions = actions(&config, false);
+        assert_eq!(actions.undo(), "nothing to undo");
+
+        wait(&mut actions, std::slice::from_ref(&added));
+        assert!(!path.exists());
+        assert_eq!(
+            actions.undo(),
+            format!("undid file pdfs for {}", path.display())
+        );
+        assert!(path.exists());
+        assert!(!dir.join("done/a.pdf").exists());
+
+        // the undo is reported, and the file isn't moved again when it reappears
+        let done = actions.update(&[added]);
+        assert!(
+            matches!(&done[..], [Event::Action { message, .. }] if message.starts_with("undid"))
+        );
+        assert_eq!(actions.next_check(), None);
+        assert_eq!(actions.undo(), "nothing to undo");
+    }
+
+    #[test]
+    fn only_undo_unchanged_files() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let path = dir.join("a.pdf");
+        fs::write(&path, "a").unwrap();
+        let added = Event::FileAdded {
+            group: "In".to_string(),
+            path: path.clone(),
+        };
+        let config = format!(
+            "name = \"keep pdfs\"\nmatch = \"*.pdf\"\nstable_for = \"0s\"\ncopy = \"{}/kept\"",
+            dir.display()
+        );
+        let mut actions = actions(&config, false);
+
+        wait(&mut actions, std::slice::from_ref(&added));
+        let copy = dir.join("kept/a.pdf");
+        fs::write(&copy, "edited since").unwrap();
+        assert_eq!(
+            actions.undo(),
+            format!(
+                "could not undo keep pdfs for {}: {} has changed since",
+                copy.display(),
+                copy.display()
+            )
+        );
+        assert!(copy.exists());
+
+        // one put back in its place isn't the copy either, once what the undo reported is out of
+        // the way of waiting for it
+        assert_eq!(actions.update(&[]).len(), 1);
+        fs::remove_file(&copy).unwrap();
+        wait(&mut actions, &[added]);
+        fs::remove_file(&copy).unwrap();
+        fs::write(&copy, "another").unwrap();
+        assert!(actions.undo().contains("has changed since"));
+        assert!(copy.exists());
+    }
+
+    #[test]
+    fn one_task_per_action() {
+        let configs: Vec<ActionConfig> =
+            vec![toml::from_str("match = \"*\"\ncopy = \"/a\"\ntag = \"b\"").unwrap()];
+        assert!(Actions::new(&configs, false, false, waker()).is_err());
+    }
+}
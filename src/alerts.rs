@@ -0,0 +1,550 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use regex::Regex;
+
+use crate::config::{AlertsConfig, CountLimit, IdleLimit};
+use crate::events::Event;
+use crate::filesystem::FileGroup;
+use crate::health::Check;
+use crate::AppState;
+
+/// Watches for the conditions configured under `[alerts]`, raising each once when it starts.
+#[derive(Debug, Default)]
+pub struct Alerts {
+    service_down_for: Option<Duration>,
+    /// When each service down was first seen down, and whether that's been alerted on yet
+    service_down: HashMap<String, (Instant, bool)>,
+    expectations: Vec<Expectation>,
+    counts: Vec<CountLimit>,
+    /// Roots of the groups whose counts are out of range, each alerted on once
+    breached: HashSet<PathBuf>,
+    idle: Vec<Idle>,
+    names: Vec<NameRule>,
+}
+
+/// A pattern new files' paths are checked against, in one group or all of them.
+#[derive(Debug)]
+struct NameRule {
+    pattern: Regex,
+    group: Option<String>,
+}
+
+#[derive(Debug)]
+struct Idle {
+    limit: IdleLimit,
+    /// When a file was last added to the group, or when watching started
+    last: Option<NaiveDateTime>,
+    /// Whether the current quiet stretch has been alerted on
+    alerted: bool,
+}
+
+impl Idle {
+    fn new(limit: IdleLimit) -> Self {
+        Self {
+            limit,
+            last: None,
+            alerted: false,
+        }
+    }
+
+    /// The start of the active hours `now` is in, `None` if it's outside them, or `Some(None)`
+    /// if they're all day.
+    fn active_since(&self, now: NaiveDateTime) -> Option<Option<NaiveDateTime>> {
+        let (from, until, time) = (self.limit.from.0, self.limit.until.0, now.time());
+        if from == until {
+            return Some(None);
+        }
+        let active = if from < until {
+            from <= time && time < until
+        } else {
+            time >= from || time < until
+        };
+        let start = if time >= from {
+            now.date()
+        } else {
+            now.date().pred_opt()?
+        };
+        active.then(|| Some(start.and_time(from)))
+    }
+
+    /// When the group will have been quiet too long, counting only the active hours.
+    fn due(&self, now: NaiveDateTime) -> Option<NaiveDateTime> {
+        let last = self.last?;
+        let start = match self.active_since(now) {
+            Some(since) => since.map_or(last, |since| since.max(last)),
+            None => {
+                let from = self.limit.from.0;
+                let day = if now.time() < from {
+                    now.date()
+                } else {
+                    now.date().succ_opt()?
+                };
+                day.and_time(from)
+            }
+        };
+        Some(start + chrono::Duration::from_std(self.limit.window.0).ok()?)
+    }
+}
+
+#[derive(Debug)]
+struct Expectation {
+    path: PathBuf,
+    by: NaiveTime,
+    /// The last day this was checked on
+    checked: Option<NaiveDate>,
+}
+
+impl Alerts {
+    pub fn new(config: &AlertsConfig) -> Self {
+        Self {
+            service_down_for: config.service_down_for.map(|d| d.0),
+            service_down: HashMap::new(),
+            expectations: config
+                .expect
+                .iter()
+                .map(|e| Expectation {
+                    path: e.path.clone(),
+                    by: e.by.0,
+                    checked: None,
+                })
+                .collect(),
+            counts: config.counts.clone(),
+            breached: HashSet::new(),
+            idle: config.idle.iter().cloned().map(Idle::new).collect(),
+            // invalid patterns are reported when the config is loaded
+            names: config
+                .names
+                .iter()
+                .filter_map(|name| {
+                    Some(NameRule {
+                        pattern: Regex::new(name.pattern.get_ref()).ok()?,
+                        group: name.group.clone(),
+                    })
+                })
+                .collect(),
+        }
+    }
+
+    /// Alerts that are due, given the state and the events it just produced.
+    pub fn check(&mut self, state: &AppState, events: &[Event]) -> Vec<Event> {
+        let mut alerts = vec![];
+        // nothing is seen being added while paused
+        if !state.paused {
+            alerts.extend(self.check_idle(events, state.timezone.now()));
+        }
+        alerts.extend(self.check_names(events));
+        alerts.extend(self.check_services(state));
+        alerts.extend(self.check_expectations(state.timezone.now()));
+        alerts.extend(self.check_counts(&state.file_groups));
+        alerts
+    }
+
+    /// Roots of the groups with more or fewer files than expected right now.
+    pub fn breached(&self) -> &HashSet<PathBuf> {
+        &self.breached
+    }
+
+    fn check_counts(&mut self, groups: &[FileGroup]) -> Vec<Event> {
+        let mut alerts = vec![];
+        for group in groups {
+            let title = crate::group_title(group);
+            let Some(limit) = self.counts.iter().find(|limit| limit.group == title) else {
+                continue;
+            };
+            let count = present(group);
+            let problem = match (limit.min, limit.max) {
+                (Some(min), _) if count < min => Some(format!("fewer than {}", min)),
+                (_, Some(max)) if count > max => Some(format!("more than {}", max)),
+                _ => None,
+            };
+            match problem {
+                Some(problem) if self.breached.insert(group.root.clone()) => {
+                    alerts.push(Event::Alert {
+                        rule: "counts".to_string(),
+                        path: None,
+                        message: format!("{} has {} files, {}", title, count, problem),
+                    })
+                }
+                Some(_) => {}
+                None => {
+                    self.breached.remove(&group.root);
+                }
+            }
+        }
+        alerts
+    }
+
+    fn check_names(&self, events: &[Event]) -> Vec<Event> {
+        let mut alerts = vec![];
+        for event in events {
+            let Event::FileAdded { group, path } = event else {
+                continue;
+            };
+            let text = path.to_string_lossy();
+            let matched = self.names.iter().find(|name| {
+                name.group.as_ref().is_none_or(|only| only == group) && name.pattern.is_match(&text)
+            });
+            if let Some(name) = matched {
+                alerts.push(Event::Alert {
+                    rule: "names".to_string(),
+                    path: Some(path.clone()),
+                    message: format!(
+                        "{}: {} matches {}",
+                        group,
+                        crate::display_name(path),
+                        name.pattern
+                    ),
+                });
+            }
+        }
+        alerts
+    }
+
+    /// Each group with a count limit, and whether it's within it.
+    pub fn health(&self, groups: &[FileGroup]) -> Vec<Check> {
+        groups
+            .iter()
+            .filter(|group| {
+                let title = crate::group_title(group);
+                self.counts.iter().any(|limit| limit.group == title)
+            })
+            .map(|group| {
+                Check::new(
+                    format!("count {}", crate::group_title(group)),
+                    !self.breached.contains(&group.root),
+                    format!("{} files", present(group)),
+                )
+            })
+            .collect()
+    }
+
+    fn check_services(&mut self, state: &AppState) -> Vec<Event> {
+        let Some(limit) = self.service_down_for else {
+            return vec![];
+        };
+        self.service_down.retain(|name, _| {
+            state
+                .services
+                .iter()
+                .any(|service| service.name() == name && service.active() == Some(false))
+        });
+
+        let mut alerts = vec![];
+        for service in &state.services {
+            if service.active() != Some(false) {
+                continue;
+            }
+            let (since, alerted) = self
+                .service_down
+                .entry(service.name().to_string())
+                .or_insert((Instant::now(), false));
+            if *alerted || since.elapsed() < limit {
+                continue;
+            }
+            *alerted = true;
+            alerts.push(Event::Alert {
+                rule: "service_down_for".to_string(),
+                path: None,
+                message: format!(
+                    "service {} has been down for {}",
+                    service.name(),
+                    humantime::format_duration(limit)
+                ),
+            });
+        }
+        alerts
+    }
+
+    fn check_idle(&mut self, events: &[Event], now: NaiveDateTime) -> Vec<Event> {
+        let mut alerts = vec![];
+        for idle in self.idle.iter_mut() {
+            let added = events.iter().any(|event| {
+                matches!(event, Event::FileAdded { group, .. } if *group == idle.limit.group)
+            });
+            if added || idle.last.is_none() {
+                idle.last = Some(now);
+                idle.alerted = false;
+            }
+            if idle.alerted || idle.active_since(now).is_none() {
+                continue;
+            }
+            if idle.due(now).is_some_and(|due| now >= due) {
+                idle.alerted = true;
+                alerts.push(Event::Alert {
+                    rule: "idle".to_string(),
+                    path: None,
+                    message: format!(
+                        "no new files in {} for {}",
+                        idle.limit.group,
+                        humantime::format_duration(idle.limit.window.0)
+                    ),
+                });
+            }
+        }
+        alerts
+    }
+
+    fn check_expectations(&mut self, now: NaiveDateTime) -> Vec<Event> {
+        let today = now.date();
+        self.expectations
+            .iter_mut()
+            .filter(|e| now.time() >= e.by && e.checked != Some(today))
+            .filter_map(|e| {
+                e.checked = Some(today);
+                (!e.path.exists()).then(|| Event::Alert {
+                    rule: "expect".to_string(),
+                    path: None,
+                    message: format!("expected {} by {}", e.path.display(), e.by.format("%H:%M")),
+                })
+            })
+            .collect()
+    }
+
+    /// How long until an alert could next be due, to check again then.
+    pub fn next_check(&self, now: NaiveDateTime) -> Option<Duration> {
+        let services = self.service_down.values().filter_map(|(since, alerted)| {
+            let limit = self.service_down_for.filter(|_| !alerted)?;
+            Some(limit.saturating_sub(since.elapsed()))
+        });
+        let expectations = self.expectations.iter().filter_map(|e| {
+            let today = now.date();
+            let day = if now.time() < e.by {
+                today
+            } else {
+                today.succ_opt()?
+            };
+            (day.and_time(e.by) - now).to_std().ok()
+        });
+        let idle = self
+            .idle
+            .iter()
+            .filter(|idle| !idle.alerted)
+            .filter_map(|idle| (idle.due(now)? - now).to_std().ok());
+        services.chain(expectations).chain(idle).min()
+    }
+}
+
+/// How many files a group has that are still there, counting those dropped from view.
+fn present(group: &FileGroup) -> usize {
+    group.hidden
+        + group
+            .items()
+            .iter()
+            .filter(|item| item.removed.is_none())
+            .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::config::{HumanDuration, TimeOfDay};
+    use crate::filesystem::FileItem;
+
+    fn at(date: &str, time: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(&format!("{} {}", date, time), "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn expectation_alerts_once_per_day() {
+        let mut alerts = Alerts {
+            expectations: vec![Expectation {
+                path: PathBuf::from("/nonexistent/report.csv"),
+                by: NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+                checked: None,
+            }],
+            ..Default::default()
+        };
+
+        assert!(alerts
+            .check_expectations(at("2024-05-01", "08:59"))
+            .is_empty());
+        assert_eq!(
+            alerts.check_expectations(at("2024-05-01", "09:00")).len(),
+            1
+        );
+        assert!(alerts
+            .check_expectations(at("2024-05-01", "17:00"))
+            .is_empty());
+        assert_eq!(
+            alerts.check_expectations(at("2024-05-02", "09:30")).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn count_alerts_once_per_breach() {
+        let mut alerts = Alerts {
+            counts: vec![CountLimit {
+                group: "spool".to_string(),
+                min: Some(1),
+                max: Some(2),
+            }],
+            ..Default::default()
+        };
+        let spool = |names: &[&str]| {
+            vec![FileGroup::new(
+                PathBuf::from("/var/spool"),
+                None,
+                names
+                    .iter()
+                    .map(|n| FileItem::new(PathBuf::from(n)))
+                    .collect(),
+            )]
+        };
+
+        let messages = |alerts: Vec<Event>| {
+            alerts
+                .into_iter()
+                .map(|e| match e {
+                    Event::Alert { message, .. } => message,
+                    e => panic!("{:?}", e),
+                })
+                .collect::<Vec<_>>()
+        };
+        assert!(alerts.check_counts(&spool(&["a"])).is_empty());
+        assert_eq!(
+            messages(alerts.check_counts(&spool(&["a", "b", "c"]))),
+            ["spool has 3 files, more than 2"]
+        );
+        assert!(alerts.breached().contains(Path::new("/var/spool")));
+        assert_eq!(
+            alerts.health(&spool(&["a", "b", "c"])),
+            [Check::new(
+                "count spool".to_string(),
+                false,
+                "3 files".to_string()
+            )]
+        );
+        assert!(alerts
+            .check_counts(&spool(&["a", "b", "c", "d"]))
+            .is_empty());
+        assert!(alerts.check_counts(&spool(&["a"])).is_empty());
+        assert!(alerts.breached().is_empty());
+        assert_eq!(
+            messages(alerts.check_counts(&spool(&[]))),
+            ["spool has 0 files, fewer than 1"]
+        );
+        // other groups aren't counted
+        assert!(alerts
+            .check_counts(&[FileGroup::new(PathBuf::from("/in"), None, vec![])])
+            .is_empty());
+    }
+
+    #[test]
+    fn idle_alerts_within_active_hours() {
+        let mut alerts = Alerts {
+            idle: vec![Idle::new(IdleLimit {
+                group: "inbox".to_string(),
+                window: HumanDuration(Duration::from_secs(2 * 60 * 60)),
+                from: TimeOfDay(NaiveTime::from_hms_opt(8, 0, 0).unwrap()),
+                until: TimeOfDay(NaiveTime::from_hms_opt(18, 0, 0).unwrap()),
+            })],
+            ..Default::default()
+        };
+        let added = [Event::FileAdded {
+            group: "inbox".to_string(),
+            path: PathBuf::from("/in/a"),
+        }];
+
+        // quiet overnight doesn't count
+        assert!(alerts.check_idle(&[], at("2024-05-01", "17:00")).is_empty());
+        assert!(alerts.check_idle(&[], at("2024-05-02", "07:00")).is_empty());
+        assert_eq!(
+            alerts.next_check(at("2024-05-02", "07:00")),
+            Some(Duration::from_secs(3 * 60 * 60))
+        );
+        assert!(alerts.check_idle(&[], at("2024-05-02", "09:59")).is_empty());
+        assert_eq!(
+            alerts.check_idle(&[], at("2024-05-02", "10:00")),
+            [Event::Alert {
+                rule: "idle".to_string(),
+                path: None,
+                message: "no new files in inbox for 2h".to_string(),
+            }]
+        );
+        assert!(alerts.check_idle(&[], at("2024-05-02", "11:00")).is_empty());
+
+        // a new file starts the wait again
+        assert!(alerts
+            .check_idle(&added, at("2024-05-02", "11:30"))
+            .is_empty());
+        assert!(alerts.check_idle(&[], at("2024-05-02", "13:00")).is_empty());
+        assert_eq!(alerts.check_idle(&[], at("2024-05-02", "13:30")).len(), 1);
+    }
+
+    #[test]
+    fn name_alerts_on_new_files() {
+        let alerts = Alerts {
+            names: vec![
+                NameRule {
+                    pattern: Regex::new(r"\.rej$").unwrap(),
+                    group: None,
+                },
+                NameRule {
+                    pattern: Regex::new("DLQ").unwrap(),
+                    group: Some("outbox".to_string()),
+                },
+            ],
+            ..Default::default()
+        };
+        let added = |group: &str, path: &str| Event::FileAdded {
+            group: group.to_string(),
+            path: PathBuf::from(path),
+        };
+        let events = [
+            added("inbox", "/in/orders.csv.rej"),
+            added("inbox", "/in/DLQ-1.json"),
+            added("outbox", "/out/DLQ-2.json"),
+            Event::FileRemoved {
+                group: "inbox".to_string(),
+                path: PathBuf::from("/in/old.rej"),
+            },
+        ];
+        assert_eq!(
+            alerts.check_names(&events),
+            [
+                Event::Alert {
+                    rule: "names".to_string(),
+                    path: Some(PathBuf::from("/in/orders.csv.rej")),
+                    message: "inbox: orders.csv.rej matches \\.rej$".to_string(),
+                },
+                Event::Alert {
+                    rule: "names".to_string(),
+                    path: Some(PathBuf::from("/out/DLQ-2.json")),
+                    message: "outbox: DLQ-2.json matches DLQ".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn next_check_at_expected_time() {
+        let by = |h| Expectation {
+            path: PathBuf::from("/nonexistent/report.csv"),
+            by: NaiveTime::from_hms_opt(h, 0, 0).unwrap(),
+            checked: None,
+        };
+        let alerts = Alerts {
+            expectations: vec![by(9), by(17)],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            alerts.next_check(at("2024-05-01", "08:30")),
+            Some(Duration::from_secs(30 * 60))
+        );
+        assert_eq!(
+            alerts.next_check(at("2024-05-01", "18:00")),
+            Some(Duration::from_secs(15 * 60 * 60))
+        );
+        assert_eq!(
+            Alerts::default().next_check(at("2024-05-01", "18:00")),
+            None
+        );
+    }
+}
@@ -0,0 +1,310 @@
+use std::collections::VecDeque;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use serde_json::{json, Value};
+use tiny_http::{Header, Request, Response, Server};
+use tungstenite::handshake::derive_accept_key;
+use tungstenite::protocol::Role;
+use tungstenite::{Message, WebSocket};
+
+use crate::events::Event;
+use crate::filesystem::{FileGroup, Signature};
+use crate::service::ServiceState;
+use crate::AppState;
+
+/// The read-only dashboard served at `/`, which reads the rest of the API.
+const DASHBOARD: &str = include_str!("dashboard.html");
+
+/// How many past events are kept for clients catching up with `/api/events`.
+const EVENT_HISTORY: usize = 1000;
+
+/// What the HTTP and gRPC APIs serve, refreshed from the dashboard's state.
+#[derive(Debug, Default)]
+pub struct Api {
+    groups: Value,
+    services: Value,
+    /// Recent events with their sequence numbers, oldest first
+    events: VecDeque<(u64, Value)>,
+    next_id: u64,
+    /// Connected `/api/stream` and gRPC `Events` clients
+    subscribers: Vec<Sender<Value>>,
+}
+
+impl Api {
+    pub fn update(&mut self, state: &AppState, events: &[Event]) {
+        self.groups = groups_json(&state.file_groups);
+        self.services = services_json(&state.services);
+        for event in events {
+            self.next_id += 1;
+            let mut record = json!(event.payload());
+            record["id"] = json!(self.next_id);
+            self.subscribers.retain(|s| s.send(record.clone()).is_ok());
+            self.events.push_back((self.next_id, record));
+        }
+        while self.events.len() > EVENT_HISTORY {
+            self.events.pop_front();
+        }
+    }
+
+    pub fn groups(&self) -> &Value {
+        &self.groups
+    }
+
+    /// The first service monitored, as served before there could be several, or null if there
+    /// are none.
+    pub fn service(&self) -> &Value {
+        self.services().first().unwrap_or(&Value::Null)
+    }
+
+    /// Every service monitored.
+    pub fn services(&self) -> &[Value] {
+        self.services.as_array().map_or(&[], Vec::as_slice)
+    }
+
+    /// Receive each event from now on, as its JSON record.
+    pub fn subscribe(&mut self) -> Receiver<Value> {
+        let (tx, rx) = channel();
+        self.subscribers.push(tx);
+        rx
+    }
+
+    pub fn items(&self) -> Value {
+        let items = self
+            .groups
+            .as_array()
+            .into_iter()
+            .flatten()
+            .flat_map(|group| {
+                group["files"].as_array().into_iter().flatten().map(|f| {
+                    json!({
+                        "group": group["title"],
+                        "path": f["path"],
+                        "removed": f["removed"],
+                    })
+                })
+            })
+            .collect::<Vec<_>>();
+        json!(items)
+    }
+
+    /// Events after the given sequence number, and the cursor to pass next time.
+    pub fn events_since(&self, since: u64) -> Value {
+        let events = self
+            .events
+            .iter()
+            .filter(|(id, _)| *id > since)
+            .map(|(_, e)| e)
+            .collect::<Vec<_>>();
+        json!({ "cursor": self.next_id, "events": events })
+    }
+
+    fn respond(&self, path: &str, query: Option<&str>) -> Option<Value> {
+        match path {
+            "/api/groups" => Some(self.groups.clone()),
+            "/api/items" => Some(self.items()),
+            "/api/service" => Some(self.service().clone()),
+            "/api/services" => Some(json!(self.services())),
+            "/api/events" => {
+                let since = query
+                    .into_iter()
+                    .flat_map(|q| q.split('&'))
+                    .find_map(|pair| pair.strip_prefix("since="))
+                    .and_then(|since| since.parse().ok())
+                    .unwrap_or(0);
+                Some(self.events_since(since))
+            }
+            _ => None,
+        }
+    }
+}
+
+pub fn groups_json(groups: &[FileGroup]) -> Value {
+    let groups = groups
+        .iter()
+        .map(|group| {
+            let files = group
+                .items()
+                .iter()
+                .map(|f| {
+                    json!({
+                        "path": group.path(f),
+                        "removed": f.removed.is_some(),
+                        "tags": f.tags,
+                        "verified": f.verified,
+                        "signature": f.signature.as_ref().map(Signature::name),
+                        "writer": f.writer.as_ref().map(|w| json!({
+                            "pid": w.pid,
+                            "uid": w.uid,
+                            "command": w.command,
+                        })),
+                    })
+                })
+                .collect::<Vec<_>>();
+            json!({
+                "title": crate::group_title(group),
+                "note": group.note,
+                "root": group.root,
+                "files": files,
+                "hidden": group.hidden,
+            })
+        })
+        .collect::<Vec<_>>();
+    json!(groups)
+}
+
+pub fn service_json(service: Option<&ServiceState>) -> Value {
+    let Some(service) = service else {
+        return Value::Null;
+    };
+    let (status, description) = match service {
+        ServiceState::Details(details) => {
+            (Some(details.status.trim()), details.description.as_deref())
+        }
+        ServiceState::Unknown(_) => (None, None),
+    };
+    json!({
+        "name": service.name(),
+        "active": service.active(),
+        "status": status,
+        "description": description,
+    })
+}
+
+pub fn services_json(services: &[ServiceState]) -> Value {
+    json!(services
+        .iter()
+        .map(|service| service_json(Some(service)))
+        .collect::<Vec<_>>())
+}
+
+/// Serve the read-only JSON API on a background thread.
+pub fn serve(addr: SocketAddr, api: Arc<Mutex<Api>>) -> Result<(), Box<dyn std::error::Error>> {
+    let server = Server::http(addr).map_err(|e| file_task::Error::Listen {
+        address: addr.to_string(),
+        source: io::Error::other(e),
+    })?;
+
+    thread::spawn(move || {
+        for request in server.incoming_requests() {
+            handle(request, &api);
+        }
+    });
+
+    Ok(())
+}
+
+fn handle(request: Request, api: &Mutex<Api>) {
+    if request.url() == "/api/stream" {
+        return stream(request, api);
+    }
+    if request.url() == "/" {
+        let content_type =
+            Header::from_bytes("Content-Type", "text/html; charset=utf-8").expect("valid header");
+        let _ = request.respond(Response::from_string(DASHBOARD).with_header(content_type));
+        return;
+    }
+
+    let url = request.url().to_string();
+    let (path, query) = match url.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (url.as_str(), None),
+    };
+    let body = api.lock().ok().and_then(|api| api.respond(path, query));
+
+    let response = match body {
+        Some(body) => {
+            let content_type =
+                Header::from_bytes("Content-Type", "application/json").expect("valid header");
+            Response::from_string(body.to_string()).with_header(content_type)
+        }
+        None => Response::from_string("not found").with_status_code(404),
+    };
+    let _ = request.respond(response);
+}
+
+/// Upgrade to a WebSocket and send each new event to it as a JSON text message.
+fn stream(request: Request, api: &Mutex<Api>) {
+    let key = request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv("Sec-WebSocket-Key"))
+        .map(|h| derive_accept_key(h.value.as_bytes()));
+    let Some(accept) = key else {
+        let _ =
+            request.respond(Response::from_string("expected a WebSocket").with_status_code(400));
+        return;
+    };
+
+    let Ok(rx) = api.lock().map(|mut api| api.subscribe()) else {
+        return;
+    };
+
+    let accept = Header::from_bytes("Sec-WebSocket-Accept", accept).expect("valid header");
+    let response = Response::empty(101).with_header(accept);
+    let socket = request.upgrade("websocket", response);
+
+    thread::spawn(move || {
+        let mut websocket = WebSocket::from_raw_socket(socket, Role::Server, None);
+        for record in rx {
+            if websocket.send(Message::text(record.to_string())).is_err() {
+                return;
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn added(name: &str) -> Event {
+        Event::FileAdded {
+            group: "in".to_string(),
+            path: PathBuf::from(name),
+        }
+    }
+
+    #[test]
+    fn events_since_cursor() {
+        let mut api = Api::default();
+        let state = AppState::default();
+        api.update(&state, &[added("/in/a"), added("/in/b")]);
+        api.update(&state, &[added("/in/c")]);
+
+        let page = api.respond("/api/events", Some("since=1")).unwrap();
+
+        assert_eq!(page["cursor"], 3);
+        let paths = page["events"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| e["path"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(paths, vec!["/in/b", "/in/c"]);
+    }
+
+    #[test]
+    fn services_until_there_are_none() {
+        let mut api = Api::default();
+        assert_eq!(api.respond("/api/service", None), Some(Value::Null));
+        assert_eq!(api.respond("/api/services", None), Some(json!([])));
+
+        let state = AppState {
+            services: vec![
+                ServiceState::Unknown("web".to_string()),
+                ServiceState::Unknown("db".to_string()),
+            ],
+            ..AppState::default()
+        };
+        api.update(&state, &[]);
+        assert_eq!(api.respond("/api/service", None).unwrap()["name"], "web");
+        let services = api.respond("/api/services", None).unwrap();
+        assert_eq!(services[1]["name"], "db");
+    }
+}
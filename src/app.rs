@@ -0,0 +1,742 @@
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+use crossterm::event::{Event as TermEvent, KeyCode, KeyEvent, KeyModifiers};
+
+use crate::filesystem::{FileGroup, FileItem};
+use crate::keymap::{Action, KeyMap};
+use crate::reactor::{Signal, Wake};
+use crate::{view, AppState};
+
+/// Something the dashboard reacts to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Msg {
+    /// A key bound to an action was pressed
+    Action(Action),
+    /// A key was pressed while typing a note
+    Edit(KeyEvent),
+    /// A command finished, with what to tell the user about it
+    Done(String),
+    Signal(Signal),
+    /// The loop came round, for whatever woke it, and the dashboard is about to be drawn
+    Tick,
+}
+
+/// Work `update` wants done outside the state, whose outcome comes back as a `Msg`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cmd {
+    Quit,
+    SaveSession,
+    Export,
+    Rescan,
+    Reload,
+    Suspend,
+    Undo,
+    /// Keep the note of the group at an index with the profile
+    SaveNote(usize),
+    Follow,
+    Peek,
+    Stats,
+    Heatmap,
+    Holders,
+    Settings,
+    /// Write the value typed on the settings screen to the config
+    SaveSetting,
+    /// Give the selected file the tag typed for it, or take it away
+    SaveTag,
+    /// Read the sizes and modified times of the files on screen that a scan left out
+    ReadVisible,
+}
+
+/// The longest a note can be, to fit under a group's title.
+const NOTE_LENGTH: usize = 80;
+
+/// A note being typed for one of the groups.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NoteInput {
+    pub group: usize,
+    pub text: String,
+}
+
+impl NoteInput {
+    /// Start from the note a group already has.
+    fn for_group(state: &AppState, group: usize) -> Self {
+        Self {
+            group,
+            text: state.file_groups[group].note.clone().unwrap_or_default(),
+        }
+    }
+}
+
+/// A tag being typed for the selected file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagInput {
+    pub path: PathBuf,
+    pub text: String,
+    /// Whether the tag is given rather than taken away
+    pub on: bool,
+}
+
+/// The file selected in the focused group, with its group.
+pub fn selected_item(state: &AppState) -> Option<(&FileGroup, &FileItem)> {
+    let group = state
+        .file_groups
+        .get(state.focus % state.file_groups.len().max(1))?;
+    let selected = state.selections.get(&group.root)?.selected()?;
+    let item = view::listed(state, group).nth(selected)?;
+    Some((group, item))
+}
+
+impl Msg {
+    /// The message for something the loop woke up for, if the dashboard cares about it. Keys
+    /// are typed into a note instead of being looked up while one is being edited.
+    pub fn from_wake(wake: Wake, keymap: &KeyMap, editing: bool) -> Option<Msg> {
+        match wake {
+            Wake::Input(TermEvent::Key(key)) if editing => Some(Msg::Edit(key)),
+            Wake::Input(TermEvent::Key(key)) => keymap.action(key.into()).map(Msg::Action),
+            Wake::Signal(signal) => Some(Msg::Signal(signal)),
+            _ => None,
+        }
+    }
+}
+
+/// Apply a message to the state, leaving anything else to be done to the caller.
+pub fn update(state: &mut AppState, msg: Msg) -> Option<Cmd> {
+    match msg {
+        Msg::Action(Action::Quit) => Some(Cmd::Quit),
+        Msg::Action(Action::SaveSession) => Some(Cmd::SaveSession),
+        Msg::Action(Action::Export) => Some(Cmd::Export),
+        Msg::Action(Action::Rescan) => Some(Cmd::Rescan),
+        Msg::Action(Action::Suspend) => Some(Cmd::Suspend),
+        Msg::Action(Action::Undo) => Some(Cmd::Undo),
+        Msg::Action(Action::ClearRemoved) => {
+            let cleared = state
+                .file_groups
+                .iter_mut()
+                .map(FileGroup::clear_removed)
+                .sum::<usize>();
+            state.message = Some(format!("cleared {} removed files", cleared));
+            None
+        }
+        Msg::Action(Action::ClearGroup) => {
+            if state.file_groups.is_empty() {
+                return None;
+            }
+            state.focus %= state.file_groups.len();
+            let group = &mut state.file_groups[state.focus];
+            let cleared = group.clear_removed();
+            state.message = Some(format!(
+                "cleared {} removed files from {}",
+                cleared,
+                crate::group_title(group)
+            ));
+            None
+        }
+        Msg::Action(action @ (Action::Tag | Action::Untag)) => {
+            let on = action == Action::Tag;
+            state.tag_input = selected_item(state).map(|(group, item)| TagInput {
+                path: group.path(item),
+                // taking one away starts from the tag given last
+                text: if on {
+                    String::new()
+                } else {
+                    item.tags.last().cloned().unwrap_or_default()
+                },
+                on,
+            });
+            if state.tag_input.is_none() {
+                state.message = Some("select a file first".to_string());
+            }
+            None
+        }
+        Msg::Action(Action::FilterTag) => {
+            let tags = state
+                .file_groups
+                .iter()
+                .flat_map(FileGroup::items)
+                .flat_map(|item| &item.tags)
+                .collect::<BTreeSet<_>>();
+            let current = state.tag_filter.as_ref();
+            state.tag_filter = tags
+                .into_iter()
+                .find(|tag| current.is_none_or(|current| *tag > current))
+                .cloned();
+            state.message = Some(match &state.tag_filter {
+                Some(tag) => format!("showing files tagged {}", tag),
+                None => "showing all files".to_string(),
+            });
+            None
+        }
+        Msg::Action(Action::EditNote) => {
+            if !state.file_groups.is_empty() {
+                state.note_input = Some(NoteInput::for_group(state, 0));
+            }
+            None
+        }
+        Msg::Action(Action::Follow) => match state.follow.take() {
+            Some(follow) => {
+                state.message = Some(format!("stopped following {}", follow.path.display()));
+                None
+            }
+            None => Some(Cmd::Follow),
+        },
+        Msg::Action(Action::Holders) => match state.holders.take() {
+            Some(_) => None,
+            None => Some(Cmd::Holders),
+        },
+        Msg::Action(Action::Peek) => match state.peek.take() {
+            Some(_) => None,
+            None => Some(Cmd::Peek),
+        },
+        Msg::Action(Action::Stats) => match state.stats.take() {
+            Some(_) => {
+                state.by_extension = false;
+                None
+            }
+            None => Some(Cmd::Stats),
+        },
+        Msg::Action(Action::Extensions) => {
+            state.by_extension = !state.by_extension;
+            // straight to the breakdown from anywhere else
+            state.stats.is_none().then_some(Cmd::Stats)
+        }
+        Msg::Action(Action::Settings) => match state.settings.take() {
+            Some(_) => None,
+            None => Some(Cmd::Settings),
+        },
+        Msg::Action(Action::Columns) => {
+            state.show_columns = !state.show_columns;
+            None
+        }
+        Msg::Action(Action::Sort) => {
+            if state.file_groups.is_empty() {
+                return None;
+            }
+            state.focus %= state.file_groups.len();
+            let root = state.file_groups[state.focus].root.clone();
+            let sort = state.sorts.get(&root).copied().unwrap_or(state.sort);
+            state.sorts.insert(root.clone(), sort.next());
+            view::apply_sorts(state);
+            // back to the top, where the first files in the new order are
+            state.selections.remove(&root);
+            None
+        }
+        Msg::Action(Action::Health) => {
+            state.show_health = !state.show_health;
+            None
+        }
+        Msg::Action(Action::Debug) => {
+            state.show_debug = !state.show_debug;
+            None
+        }
+        Msg::Action(Action::Notifications) => {
+            state.show_notifications = !state.show_notifications;
+            None
+        }
+        Msg::Action(Action::Heatmap) => match state.heatmap.take() {
+            Some(_) => None,
+            None => Some(Cmd::Heatmap),
+        },
+        Msg::Action(Action::Down) => {
+            move_selection(state, 1);
+            None
+        }
+        Msg::Action(Action::Up) => {
+            move_selection(state, -1);
+            None
+        }
+        Msg::Action(Action::PageDown) => {
+            move_selection(state, state.page.max(1) as isize);
+            None
+        }
+        Msg::Action(Action::PageUp) => {
+            move_selection(state, -(state.page.max(1) as isize));
+            None
+        }
+        Msg::Action(Action::NextGroup) => {
+            if !state.file_groups.is_empty() {
+                state.focus = (state.focus + 1) % state.file_groups.len();
+                move_selection(state, 0);
+            }
+            None
+        }
+        Msg::Edit(key) if state.settings.is_some() => edit_settings(state, key),
+        Msg::Edit(key) if state.tag_input.is_some() => edit_tag(state, key),
+        Msg::Edit(key) => edit_note(state, key),
+        Msg::Action(Action::Pause) => {
+            state.paused = !state.paused;
+            state.message = Some(if state.paused { "paused" } else { "resumed" }.to_string());
+            None
+        }
+        Msg::Signal(Signal::Stop) => Some(Cmd::Quit),
+        Msg::Signal(Signal::Reload) => Some(Cmd::Reload),
+        Msg::Done(message) => {
+            state.message = Some(message);
+            None
+        }
+        Msg::Tick => {
+            // the rest are read in the background if they're needed
+            view::apply_sorts(state);
+            let unread = state.show_columns
+                && !state.columns.is_empty()
+                && state
+                    .file_groups
+                    .iter()
+                    .any(|group| view::on_screen(state, group).any(|item| !item.metadata_read));
+            unread.then_some(Cmd::ReadVisible)
+        }
+    }
+}
+
+/// Move the selection in the focused group by some files, starting at the first file shown if
+/// nothing in the group is selected yet.
+fn move_selection(state: &mut AppState, by: isize) {
+    if state.file_groups.is_empty() {
+        return;
+    }
+    // groups may have gone since the focus was last moved
+    state.focus %= state.file_groups.len();
+    let group = &state.file_groups[state.focus];
+    let filter = state.tag_filter.as_deref();
+    let shown = group
+        .items()
+        .iter()
+        .filter(|item| crate::is_shown(item, filter))
+        .count();
+    let selection = state.selections.entry(group.root.clone()).or_default();
+    let selected = match selection.selected() {
+        _ if shown == 0 => None,
+        None => Some(0),
+        Some(selected) => Some(selected.saturating_add_signed(by).min(shown - 1)),
+    };
+    selection.select(selected);
+}
+
+/// Move between the settings, or type a new value for one.
+fn edit_settings(state: &mut AppState, key: KeyEvent) -> Option<Cmd> {
+    let settings = state.settings.as_mut()?;
+    let last = settings.values.len().saturating_sub(1);
+    match (&mut settings.input, key.code) {
+        (None, KeyCode::Esc | KeyCode::Char('q')) => state.settings = None,
+        (None, KeyCode::Up | KeyCode::Char('k')) => {
+            settings.selected = settings.selected.saturating_sub(1)
+        }
+        (None, KeyCode::Down | KeyCode::Char('j')) => {
+            settings.selected = (settings.selected + 1).min(last)
+        }
+        (None, KeyCode::Enter) => {
+            settings.input = Some(settings.values[settings.selected].1.clone());
+            settings.error = None;
+        }
+        (Some(_), KeyCode::Esc) => {
+            settings.input = None;
+            settings.error = None;
+        }
+        (Some(_), KeyCode::Enter) => return Some(Cmd::SaveSetting),
+        (Some(input), KeyCode::Backspace) => {
+            input.pop();
+        }
+        (Some(input), KeyCode::Char(c)) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+            input.push(c)
+        }
+        _ => {}
+    }
+    None
+}
+
+/// Type a key into the tag being given or taken away.
+fn edit_tag(state: &mut AppState, key: KeyEvent) -> Option<Cmd> {
+    let input = state.tag_input.as_mut()?;
+    match key.code {
+        KeyCode::Esc => state.tag_input = None,
+        KeyCode::Enter if input.text.is_empty() => state.tag_input = None,
+        KeyCode::Enter => return Some(Cmd::SaveTag),
+        KeyCode::Backspace => {
+            input.text.pop();
+        }
+        // tags are single words, as on the control socket
+        KeyCode::Char(c)
+            if !c.is_whitespace() && !key.modifiers.contains(KeyModifiers::CONTROL) =>
+        {
+            input.text.push(c)
+        }
+        _ => {}
+    }
+    None
+}
+
+/// Type a key into the note being edited.
+fn edit_note(state: &mut AppState, key: KeyEvent) -> Option<Cmd> {
+    let group = state.note_input.as_ref()?.group;
+    let count = state.file_groups.len();
+    match key.code {
+        KeyCode::Esc => state.note_input = None,
+        KeyCode::Enter => {
+            let input = state.note_input.take()?;
+            let text = input.text.trim();
+            state.file_groups.get_mut(group)?.note = (!text.is_empty()).then(|| text.to_string());
+            return Some(Cmd::SaveNote(group));
+        }
+        KeyCode::Tab => state.note_input = Some(NoteInput::for_group(state, (group + 1) % count)),
+        KeyCode::BackTab => {
+            state.note_input = Some(NoteInput::for_group(state, (group + count - 1) % count))
+        }
+        KeyCode::Backspace => {
+            state.note_input.as_mut()?.text.pop();
+        }
+        KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+            let text = &mut state.note_input.as_mut()?.text;
+            if text.chars().count() < NOTE_LENGTH {
+                text.push(c);
+            }
+        }
+        _ => {}
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use std::time::Instant;
+
+    use itertools::assert_equal;
+
+    use super::*;
+    use crate::filesystem::{FileItem, Sort};
+    use crate::settings::{Setting, SettingsEditor};
+    use crate::view::Column;
+
+    #[test]
+    fn pause_and_commands() {
+        let mut state = AppState::default();
+
+        assert_eq!(update(&mut state, Msg::Action(Action::Pause)), None);
+        assert!(state.paused);
+        assert_eq!(state.message.as_deref(), Some("paused"));
+        assert_eq!(update(&mut state, Msg::Action(Action::Pause)), None);
+        assert!(!state.paused);
+
+        assert_eq!(
+            update(&mut state, Msg::Action(Action::Export)),
+            Some(Cmd::Export)
+        );
+        assert_eq!(update(&mut state, Msg::Done("exported".to_string())), None);
+        assert_eq!(state.message.as_deref(), Some("exported"));
+
+        assert_eq!(
+            update(&mut state, Msg::Action(Action::Follow)),
+            Some(Cmd::Follow)
+        );
+    }
+
+    #[test]
+    fn switch_stats_to_extensions() {
+        let mut state = AppState::default();
+
+        assert_eq!(
+            update(&mut state, Msg::Action(Action::Extensions)),
+            Some(Cmd::Stats)
+        );
+        assert!(state.by_extension);
+        state.stats = Some(vec![]);
+        assert_eq!(update(&mut state, Msg::Action(Action::Extensions)), None);
+        assert!(!state.by_extension);
+        assert_eq!(update(&mut state, Msg::Action(Action::Extensions)), None);
+        assert!(state.by_extension);
+
+        assert_eq!(update(&mut state, Msg::Action(Action::Stats)), None);
+        assert_eq!(state.stats, None);
+        assert!(!state.by_extension);
+    }
+
+    #[test]
+    fn clear_removed_files() {
+        let mut group = FileGroup::new(
+            PathBuf::from("/in"),
+            None,
+            vec![
+                FileItem::new(PathBuf::from("a")),
+                FileItem::new(PathBuf::from("b")),
+            ],
+        );
+        group.get_mut(Path::new("/in/a")).unwrap().removed = Some(Instant::now());
+        let mut state = AppState {
+            file_groups: vec![group],
+            ..AppState::default()
+        };
+
+        assert_eq!(update(&mut state, Msg::Action(Action::ClearRemoved)), None);
+        assert_eq!(state.message.as_deref(), Some("cleared 1 removed files"));
+        assert_equal(
+            state.file_groups[0].items(),
+            &[FileItem::new(PathBuf::from("b"))],
+        );
+    }
+
+    #[test]
+    fn cycle_tag_filter() {
+        let tagged = |name: &str, tags: &[&str]| FileItem {
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            ..FileItem::new(PathBuf::from(name))
+        };
+        let group = FileGroup::new(
+            PathBuf::from("/in"),
+            None,
+            vec![
+                tagged("a", &["problem"]),
+                tagged("b", &["checked", "problem"]),
+            ],
+        );
+        let mut state = AppState {
+            file_groups: vec![group],
+            ..AppState::default()
+        };
+
+        let mut filters = vec![];
+        for _ in 0..3 {
+            update(&mut state, Msg::Action(Action::FilterTag));
+            filters.push(state.tag_filter.clone());
+        }
+        assert_eq!(
+            filters,
+            vec![
+                Some("checked".to_string()),
+                Some("problem".to_string()),
+                None
+            ]
+        );
+        assert_eq!(state.message.as_deref(), Some("showing all files"));
+    }
+
+    #[test]
+    fn select_and_scroll() {
+        let group = |root: &str, count: usize| {
+            let items = (0..count)
+                .map(|i| FileItem::new(PathBuf::from(i.to_string())))
+                .collect();
+            FileGroup::new(PathBuf::from(root), None, items)
+        };
+        let mut state = AppState {
+            file_groups: vec![group("/a", 30), group("/b", 2)],
+            page: 10,
+            ..AppState::default()
+        };
+        let selected =
+            |state: &AppState, root: &str| state.selections.get(Path::new(root))?.selected();
+
+        update(&mut state, Msg::Action(Action::Down));
+        assert_eq!(selected(&state, "/a"), Some(0));
+        update(&mut state, Msg::Action(Action::Down));
+        update(&mut state, Msg::Action(Action::PageDown));
+        assert_eq!(selected(&state, "/a"), Some(11));
+        for _ in 0..3 {
+            update(&mut state, Msg::Action(Action::PageDown));
+        }
+        assert_eq!(selected(&state, "/a"), Some(29));
+        update(&mut state, Msg::Action(Action::PageUp));
+        update(&mut state, Msg::Action(Action::Up));
+        assert_eq!(selected(&state, "/a"), Some(18));
+
+        update(&mut state, Msg::Action(Action::NextGroup));
+        assert_eq!(selected(&state, "/b"), Some(0));
+        update(&mut state, Msg::Action(Action::Up));
+        assert_eq!(selected(&state, "/b"), Some(0));
+        update(&mut state, Msg::Action(Action::NextGroup));
+        update(&mut state, Msg::Action(Action::Down));
+        assert_eq!(selected(&state, "/a"), Some(19));
+    }
+
+    #[test]
+    fn read_the_rows_on_screen() {
+        let items = (0..40)
+            .map(|i| FileItem::new(PathBuf::from(format!("{:02}", i))))
+            .collect();
+        let mut state = AppState {
+            file_groups: vec![FileGroup::new(PathBuf::from("/a"), None, items)],
+            page: 10,
+            ..AppState::default()
+        };
+        // without columns there's nothing to show them in
+        assert_eq!(update(&mut state, Msg::Tick), None);
+
+        state.show_columns = true;
+        state.columns = vec![Column::Size];
+        assert_eq!(update(&mut state, Msg::Tick), Some(Cmd::ReadVisible));
+        let shown = view::on_screen(&state, &state.file_groups[0])
+            .map(|item| item.name.to_path_buf())
+            .collect::<Vec<_>>();
+        assert_eq!(shown.len(), 26);
+        for name in shown {
+            let item = state.file_groups[0].get_mut(&name).unwrap();
+            item.set_metadata((Some(0), None));
+        }
+        assert_eq!(update(&mut state, Msg::Tick), None);
+
+        // scrolling to the end brings unread ones on screen
+        state
+            .selections
+            .entry(PathBuf::from("/a"))
+            .or_default()
+            .select(Some(39));
+        assert_eq!(update(&mut state, Msg::Tick), Some(Cmd::ReadVisible));
+    }
+
+    #[test]
+    fn selected_as_listed() {
+        let items = ["b", "c", "a"]
+            .iter()
+            .map(|name| FileItem::new(PathBuf::from(name)))
+            .collect();
+        let mut state = AppState {
+            file_groups: vec![FileGroup::new(PathBuf::from("/in"), None, items)],
+            ..AppState::default()
+        };
+        let selected = |state: &AppState| {
+            selected_item(state).map(|(group, item)| group.path(item).display().to_string())
+        };
+
+        assert_eq!(selected(&state), None);
+        update(&mut state, Msg::Action(Action::Down));
+        update(&mut state, Msg::Action(Action::Down));
+        assert_eq!(selected(&state).as_deref(), Some("/in/c"));
+        state.sort = Sort::Name;
+        view::apply_sorts(&mut state);
+        assert_eq!(selected(&state).as_deref(), Some("/in/b"));
+    }
+
+    #[test]
+    fn tag_the_selected_file() {
+        let key = |code| Msg::Edit(KeyEvent::new(code, KeyModifiers::NONE));
+        let item = FileItem {
+            tags: vec!["new".to_string()],
+            ..FileItem::new(PathBuf::from("a"))
+        };
+        let mut state = AppState {
+            file_groups: vec![FileGroup::new(PathBuf::from("/in"), None, vec![item])],
+            ..AppState::default()
+        };
+
+        update(&mut state, Msg::Action(Action::Tag));
+        assert_eq!(state.tag_input, None);
+        assert_eq!(state.message.as_deref(), Some("select a file first"));
+
+        update(&mut state, Msg::Action(Action::Down));
+        update(&mut state, Msg::Action(Action::Tag));
+        for c in "on hold".chars() {
+            update(&mut state, key(KeyCode::Char(c)));
+        }
+        assert_eq!(update(&mut state, key(KeyCode::Enter)), Some(Cmd::SaveTag));
+        assert_eq!(
+            state.tag_input,
+            Some(TagInput {
+                path: PathBuf::from("/in/a"),
+                text: "onhold".to_string(),
+                on: true,
+            })
+        );
+
+        state.tag_input = None;
+        update(&mut state, Msg::Action(Action::Untag));
+        let input = state.tag_input.as_ref().unwrap();
+        assert_eq!((input.text.as_str(), input.on), ("new", false));
+        assert_eq!(update(&mut state, key(KeyCode::Esc)), None);
+        assert_eq!(state.tag_input, None);
+    }
+
+    #[test]
+    fn clear_the_focused_group() {
+        let group = |root: &str| {
+            let mut group = FileGroup::new(
+                PathBuf::from(root),
+                None,
+                vec![FileItem::new(PathBuf::from("a"))],
+            );
+            group.get_mut(&Path::new(root).join("a")).unwrap().removed = Some(Instant::now());
+            group
+        };
+        let mut state = AppState {
+            file_groups: vec![group("/a"), group("/b")],
+            focus: 1,
+            ..AppState::default()
+        };
+
+        update(&mut state, Msg::Action(Action::ClearGroup));
+        assert_eq!(
+            state.message.as_deref(),
+            Some("cleared 1 removed files from b")
+        );
+        assert_eq!(state.file_groups[0].items().len(), 1);
+        assert!(state.file_groups[1].items().is_empty());
+    }
+
+    #[test]
+    fn edit_a_note() {
+        let key = |code| Msg::Edit(KeyEvent::new(code, KeyModifiers::NONE));
+        let group = |root: &str| FileGroup::new(PathBuf::from(root), None, vec![]);
+        let mut state = AppState {
+            file_groups: vec![group("/a"), group("/b")],
+            ..AppState::default()
+        };
+        state.file_groups[1].note = Some("old".to_string());
+
+        update(&mut state, Msg::Action(Action::EditNote));
+        update(&mut state, key(KeyCode::Tab));
+        assert_eq!(state.note_input.as_ref().unwrap().text, "old");
+        for code in [KeyCode::Backspace, KeyCode::Backspace, KeyCode::Backspace] {
+            update(&mut state, key(code));
+        }
+        for c in "paused".chars() {
+            update(&mut state, key(KeyCode::Char(c)));
+        }
+        assert_eq!(
+            update(&mut state, key(KeyCode::Enter)),
+            Some(Cmd::SaveNote(1))
+        );
+        assert_eq!(state.note_input, None);
+        assert_eq!(state.file_groups[1].note.as_deref(), Some("paused"));
+
+        // nothing changes when it's cancelled
+        update(&mut state, Msg::Action(Action::EditNote));
+        update(&mut state, key(KeyCode::Char('x')));
+        assert_eq!(update(&mut state, key(KeyCode::Esc)), None);
+        assert_eq!(state.file_groups[0].note, None);
+    }
+
+    #[test]
+    fn change_a_setting() {
+        let key = |code| Msg::Edit(KeyEvent::new(code, KeyModifiers::NONE));
+        let mut state = AppState {
+            settings: Some(SettingsEditor {
+                path: PathBuf::from("/etc/file_task.toml"),
+                profile: None,
+                values: vec![
+                    (Setting::Paths, "/in".to_string()),
+                    (Setting::MaxItems, "50".to_string()),
+                ],
+                selected: 0,
+                input: None,
+                error: None,
+            }),
+            ..AppState::default()
+        };
+
+        for code in [KeyCode::Down, KeyCode::Down, KeyCode::Enter] {
+            update(&mut state, key(code));
+        }
+        update(&mut state, key(KeyCode::Char('0')));
+        let settings = state.settings.as_ref().unwrap();
+        assert_eq!(settings.setting(), Setting::MaxItems);
+        assert_eq!(settings.input.as_deref(), Some("500"));
+        assert_eq!(
+            update(&mut state, key(KeyCode::Enter)),
+            Some(Cmd::SaveSetting)
+        );
+
+        // esc stops typing, then leaves the screen
+        update(&mut state, key(KeyCode::Esc));
+        assert_eq!(state.settings.as_ref().unwrap().input, None);
+        update(&mut state, key(KeyCode::Esc));
+        assert_eq!(state.settings, None);
+    }
+}
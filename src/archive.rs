@@ -0,0 +1,149 @@
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+
+use flate2::read::GzDecoder;
+
+/// A file or directory inside an archive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub name: String,
+    /// Uncompressed, in bytes
+    pub size: u64,
+}
+
+/// What's inside an archive, read without extracting anything.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Peek {
+    pub path: PathBuf,
+    pub entries: Vec<Entry>,
+}
+
+impl Peek {
+    pub fn open(path: PathBuf) -> Result<Self, String> {
+        let entries =
+            list(&path).map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+        Ok(Self { path, entries })
+    }
+
+    /// The total uncompressed size of everything in the archive.
+    pub fn size(&self) -> u64 {
+        self.entries.iter().map(|entry| entry.size).sum()
+    }
+}
+
+/// Whether a file looks like an archive that can be peeked into, by its name.
+pub fn is_archive(path: &Path) -> bool {
+    kind(path).is_some()
+}
+
+enum Kind {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+fn kind(path: &Path) -> Option<Kind> {
+    let name = path.file_name()?.to_str()?.to_lowercase();
+    if name.ends_with(".zip") {
+        Some(Kind::Zip)
+    } else if name.ends_with(".tar") {
+        Some(Kind::Tar)
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        Some(Kind::TarGz)
+    } else {
+        None
+    }
+}
+
+fn list(path: &Path) -> io::Result<Vec<Entry>> {
+    let file = BufReader::new(File::open(path)?);
+    match kind(path) {
+        Some(Kind::Zip) => list_zip(file),
+        Some(Kind::Tar) => list_tar(file),
+        Some(Kind::TarGz) => list_tar(GzDecoder::new(file)),
+        None => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "not a .zip, .tar, or .tar.gz file",
+        )),
+    }
+}
+
+fn list_zip(file: BufReader<File>) -> io::Result<Vec<Entry>> {
+    let mut archive = zip::ZipArchive::new(file).map_err(io::Error::other)?;
+    (0..archive.len())
+        .map(|i| {
+            let entry = archive.by_index_raw(i).map_err(io::Error::other)?;
+            Ok(Entry {
+                name: entry.name().to_string(),
+                size: entry.size(),
+            })
+        })
+        .collect()
+}
+
+fn list_tar(reader: impl Read) -> io::Result<Vec<Entry>> {
+    tar::Archive::new(reader)
+        .entries()?
+        .map(|entry| {
+            let entry = entry?;
+            Ok(Entry {
+                name: entry.path()?.display().to_string(),
+                size: entry.header().size()?,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    use super::*;
+
+    fn entries(peek: &Peek) -> Vec<(&str, u64)> {
+        peek.entries
+            .iter()
+            .map(|e| (e.name.as_str(), e.size))
+            .collect()
+    }
+
+    #[test]
+    fn peek_into_archives() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+
+        let zip_path = dir.join("delivery.zip");
+        let mut zip = zip::ZipWriter::new(File::create(&zip_path).unwrap());
+        let options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Stored);
+        zip.start_file("report.csv", options).unwrap();
+        zip.write_all(b"a,b\n1,2\n").unwrap();
+        zip.start_file("images/logo.png", options).unwrap();
+        zip.write_all(b"png").unwrap();
+        zip.finish().unwrap();
+
+        let peek = Peek::open(zip_path).unwrap();
+        assert_eq!(entries(&peek), [("report.csv", 8), ("images/logo.png", 3)]);
+        assert_eq!(peek.size(), 11);
+
+        let tgz_path = dir.join("delivery.tar.gz");
+        let gz = GzEncoder::new(File::create(&tgz_path).unwrap(), Compression::default());
+        let mut tar = tar::Builder::new(gz);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(5);
+        header.set_cksum();
+        tar.append_data(&mut header, "data/part1", &b"hello"[..])
+            .unwrap();
+        tar.into_inner().unwrap().finish().unwrap();
+
+        let peek = Peek::open(tgz_path).unwrap();
+        assert_eq!(entries(&peek), [("data/part1", 5)]);
+
+        assert!(!is_archive(Path::new("notes.txt")));
+        assert!(Peek::open(dir.join("missing.tgz")).is_err());
+    }
+}
@@ -0,0 +1,477 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossterm::event::Event;
+use serde_json::Value;
+use tui::backend::Backend;
+use tui::layout::{Constraint, Direction, Layout};
+use tui::style::{Color, Style};
+use tui::text::{Line, Span};
+use tui::widgets::{Block, Borders, Paragraph};
+use tui::Frame;
+
+use crate::cli::AttachArgs;
+use crate::control::{self, Connection};
+use crate::debug::Internals;
+use crate::filesystem::{FileGroup, FileItem, Signature, Sort, Writer};
+use crate::highlight::Highlights;
+use crate::keymap::{Action, KeyMap};
+use crate::reactor::{Reactor, Wake, Waker};
+use crate::service::{ServiceDetails, ServiceState};
+use crate::timezone::Timezone;
+use crate::{terminal, AppState};
+
+/// How long to wait before connecting again after losing the daemon.
+const RECONNECT: Duration = Duration::from_secs(5);
+
+/// What a connection thread has to tell the dashboard.
+enum Update {
+    State(Value),
+    Message(String),
+}
+
+/// One daemon being shown, with its last reported state.
+struct Host {
+    address: String,
+    /// How its groups and messages are labelled when there's more than one host
+    name: String,
+    state: Option<Value>,
+}
+
+/// Show remote daemons' state in the dashboard, sending them the commands for bound actions.
+pub fn attach(
+    args: AttachArgs,
+    keymap: &KeyMap,
+    highlights: &Highlights,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut reactor = Reactor::new(crate::SERVICE_POLL)?;
+    let (updates_tx, updates) = channel();
+    let mut hosts = vec![];
+    let mut commands = vec![];
+    for (index, address) in args.addresses.iter().enumerate() {
+        let (tx, pending) = channel();
+        let updates = updates_tx.clone();
+        let relayed = address.clone();
+        let waker = reactor.waker();
+        thread::spawn(move || relay(&relayed, &pending, index, &updates, &waker));
+        commands.push(tx);
+        hosts.push(Host {
+            address: address.clone(),
+            name: host_name(address).to_string(),
+            state: None,
+        });
+    }
+    let several = hosts.len() > 1;
+
+    let timezone = args.timezone.unwrap_or_default();
+    let mut message = None;
+    let mut state = terminal::TerminalState::init()?;
+    reactor.read_input();
+    // keys pressed since the last draw, each handled with a redraw after it
+    let mut actions = VecDeque::new();
+
+    loop {
+        for (index, update) in updates.try_iter() {
+            let host = &mut hosts[index];
+            match update {
+                Update::State(value) => host.state = Some(value),
+                Update::Message(text) if several => {
+                    message = Some(format!("{}: {}", host.name, text))
+                }
+                Update::Message(text) => message = Some(text),
+            }
+        }
+        let mut views = hosts
+            .iter()
+            .map(|host| {
+                let prefix = several.then_some(host.name.as_str());
+                host.state
+                    .as_ref()
+                    .map(|s| app_state(s, timezone, prefix, highlights))
+            })
+            .collect::<Vec<_>>();
+
+        state
+            .terminal
+            .draw(|f| ui(f, &hosts, &mut views, message.as_deref(), timezone))?;
+        reactor.wake_at(crate::next_minute());
+
+        // the hosts' threads wake the loop as their states come in
+        if actions.is_empty() {
+            let keys = reactor.wait().into_iter().filter_map(|wake| match wake {
+                Wake::Input(Event::Key(key)) => keymap.action(key.into()),
+                _ => None,
+            });
+            actions.extend(keys);
+        }
+        match actions.pop_front() {
+            Some(Action::Quit) => return Ok(()),
+            Some(Action::Pause) => {
+                // pause everything unless it's all paused already
+                let paused = views.iter().flatten().all(|data| data.paused);
+                for tx in commands.iter() {
+                    let _ = tx.send(if paused { "resume" } else { "pause" });
+                }
+            }
+            Some(Action::Suspend) => terminal::suspend(&mut state.terminal)?,
+            Some(Action::Rescan) => {
+                for tx in commands.iter() {
+                    let _ = tx.send("rescan");
+                }
+            }
+            Some(Action::ClearRemoved) => {
+                for tx in commands.iter() {
+                    let _ = tx.send("clear");
+                }
+            }
+            Some(Action::Export) => {
+                let all = AppState {
+                    file_groups: views
+                        .into_iter()
+                        .flatten()
+                        .flat_map(|data| data.file_groups)
+                        .collect(),
+                    timezone,
+                    ..AppState::default()
+                };
+                message = Some(crate::export_csv(&all));
+            }
+            Some(Action::SaveSession) => {
+                message = Some("settings can't be saved while attached".to_string())
+            }
+            Some(Action::EditNote) => {
+                message = Some("notes can't be edited while attached".to_string())
+            }
+            Some(Action::FilterTag) => {
+                message = Some("tags can't be filtered while attached".to_string())
+            }
+            Some(Action::Tag | Action::Untag) => {
+                message = Some("files can't be tagged while attached".to_string())
+            }
+            Some(Action::ClearGroup) => {
+                message = Some("groups can't be focused while attached".to_string())
+            }
+            Some(Action::Settings) => {
+                message = Some("settings can't be changed while attached".to_string())
+            }
+            Some(Action::Health) => {
+                message = Some("checks can't be listed while attached".to_string())
+            }
+            Some(Action::Debug) => {
+                message =
+                    Some("the dashboard's internals can't be shown while attached".to_string())
+            }
+            Some(Action::Notifications) => {
+                message = Some("alerts raised can't be listed while attached".to_string())
+            }
+            Some(Action::Heatmap) => {
+                message = Some("history can't be read while attached".to_string())
+            }
+            Some(Action::Stats | Action::Extensions | Action::Columns) => {
+                message = Some("file sizes can't be read while attached".to_string())
+            }
+            Some(Action::Holders) => {
+                message = Some("open files can't be looked up while attached".to_string())
+            }
+            Some(Action::Peek) => {
+                message = Some("archives can't be looked inside while attached".to_string())
+            }
+            Some(
+                Action::Down | Action::Up | Action::PageDown | Action::PageUp | Action::NextGroup,
+            ) => message = Some("files can't be selected while attached".to_string()),
+
+            Some(Action::Sort) => {
+                message = Some("files can't be reordered while attached".to_string())
+            }
+            Some(Action::Follow) => {
+                message = Some("files can't be followed while attached".to_string())
+            }
+            Some(Action::Undo) => {
+                message = Some("actions can't be undone while attached".to_string())
+            }
+            None => {}
+        }
+    }
+}
+
+/// The host part of an address, e.g. "build1" for "build1:7070".
+fn host_name(address: &str) -> &str {
+    address.rsplit_once(':').map_or(address, |(host, _)| host)
+}
+
+/// Each host's groups stacked in a column of its own, above one status bar for all of them.
+fn ui<B: Backend>(
+    frame: &mut Frame<B>,
+    hosts: &[Host],
+    views: &mut [Option<AppState>],
+    message: Option<&str>,
+    timezone: Timezone,
+) {
+    const STATUS_BAR_HEIGHT: u16 = 1;
+    let areas = Layout::default()
+        .constraints([Constraint::Min(0), Constraint::Length(STATUS_BAR_HEIGHT)])
+        .split(frame.size());
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(vec![Constraint::Ratio(1, hosts.len() as u32); hosts.len()])
+        .split(areas[0]);
+
+    for (view, area) in views.iter_mut().zip(columns.iter()) {
+        if let Some(data) = view.as_mut().filter(|data| !data.file_groups.is_empty()) {
+            crate::view::draw_groups(frame, data, *area);
+        }
+    }
+
+    let mut bar = crate::view::draw_time(timezone.now());
+    for ((host, view), area) in hosts.iter().zip(views.iter()).zip(columns.iter()) {
+        bar.push(Span::raw(format!(" {}", host.name)));
+        match view {
+            Some(data) if !data.file_groups.is_empty() => {
+                bar.extend(crate::view::draw_service_status(data));
+                bar.extend(crate::view::draw_paused(data));
+            }
+            _ => {
+                let text = match view {
+                    Some(_) => "no watched paths".to_string(),
+                    None => format!("connecting to {}...", host.address),
+                };
+                let block = Block::default()
+                    .title(host.name.as_str())
+                    .borders(Borders::ALL);
+                frame.render_widget(Paragraph::new(text).block(block), *area);
+            }
+        }
+    }
+    if let Some(message) = message {
+        bar.extend([Span::raw(" "), Span::raw(message)]);
+    }
+
+    let bar = Paragraph::new(Line::from(bar)).style(Style::default().bg(Color::Blue));
+    frame.render_widget(bar, areas[1]);
+}
+
+/// Keep asking the daemon for its state, passing on commands in between and waking the
+/// dashboard with each reply, until the dashboard goes away.
+fn relay(
+    address: &str,
+    commands: &Receiver<&'static str>,
+    index: usize,
+    updates: &Sender<(usize, Update)>,
+    waker: &Waker,
+) {
+    let send = |update| {
+        let sent = updates.send((index, update)).is_ok();
+        waker.wake();
+        sent
+    };
+    let mut connection = None;
+    let mut lost = false;
+    loop {
+        let remote = match connection.as_mut() {
+            Some(remote) => remote,
+            None => match Connection::connect(address) {
+                Ok(remote) => {
+                    if lost {
+                        send(Update::Message(format!("reconnected to {}", address)));
+                    }
+                    connection.insert(remote)
+                }
+                Err(e) => {
+                    if !send(Update::Message(e)) {
+                        return;
+                    }
+                    lost = true;
+                    thread::sleep(RECONNECT);
+                    continue;
+                }
+            },
+        };
+
+        let mut results = commands
+            .try_iter()
+            .map(|command| {
+                remote
+                    .request(command)
+                    .map(|_| Update::Message(format!("{}: ok", command)))
+            })
+            .collect::<Vec<_>>();
+        // the reply only comes after the daemon's next tick, so this also paces the loop
+        results.push(remote.request("state").and_then(|reply| {
+            serde_json::from_str(&reply)
+                .map(Update::State)
+                .map_err(|e| format!("unexpected reply from {}: {}", address, e))
+        }));
+
+        for result in results {
+            let update = result.unwrap_or_else(|e| {
+                // start over rather than risk reading replies meant for earlier requests
+                connection = None;
+                lost = true;
+                Update::Message(e)
+            });
+            if !send(update) {
+                return;
+            }
+        }
+    }
+}
+
+/// Rebuild enough of the dashboard's state from a `state` reply to draw it.
+fn app_state(
+    state: &Value,
+    timezone: Timezone,
+    prefix: Option<&str>,
+    highlights: &Highlights,
+) -> AppState {
+    let file_groups = array(&state["groups"])
+        .map(|group| {
+            let mut file_group = FileGroup::new(
+                PathBuf::from(group["root"].as_str().unwrap_or_default()),
+                group["title"].as_str().map(|title| match prefix {
+                    Some(prefix) => format!("{}: {}", prefix, title),
+                    None => title.to_string(),
+                }),
+                vec![],
+            );
+            for file in array(&group["files"]) {
+                let path = Path::new(file["path"].as_str().unwrap_or_default());
+                file_group.insert(FileItem {
+                    // only whether it's been removed is known, not when
+                    removed: (file["removed"].as_bool() == Some(true)).then(Instant::now),
+                    tags: array(&file["tags"])
+                        .filter_map(|tag| Some(tag.as_str()?.to_string()))
+                        .collect(),
+                    verified: file["verified"].as_bool(),
+                    signature: file["signature"].as_str().and_then(Signature::from_name),
+                    writer: file["writer"]["pid"].as_u64().map(|pid| Writer {
+                        pid: pid as u32,
+                        uid: file["writer"]["uid"].as_u64().map(|uid| uid as u32),
+                        command: file["writer"]["command"]
+                            .as_str()
+                            .unwrap_or_default()
+                            .to_string(),
+                    }),
+                    ..FileItem::new(file_group.name(path))
+                });
+            }
+            file_group.hidden = group["hidden"].as_u64().unwrap_or_default() as usize;
+            file_group.note = group["note"].as_str().map(str::to_string);
+            file_group
+        })
+        .collect();
+
+    let services = control::services(state)
+        .into_iter()
+        .filter_map(|service| {
+            let name = service["name"].as_str()?;
+            Some(
+                match (service["active"].as_bool(), service["status"].as_str()) {
+                    (Some(active), status) => ServiceState::Details(ServiceDetails::new(
+                        name.to_string(),
+                        active,
+                        status.unwrap_or_default().to_string(),
+                    )),
+                    (None, _) => ServiceState::Unknown(name.to_string()),
+                },
+            )
+        })
+        .collect();
+
+    AppState {
+        file_groups,
+        services,
+        message: None,
+        timezone,
+        paused: state["paused"].as_bool().unwrap_or_default(),
+        tag_filter: None,
+        focus: 0,
+        selections: HashMap::new(),
+        page: 0,
+        columns: vec![],
+        show_columns: false,
+        sort: Sort::default(),
+        sorts: HashMap::new(),
+        note_input: None,
+        tag_input: None,
+        highlights: highlights.clone(),
+        breached: HashSet::new(),
+        out_of_sync: HashSet::new(),
+        lags: vec![],
+        disks: vec![],
+        low_space: vec![],
+        unmounted: HashMap::new(),
+        follow: None,
+        follow_highlights: Highlights::default(),
+        peek: None,
+        holders: None,
+        stats: None,
+        by_extension: false,
+        heatmap: None,
+        notifications: Default::default(),
+        show_notifications: false,
+        show_debug: false,
+        internals: Internals::default(),
+        health: vec![],
+        show_health: false,
+        settings: None,
+    }
+}
+
+fn array(value: &Value) -> impl Iterator<Item = &Value> {
+    value.as_array().into_iter().flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn host_names() {
+        assert_eq!(host_name("build1:7070"), "build1");
+        assert_eq!(host_name("[::1]:7070"), "[::1]");
+        assert_eq!(host_name("build1"), "build1");
+    }
+
+    #[test]
+    fn state_from_reply() {
+        let state = json!({
+            "profile": null,
+            "paused": true,
+            "service": { "name": "web", "active": false, "status": "failed" },
+            "groups": [{ "title": "In", "root": "/in", "files": [
+                { "path": "/in/a", "removed": false },
+                { "path": "/in/b", "removed": true },
+            ] }],
+        });
+
+        let data = app_state(
+            &state,
+            Timezone::default(),
+            Some("build1"),
+            &Highlights::default(),
+        );
+
+        assert!(data.paused);
+        assert_eq!(data.file_groups[0].title.as_deref(), Some("build1: In"));
+        assert_eq!(data.file_groups[0].root, PathBuf::from("/in"));
+        let removed = data.file_groups[0]
+            .items()
+            .iter()
+            .map(|f| f.removed.is_some())
+            .collect::<Vec<_>>();
+        assert_eq!(removed, vec![false, true]);
+        assert_eq!(
+            data.services,
+            [ServiceState::Details(ServiceDetails::new(
+                "web".to_string(),
+                false,
+                "failed".to_string()
+            ))]
+        );
+    }
+}
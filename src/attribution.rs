@@ -0,0 +1,220 @@
+use std::collections::{HashMap, HashSet};
+use std::ffi::CString;
+use std::fs;
+use std::io;
+use std::mem::size_of;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::filesystem::{FileGroup, Writer};
+use crate::reactor::Waker;
+
+/// How long to hold on to a writer for a file the watcher hasn't reported yet.
+const UNCLAIMED_FOR: Duration = Duration::from_secs(10);
+
+/// Finds out which process wrote each new file, from fanotify's close-after-writing events on
+/// the groups' roots. Setting up fanotify needs CAP_SYS_ADMIN, so this is only started when asked
+/// for.
+pub struct Attribution {
+    fanotify: Arc<OwnedFd>,
+    marked: HashSet<PathBuf>,
+    done: Receiver<(PathBuf, Writer)>,
+    /// Writers of files not tracked yet, in case the watcher's event comes after fanotify's
+    unclaimed: HashMap<PathBuf, (Writer, Instant)>,
+}
+
+impl Attribution {
+    pub fn start(waker: Waker) -> io::Result<Self> {
+        let fd = unsafe {
+            libc::fanotify_init(
+                libc::FAN_CLASS_NOTIF | libc::FAN_CLOEXEC,
+                (libc::O_RDONLY | libc::O_CLOEXEC | libc::O_LARGEFILE) as u32,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let fanotify = Arc::new(unsafe { OwnedFd::from_raw_fd(fd) });
+        let (tx, done) = channel();
+        let reader = Arc::clone(&fanotify);
+        thread::spawn(move || {
+            // the kernel lays events out aligned for reading in place
+            let mut buffer = vec![0u64; 512];
+            loop {
+                let read = unsafe {
+                    libc::read(
+                        reader.as_raw_fd(),
+                        buffer.as_mut_ptr().cast(),
+                        buffer.len() * size_of::<u64>(),
+                    )
+                };
+                if read < 0 {
+                    if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted {
+                        continue;
+                    }
+                    return;
+                }
+                let bytes = unsafe {
+                    std::slice::from_raw_parts(buffer.as_ptr().cast::<u8>(), read as usize)
+                };
+                for (fd, pid) in events(bytes) {
+                    let path = fs::read_link(format!("/proc/self/fd/{}", fd));
+                    unsafe { libc::close(fd) };
+                    // what this process does itself, like copying, isn't of interest
+                    if pid == std::process::id() {
+                        continue;
+                    }
+                    let Ok(path) = path else {
+                        continue;
+                    };
+                    if tx.send((path, writer(pid))).is_err() {
+                        return;
+                    }
+                    waker.wake();
+                }
+            }
+        });
+        Ok(Self {
+            fanotify,
+            marked: HashSet::new(),
+            done,
+            unclaimed: HashMap::new(),
+        })
+    }
+
+    /// Start watching any new roots, and note who wrote the files written since the last call on
+    /// the groups' items, keeping the first writer of each.
+    pub fn update(&mut self, groups: &mut [FileGroup]) {
+        for group in groups.iter() {
+            if !self.marked.contains(&group.root) && self.mark(&group.root).is_ok() {
+                self.marked.insert(group.root.clone());
+            }
+        }
+        for (path, writer) in self.done.try_iter() {
+            self.unclaimed
+                .entry(path)
+                .or_insert_with(|| (writer, Instant::now()));
+        }
+        self.unclaimed.retain(|path, (writer, since)| {
+            let item = groups.iter_mut().find_map(|group| group.get_mut(path));
+            match item {
+                Some(item) => {
+                    item.writer.get_or_insert_with(|| writer.clone());
+                    false
+                }
+                None => since.elapsed() < UNCLAIMED_FOR,
+            }
+        });
+    }
+
+    fn mark(&self, root: &Path) -> io::Result<()> {
+        let path = CString::new(root.as_os_str().as_bytes())?;
+        let marked = unsafe {
+            libc::fanotify_mark(
+                self.fanotify.as_raw_fd(),
+                libc::FAN_MARK_ADD,
+                libc::FAN_CLOSE_WRITE | libc::FAN_EVENT_ON_CHILD,
+                libc::AT_FDCWD,
+                path.as_ptr(),
+            )
+        };
+        if marked < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+/// The descriptor and process of each event in what was read from fanotify.
+fn events(mut bytes: &[u8]) -> Vec<(i32, u32)> {
+    let mut events = vec![];
+    while bytes.len() >= size_of::<libc::fanotify_event_metadata>() {
+        let event = unsafe {
+            bytes
+                .as_ptr()
+                .cast::<libc::fanotify_event_metadata>()
+                .read_unaligned()
+        };
+        let len = event.event_len as usize;
+        if event.vers != libc::FANOTIFY_METADATA_VERSION || len < size_of_val(&event) {
+            break;
+        }
+        if event.fd >= 0 {
+            events.push((event.fd, event.pid as u32));
+        }
+        bytes = bytes.get(len..).unwrap_or_default();
+    }
+    events
+}
+
+/// What's known about a process, which may already have exited.
+fn writer(pid: u32) -> Writer {
+    let proc = PathBuf::from(format!("/proc/{}", pid));
+    let command = fs::read_to_string(proc.join("comm")).unwrap_or_default();
+    let status = fs::read_to_string(proc.join("status")).unwrap_or_default();
+    Writer {
+        pid,
+        uid: uid(&status),
+        command: command.trim().to_string(),
+    }
+}
+
+/// The real user id from a process's status.
+fn uid(status: &str) -> Option<u32> {
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("Uid:"))?
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(fd: i32, pid: i32) -> Vec<u8> {
+        let metadata = libc::fanotify_event_metadata {
+            event_len: size_of::<libc::fanotify_event_metadata>() as u32,
+            vers: libc::FANOTIFY_METADATA_VERSION,
+            reserved: 0,
+            metadata_len: size_of::<libc::fanotify_event_metadata>() as u16,
+            mask: libc::FAN_CLOSE_WRITE,
+            fd,
+            pid,
+        };
+        let bytes = unsafe {
+            std::slice::from_raw_parts(
+                (&metadata as *const libc::fanotify_event_metadata).cast::<u8>(),
+                size_of_val(&metadata),
+            )
+        };
+        bytes.to_vec()
+    }
+
+    #[test]
+    fn read_events_and_writers() {
+        let mut bytes = event(7, 1200);
+        bytes.extend(event(libc::FAN_NOFD, 1300));
+        bytes.extend(event(9, 1400));
+        // a partial event at the end is left alone
+        bytes.extend([0; 4]);
+        assert_eq!(events(&bytes), [(7, 1200), (9, 1400)]);
+
+        assert_eq!(
+            uid("Name:\trsync\nUid:\t1001\t1001\t1001\t1001\n"),
+            Some(1001)
+        );
+        assert_eq!(uid("Name:\trsync\n"), None);
+
+        let me = writer(std::process::id());
+        assert_eq!(me.uid, Some(unsafe { libc::getuid() }));
+        assert!(!me.command.is_empty());
+    }
+}
@@ -0,0 +1,180 @@
+use std::io::{self, Write};
+use std::sync::mpsc::{Receiver, Sender};
+
+use crate::filesystem::WatchSource;
+use clap::ValueEnum;
+use serde_json::{json, Value};
+
+use crate::brief::{self, Style};
+use crate::control;
+use crate::filesystem::FileChange;
+use crate::reactor::Wake;
+use crate::{AppState, Session};
+
+const GREEN: &str = "#00ff00";
+const RED: &str = "#ff0000";
+
+/// Which status bar protocol to speak on stdout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum BarFormat {
+    /// The i3bar protocol (also read by swaybar): a header, then an array of blocks per update
+    I3bar,
+    /// One JSON object per update for a waybar custom module with "return-type": "json"
+    Waybar,
+}
+
+/// Watch without a terminal, writing a status bar update to stdout whenever anything changes.
+pub fn run(
+    mut data: AppState,
+    changes: (Sender<FileChange>, Receiver<FileChange>),
+    source: &mut dyn WatchSource,
+    session: &mut Session,
+    format: BarFormat,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut out = io::stdout().lock();
+    if format == BarFormat::I3bar {
+        writeln!(out, "{}", json!({ "version": 1 }))?;
+        // the updates are elements of one endless array
+        writeln!(out, "[")?;
+    }
+
+    let mut last = None;
+    loop {
+        crate::tick(&mut data, &changes, source, session);
+        if let Some(message) = data.message.take() {
+            eprintln!("{}", message);
+        }
+
+        let state = control::state_value(&data, session);
+        let update = match format {
+            BarFormat::I3bar => format!("{},", i3bar_blocks(&state)),
+            BarFormat::Waybar => waybar_module(&state).to_string(),
+        };
+        if last.as_ref() != Some(&update) {
+            // the bar going away is the usual way to be told to stop
+            writeln!(out, "{}", update)?;
+            out.flush()?;
+            last = Some(update);
+        }
+        for wake in session.reactor.wait() {
+            if let Wake::Signal(signal) = wake {
+                if crate::handle_signal(signal, &mut data, source, session)? {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+fn present_files(group: &Value) -> usize {
+    files(group)
+        .filter(|f| f["removed"].as_bool() != Some(true))
+        .count()
+}
+
+fn files(group: &Value) -> impl Iterator<Item = &Value> {
+    group["files"].as_array().into_iter().flatten()
+}
+
+/// A group is urgent while files removed from it are still shown.
+fn group_urgent(group: &Value) -> bool {
+    files(group).any(|f| f["removed"].as_bool() == Some(true))
+}
+
+fn service_down(state: &Value) -> bool {
+    control::services(state)
+        .iter()
+        .any(|service| service["active"].as_bool() == Some(false))
+}
+
+/// One block per group and one per service.
+fn i3bar_blocks(state: &Value) -> Value {
+    let mut blocks = state["groups"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|group| {
+            let title = group["title"].as_str().unwrap_or_default();
+            json!({
+                "name": "file_task_group",
+                "instance": group["root"],
+                "full_text": format!("{}: {}", title, present_files(group)),
+                "short_text": present_files(group).to_string(),
+                "urgent": group_urgent(group),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for service in control::services(state) {
+        let Some(name) = service["name"].as_str() else {
+            continue;
+        };
+        let (text, color) = match service["active"].as_bool() {
+            Some(true) => ("up", GREEN),
+            Some(false) => ("down", RED),
+            None => ("?", RED),
+        };
+        blocks.push(json!({
+            "name": "file_task_service",
+            "instance": name,
+            "full_text": format!("{} {}", name, text),
+            "color": color,
+            "urgent": service["active"].as_bool() == Some(false),
+        }));
+    }
+    if state["paused"].as_bool() == Some(true) {
+        blocks.insert(
+            0,
+            json!({ "name": "file_task_paused", "full_text": "paused" }),
+        );
+    }
+
+    json!(blocks)
+}
+
+/// The brief summary as text, with a line per group in the tooltip.
+fn waybar_module(state: &Value) -> Value {
+    let mut groups = state["groups"].as_array().into_iter().flatten();
+    let tooltip = groups
+        .clone()
+        .map(|group| {
+            let title = group["title"].as_str().unwrap_or_default();
+            format!("{}: {} files", title, present_files(group))
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let urgent = service_down(state) || groups.any(group_urgent);
+
+    json!({
+        "text": brief::summary(state, Style::Plain),
+        "tooltip": tooltip,
+        "class": if urgent { "urgent" } else { "ok" },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn urgent_blocks() {
+        let state = json!({
+            "paused": false,
+            "service": { "name": "web", "active": false, "status": "failed" },
+            "groups": [{ "title": "In", "root": "/in", "files": [
+                { "path": "/in/a", "removed": false },
+                { "path": "/in/b", "removed": true },
+            ] }],
+        });
+
+        let blocks = i3bar_blocks(&state);
+        assert_eq!(blocks[0]["full_text"], "In: 1");
+        assert_eq!(blocks[0]["urgent"], true);
+        assert_eq!(blocks[1]["full_text"], "web down");
+        assert_eq!(blocks[1]["urgent"], true);
+
+        let module = waybar_module(&state);
+        assert_eq!(module["text"], "In:1 web:down");
+        assert_eq!(module["class"], "urgent");
+    }
+}
@@ -0,0 +1,140 @@
+use std::process::{Command, ExitCode, Stdio};
+
+use serde_json::Value;
+
+use crate::cli::BriefArgs;
+use crate::control;
+
+/// The tmux option the daemon keeps up to date with `--tmux`, for `#{@file_task}`.
+const TMUX_OPTION: &str = "@file_task";
+
+/// How to mark up the one line summary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    /// With tmux `#[fg=...]` colors
+    Tmux,
+    Plain,
+}
+
+/// A one line summary of a state from the control socket, e.g. "Inbox:3 Outbox:0 web:up".
+pub fn summary(state: &Value, style: Style) -> String {
+    let colored = |text: String, color: &str| match style {
+        Style::Tmux => format!("#[fg={}]{}#[default]", color, text),
+        Style::Plain => text,
+    };
+
+    let mut parts = vec![];
+    if state["paused"].as_bool() == Some(true) {
+        parts.push(colored("paused".to_string(), "yellow"));
+    }
+    for group in state["groups"].as_array().into_iter().flatten() {
+        let present = group["files"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|f| f["removed"].as_bool() != Some(true))
+            .count();
+        let title = group["title"].as_str().unwrap_or_default();
+        // tmux would read a # in a title as the start of a format
+        let title = match style {
+            Style::Tmux => title.replace('#', "##"),
+            Style::Plain => title.to_string(),
+        };
+        parts.push(format!("{}:{}", title, present));
+    }
+    for service in control::services(state) {
+        let Some(name) = service["name"].as_str() else {
+            continue;
+        };
+        let name = match style {
+            Style::Tmux => name.replace('#', "##"),
+            Style::Plain => name.to_string(),
+        };
+        parts.push(match service["active"].as_bool() {
+            Some(true) => colored(format!("{}:up", name), "green"),
+            Some(false) => colored(format!("{}:down", name), "red"),
+            None => format!("{}:?", name),
+        });
+    }
+    parts.join(" ")
+}
+
+/// Print the summary of a running instance, for `status-brief`.
+pub fn status_brief(args: BriefArgs) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let path = args
+        .watch
+        .control
+        .ok_or("status-brief needs the running instance's --control socket")?;
+    let reply = control::request(&path, "state")?;
+    let state: Value = serde_json::from_str(&reply)
+        .map_err(|e| format!("unexpected reply from {}: {}", path.display(), e))?;
+    let style = if args.plain {
+        Style::Plain
+    } else {
+        Style::Tmux
+    };
+    println!("{}", summary(&state, style));
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Keeps a tmux option set to the current summary, only calling tmux when it changes.
+#[derive(Debug, Default)]
+pub struct Tmux {
+    last: Option<String>,
+}
+
+impl Tmux {
+    pub fn push(&mut self, state: &Value) -> Result<(), String> {
+        let summary = summary(state, Style::Tmux);
+        if self.last.as_ref() == Some(&summary) {
+            return Ok(());
+        }
+        // a failure is reported once and tried again when there's something new to show
+        self.last = Some(summary.clone());
+        let status = Command::new("tmux")
+            .args(["set-option", "-g", TMUX_OPTION, &summary])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .map_err(|e| format!("could not run tmux: {}", e))?;
+        if !status.success() {
+            return Err(format!("tmux set-option failed: {}", status));
+        }
+        // redraw now rather than at the next status-interval; fails harmlessly with no clients
+        let _ = Command::new("tmux")
+            .args(["refresh-client", "-S"])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn summarize_state() {
+        let state = json!({
+            "paused": false,
+            "service": { "name": "web", "active": false, "status": "failed" },
+            "groups": [
+                { "title": "In#box", "files": [
+                    { "path": "/in/a", "removed": false },
+                    { "path": "/in/b", "removed": true },
+                ] },
+                { "title": "Out", "files": [] },
+            ],
+        });
+        assert_eq!(summary(&state, Style::Plain), "In#box:1 Out:0 web:down");
+        assert_eq!(
+            summary(&state, Style::Tmux),
+            "In##box:1 Out:0 #[fg=red]web:down#[default]"
+        );
+    }
+}
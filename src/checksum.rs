@@ -0,0 +1,194 @@
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use md5::Md5;
+use sha2::{Digest, Sha256};
+
+use crate::events::Event;
+use crate::filesystem::FileGroup;
+use crate::reactor::Waker;
+use crate::settling::Settling;
+
+/// How long a file and its sidecar have to stop changing before the file is checked.
+const STABLE_FOR: Duration = Duration::from_secs(5);
+
+/// The sidecar extensions understood, in the order they're looked for.
+const SIDECARS: &[&str] = &["sha256", "md5"];
+
+/// Checks new files against a `.sha256` or `.md5` sidecar arriving alongside them, once both
+/// have stopped changing. Whichever arrives second starts the check, and the hashing is done in
+/// the background so large files don't hold up the dashboard.
+pub struct Checksums {
+    /// Files waiting for them and their sidecar to stop changing
+    pending: Settling<PathBuf>,
+    jobs: Sender<(PathBuf, PathBuf)>,
+    done: Receiver<(PathBuf, io::Result<bool>)>,
+}
+
+impl Checksums {
+    pub fn new(waker: Waker) -> Self {
+        let (jobs, rx) = channel::<(PathBuf, PathBuf)>();
+        let (tx, done) = channel();
+        thread::spawn(move || {
+            for (path, sidecar) in rx {
+                let verified = verify(&path, &sidecar);
+                if tx.send((path, verified)).is_err() {
+                    return;
+                }
+                waker.wake();
+            }
+        });
+        Self {
+            pending: Settling::default(),
+            jobs,
+            done,
+        }
+    }
+
+    /// Note new files that have a sidecar, start checking any that have settled, and mark what's
+    /// been checked since the last call on the groups' items, raising an alert for each mismatch.
+    pub fn update(&mut self, events: &[Event], groups: &mut [FileGroup]) -> Vec<Event> {
+        for event in events {
+            match event {
+                Event::FileAdded { path, .. } => {
+                    if let Some((file, sidecar)) = pair(path) {
+                        let watched = Some(sidecar.clone());
+                        self.pending.insert(file, watched, STABLE_FOR, sidecar);
+                    }
+                }
+                Event::FileRemoved { path, .. } => {
+                    self.pending.remove(path);
+                }
+                _ => {}
+            }
+        }
+
+        for job in self.pending.settled() {
+            let _ = self.jobs.send(job);
+        }
+
+        let mut alerts = vec![];
+        for (path, verified) in self.done.try_iter() {
+            let verified = match verified {
+                Ok(verified) => verified,
+                Err(e) => {
+                    let message = format!("could not verify {}: {}", path.display(), e);
+                    alerts.push(alert(path, message));
+                    continue;
+                }
+            };
+            for group in groups.iter_mut() {
+                if let Some(item) = group.get_mut(&path) {
+                    item.verified = Some(verified);
+                }
+            }
+            if !verified {
+                let message = format!("{} doesn't match its checksum", path.display());
+                alerts.push(alert(path, message));
+            }
+        }
+        alerts
+    }
+
+    /// When the soonest pending file could have settled, to check on it then.
+    pub fn next_check(&self) -> Option<Instant> {
+        self.pending.next_check()
+    }
+}
+
+fn alert(path: PathBuf, message: String) -> Event {
+    Event::Alert {
+        rule: "checksum".to_string(),
+        path: Some(path),
+        message,
+    }
+}
+
+/// A file and its sidecar, if both are there, from either one of them.
+fn pair(path: &Path) -> Option<(PathBuf, PathBuf)> {
+    let (file, sidecar) = match path.extension().and_then(|e| e.to_str()) {
+        Some(extension) if SIDECARS.contains(&extension) => {
+            (path.with_extension(""), path.to_path_buf())
+        }
+        _ => {
+            let sidecar = SIDECARS
+                .iter()
+                .map(|extension| sidecar_path(path, extension))
+                .find(|sidecar| sidecar.exists())?;
+            (path.to_path_buf(), sidecar)
+        }
+    };
+    (file.is_file() && sidecar.is_file()).then_some((file, sidecar))
+}
+
+fn sidecar_path(path: &Path, extension: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(extension);
+    PathBuf::from(name)
+}
+
+/// Whether a file's hash is the one in its sidecar, which holds the hex digest first, as
+/// written by `sha256sum` or `md5sum`.
+fn verify(path: &Path, sidecar: &Path) -> io::Result<bool> {
+    let contents = fs::read_to_string(sidecar)?;
+    let expected = contents
+        .split_whitespace()
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "the sidecar is empty"))?
+        .to_lowercase();
+    let mut file = File::open(path)?;
+    let actual = if sidecar.extension().is_some_and(|e| e == "md5") {
+        hex(&hash::<Md5>(&mut file)?)
+    } else {
+        hex(&hash::<Sha256>(&mut file)?)
+    };
+    Ok(actual == expected)
+}
+
+/// The digest of everything in a file.
+pub fn hash<D: Digest + io::Write>(file: &mut File) -> io::Result<Vec<u8>> {
+    let mut hasher = D::new();
+    io::copy(file, &mut hasher)?;
+    Ok(hasher.finalize().to_vec())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify_sidecars() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let file = dir.join("data.csv");
+        fs::write(&file, "hello\n").unwrap();
+
+        // as written by sha256sum and md5sum
+        let sha256 = dir.join("data.csv.sha256");
+        fs::write(
+            &sha256,
+            "5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03  data.csv\n",
+        )
+        .unwrap();
+        assert_eq!(pair(&file), Some((file.clone(), sha256.clone())));
+        assert_eq!(pair(&sha256), Some((file.clone(), sha256.clone())));
+        assert!(verify(&file, &sha256).unwrap());
+
+        let md5 = dir.join("data.csv.md5");
+        fs::write(&md5, "B1946AC92492D2347C6235B4D2611184\n").unwrap();
+        assert!(verify(&file, &md5).unwrap());
+
+        fs::write(&file, "corrupted\n").unwrap();
+        assert!(!verify(&file, &sha256).unwrap());
+        assert_eq!(pair(&dir.join("other.csv")), None);
+    }
+}
@@ -0,0 +1,420 @@
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::bar::BarFormat;
+use crate::dbus::Bus;
+use crate::filesystem::Sort;
+use crate::filesystem::{Limits, SourceKind};
+use crate::log::Output;
+use crate::timezone::Timezone;
+use crate::view::Column;
+use clap::error::ErrorKind;
+
+use clap::{ArgMatches, Args, CommandFactory, FromArgMatches, Parser, Subcommand};
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, subcommand_precedence_over_arg = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Arguments for `watch` when no subcommand is given
+    #[command(flatten)]
+    pub watch: Option<WatchArgs>,
+
+    /// Config file to read paths, service, and keys from [default: the platform config
+    /// directory, e.g. ~/.config/file_task/config.toml]
+    #[arg(long, global = true, value_name = "FILE", env = "FILE_TASK_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// Profile from the config file to use
+    #[arg(long, global = true, value_name = "NAME", env = "FILE_TASK_PROFILE")]
+    pub profile: Option<String>,
+}
+
+impl Cli {
+    /// Parse the command line, exiting with usage on errors like `Parser::parse`.
+    pub fn parse_args() -> Cli {
+        let matches = Cli::command().get_matches();
+        let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+        // watch arguments are always on the innermost subcommand
+        let mut leaf = &matches;
+        while let Some((_, sub)) = leaf.subcommand() {
+            leaf = sub;
+        }
+        if let Some(args) = cli.watch_args_mut() {
+            args.pair_titles(leaf)
+                .unwrap_or_else(|e| Cli::command().error(ErrorKind::ArgumentConflict, e).exit());
+        }
+
+        cli
+    }
+
+    fn watch_args_mut(&mut self) -> Option<&mut WatchArgs> {
+        match &mut self.command {
+            Some(Command::Watch(args) | Command::Daemon(args) | Command::Status(args)) => {
+                Some(args)
+            }
+            Some(Command::Check(args)) => Some(&mut args.watch),
+            Some(Command::Export(args)) => Some(&mut args.watch),
+            Some(Command::StatusBrief(args)) => Some(&mut args.watch),
+            Some(Command::Bar(args)) => Some(&mut args.watch),
+            Some(Command::Config(ConfigCommand::Save(args))) => Some(args),
+            Some(_) => None,
+            None => self.watch.as_mut(),
+        }
+    }
+
+    /// The subcommand to run, falling back to `watch` for bare invocations.
+    pub fn into_command(self) -> Command {
+        match (self.command, self.watch) {
+            (Some(command), _) => command,
+            (None, watch) => Command::Watch(watch.unwrap_or_default()),
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Monitor the watched paths in an interactive dashboard (default)
+    Watch(WatchArgs),
+    /// Watch without the dashboard, e.g. as a systemd service (Type=notify with WatchdogSec is
+    /// supported)
+    Daemon(WatchArgs),
+    /// Watch without the dashboard, writing updates for a status bar (i3bar, swaybar, or
+    /// waybar) to stdout
+    Bar(BarArgs),
+    /// Print the current state of the watched paths and service once
+    Status(WatchArgs),
+    /// Print a one line summary of a running instance, queried through its control socket, with
+    /// tmux colors for status-right
+    StatusBrief(BriefArgs),
+    /// Show the dashboards of daemons on other hosts side by side, through their
+    /// --control-addr, instead of watching locally
+    Attach(AttachArgs),
+    /// Exit non-zero if expected files are missing or the service is down
+    Check(CheckArgs),
+    /// Write the watched files as CSV (group, path, size, observed_at, removed_at)
+    Export(ExportArgs),
+    /// Work with the config file
+    #[command(subcommand)]
+    Config(ConfigCommand),
+    /// Write a man page to stdout
+    #[command(hide = true)]
+    Man,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    /// Check the config for problems without starting to watch
+    Validate,
+    /// Write the given settings to the config file (or the selected profile in it)
+    Save(Box<WatchArgs>),
+}
+
+#[derive(Args, Debug, Default, Clone)]
+pub struct WatchArgs {
+    /// Paths to watch, instead of those in the config file [env: FILE_TASK_PATHS, separated
+    /// like PATH]
+    pub paths: Vec<PathBuf>,
+
+    /// Path to watch, like the positional paths but can be followed by --title
+    #[arg(long = "path", value_name = "PATH")]
+    pub path: Vec<PathBuf>,
+
+    /// Display title for the preceding --path, instead of its directory name
+    #[arg(long, value_name = "TITLE", requires = "path")]
+    pub title: Vec<String>,
+
+    /// Titles for paths, from --title or the config
+    #[arg(skip)]
+    pub titles: HashMap<PathBuf, String>,
+
+    /// Notes for paths, from the config or edited in the dashboard
+    #[arg(skip)]
+    pub notes: HashMap<PathBuf, String>,
+
+    /// Watch the files in every path's subdirectories too, shown by their path under it
+    #[arg(long, env = "FILE_TASK_RECURSIVE")]
+    pub recursive: bool,
+
+    /// Paths whose subdirectories are watched, from the config
+    #[arg(skip)]
+    pub recursive_paths: HashSet<PathBuf>,
+
+    /// Only show files whose names match this glob (e.g. '*.csv') in every path; can be repeated
+    #[arg(long, value_name = "GLOB")]
+    pub include: Vec<String>,
+
+    /// Don't show files whose names match this glob (e.g. '*.tmp') in any path, even if included;
+    /// can be repeated
+    #[arg(long, value_name = "GLOB")]
+    pub exclude: Vec<String>,
+
+    /// Globs for the files to show in particular paths, from the config
+    #[arg(skip)]
+    pub path_include: HashMap<PathBuf, Vec<String>>,
+
+    /// Globs for the files not to show in particular paths, from the config
+    #[arg(skip)]
+    pub path_exclude: HashMap<PathBuf, Vec<String>>,
+
+    /// Read additional paths to watch from a file, one per line ("-" for stdin)
+    #[arg(long, value_name = "FILE", env = "FILE_TASK_PATHS_FROM")]
+    pub paths_from: Option<PathBuf>,
+
+    /// Watch list file with one path (and options) per line, reloaded when it changes
+    #[arg(short = 'F', long, value_name = "FILE", env = "FILE_TASK_WATCHLIST")]
+    pub watchlist: Option<PathBuf>,
+
+    /// How to watch the paths [default: local]
+    #[arg(long, value_name = "SOURCE", env = "FILE_TASK_SOURCE")]
+    pub source: Option<SourceKind>,
+
+    /// Most files to track per group, including removed ones; the oldest are dropped beyond it
+    /// [default: 100000]
+    #[arg(long, value_name = "N", env = "FILE_TASK_MAX_ITEMS")]
+    pub max_items: Option<usize>,
+
+    /// Most removed files to keep showing across all groups [default: 10000]
+    #[arg(long, value_name = "N", env = "FILE_TASK_MAX_REMOVED")]
+    pub max_removed: Option<usize>,
+
+    /// How long to keep showing removed files, like "30m" or "2h" [default: 1day]
+    #[arg(long, value_name = "DURATION", env = "FILE_TASK_RETENTION", value_parser = humantime::parse_duration)]
+    pub retention: Option<Duration>,
+
+    /// Systemd unit to monitor: a service, or a .mount or .path unit, shown as mounted or armed.
+    /// Repeat it, or separate units with commas, to monitor several
+    #[arg(
+        long = "service",
+        value_name = "UNIT",
+        env = "FILE_TASK_SERVICE",
+        value_delimiter = ','
+    )]
+    pub services: Vec<String>,
+
+    /// Time zone for displayed times: an IANA name like Europe/Berlin, UTC, or local
+    #[arg(long, value_name = "ZONE", env = "FILE_TASK_TIMEZONE")]
+    pub timezone: Option<Timezone>,
+
+    /// Columns to show before each file's name, separated by commas: size, mtime, or age (since
+    /// it was modified). The columns key hides them, or shows them all when none are given
+    #[arg(
+        long,
+        value_name = "COLUMNS",
+        env = "FILE_TASK_COLUMNS",
+        value_delimiter = ','
+    )]
+    pub columns: Vec<Column>,
+
+    /// Order to list each group's files in: found (in the order they were discovered, which
+    /// isn't necessarily when they were created), name, mtime (newest first), or size (largest
+    /// first). The sort key changes it for the focused group
+    #[arg(long, value_name = "ORDER", env = "FILE_TASK_SORT")]
+    pub sort: Option<Sort>,
+
+    /// Serve Prometheus metrics at /metrics on this address while watching, e.g. 127.0.0.1:9184
+    #[arg(long, value_name = "ADDR", env = "FILE_TASK_METRICS")]
+    pub metrics: Option<SocketAddr>,
+
+    /// Serve a read-only JSON API under /api, and a web dashboard using it at /, on this address
+    /// while watching
+    #[arg(long, value_name = "ADDR", env = "FILE_TASK_LISTEN")]
+    pub listen: Option<SocketAddr>,
+
+    /// Serve the same API over gRPC on this address while watching (see proto/file_task.proto)
+    #[arg(long, value_name = "ADDR", env = "FILE_TASK_GRPC")]
+    pub grpc: Option<SocketAddr>,
+
+    /// Append all events as JSON lines to events.jsonl in this directory, rotating it by size
+    /// and age (see event_log in the config)
+    #[arg(long, value_name = "DIR", env = "FILE_TASK_EVENT_LOG")]
+    pub event_log: Option<PathBuf>,
+
+    /// Only record what the configured actions would do, without moving, copying, running, or
+    /// tagging anything
+    #[arg(long, env = "FILE_TASK_DRY_RUN")]
+    pub dry_run: bool,
+
+    /// Find out which process wrote each new file in the groups' roots, with fanotify. Linux
+    /// only, and needs CAP_SYS_ADMIN
+    #[arg(long, env = "FILE_TASK_ATTRIBUTE")]
+    pub attribute: bool,
+
+    /// Rhai script to filter events, raise alerts, and title groups with (see SCRIPTING)
+    #[arg(long, value_name = "FILE", env = "FILE_TASK_SCRIPT")]
+    pub script: Option<PathBuf>,
+
+    /// Write events as tab separated lines to this named pipe, creating it if needed
+    #[arg(long, value_name = "PATH", env = "FILE_TASK_FIFO")]
+    pub fifo: Option<PathBuf>,
+
+    /// File to keep the tracked files (including recently removed ones) in between runs
+    /// [default: state.json in the platform data directory]
+    #[arg(long, value_name = "FILE", env = "FILE_TASK_STATE")]
+    pub state: Option<PathBuf>,
+
+    /// Don't restore or save the tracked files between runs
+    #[arg(long, env = "FILE_TASK_NO_STATE")]
+    pub no_state: bool,
+
+    /// SQLite database to record files, events, and service changes in, so `status` can show
+    /// history from earlier runs
+    #[arg(long, value_name = "FILE", env = "FILE_TASK_DB")]
+    pub db: Option<PathBuf>,
+
+    /// In daemon mode, register on this message bus, emitting signals for events and answering
+    /// State calls
+    #[arg(long, value_name = "BUS", env = "FILE_TASK_DBUS")]
+    pub dbus: Option<Bus>,
+
+    /// Don't take over the terminal, but print file changes, service transitions, and alerts to
+    /// stdout as timestamped lines, e.g. to run under a process supervisor
+    #[arg(long, env = "FILE_TASK_NO_TUI")]
+    pub no_tui: bool,
+
+    /// Print events to stdout in this format rather than showing the dashboard, as with
+    /// --no-tui: json gives an object per line, for jq or a log collector
+    #[arg(long, value_name = "FORMAT", env = "FILE_TASK_OUTPUT")]
+    pub output: Option<Output>,
+
+    /// In daemon mode, keep the tmux option @file_task set to the status-brief summary, for
+    /// #{@file_task} in status-right
+    #[arg(long, env = "FILE_TASK_TMUX")]
+    pub tmux: bool,
+
+    /// Accept commands on a Unix socket at this path while watching
+    #[arg(long, value_name = "PATH", env = "FILE_TASK_CONTROL")]
+    pub control: Option<PathBuf>,
+
+    /// Accept commands over TCP on this address, e.g. for `file_task attach`. There is no
+    /// authentication, so only state, pause, resume, rescan, and clear are accepted; anyone who
+    /// can connect may read every watched file's name and use those
+    #[arg(long, value_name = "ADDR", env = "FILE_TASK_CONTROL_ADDR")]
+    pub control_addr: Option<SocketAddr>,
+
+    /// Record file changes, service changes, key presses, and resizes, with their timing, to
+    /// this file, for --play
+    #[arg(long, value_name = "FILE")]
+    pub record: Option<PathBuf>,
+
+    /// Play back a session recorded with --record in the dashboard, instead of watching. Nothing
+    /// is read from the config, and nothing is sent, run, or saved
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["paths", "path", "record"])]
+    pub play: Option<PathBuf>,
+}
+
+impl WatchArgs {
+    /// Merge --path into the positional paths in command line order, giving each --title to
+    /// the path before it.
+    fn pair_titles(&mut self, matches: &ArgMatches) -> Result<(), String> {
+        let indices = |id| matches.indices_of(id).into_iter().flatten();
+        let mut paths = indices("paths")
+            .zip(self.paths.drain(..))
+            .chain(indices("path").zip(self.path.drain(..)))
+            .collect::<Vec<_>>();
+        paths.sort_by_key(|(index, _)| *index);
+
+        for (index, title) in indices("title").zip(self.title.drain(..)) {
+            let (_, path) = paths
+                .iter()
+                .rev()
+                .find(|(i, _)| *i < index)
+                .ok_or_else(|| format!("--title \"{}\" must follow a --path", title))?;
+            self.titles.insert(path.clone(), title);
+        }
+
+        self.paths = paths.into_iter().map(|(_, path)| path).collect();
+        Ok(())
+    }
+
+    /// How much to keep track of, with the defaults for anything not given.
+    pub fn limits(&self) -> Limits {
+        let default = Limits::default();
+        Limits {
+            items: self.max_items.unwrap_or(default.items),
+            removed: self.max_removed.unwrap_or(default.removed),
+            retention: self.retention.unwrap_or(default.retention),
+        }
+    }
+
+    /// Fill in anything clap can't read from the environment itself.
+    pub fn apply_env(&mut self) {
+        if self.paths.is_empty() {
+            self.paths = env_paths("FILE_TASK_PATHS");
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct CheckArgs {
+    #[command(flatten)]
+    pub watch: WatchArgs,
+
+    /// File that must exist for the check to pass (may be repeated) [env: FILE_TASK_EXPECT,
+    /// separated like PATH]
+    #[arg(long, value_name = "PATH")]
+    pub expect: Vec<PathBuf>,
+}
+
+impl CheckArgs {
+    pub fn apply_env(&mut self) {
+        self.watch.apply_env();
+        if self.expect.is_empty() {
+            self.expect = env_paths("FILE_TASK_EXPECT");
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct ExportArgs {
+    #[command(flatten)]
+    pub watch: WatchArgs,
+
+    /// File to write to instead of stdout
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct BarArgs {
+    #[command(flatten)]
+    pub watch: WatchArgs,
+
+    /// Status bar protocol to write
+    #[arg(long, value_enum, default_value_t = BarFormat::I3bar)]
+    pub format: BarFormat,
+}
+
+#[derive(Args, Debug)]
+pub struct AttachArgs {
+    /// Each daemon's --control-addr
+    #[arg(value_name = "HOST:PORT", required = true)]
+    pub addresses: Vec<String>,
+
+    /// Time zone for displayed times: an IANA name like Europe/Berlin, UTC, or local
+    #[arg(long, value_name = "ZONE", env = "FILE_TASK_TIMEZONE")]
+    pub timezone: Option<Timezone>,
+}
+
+#[derive(Args, Debug)]
+pub struct BriefArgs {
+    #[command(flatten)]
+    pub watch: WatchArgs,
+
+    /// Leave out the tmux color markup
+    #[arg(long)]
+    pub plain: bool,
+}
+
+/// Lists of paths are split the same way as `PATH`, since paths themselves may contain commas.
+fn env_paths(name: &str) -> Vec<PathBuf> {
+    env::var_os(name)
+        .map(|value| env::split_paths(&value).collect())
+        .unwrap_or_default()
+}
@@ -0,0 +1,438 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use sha2::Sha256;
+
+use crate::checksum::hash;
+use crate::config::{CompareBy, CompareConfig};
+use crate::events::Event;
+use crate::filesystem::FileGroup;
+use crate::health::Check;
+use crate::reactor::Waker;
+
+/// Matches up the files in pairs of groups that should be in sync, finding those in one without
+/// a counterpart in the other. The files are read in the background, and only again once one of
+/// a pair's groups has changed; digests are kept until a file changes, so each is only read once.
+pub struct Comparisons {
+    pairs: Vec<CompareConfig>,
+    jobs: Sender<Vec<Job>>,
+    done: Receiver<Compared>,
+    /// What was last sent to be compared, none before the first time
+    sent: Option<Vec<Job>>,
+    /// For each pair compared, by index, how many source files are waiting and since when
+    waiting: Vec<(usize, usize, Option<SystemTime>)>,
+    lags: Vec<Lag>,
+    /// Pairs, by index, whose lag has been alerted on and not yet come back down
+    lagging: HashSet<usize>,
+}
+
+/// Digests of files by path, with the size and modification time they were read at.
+type Digests = HashMap<PathBuf, (u64, SystemTime, Vec<u8>)>;
+
+/// A group's present files, by name, with when each was first seen and its size and
+/// modification time as last read, so a file being written to counts as a change.
+type Listing = HashMap<Arc<Path>, (SystemTime, Option<u64>, Option<SystemTime>)>;
+
+/// A pair, by index, whose groups are both there, with their files as of when it was sent.
+#[derive(Clone, PartialEq)]
+struct Job {
+    pair: usize,
+    by: CompareBy,
+    source: (PathBuf, Listing),
+    mirror: (PathBuf, Listing),
+}
+
+/// What the worker found for a set of jobs.
+#[derive(Debug, Default)]
+struct Compared {
+    out_of_sync: HashSet<PathBuf>,
+    /// For each job's pair, how many source files have no match and the earliest seen of them
+    waiting: Vec<(usize, usize, Option<SystemTime>)>,
+}
+
+/// How far a mirror is behind its source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lag {
+    pub source: String,
+    pub mirror: String,
+    /// Files in the source without a match in the mirror yet
+    pub pending: usize,
+    /// How long the longest-waiting of them has been there
+    pub oldest: Duration,
+}
+
+impl Comparisons {
+    pub fn new(pairs: &[CompareConfig], waker: Waker) -> Self {
+        let (jobs, rx) = channel::<Vec<Job>>();
+        let (tx, done) = channel();
+        thread::spawn(move || {
+            let mut digests = Digests::new();
+            while let Ok(mut jobs) = rx.recv() {
+                // only the latest groups matter if they changed again in the meantime
+                jobs = rx.try_iter().last().unwrap_or(jobs);
+                if tx.send(compare(&jobs, &mut digests)).is_err() {
+                    return;
+                }
+                waker.wake();
+            }
+        });
+        Self {
+            pairs: pairs.to_vec(),
+            jobs,
+            done,
+            sent: None,
+            waiting: vec![],
+            lags: vec![],
+            lagging: HashSet::new(),
+        }
+    }
+
+    /// Have the pairs compared again if either of a pair's groups has changed, and work out how
+    /// far behind each mirror is as of now. Returns the paths of present files, in either group
+    /// of a pair, that don't match a file in the other one, when there's a new comparison.
+    pub fn update(&mut self, groups: &[FileGroup], now: SystemTime) -> Option<HashSet<PathBuf>> {
+        let find = |title: &str| groups.iter().find(|g| crate::group_title(g) == title);
+        let jobs = self
+            .pairs
+            .iter()
+            .enumerate()
+            .filter_map(|(i, pair)| {
+                let (source, mirror) = (find(&pair.source)?, find(&pair.mirror)?);
+                Some(Job {
+                    pair: i,
+                    by: pair.by,
+                    source: (source.root.clone(), listing(source)),
+                    mirror: (mirror.root.clone(), listing(mirror)),
+                })
+            })
+            .collect::<Vec<_>>();
+        if self.sent.as_ref() != Some(&jobs) {
+            let _ = self.jobs.send(jobs.clone());
+            self.sent = Some(jobs);
+        }
+        let compared = self.done.try_iter().last();
+        let out_of_sync = compared.map(|compared| {
+            self.waiting = compared.waiting;
+            compared.out_of_sync
+        });
+        self.lags = self
+            .waiting
+            .iter()
+            .map(|&(i, pending, since)| Lag {
+                source: self.pairs[i].source.clone(),
+                mirror: self.pairs[i].mirror.clone(),
+                pending,
+                oldest: since.map_or(Duration::ZERO, |since| {
+                    now.duration_since(since).unwrap_or_default()
+                }),
+            })
+            .collect();
+        out_of_sync
+    }
+
+    /// How far behind each mirror was when last compared, for pairs whose groups are both there.
+    pub fn lags(&self) -> &[Lag] {
+        &self.lags
+    }
+
+    /// Each mirror with a lag limit, and whether it's within it.
+    pub fn health(&self) -> Vec<Check> {
+        self.pairs
+            .iter()
+            .enumerate()
+            .filter(|(_, pair)| pair.max_lag.is_some())
+            .filter_map(|(i, pair)| {
+                let lag = self
+                    .lags
+                    .iter()
+                    .find(|lag| lag.source == pair.source && lag.mirror == pair.mirror)?;
+                let detail = match lag.pending {
+                    0 => "in sync".to_string(),
+                    pending => format!("{} files waiting", pending),
+                };
+                Some(Check::new(
+                    format!("mirror {}", pair.mirror),
+                    !self.lagging.contains(&i),
+                    detail,
+                ))
+            })
+            .collect()
+    }
+
+    /// Raise an alert for each mirror that has fallen further behind than its limit, once until
+    /// it catches back up.
+    pub fn check_lag(&mut self) -> Vec<Event> {
+        let mut alerts = vec![];
+        for (i, pair) in self.pairs.iter().enumerate() {
+            let Some(limit) = pair.max_lag else {
+                continue;
+            };
+            let lag = self
+                .lags
+                .iter()
+                .find(|lag| lag.source == pair.source && lag.mirror == pair.mirror);
+            match lag.filter(|lag| lag.oldest > limit.0) {
+                Some(lag) if self.lagging.insert(i) => alerts.push(Event::Alert {
+                    rule: "lag".to_string(),
+                    path: None,
+                    message: format!(
+                        "{} is {} behind {}, with {} files waiting",
+                        lag.mirror,
+                        humantime::format_duration(Duration::from_secs(lag.oldest.as_secs())),
+                        lag.source,
+                        lag.pending
+                    ),
+                }),
+                Some(_) => {}
+                None => {
+                    self.lagging.remove(&i);
+                }
+            }
+        }
+        alerts
+    }
+
+    /// How long until the oldest waiting file of a pair that isn't lagging yet would be over the
+    /// limit, to check on it then.
+    pub fn next_check(&self) -> Option<Duration> {
+        self.pairs
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !self.lagging.contains(i))
+            .filter_map(|(_, pair)| {
+                let limit = pair.max_lag?;
+                let lag = self
+                    .lags
+                    .iter()
+                    .find(|lag| lag.source == pair.source && lag.mirror == pair.mirror)
+                    .filter(|lag| lag.pending > 0)?;
+                // just past the limit, since it has to be exceeded
+                Some(limit.0.saturating_sub(lag.oldest) + Duration::from_secs(1))
+            })
+            .min()
+    }
+}
+
+/// Match up each job's files, reading the sizes and digests it needs once per path, and drop
+/// digests of files no longer compared by hash.
+fn compare(jobs: &[Job], digests: &mut Digests) -> Compared {
+    let mut compared = Compared::default();
+    let mut hashed = HashSet::new();
+    for job in jobs {
+        let ((source_root, source), (mirror_root, mirror)) = (&job.source, &job.mirror);
+        let matched = source
+            .keys()
+            .filter(|name| mirror.contains_key(*name))
+            .filter(|name| {
+                let (path, other) = (source_root.join(name), mirror_root.join(name));
+                match job.by {
+                    CompareBy::Name => true,
+                    CompareBy::Size => match (read(&path), read(&other)) {
+                        (Some(a), Some(b)) => a.0 == b.0,
+                        _ => false,
+                    },
+                    CompareBy::Hash => {
+                        let matched = match (read(&path), read(&other)) {
+                            (Some(a), Some(b)) if a.0 == b.0 => {
+                                let ours = digest(digests, &path, a);
+                                ours.is_some() && ours == digest(digests, &other, b)
+                            }
+                            _ => false,
+                        };
+                        hashed.extend([path, other]);
+                        matched
+                    }
+                }
+            })
+            .cloned()
+            .collect::<HashSet<_>>();
+
+        let waiting = source
+            .iter()
+            .filter(|(name, _)| !matched.contains(*name))
+            .map(|(_, (observed, ..))| *observed)
+            .collect::<Vec<_>>();
+        compared
+            .waiting
+            .push((job.pair, waiting.len(), waiting.into_iter().min()));
+        for (root, files) in [(source_root, source), (mirror_root, mirror)] {
+            let unmatched = files.keys().filter(|name| !matched.contains(*name));
+            compared
+                .out_of_sync
+                .extend(unmatched.map(|name| root.join(name)));
+        }
+    }
+    digests.retain(|path, _| hashed.contains(path));
+    compared
+}
+
+/// A file's size and modification time.
+fn read(path: &Path) -> Option<(u64, SystemTime)> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.len(), metadata.modified().ok()?))
+}
+
+/// A file's SHA-256, read again only if its size or modification time has changed since.
+fn digest(digests: &mut Digests, path: &Path, seen: (u64, SystemTime)) -> Option<Vec<u8>> {
+    if let Some((len, modified, digest)) = digests.get(path) {
+        if (*len, *modified) == seen {
+            return Some(digest.clone());
+        }
+    }
+    let digest = File::open(path)
+        .and_then(|mut file| hash::<Sha256>(&mut file))
+        .ok()?;
+    digests.insert(path.to_path_buf(), (seen.0, seen.1, digest.clone()));
+    Some(digest)
+}
+
+/// A group's present files.
+fn listing(group: &FileGroup) -> Listing {
+    group
+        .items()
+        .iter()
+        .filter(|item| item.removed.is_none())
+        .map(|item| {
+            (
+                Arc::clone(&item.name),
+                (item.observed, item.size, item.modified),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::HumanDuration;
+    use crate::filesystem::FileItem;
+    use crate::reactor::Reactor;
+
+    fn group(root: &Path, title: &str, names: &[&str]) -> FileGroup {
+        let items = names
+            .iter()
+            .map(|name| FileItem::new(PathBuf::from(name)))
+            .collect();
+        FileGroup::new(root.to_path_buf(), Some(title.to_string()), items)
+    }
+
+    fn pair(by: CompareBy) -> CompareConfig {
+        CompareConfig {
+            source: "src".to_string(),
+            mirror: "dst".to_string(),
+            by,
+            max_lag: None,
+        }
+    }
+
+    #[test]
+    fn files_missing_or_different_in_the_mirror() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let (src, dst) = (dir.join("src"), dir.join("dst"));
+        fs::create_dir_all(&src).unwrap();
+        fs::create_dir_all(&dst).unwrap();
+        for (path, contents) in [
+            (src.join("same"), "hello"),
+            (dst.join("same"), "hello"),
+            (src.join("edited"), "hello"),
+            (dst.join("edited"), "jello"),
+            (src.join("short"), "hello"),
+            (dst.join("short"), "hell"),
+            (src.join("unsent"), "hello"),
+            (dst.join("stray"), "hello"),
+        ] {
+            fs::write(path, contents).unwrap();
+        }
+        let groups = [
+            group(&src, "src", &["same", "edited", "short", "unsent"]),
+            group(&dst, "dst", &["same", "edited", "short", "stray"]),
+        ];
+
+        let mut digests = Digests::new();
+        let mut compare = |by| {
+            let job = Job {
+                pair: 0,
+                by,
+                source: (src.clone(), listing(&groups[0])),
+                mirror: (dst.clone(), listing(&groups[1])),
+            };
+            let mut paths = compare(&[job], &mut digests)
+                .out_of_sync
+                .into_iter()
+                .collect::<Vec<_>>();
+            paths.sort();
+            paths
+        };
+        assert_eq!(
+            compare(CompareBy::Name),
+            [dst.join("stray"), src.join("unsent")]
+        );
+        assert_eq!(
+            compare(CompareBy::Size),
+            [
+                dst.join("short"),
+                dst.join("stray"),
+                src.join("short"),
+                src.join("unsent")
+            ]
+        );
+
+        let by_hash = compare(CompareBy::Hash);
+        assert_eq!(by_hash.len(), 6);
+        assert!(by_hash.contains(&src.join("edited")));
+        assert!(!by_hash.contains(&src.join("same")));
+        assert!(digests.contains_key(&dst.join("same")));
+    }
+
+    #[test]
+    fn mirror_lag() {
+        let now = SystemTime::now();
+        let mut source = group(Path::new("/src"), "src", &["old", "new", "copied"]);
+        let mirror = group(Path::new("/dst"), "dst", &["copied"]);
+        for (name, age) in [("old", 600), ("new", 60), ("copied", 900)] {
+            source.get_mut(Path::new(name)).unwrap().observed = now - Duration::from_secs(age);
+        }
+        let mut groups = [source, mirror];
+        let mut reactor = Reactor::new(Duration::from_secs(60)).unwrap();
+        let pairs = [CompareConfig {
+            max_lag: Some(HumanDuration(Duration::from_secs(300))),
+            ..pair(CompareBy::Name)
+        }];
+        let mut comparisons = Comparisons::new(&pairs, reactor.waker());
+
+        // the worker may already be done by the time the first update looks
+        let mut compare = |comparisons: &mut Comparisons, groups: &[FileGroup]| {
+            comparisons.update(groups, now).unwrap_or_else(|| {
+                reactor.wait();
+                comparisons.update(groups, now).unwrap()
+            })
+        };
+        let out_of_sync = compare(&mut comparisons, &groups);
+        assert_eq!(out_of_sync.len(), 2);
+        assert_eq!(
+            comparisons.lags(),
+            [Lag {
+                source: "src".to_string(),
+                mirror: "dst".to_string(),
+                pending: 2,
+                oldest: Duration::from_secs(600),
+            }]
+        );
+        assert_eq!(comparisons.check_lag().len(), 1);
+        assert!(comparisons.check_lag().is_empty());
+        assert_eq!(comparisons.next_check(), None);
+
+        // the old file reaches the mirror, leaving one that's still within the limit
+        groups[1].add(Path::new("/dst/old"));
+        compare(&mut comparisons, &groups);
+        assert_eq!(comparisons.lags()[0].pending, 1);
+        assert!(comparisons.check_lag().is_empty());
+        assert_eq!(comparisons.next_check(), Some(Duration::from_secs(241)));
+    }
+}
@@ -0,0 +1,1596 @@
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::net::SocketAddr;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use chrono::NaiveTime;
+use globset::Glob;
+use itertools::Itertools;
+use serde::Deserialize;
+use toml::Spanned;
+use toml_edit::{DocumentMut, Item, Table};
+
+use crate::alerts::Alerts;
+use crate::cli::WatchArgs;
+use crate::dbus::Bus;
+use crate::events::{EventKind, Rule};
+use crate::filesystem::Sort;
+use crate::filesystem::SourceKind;
+use crate::highlight::{parse_style, Highlights};
+use crate::journal::{self, JournalRule};
+use crate::keymap::{Action, Key, KeyMap};
+use crate::service::service_exists;
+use crate::timezone::Timezone;
+use crate::view::Column;
+use crate::watchlist::globs;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Paths to watch
+    #[serde(default)]
+    pub paths: Vec<Spanned<PathEntry>>,
+
+    /// Watch the files in every path's subdirectories too, like --recursive
+    #[serde(default)]
+    pub recursive: bool,
+
+    /// Globs for the files to show in every path, like --include
+    #[serde(default)]
+    pub include: Vec<Spanned<String>>,
+
+    /// Globs for the files not to show in any path, like --exclude
+    #[serde(default)]
+    pub exclude: Vec<Spanned<String>>,
+
+    /// Watch list file to read more paths from, like --watchlist
+    pub watchlist: Option<PathBuf>,
+
+    /// Systemd unit to monitor: a service, or a .mount or .path unit
+    pub service: Option<Spanned<String>>,
+
+    /// More units to monitor alongside it
+    #[serde(default)]
+    pub services: Vec<Spanned<String>>,
+
+    /// Time zone for displayed times (IANA name, "UTC", or "local")
+    pub timezone: Option<Timezone>,
+
+    /// Columns to show before each file's name
+    #[serde(default)]
+    pub columns: Vec<Column>,
+
+    /// Order to list each group's files in
+    pub sort: Option<Sort>,
+
+    /// Address to serve Prometheus metrics on
+    pub metrics: Option<SocketAddr>,
+
+    /// Address to serve the JSON API on
+    pub listen: Option<SocketAddr>,
+
+    /// Address to serve the gRPC API on
+    pub grpc: Option<SocketAddr>,
+
+    /// Keep the tmux option @file_task up to date in daemon mode
+    #[serde(default)]
+    pub tmux: bool,
+
+    /// Message bus to register on in daemon mode
+    pub dbus: Option<Bus>,
+
+    /// OpenTelemetry collector to export metrics and traces to
+    pub otlp: Option<OtlpConfig>,
+
+    /// Path of a Unix socket to accept control commands on
+    pub control: Option<PathBuf>,
+
+    /// Address to accept the read-only and keybound control commands on over TCP
+    pub control_addr: Option<SocketAddr>,
+
+    /// Key overrides, from action name to key
+    #[serde(default)]
+    pub keys: HashMap<Spanned<String>, Spanned<String>>,
+
+    /// Desktop notifications to show, by event kind
+    #[serde(default)]
+    pub desktop_notifications: HashMap<EventKind, Rule>,
+
+    /// URLs to POST selected events to as JSON
+    #[serde(default)]
+    pub webhooks: Vec<WebhookConfig>,
+
+    /// MQTT broker to publish events to
+    pub mqtt: Option<MqttConfig>,
+
+    /// Conditions to raise alerts for
+    #[serde(default)]
+    pub alerts: AlertsConfig,
+
+    /// Checking new files' detached signatures
+    pub signatures: Option<SignaturesConfig>,
+
+    /// Pairs of groups that should hold the same files, like a directory and its mirror
+    #[serde(default)]
+    pub compare: Vec<CompareConfig>,
+
+    /// SMTP server to email selected events through
+    pub email: Option<EmailConfig>,
+
+    /// Syslog server to send selected events to
+    pub syslog: Option<SyslogConfig>,
+
+    /// Directory to keep a JSON lines log of all events in, and how to rotate it
+    pub event_log: Option<EventLogConfig>,
+
+    /// How to watch the paths, by watch source name
+    pub source: Option<SourceKind>,
+
+    /// Most files to track per group
+    pub max_items: Option<usize>,
+
+    /// Most removed files to keep showing across all groups
+    pub max_removed: Option<usize>,
+
+    /// How long to keep showing removed files
+    pub retention: Option<HumanDuration>,
+
+    /// Rhai script to filter events, raise alerts, and title groups with
+    pub script: Option<PathBuf>,
+
+    /// Named pipe to write events to as lines
+    pub fifo: Option<PathBuf>,
+
+    /// SQLite database to record history in
+    pub database: Option<PathBuf>,
+
+    /// File to keep the tracked files in between runs, like --state
+    pub state: Option<PathBuf>,
+
+    /// What the dashboard shows when it starts
+    #[serde(default)]
+    pub display: DisplayConfig,
+
+    /// Commands to run on events
+    pub hooks: Option<HooksConfig>,
+
+    /// Things to do automatically with new files
+    #[serde(default)]
+    pub actions: Vec<ActionConfig>,
+
+    /// Find out which process wrote each new file, like --attribute
+    #[serde(default)]
+    pub attribute: bool,
+
+    /// Delete files outright rather than moving them to the trash
+    #[serde(default)]
+    pub delete_permanently: bool,
+
+    /// Styles for file names matching regular expressions
+    #[serde(default)]
+    pub highlights: Vec<HighlightConfig>,
+
+    /// Styles for lines of a followed file matching regular expressions
+    #[serde(default)]
+    pub follow_highlights: Vec<HighlightConfig>,
+
+    /// Named sets of settings, selected with `--profile`
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+/// Panes to open when the dashboard starts, rather than toggling them each time.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DisplayConfig {
+    /// Show the alerts in place of the groups
+    #[serde(default)]
+    pub notifications: bool,
+    /// List the checks
+    #[serde(default)]
+    pub health: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookConfig {
+    pub url: String,
+
+    /// Events to send, by event kind
+    pub events: HashMap<EventKind, Rule>,
+
+    #[serde(default)]
+    pub format: WebhookFormat,
+
+    /// Seconds to wait for each attempt
+    #[serde(default = "default_webhook_timeout")]
+    pub timeout_secs: u64,
+
+    /// How many times to try again after a failed attempt
+    #[serde(default = "default_webhook_retries")]
+    pub retries: u32,
+}
+
+/// How a webhook's JSON body is laid out.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookFormat {
+    /// The event payload as is
+    #[default]
+    Json,
+    /// A Slack incoming webhook message with blocks
+    Slack,
+    /// A Discord webhook message with an embed
+    Discord,
+}
+
+fn default_webhook_timeout() -> u64 {
+    10
+}
+
+fn default_webhook_retries() -> u32 {
+    3
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OtlpConfig {
+    /// Base URL of the collector's OTLP/HTTP receiver, e.g. "http://localhost:4318"
+    pub endpoint: String,
+
+    /// How often to export
+    #[serde(default = "default_otlp_interval")]
+    pub interval: HumanDuration,
+
+    /// Reported as the service.name resource attribute
+    #[serde(default = "default_otlp_service_name")]
+    pub service_name: String,
+
+    /// Extra HTTP headers to send, e.g. for authentication
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+}
+
+fn default_otlp_interval() -> HumanDuration {
+    HumanDuration(Duration::from_secs(30))
+}
+
+fn default_otlp_service_name() -> String {
+    "file_task".to_string()
+}
+
+/// Something to do with each new file matching a pattern, once it has stopped changing.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ActionConfig {
+    /// Name to record what it does under [default: the pattern]
+    pub name: Option<String>,
+
+    /// Glob for the file names to act on
+    #[serde(rename = "match")]
+    pub pattern: Spanned<String>,
+
+    /// Only act on files in the group with this title
+    pub group: Option<String>,
+
+    /// How long a file's size and modification time must stay the same before acting on it
+    #[serde(default = "default_stable_for")]
+    pub stable_for: HumanDuration,
+
+    /// Directory to move the file into, with the same placeholders as hooks
+    #[serde(rename = "move")]
+    pub move_to: Option<String>,
+
+    /// Directory to copy the file into, with the same placeholders as hooks
+    #[serde(rename = "copy")]
+    pub copy_to: Option<String>,
+
+    /// Command to run, like a hook
+    pub run: Option<String>,
+
+    /// Tag to add to the file's user.xdg.tags attribute
+    pub tag: Option<String>,
+
+    /// Delete the file, to the trash unless `delete_permanently` is set
+    #[serde(default)]
+    pub delete: bool,
+
+    /// Only record what would be done
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// A style for file names matching a regular expression.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HighlightConfig {
+    #[serde(rename = "match")]
+    pub pattern: Spanned<String>,
+
+    /// Words like "bold red on black"
+    pub style: Spanned<String>,
+}
+
+fn default_stable_for() -> HumanDuration {
+    HumanDuration(Duration::from_secs(5))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HooksConfig {
+    pub on_added: Option<String>,
+    pub on_removed: Option<String>,
+    pub on_service_down: Option<String>,
+    pub on_service_up: Option<String>,
+    pub on_alert: Option<String>,
+    pub on_action: Option<String>,
+
+    /// How many hook commands may run at once
+    #[serde(default = "default_max_concurrent")]
+    pub max_concurrent: usize,
+}
+
+fn default_max_concurrent() -> usize {
+    4
+}
+
+impl HooksConfig {
+    pub fn commands(&self) -> impl Iterator<Item = (EventKind, &str)> {
+        [
+            (EventKind::Added, &self.on_added),
+            (EventKind::Removed, &self.on_removed),
+            (EventKind::ServiceDown, &self.on_service_down),
+            (EventKind::ServiceUp, &self.on_service_up),
+            (EventKind::Alert, &self.on_alert),
+            (EventKind::Action, &self.on_action),
+        ]
+        .into_iter()
+        .filter_map(|(kind, command)| Some((kind, command.as_deref()?)))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MqttConfig {
+    pub host: String,
+
+    #[serde(default = "default_mqtt_port")]
+    pub port: u16,
+
+    #[serde(default = "default_mqtt_client_id")]
+    pub client_id: String,
+
+    pub username: Option<String>,
+    pub password: Option<String>,
+
+    /// Topic to publish to, with the same placeholders as hooks
+    #[serde(default = "default_mqtt_topic")]
+    pub topic: String,
+
+    #[serde(default)]
+    pub retain: bool,
+
+    /// Events to publish, by event kind
+    pub events: HashMap<EventKind, Rule>,
+}
+
+fn default_mqtt_port() -> u16 {
+    1883
+}
+
+fn default_mqtt_client_id() -> String {
+    "file_task".to_string()
+}
+
+fn default_mqtt_topic() -> String {
+    "file_task/{event}".to_string()
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AlertsConfig {
+    /// Alert when the service has been down for this long
+    pub service_down_for: Option<HumanDuration>,
+
+    /// Files that should exist by a time of day
+    #[serde(default)]
+    pub expect: Vec<Expectation>,
+
+    /// How many files groups should have
+    #[serde(default)]
+    pub counts: Vec<CountLimit>,
+
+    /// Groups that should keep getting new files
+    #[serde(default)]
+    pub idle: Vec<IdleLimit>,
+
+    /// How much room should be left on the groups' filesystems
+    pub free: Option<FreeLimits>,
+
+    /// New files whose paths shouldn't turn up
+    #[serde(default)]
+    pub names: Vec<NameAlert>,
+
+    /// Lines the service shouldn't log
+    #[serde(default)]
+    pub journal: Vec<JournalAlert>,
+}
+
+/// Percentages of a filesystem that should stay free.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FreeLimits {
+    pub space: Option<f64>,
+    pub inodes: Option<f64>,
+}
+
+/// A regular expression that raises an alert when a new file's path matches it.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NameAlert {
+    #[serde(rename = "match")]
+    pub pattern: Spanned<String>,
+    /// The title of the only group to look in; all of them if not given
+    pub group: Option<String>,
+}
+
+/// A regular expression that raises an alert when the service logs a line matching it.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JournalAlert {
+    #[serde(rename = "match")]
+    pub pattern: Spanned<String>,
+    /// The least severe syslog priority to look at, like "warning"; "err" if not given
+    pub priority: Option<Spanned<String>>,
+}
+
+/// The range of present files a group should stay within.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CountLimit {
+    /// The group's title
+    pub group: String,
+    pub min: Option<usize>,
+    pub max: Option<usize>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Expectation {
+    pub path: PathBuf,
+    /// Time of day, in the configured time zone, after which a missing file is alerted on
+    pub by: TimeOfDay,
+}
+
+/// How long a group can go without new files during the hours it's expected to get them.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct IdleLimit {
+    /// The group's title
+    pub group: String,
+    #[serde(rename = "for")]
+    pub window: HumanDuration,
+    /// When the active hours start; all day if the same as `until`
+    #[serde(default)]
+    pub from: TimeOfDay,
+    #[serde(default)]
+    pub until: TimeOfDay,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SignaturesConfig {
+    /// The keys signatures are trusted from, as read by gpgv
+    pub keyring: PathBuf,
+    /// Titles of the groups to check; all of them if empty
+    #[serde(default)]
+    pub groups: Vec<String>,
+}
+
+/// Two groups, by title, whose files are matched up by name.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CompareConfig {
+    pub source: String,
+    pub mirror: String,
+    /// What else has to match besides the name
+    #[serde(default)]
+    pub by: CompareBy,
+    /// Alert when a file has been waiting in the source this long without reaching the mirror
+    pub max_lag: Option<HumanDuration>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CompareBy {
+    /// Only the name
+    #[default]
+    Name,
+    /// The name and size
+    Size,
+    /// The name, size, and SHA-256 of the contents
+    Hash,
+}
+
+/// A duration written like "90s", "10m", or "1h 30m".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct HumanDuration(pub Duration);
+
+impl TryFrom<String> for HumanDuration {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        humantime::parse_duration(&s)
+            .map(Self)
+            .map_err(|e| format!("invalid duration \"{}\": {}", s, e))
+    }
+}
+
+/// A time of day written like "09:30".
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct TimeOfDay(pub NaiveTime);
+
+impl TryFrom<String> for TimeOfDay {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        NaiveTime::parse_from_str(&s, "%H:%M")
+            .map(Self)
+            .map_err(|_| format!("invalid time \"{}\", expected HH:MM", s))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EmailConfig {
+    pub server: String,
+
+    /// Defaults to the usual port for the kind of TLS
+    pub port: Option<u16>,
+
+    #[serde(default)]
+    pub tls: EmailTls,
+
+    pub username: Option<String>,
+    pub password: Option<String>,
+
+    pub from: String,
+    pub to: Vec<String>,
+
+    /// How long to gather events for before sending them together in one email
+    #[serde(default = "default_digest")]
+    pub digest: HumanDuration,
+
+    /// Events to send, by event kind
+    pub events: HashMap<EventKind, Rule>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EmailTls {
+    /// Upgrade a plain connection, on port 587
+    #[default]
+    Starttls,
+    /// Connect with TLS directly, on port 465
+    Tls,
+    /// No encryption, on port 25; only sensible for a relay on the same machine
+    None,
+}
+
+fn default_digest() -> HumanDuration {
+    HumanDuration(Duration::from_secs(60))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SyslogConfig {
+    /// "udp://host:port" or "tcp://host:port"; the local syslog socket when not given
+    pub server: Option<String>,
+
+    #[serde(default)]
+    pub facility: SyslogFacility,
+
+    /// Severity to log each event kind at, instead of the default for it
+    #[serde(default)]
+    pub severity: HashMap<EventKind, SyslogSeverity>,
+
+    /// Events to send, by event kind
+    pub events: HashMap<EventKind, Rule>,
+}
+
+impl SyslogConfig {
+    pub fn severity(&self, kind: EventKind) -> SyslogSeverity {
+        self.severity.get(&kind).copied().unwrap_or(match kind {
+            EventKind::Added | EventKind::Removed | EventKind::Action => SyslogSeverity::Info,
+            EventKind::ServiceUp => SyslogSeverity::Notice,
+            EventKind::Alert => SyslogSeverity::Warning,
+            EventKind::ServiceDown => SyslogSeverity::Err,
+        })
+    }
+}
+
+/// A syslog facility written like "daemon" or "local0".
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(try_from = "String")]
+pub struct SyslogFacility(pub syslog::Facility);
+
+impl TryFrom<String> for SyslogFacility {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+            .map(Self)
+            .map_err(|()| format!("unknown syslog facility \"{}\"", s))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SyslogSeverity {
+    Emerg,
+    Alert,
+    Crit,
+    Err,
+    Warning,
+    Notice,
+    Info,
+    Debug,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EventLogConfig {
+    /// Relative to the config file; --event-log takes precedence
+    pub dir: Option<PathBuf>,
+
+    /// Start a new file once the current one reaches this size
+    #[serde(default = "default_event_log_max_bytes")]
+    pub max_bytes: u64,
+
+    /// Start a new file once the current one is this old
+    #[serde(default = "default_event_log_rotate_after")]
+    pub rotate_after: HumanDuration,
+
+    /// How many old files to keep
+    #[serde(default = "default_event_log_keep")]
+    pub keep: usize,
+}
+
+impl Default for EventLogConfig {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            max_bytes: default_event_log_max_bytes(),
+            rotate_after: default_event_log_rotate_after(),
+            keep: default_event_log_keep(),
+        }
+    }
+}
+
+fn default_event_log_max_bytes() -> u64 {
+    10 * 1024 * 1024
+}
+
+fn default_event_log_rotate_after() -> HumanDuration {
+    HumanDuration(Duration::from_secs(60 * 60 * 24))
+}
+
+fn default_event_log_keep() -> usize {
+    30
+}
+
+/// Settings that can be overridden per profile.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    #[serde(default)]
+    pub paths: Vec<Spanned<PathEntry>>,
+
+    pub service: Option<Spanned<String>>,
+
+    #[serde(default)]
+    pub services: Vec<Spanned<String>>,
+
+    pub timezone: Option<Timezone>,
+}
+
+/// A watched path, written either as a plain string or as a table with per-path options.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(from = "PathEntryRepr")]
+pub struct PathEntry {
+    pub path: PathBuf,
+    pub title: Option<String>,
+    pub note: Option<String>,
+    pub recursive: bool,
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PathEntryRepr {
+    Path(PathBuf),
+    Table(PathTable),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PathTable {
+    path: PathBuf,
+    title: Option<String>,
+    note: Option<String>,
+    #[serde(default)]
+    recursive: bool,
+    #[serde(default)]
+    include: Vec<String>,
+    #[serde(default)]
+    exclude: Vec<String>,
+}
+
+impl From<PathEntryRepr> for PathEntry {
+    fn from(repr: PathEntryRepr) -> Self {
+        match repr {
+            PathEntryRepr::Path(path) => Self {
+                path,
+                title: None,
+                note: None,
+                recursive: false,
+                include: vec![],
+                exclude: vec![],
+            },
+            PathEntryRepr::Table(PathTable {
+                path,
+                title,
+                note,
+                recursive,
+                include,
+                exclude,
+            }) => Self {
+                path,
+                title,
+                note,
+                recursive,
+                include,
+                exclude,
+            },
+        }
+    }
+}
+
+/// A parsed config along with the source it came from, for reporting problems.
+#[derive(Debug)]
+pub struct ConfigFile {
+    pub path: PathBuf,
+    source: String,
+    pub config: Config,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Problem {
+    span: Range<usize>,
+    message: String,
+}
+
+impl Problem {
+    fn new(span: Range<usize>, message: impl Into<String>) -> Self {
+        Self {
+            span,
+            message: message.into(),
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl ConfigFile {
+    pub fn load(path: &Path) -> file_task::Result<ConfigFile> {
+        let source = fs::read_to_string(path).map_err(|e| config_error(path, e))?;
+        Self::parse(path, source)
+    }
+
+    /// Read a config from source text, as though it were in the file at a path.
+    pub fn parse(path: &Path, source: String) -> file_task::Result<ConfigFile> {
+        let mut config: Config = toml::from_str(&source).map_err(|e| config_error(path, e))?;
+
+        // relative watch paths are relative to the config file rather than the working directory
+        let base = path.parent().unwrap_or(Path::new(""));
+        let profile_paths = config
+            .profiles
+            .values_mut()
+            .flat_map(|p| p.paths.iter_mut());
+        for watch in config.paths.iter_mut().chain(profile_paths) {
+            let entry = watch.get_mut();
+            if entry.path.is_relative() {
+                entry.path = base.join(&entry.path);
+            }
+        }
+        if let Some(control) = config.control.as_mut().filter(|p| p.is_relative()) {
+            *control = base.join(&*control);
+        }
+        for file in [config.watchlist.as_mut(), config.state.as_mut()]
+            .into_iter()
+            .flatten()
+            .filter(|p| p.is_relative())
+        {
+            *file = base.join(&*file);
+        }
+        if let Some(dir) = config
+            .event_log
+            .as_mut()
+            .and_then(|l| l.dir.as_mut())
+            .filter(|p| p.is_relative())
+        {
+            *dir = base.join(&*dir);
+        }
+        if let Some(script) = config.script.as_mut().filter(|p| p.is_relative()) {
+            *script = base.join(&*script);
+        }
+        if let Some(fifo) = config.fifo.as_mut().filter(|p| p.is_relative()) {
+            *fifo = base.join(&*fifo);
+        }
+        if let Some(database) = config.database.as_mut().filter(|p| p.is_relative()) {
+            *database = base.join(&*database);
+        }
+        // destinations are templates, but can only be relative to something once filled in
+        let destinations = config
+            .actions
+            .iter_mut()
+            .flat_map(|a| [a.move_to.as_mut(), a.copy_to.as_mut()])
+            .flatten();
+        for destination in destinations {
+            if Path::new(destination).is_relative() && !destination.starts_with('{') {
+                *destination = base.join(&*destination).to_string_lossy().into_owned();
+            }
+        }
+        if let Some(signatures) = config
+            .signatures
+            .as_mut()
+            .filter(|s| s.keyring.is_relative())
+        {
+            signatures.keyring = base.join(&signatures.keyring);
+        }
+        for expectation in config.alerts.expect.iter_mut() {
+            if expectation.path.is_relative() {
+                expectation.path = base.join(&expectation.path);
+            }
+        }
+
+        Ok(ConfigFile {
+            path: path.to_path_buf(),
+            source,
+            config,
+        })
+    }
+
+    pub fn has_profile(&self, name: &str) -> bool {
+        self.config.profiles.contains_key(name)
+    }
+
+    /// Fill in anything not given on the command line from the profile, then the top level.
+    pub fn merge_into(&self, args: &mut WatchArgs, profile: Option<&str>) {
+        let profile = profile.and_then(|name| self.config.profiles.get(name));
+
+        if args.paths.is_empty() && args.watchlist.is_none() {
+            let paths = match profile {
+                Some(profile) if !profile.paths.is_empty() => &profile.paths,
+                _ => &self.config.paths,
+            };
+            for entry in paths.iter().map(|p| p.get_ref()) {
+                args.paths.push(entry.path.clone());
+                if let Some(title) = &entry.title {
+                    args.titles.insert(entry.path.clone(), title.clone());
+                }
+                if let Some(note) = &entry.note {
+                    args.notes.insert(entry.path.clone(), note.clone());
+                }
+                if entry.recursive {
+                    args.recursive_paths.insert(entry.path.clone());
+                }
+                if !entry.include.is_empty() {
+                    args.path_include
+                        .insert(entry.path.clone(), entry.include.clone());
+                }
+                if !entry.exclude.is_empty() {
+                    args.path_exclude
+                        .insert(entry.path.clone(), entry.exclude.clone());
+                }
+            }
+            args.watchlist = self.config.watchlist.clone();
+        }
+        args.recursive |= self.config.recursive;
+        if args.include.is_empty() {
+            args.include = self
+                .config
+                .include
+                .iter()
+                .map(|g| g.get_ref().clone())
+                .collect();
+        }
+        if args.exclude.is_empty() {
+            args.exclude = self
+                .config
+                .exclude
+                .iter()
+                .map(|g| g.get_ref().clone())
+                .collect();
+        }
+        if args.services.is_empty() {
+            let (service, services) = match profile {
+                Some(p) if p.service.is_some() || !p.services.is_empty() => {
+                    (&p.service, &p.services)
+                }
+                _ => (&self.config.service, &self.config.services),
+            };
+            args.services = service
+                .iter()
+                .chain(services)
+                .map(|s| s.get_ref().clone())
+                .collect();
+        }
+        if args.timezone.is_none() {
+            args.timezone = profile.and_then(|p| p.timezone).or(self.config.timezone);
+        }
+        if args.columns.is_empty() {
+            args.columns = self.config.columns.clone();
+        }
+        if args.sort.is_none() {
+            args.sort = self.config.sort;
+        }
+        if args.metrics.is_none() {
+            args.metrics = self.config.metrics;
+        }
+        if args.listen.is_none() {
+            args.listen = self.config.listen;
+        }
+        if args.grpc.is_none() {
+            args.grpc = self.config.grpc;
+        }
+        if args.event_log.is_none() {
+            args.event_log = self.config.event_log.as_ref().and_then(|l| l.dir.clone());
+        }
+        if args.control.is_none() {
+            args.control = self.config.control.clone();
+        }
+        if args.control_addr.is_none() {
+            args.control_addr = self.config.control_addr;
+        }
+        if args.source.is_none() {
+            args.source = self.config.source;
+        }
+        if args.max_items.is_none() {
+            args.max_items = self.config.max_items;
+        }
+        if args.max_removed.is_none() {
+            args.max_removed = self.config.max_removed;
+        }
+        if args.retention.is_none() {
+            args.retention = self.config.retention.map(|retention| retention.0);
+        }
+        if args.script.is_none() {
+            args.script = self.config.script.clone();
+        }
+        if args.fifo.is_none() {
+            args.fifo = self.config.fifo.clone();
+        }
+        args.tmux |= self.config.tmux;
+        args.attribute |= self.config.attribute;
+        if args.dbus.is_none() {
+            args.dbus = self.config.dbus;
+        }
+        if args.db.is_none() {
+            args.db = self.config.database.clone();
+        }
+        if args.state.is_none() {
+            args.state = self.config.state.clone();
+        }
+    }
+
+    pub fn keymap(&self) -> Result<KeyMap, String> {
+        let (overrides, problems) = self.key_overrides();
+        match problems.first() {
+            Some(problem) => Err(self.describe(problem).to_string()),
+            None => Ok(KeyMap::with_overrides(overrides)),
+        }
+    }
+
+    pub fn highlights(&self) -> Result<Highlights, String> {
+        self.build_highlights(&self.config.highlights)
+    }
+
+    pub fn follow_highlights(&self) -> Result<Highlights, String> {
+        self.build_highlights(&self.config.follow_highlights)
+    }
+
+    fn build_highlights(&self, rules: &[HighlightConfig]) -> Result<Highlights, String> {
+        if let Some(problem) = Self::highlight_problems(rules).first() {
+            return Err(self.describe(problem).to_string());
+        }
+        Highlights::new(
+            rules
+                .iter()
+                .map(|h| (h.pattern.get_ref().as_str(), h.style.get_ref().as_str())),
+        )
+    }
+
+    pub fn alerts(&self) -> Result<Alerts, String> {
+        if let Some(problem) = self.name_problems().first() {
+            return Err(self.describe(problem).to_string());
+        }
+        Ok(Alerts::new(&self.config.alerts))
+    }
+
+    pub fn journal_rules(&self) -> Result<Vec<JournalRule>, String> {
+        if let Some(problem) = self.journal_problems().first() {
+            return Err(self.describe(problem).to_string());
+        }
+        Ok(self
+            .config
+            .alerts
+            .journal
+            .iter()
+            .filter_map(JournalRule::new)
+            .collect())
+    }
+
+    fn journal_problems(&self) -> Vec<Problem> {
+        let mut problems = vec![];
+        for rule in &self.config.alerts.journal {
+            if let Err(e) = regex::Regex::new(rule.pattern.get_ref()) {
+                problems.push(Problem::new(
+                    rule.pattern.span(),
+                    format!("invalid regex: {}", e),
+                ));
+            }
+            if let Some(priority) = &rule.priority {
+                if journal::priority(priority.get_ref()).is_none() {
+                    problems.push(Problem::new(
+                        priority.span(),
+                        format!(
+                            "unknown priority \"{}\" (expected emerg, alert, crit, err, warning, notice, info, or debug)",
+                            priority.get_ref()
+                        ),
+                    ));
+                }
+            }
+        }
+        problems
+    }
+
+    fn name_problems(&self) -> Vec<Problem> {
+        self.config
+            .alerts
+            .names
+            .iter()
+            .filter_map(|name| {
+                let e = regex::Regex::new(name.pattern.get_ref()).err()?;
+                Some(Problem::new(
+                    name.pattern.span(),
+                    format!("invalid regex: {}", e),
+                ))
+            })
+            .collect()
+    }
+
+    fn highlight_problems(rules: &[HighlightConfig]) -> Vec<Problem> {
+        let mut problems = vec![];
+        for highlight in rules {
+            if let Err(e) = regex::Regex::new(highlight.pattern.get_ref()) {
+                problems.push(Problem::new(
+                    highlight.pattern.span(),
+                    format!("invalid regex: {}", e),
+                ));
+            }
+            if let Err(e) = parse_style(highlight.style.get_ref()) {
+                problems.push(Problem::new(highlight.style.span(), e));
+            }
+        }
+        problems
+    }
+
+    fn key_overrides(&self) -> (Vec<(Action, Key)>, Vec<Problem>) {
+        let mut overrides = vec![];
+        let mut problems = vec![];
+
+        for (name, key) in self.config.keys.iter() {
+            let action = Action::from_name(name.get_ref());
+            if action.is_none() {
+                problems.push(Problem::new(
+                    name.span(),
+                    format!("unknown action \"{}\"", name.get_ref()),
+                ));
+            }
+            let parsed = key
+                .get_ref()
+                .parse::<Key>()
+                .map_err(|e| problems.push(Problem::new(key.span(), e)))
+                .ok();
+            if let (Some(action), Some(parsed)) = (action, parsed) {
+                overrides.push((action, parsed));
+            }
+        }
+
+        (overrides, problems)
+    }
+
+    /// Check everything that can be checked without starting to watch.
+    pub fn validate(&self) -> Vec<Problem> {
+        let mut problems = vec![];
+
+        let profiles = self.config.profiles.values();
+        let all_paths = self
+            .config
+            .paths
+            .iter()
+            .chain(profiles.clone().flat_map(|p| p.paths.iter()));
+        let all_services = self
+            .config
+            .service
+            .iter()
+            .chain(&self.config.services)
+            .chain(profiles.flat_map(|p| p.service.iter().chain(&p.services)));
+
+        for path in all_paths {
+            let p = &path.get_ref().path;
+            if !p.exists() {
+                problems.push(Problem::new(
+                    path.span(),
+                    format!("path {} does not exist", p.display()),
+                ));
+            } else if !p.is_dir() {
+                problems.push(Problem::new(
+                    path.span(),
+                    format!("path {} is not a directory", p.display()),
+                ));
+            }
+        }
+
+        for service in all_services {
+            match service_exists(service.get_ref()) {
+                Ok(true) => {}
+                Ok(false) => problems.push(Problem::new(
+                    service.span(),
+                    format!("service {} does not exist", service.get_ref()),
+                )),
+                Err(e) => problems.push(Problem::new(
+                    service.span(),
+                    format!("could not resolve service {}: {}", service.get_ref(), e),
+                )),
+            }
+        }
+
+        problems.extend(self.pattern_problems());
+        problems.extend(self.name_problems());
+        problems.extend(self.journal_problems());
+        problems.extend(Self::highlight_problems(&self.config.highlights));
+        problems.extend(Self::highlight_problems(&self.config.follow_highlights));
+
+        let (overrides, key_problems) = self.key_overrides();
+        problems.extend(key_problems);
+        problems.extend(self.key_conflicts(&overrides));
+
+        problems.sort_by_key(|p| p.span.start);
+        problems
+    }
+
+    fn rules(&self) -> impl Iterator<Item = &Rule> {
+        let webhooks = self.config.webhooks.iter().flat_map(|w| w.events.values());
+        let mqtt = self.config.mqtt.iter().flat_map(|m| m.events.values());
+        let email = self.config.email.iter().flat_map(|e| e.events.values());
+        let syslog = self.config.syslog.iter().flat_map(|s| s.events.values());
+        self.config
+            .desktop_notifications
+            .values()
+            .chain(webhooks)
+            .chain(mqtt)
+            .chain(email)
+            .chain(syslog)
+    }
+
+    fn pattern_problems(&self) -> Vec<Problem> {
+        let actions = self.config.actions.iter().map(|a| &a.pattern);
+        let filters = self.config.include.iter().chain(&self.config.exclude);
+        let patterns = self
+            .rules()
+            .filter_map(|rule| rule.pattern.as_ref())
+            .chain(actions)
+            .chain(filters)
+            .map(|pattern| (pattern.span(), pattern.get_ref()));
+        // a path's own globs are pointed at by the path's entry
+        let profiles = self.config.profiles.values().flat_map(|p| &p.paths);
+        let paths = self.config.paths.iter().chain(profiles).flat_map(|entry| {
+            let PathEntry {
+                include, exclude, ..
+            } = entry.get_ref();
+            include
+                .iter()
+                .chain(exclude)
+                .map(|glob| (entry.span(), glob))
+        });
+        patterns
+            .chain(paths)
+            .filter_map(|(span, pattern)| {
+                let error = Glob::new(pattern).err()?;
+                Some(Problem::new(span, format!("invalid glob: {}", error)))
+            })
+            .collect()
+    }
+
+    fn key_conflicts(&self, overrides: &[(Action, Key)]) -> Vec<Problem> {
+        let bound = Action::ALL
+            .iter()
+            .flat_map(|action| {
+                let keys = match overrides.iter().rev().find(|(a, _)| a == action) {
+                    Some((_, key)) => vec![*key],
+                    None => action.default_keys(),
+                };
+                keys.into_iter().map(|key| (key, *action))
+            })
+            .into_group_map();
+
+        bound
+            .into_iter()
+            .filter(|(_, actions)| actions.len() > 1)
+            .map(|(key, actions)| {
+                // point at whichever configured key caused the clash
+                let span = self
+                    .config
+                    .keys
+                    .iter()
+                    .find(|(name, _)| actions.iter().any(|a| a.name() == name.get_ref()))
+                    .map(|(_, key)| key.span())
+                    .unwrap_or_default();
+                let names = actions.iter().map(|a| a.name()).join(", ");
+                Problem::new(span, format!("key \"{}\" is bound to {}", key, names))
+            })
+            .collect()
+    }
+
+    pub fn describe<'a>(&'a self, problem: &'a Problem) -> ProblemReport<'a> {
+        ProblemReport {
+            file: self,
+            problem,
+        }
+    }
+}
+
+fn config_error(path: &Path, source: impl Into<Box<dyn Error + Send + Sync>>) -> file_task::Error {
+    file_task::Error::Config {
+        path: path.to_path_buf(),
+        source: source.into(),
+    }
+}
+
+/// Write the given settings to the config file, either at the top level or into a profile,
+/// leaving everything else in the file (including comments) as it was.
+pub fn save_profile(path: &Path, profile: Option<&str>, args: &WatchArgs) -> file_task::Result<()> {
+    write_profile(path, profile, args).map_err(|e| config_error(path, e))
+}
+
+fn write_profile(
+    path: &Path,
+    profile: Option<&str>,
+    args: &WatchArgs,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+    let mut doc = source.parse::<DocumentMut>()?;
+
+    let table = match profile {
+        Some(name) => {
+            let profiles = doc
+                .entry("profiles")
+                .or_insert_with(|| {
+                    let mut profiles = Table::new();
+                    profiles.set_implicit(true);
+                    Item::Table(profiles)
+                })
+                .as_table_mut()
+                .ok_or("profiles in config is not a table")?;
+            profiles
+                .entry(name)
+                .or_insert_with(|| Item::Table(Table::new()))
+                .as_table_mut()
+                .ok_or_else(|| format!("profile {} in config is not a table", name))?
+        }
+        None => doc.as_table_mut(),
+    };
+
+    // the config resolves relative paths against its own directory, so store absolute ones
+    let mut paths = toml_edit::Array::new();
+    for path in args.paths.iter() {
+        let absolute = std::path::absolute(path)?.to_string_lossy().into_owned();
+        let (title, note) = (args.titles.get(path), args.notes.get(path));
+        let recursive = args.recursive_paths.contains(path);
+        let include = globs(&args.include, &args.path_include, path);
+        let exclude = globs(&args.exclude, &args.path_exclude, path);
+        if title.is_none()
+            && note.is_none()
+            && !recursive
+            && include.is_empty()
+            && exclude.is_empty()
+        {
+            paths.push(absolute);
+            continue;
+        }
+        let mut entry = toml_edit::InlineTable::new();
+        entry.insert("path", absolute.into());
+        if let Some(title) = title {
+            entry.insert("title", title.as_str().into());
+        }
+        if let Some(note) = note {
+            entry.insert("note", note.as_str().into());
+        }
+        if recursive {
+            entry.insert("recursive", true.into());
+        }
+        for (key, globs) in [("include", include), ("exclude", exclude)] {
+            if !globs.is_empty() {
+                entry.insert(key, globs.iter().collect::<toml_edit::Array>().into());
+            }
+        }
+        paths.push(entry);
+    }
+    table["paths"] = toml_edit::value(paths);
+    match args.services.as_slice() {
+        [] => {
+            table.remove("service");
+            table.remove("services");
+        }
+        [service] => {
+            table["service"] = toml_edit::value(service.as_str());
+            table.remove("services");
+        }
+        services => {
+            table.remove("service");
+            table["services"] = toml_edit::value(services.iter().collect::<toml_edit::Array>());
+        }
+    }
+    match &args.timezone {
+        Some(timezone) => table["timezone"] = toml_edit::value(timezone.to_string()),
+        None => {
+            table.remove("timezone");
+        }
+    }
+
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, doc.to_string())?;
+    Ok(())
+}
+
+/// A problem formatted with the file, line, and source text it refers to.
+pub struct ProblemReport<'a> {
+    file: &'a ConfigFile,
+    problem: &'a Problem,
+}
+
+impl fmt::Display for ProblemReport<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let source = &self.file.source;
+        let start = self.problem.span.start.min(source.len());
+        let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[start..]
+            .find('\n')
+            .map_or(source.len(), |i| start + i);
+        let line_number = source[..start].matches('\n').count() + 1;
+        let column = source[line_start..start].chars().count() + 1;
+
+        writeln!(
+            f,
+            "{}:{}:{}: {}",
+            self.file.path.display(),
+            line_number,
+            column,
+            self.problem.message
+        )?;
+        write!(
+            f,
+            "{:>4} | {}",
+            line_number,
+            &source[line_start..line_end].trim_end()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> ConfigFile {
+        ConfigFile::parse(Path::new("/config/file_task.toml"), source.to_string()).unwrap()
+    }
+
+    #[test]
+    fn relative_paths_from_config_dir() {
+        let file = parse(r#"paths = ["/abs", "rel"]"#);
+        let paths = file
+            .config
+            .paths
+            .iter()
+            .map(|p| p.get_ref().path.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            vec![PathBuf::from("/abs"), PathBuf::from("/config/rel")]
+        );
+    }
+
+    #[test]
+    fn paths_with_titles() {
+        let file = parse(
+            r#"paths = ["/a", { path = "/b", title = "Customer B", note = "paused until Monday" }]"#,
+        );
+        let mut args = WatchArgs::default();
+        file.merge_into(&mut args, None);
+        assert_eq!(args.paths, vec![PathBuf::from("/a"), PathBuf::from("/b")]);
+        assert_eq!(
+            args.titles.get(Path::new("/b")).map(String::as_str),
+            Some("Customer B")
+        );
+        assert_eq!(
+            args.notes.get(Path::new("/b")).map(String::as_str),
+            Some("paused until Monday")
+        );
+    }
+
+    #[test]
+    fn globs_from_config() {
+        let file = parse(
+            "exclude = [\"*.tmp\"]\npaths = [\"/a\", { path = \"/b\", include = [\"*.csv\"], exclude = [\"*.part\"] }]",
+        );
+        let mut args = WatchArgs::default();
+        file.merge_into(&mut args, None);
+        let paths = crate::watchlist::with_listed(&args, vec![]);
+        assert!(paths[0].include.is_empty());
+        assert_eq!(paths[0].exclude, ["*.tmp"]);
+        assert_eq!(paths[1].include, ["*.csv"]);
+        assert_eq!(paths[1].exclude, ["*.tmp", "*.part"]);
+
+        let file = parse("paths = [{ path = \"/a\", include = [\"a[\"] }]");
+        let problems = file.validate();
+        assert!(problems
+            .iter()
+            .any(|p| p.message.starts_with("invalid glob") && p.span == (9..42)));
+    }
+
+    #[test]
+    fn watchlist_and_state_from_config() {
+        let source = "paths = [\"/a\"]\nwatchlist = \"dirs.txt\"\nstate = \"state.json\"\n[display]\nhealth = true";
+        let file =
+            ConfigFile::parse(Path::new("/etc/file_task/config.toml"), source.into()).unwrap();
+        let mut args = WatchArgs::default();
+        file.merge_into(&mut args, None);
+        assert_eq!(args.paths, vec![PathBuf::from("/a")]);
+        assert_eq!(
+            args.watchlist.as_deref(),
+            Some(Path::new("/etc/file_task/dirs.txt"))
+        );
+        assert_eq!(
+            args.state.as_deref(),
+            Some(Path::new("/etc/file_task/state.json"))
+        );
+        assert!(file.config.display.health && !file.config.display.notifications);
+
+        // paths from the command line replace the config's watch list along with its paths
+        let mut args = WatchArgs {
+            paths: vec![PathBuf::from("/b")],
+            state: Some(PathBuf::from("/tmp/state.json")),
+            ..Default::default()
+        };
+        file.merge_into(&mut args, None);
+        assert_eq!(args.watchlist, None);
+        assert_eq!(args.state.as_deref(), Some(Path::new("/tmp/state.json")));
+    }
+
+    #[test]
+    fn unknown_field_rejected() {
+        let result = ConfigFile::parse(Path::new("x.toml"), "nope = 1".to_string());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn cli_overrides_config() {
+        let file = parse("paths = [\"/a\"]\nservice = \"foo\"");
+        let mut args = WatchArgs {
+            paths: vec![PathBuf::from("/b")],
+            ..Default::default()
+        };
+        file.merge_into(&mut args, None);
+        assert_eq!(args.paths, vec![PathBuf::from("/b")]);
+        assert_eq!(args.services, ["foo"]);
+    }
+
+    #[test]
+    fn profile_overrides_top_level() {
+        let file =
+            parse("paths = [\"/a\"]\nservice = \"foo\"\n[profiles.other]\nservice = \"bar\"");
+        let mut args = WatchArgs::default();
+        file.merge_into(&mut args, Some("other"));
+        assert_eq!(args.paths, vec![PathBuf::from("/a")]);
+        assert_eq!(args.services, ["bar"]);
+
+        let file = parse("service = \"fetch\"\nservices = [\"transform\", \"upload\"]");
+        let mut args = WatchArgs::default();
+        file.merge_into(&mut args, None);
+        assert_eq!(args.services, ["fetch", "transform", "upload"]);
+    }
+
+    #[test]
+    fn invalid_timezone_rejected() {
+        let result = ConfigFile::parse(Path::new("x.toml"), "timezone = \"Mars/Base\"".into());
+        let message = result.unwrap_err().to_string();
+        assert!(message.contains("unknown time zone \"Mars/Base\""));
+        assert!(message.contains("line 1"));
+    }
+
+    #[test]
+    fn validate_reports_line() {
+        let file = parse("paths = []\n\n[keys]\nquit = \"nope\"");
+        let problems = file.validate();
+        assert_eq!(problems.len(), 1);
+        let report = file.describe(&problems[0]).to_string();
+        assert!(report.starts_with("/config/file_task.toml:4:8: unknown key"));
+        assert!(report.ends_with("   4 | quit = \"nope\""));
+    }
+
+    #[test]
+    fn validate_key_conflict() {
+        let file = parse("[keys]\nsave = \"q\"");
+        let problems = file.validate();
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].message, "key \"q\" is bound to quit, save");
+        assert_eq!(problems[0].span, 14..17);
+    }
+
+    #[test]
+    fn validate_bad_glob() {
+        let file = parse("[desktop_notifications]\nadded = { match = \"a[\" }");
+        let problems = file.validate();
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].message.starts_with("invalid glob"));
+        assert_eq!(problems[0].span, 42..46);
+    }
+
+    #[test]
+    fn validate_highlights() {
+        let file = parse("[[highlights]]\nmatch = \"FAIL(\"\nstyle = \"bold red\"\n\n[[highlights]]\nmatch = \"ok\"\nstyle = \"sparkly\"");
+        let problems = file.validate();
+        assert_eq!(problems.len(), 2);
+        assert!(problems[0].message.starts_with("invalid regex"));
+        assert_eq!(problems[1].message, "unknown color or style \"sparkly\"");
+        assert!(file.highlights().is_err());
+    }
+
+    #[test]
+    fn validate_journal_alerts() {
+        let file = parse("[[alerts.journal]]\nmatch = \"failed\"\npriority = \"loud\"\n\n[[alerts.journal]]\nmatch = \"error\"");
+        let problems = file.validate();
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].message.starts_with("unknown priority \"loud\""));
+        assert!(file.journal_rules().is_err());
+
+        let file = parse("[[alerts.journal]]\nmatch = \"failed\"\npriority = \"warning\"");
+        assert_eq!(file.journal_rules().unwrap()[0].priority, 4);
+    }
+
+    #[test]
+    fn validate_unknown_action() {
+        let file = parse("[keys]\nnope = \"x\"\nquit = \"q\"");
+        let problems = file.validate();
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].message, "unknown action \"nope\"");
+    }
+}
@@ -0,0 +1,343 @@
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+use crate::api::{groups_json, service_json, services_json};
+use crate::reactor::Waker;
+use crate::watchlist::tokenize;
+use crate::{AppState, Session};
+
+const REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A command accepted on the control socket, one per line.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command {
+    /// Reply with the current state as JSON
+    State,
+    /// Compare the watched directories with what's tracked, picking up anything missed
+    Rescan,
+    /// Stop applying file and service changes until resumed
+    Pause,
+    Resume,
+    Add(PathBuf),
+    Remove(PathBuf),
+    /// Give a tracked file a tag, or take one away
+    Tag(PathBuf, String),
+    Untag(PathBuf, String),
+    /// Stop showing removed files in a watched directory, or in all of them, straight away
+    Clear(Option<PathBuf>),
+    /// Switch to another profile from the config
+    Profile(String),
+}
+
+impl Command {
+    fn parse(line: &str) -> Result<Self, String> {
+        let words = tokenize(line)?;
+        let args = words.iter().map(String::as_str).collect::<Vec<_>>();
+        match args.as_slice() {
+            ["state"] => Ok(Self::State),
+            ["rescan"] => Ok(Self::Rescan),
+            ["pause"] => Ok(Self::Pause),
+            ["resume"] => Ok(Self::Resume),
+            ["add", path] => Ok(Self::Add(PathBuf::from(path))),
+            ["remove", path] => Ok(Self::Remove(PathBuf::from(path))),
+            ["tag", path, tag] => Ok(Self::Tag(PathBuf::from(path), tag.to_string())),
+            ["untag", path, tag] => Ok(Self::Untag(PathBuf::from(path), tag.to_string())),
+            ["clear"] => Ok(Self::Clear(None)),
+            ["clear", path] => Ok(Self::Clear(Some(PathBuf::from(path)))),
+            ["profile", name] => Ok(Self::Profile(name.to_string())),
+            [] => Err("empty command".to_string()),
+            [name, ..] => Err(format!("unknown command or arguments for \"{}\"", name)),
+        }
+    }
+
+    /// Whether the command may come from the unauthenticated TCP listener: reading the state
+    /// and the keybound commands `file_task attach` forwards, but nothing that changes what's
+    /// watched or tagged.
+    fn is_remote(&self) -> bool {
+        matches!(
+            self,
+            Self::State | Self::Rescan | Self::Pause | Self::Resume | Self::Clear(_)
+        )
+    }
+}
+
+/// A command along with where to send its one-line reply.
+pub struct Request {
+    pub command: Command,
+    pub reply: Sender<String>,
+}
+
+/// Listening control sockets; the socket file is removed again when this is dropped.
+pub struct ControlSocket {
+    path: Option<PathBuf>,
+    pub requests: Receiver<Request>,
+}
+
+impl ControlSocket {
+    /// Accept commands on a Unix socket at `path` and on TCP at `addr`, if given, waking the
+    /// main loop for each.
+    pub fn bind(
+        path: Option<&Path>,
+        addr: Option<SocketAddr>,
+        waker: Waker,
+    ) -> file_task::Result<Option<Self>> {
+        if path.is_none() && addr.is_none() {
+            return Ok(None);
+        }
+        let (tx, rx) = channel();
+
+        if let Some(path) = path {
+            let failed = |source| file_task::Error::Listen {
+                address: path.display().to_string(),
+                source,
+            };
+            if UnixStream::connect(path).is_ok() {
+                return Err(failed(io::ErrorKind::AddrInUse.into()));
+            }
+            match fs::symlink_metadata(path) {
+                // a socket left behind by an instance that didn't clean up after itself
+                Ok(metadata) if metadata.file_type().is_socket() => {
+                    let _ = fs::remove_file(path);
+                }
+                Ok(_) => {
+                    return Err(failed(io::Error::new(
+                        io::ErrorKind::AlreadyExists,
+                        "exists and is not a socket",
+                    )))
+                }
+                Err(_) => {}
+            }
+            let listener = UnixListener::bind(path).map_err(failed)?;
+            let (tx, waker) = (tx.clone(), waker.clone());
+            thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    let (tx, waker) = (tx.clone(), waker.clone());
+                    if let Ok(writer) = stream.try_clone() {
+                        thread::spawn(move || serve(stream, writer, false, &tx, &waker));
+                    }
+                }
+            });
+        }
+
+        if let Some(addr) = addr {
+            let listener = TcpListener::bind(addr).map_err(|source| file_task::Error::Listen {
+                address: addr.to_string(),
+                source,
+            })?;
+            thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    let (tx, waker) = (tx.clone(), waker.clone());
+                    if let Ok(writer) = stream.try_clone() {
+                        thread::spawn(move || serve(stream, writer, true, &tx, &waker));
+                    }
+                }
+            });
+        }
+
+        Ok(Some(Self {
+            path: path.map(Path::to_path_buf),
+            requests: rx,
+        }))
+    }
+}
+
+impl Drop for ControlSocket {
+    fn drop(&mut self) {
+        if let Some(path) = &self.path {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// Answer commands from one connection; `remote` ones are limited to what `Command::is_remote`
+/// allows.
+fn serve(
+    reader: impl Read,
+    mut writer: impl Write,
+    remote: bool,
+    requests: &Sender<Request>,
+    waker: &Waker,
+) {
+    for line in BufReader::new(reader).lines() {
+        let Ok(line) = line else {
+            return;
+        };
+        let reply = match Command::parse(&line) {
+            Ok(command) if remote && !command.is_remote() => {
+                format!(
+                    "error: \"{}\" is only accepted on the control socket",
+                    line.trim()
+                )
+            }
+            Ok(command) => {
+                let (reply, response) = channel();
+                if requests.send(Request { command, reply }).is_err() {
+                    return;
+                }
+                waker.wake();
+                response.recv().unwrap_or_default()
+            }
+            Err(e) => format!("error: {}", e),
+        };
+        if writeln!(writer, "{}", reply).is_err() {
+            return;
+        }
+    }
+}
+
+/// The dashboard's current state, as a single line of JSON.
+pub fn state_json(state: &AppState, session: &Session) -> String {
+    state_value(state, session).to_string()
+}
+
+pub fn state_value(state: &AppState, session: &Session) -> Value {
+    json!({
+        "profile": session.profile,
+        "paused": state.paused,
+        "service": service_json(state.services.first()),
+        "services": services_json(&state.services),
+        "groups": groups_json(&state.file_groups),
+    })
+}
+
+/// The services in a state from the control socket, including the one an instance from before
+/// there could be several reports.
+pub fn services(state: &Value) -> Vec<&Value> {
+    match state["services"].as_array() {
+        Some(services) => services.iter().collect(),
+        None => state
+            .get("service")
+            .filter(|s| !s.is_null())
+            .into_iter()
+            .collect(),
+    }
+}
+
+/// Send one command to a running instance's control socket and return its reply.
+pub fn request(path: &Path, command: &str) -> Result<String, String> {
+    let describe = |e: std::io::Error| format!("control socket {}: {}", path.display(), e);
+    let stream = UnixStream::connect(path).map_err(describe)?;
+    // replies wait for the next tick, which is a few seconds at most
+    stream
+        .set_read_timeout(Some(REPLY_TIMEOUT))
+        .map_err(describe)?;
+    let writer = stream.try_clone().map_err(describe)?;
+    Connection::new(format!("control socket {}", path.display()), stream, writer).request(command)
+}
+
+/// A connection to another instance's control port, for sending it any number of commands.
+pub struct Connection {
+    /// What's connected to, for errors
+    name: String,
+    reader: BufReader<Box<dyn Read + Send>>,
+    writer: Box<dyn Write + Send>,
+}
+
+impl Connection {
+    fn new(
+        name: String,
+        reader: impl Read + Send + 'static,
+        writer: impl Write + Send + 'static,
+    ) -> Self {
+        Self {
+            name,
+            reader: BufReader::new(Box::new(reader)),
+            writer: Box::new(writer),
+        }
+    }
+
+    /// Connect to an instance's `--control-addr`.
+    pub fn connect(addr: &str) -> Result<Self, String> {
+        let describe = |e: std::io::Error| format!("{}: {}", addr, e);
+        let stream = TcpStream::connect(addr).map_err(describe)?;
+        stream
+            .set_read_timeout(Some(REPLY_TIMEOUT))
+            .map_err(describe)?;
+        let writer = stream.try_clone().map_err(describe)?;
+        Ok(Self::new(addr.to_string(), stream, writer))
+    }
+
+    /// Send a command and wait for its reply, which is an error if it starts with "error: ".
+    pub fn request(&mut self, command: &str) -> Result<String, String> {
+        let describe = |e: std::io::Error| format!("{}: {}", self.name, e);
+        writeln!(self.writer, "{}", command).map_err(describe)?;
+        let mut reply = String::new();
+        match self.reader.read_line(&mut reply) {
+            Ok(0) => return Err(format!("{}: connection closed", self.name)),
+            Ok(_) => {}
+            Err(e) => return Err(describe(e)),
+        }
+        match reply.trim_end().strip_prefix("error: ") {
+            Some(e) => Err(e.to_string()),
+            None => Ok(reply.trim_end().to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_commands() {
+        assert_eq!(Command::parse("state"), Ok(Command::State));
+        assert_eq!(
+            Command::parse("add \"/in box\""),
+            Ok(Command::Add(PathBuf::from("/in box")))
+        );
+        assert_eq!(
+            Command::parse("profile"),
+            Err("unknown command or arguments for \"profile\"".to_string())
+        );
+        assert_eq!(
+            Command::parse("clear /in"),
+            Ok(Command::Clear(Some(PathBuf::from("/in"))))
+        );
+        assert_eq!(Command::parse("  "), Err("empty command".to_string()));
+    }
+
+    #[test]
+    fn remote_commands() {
+        assert!(Command::State.is_remote());
+        assert!(Command::Clear(None).is_remote());
+        assert!(!Command::Add(PathBuf::from("/etc")).is_remote());
+        assert!(!Command::Tag(PathBuf::from("/in/a"), "done".to_string()).is_remote());
+        assert!(!Command::Profile("night".to_string()).is_remote());
+    }
+
+    #[test]
+    fn leave_other_files_alone() {
+        let temp = tempfile::tempdir().unwrap();
+        let notes = temp.path().join("notes.txt");
+        fs::write(&notes, "keep me").unwrap();
+        let waker = crate::reactor::Reactor::new(Duration::from_secs(60))
+            .unwrap()
+            .waker();
+
+        let error = ControlSocket::bind(Some(&notes), None, waker.clone()).err();
+        assert_eq!(
+            error.map(|e| e.to_string()),
+            Some(format!(
+                "could not listen on {}: exists and is not a socket",
+                notes.display()
+            ))
+        );
+        assert_eq!(fs::read_to_string(&notes).unwrap(), "keep me");
+
+        // a stale socket is replaced
+        let stale = temp.path().join("control.sock");
+        drop(UnixListener::bind(&stale).unwrap());
+        let control = ControlSocket::bind(Some(&stale), None, waker).unwrap();
+        assert!(control.is_some());
+        assert!(UnixStream::connect(&stale).is_ok());
+    }
+}
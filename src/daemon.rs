@@ -0,0 +1,90 @@
+use std::sync::mpsc::{Receiver, Sender};
+use std::time::Instant;
+
+use crate::filesystem::WatchSource;
+
+use crate::brief::Tmux;
+use crate::control;
+use crate::dbus::DBus;
+use crate::filesystem::FileChange;
+use crate::log::{Logger, Output};
+use crate::reactor::Wake;
+use crate::systemd::Notifier;
+use crate::{AppState, Session};
+
+/// Watch without a terminal, logging events and reporting to systemd if it's supervising us. This
+/// is also how `watch --no-tui` and `watch --output` run.
+pub fn run(
+    mut data: AppState,
+    changes: (Sender<FileChange>, Receiver<FileChange>),
+    source: &mut dyn WatchSource,
+    session: &mut Session,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let output = session
+        .args
+        .output
+        .or(session.args.no_tui.then_some(Output::Text));
+    let logger = || Logger::new(output, data.timezone, session.reactor.waker());
+    let log = logger();
+    session.sinks.push(Box::new(logger()));
+    let mut tmux = session.args.tmux.then(Tmux::default);
+    let bus = session.args.dbus.map(DBus::connect).transpose()?;
+    if let Some(bus) = &bus {
+        bus.set_state(control::state_json(&data, session));
+        session.sinks.push(Box::new(bus.clone()));
+    }
+    let mut systemd = Notifier::from_env();
+    let keep_alive = systemd.as_ref().and_then(Notifier::keep_alive_interval);
+
+    if let Some(systemd) = systemd.as_mut() {
+        systemd.status(summary(&data));
+        systemd.ready();
+    }
+
+    loop {
+        crate::tick(&mut data, &changes, source, session);
+        if let Some(message) = data.message.take() {
+            log.message(&message);
+        }
+        if let Some(bus) = &bus {
+            bus.set_state(control::state_json(&data, session));
+        }
+        if let Some(tmux) = tmux.as_mut() {
+            if let Err(e) = tmux.push(&control::state_value(&data, session)) {
+                log.message(&e);
+            }
+        }
+        if let Some(systemd) = systemd.as_mut() {
+            systemd.keep_alive();
+            systemd.status(summary(&data));
+        }
+        if let Some(interval) = keep_alive {
+            session.reactor.wake_at(Instant::now() + interval);
+        }
+        for wake in session.reactor.wait() {
+            if let Wake::Signal(signal) = wake {
+                if crate::handle_signal(signal, &mut data, source, session)? {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+/// File counts per group, e.g. "Inbox: 3 files, Outbox: 0 files".
+fn summary(data: &AppState) -> String {
+    let groups = data
+        .file_groups
+        .iter()
+        .map(|group| {
+            let count = group.items().iter().filter(|f| f.removed.is_none()).count();
+            format!("{}: {} files", crate::group_title(group), count)
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    if data.paused {
+        format!("paused; {}", groups)
+    } else {
+        groups
+    }
+}
@@ -0,0 +1,130 @@
+use std::sync::{Arc, Mutex};
+
+use clap::ValueEnum;
+use serde::Deserialize;
+use zbus::blocking::connection::Builder;
+use zbus::blocking::Connection;
+use zbus::object_server::SignalEmitter;
+
+use crate::events::{Event, Receipt, Sink};
+
+const NAME: &str = "org.file_task.FileTask";
+const PATH: &str = "/org/file_task/FileTask";
+const INTERFACE: &str = "org.file_task.FileTask1";
+
+/// Which message bus to register on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Bus {
+    Session,
+    System,
+}
+
+/// The object other programs see on the bus.
+struct FileTask {
+    state: Arc<Mutex<String>>,
+}
+
+#[zbus::interface(name = "org.file_task.FileTask1")]
+impl FileTask {
+    /// The current groups, files, and service as JSON, like the control socket's state.
+    fn state(&self) -> String {
+        self.state.lock().map(|s| s.clone()).unwrap_or_default()
+    }
+
+    #[zbus(signal)]
+    async fn file_added(emitter: &SignalEmitter<'_>, group: &str, path: &str) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn file_removed(emitter: &SignalEmitter<'_>, group: &str, path: &str)
+        -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn service_changed(
+        emitter: &SignalEmitter<'_>,
+        service: &str,
+        active: bool,
+        status: &str,
+    ) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn alert(emitter: &SignalEmitter<'_>, rule: &str, message: &str) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn action(
+        emitter: &SignalEmitter<'_>,
+        rule: &str,
+        path: &str,
+        message: &str,
+    ) -> zbus::Result<()>;
+}
+
+/// Owns a well-known name on the bus, answers State calls, and emits a signal for each event.
+#[derive(Clone)]
+pub struct DBus {
+    connection: Connection,
+    state: Arc<Mutex<String>>,
+}
+
+impl DBus {
+    pub fn connect(bus: Bus) -> Result<Self, String> {
+        let state = Arc::new(Mutex::new(String::new()));
+        let object = FileTask {
+            state: Arc::clone(&state),
+        };
+        let builder = match bus {
+            Bus::Session => Builder::session(),
+            Bus::System => Builder::system(),
+        };
+        let connection = builder
+            .and_then(|b| b.name(NAME))
+            .and_then(|b| b.serve_at(PATH, object))
+            .and_then(|b| b.build())
+            .map_err(|e| format!("could not register {} on the {:?} bus: {}", NAME, bus, e))?;
+        Ok(Self { connection, state })
+    }
+
+    /// Set what the State method returns.
+    pub fn set_state(&self, json: String) {
+        if let Ok(mut state) = self.state.lock() {
+            *state = json;
+        }
+    }
+
+    fn emit<B>(&self, signal: &str, body: &B) -> zbus::Result<()>
+    where
+        B: serde::Serialize + zbus::zvariant::DynamicType,
+    {
+        self.connection
+            .emit_signal(None::<&str>, PATH, INTERFACE, signal, body)
+    }
+}
+
+impl Sink for DBus {
+    fn send(&mut self, event: &Event, receipt: Receipt) {
+        let emitted = match event {
+            Event::FileAdded { group, path } => {
+                self.emit("FileAdded", &(group, path.to_string_lossy()))
+            }
+            Event::FileRemoved { group, path } => {
+                self.emit("FileRemoved", &(group, path.to_string_lossy()))
+            }
+            Event::ServiceChanged {
+                service,
+                active,
+                status,
+            } => self.emit("ServiceChanged", &(service, active, status.trim())),
+            Event::Alert { rule, message, .. } => self.emit("Alert", &(rule, message)),
+            Event::Action {
+                rule,
+                path,
+                message,
+            } => self.emit("Action", &(rule, path.to_string_lossy(), message)),
+        };
+        receipt.result(&emitted);
+    }
+
+    fn name(&self) -> String {
+        "D-Bus".to_string()
+    }
+}
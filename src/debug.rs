@@ -0,0 +1,91 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::time::{Duration, Instant};
+
+/// How far back file changes are counted to give a rate.
+const WINDOW: Duration = Duration::from_secs(5);
+
+/// The dashboard's internals, shown in the debug overlay to find where the time goes when it
+/// lags.
+#[derive(Debug, Default)]
+pub struct Internals {
+    /// The watcher backend in use
+    pub backend: String,
+    /// How many file changes each recent tick applied, and when
+    recent: VecDeque<(Instant, usize)>,
+    /// File changes that were waiting to be applied at the last tick
+    pub backlog: usize,
+    /// Wakes handled together after the last wait
+    pub wakes: usize,
+    /// How long the last frame took to draw
+    pub draw: Duration,
+    /// Resident memory in bytes, read while the overlay is shown
+    pub memory: Option<u64>,
+}
+
+impl Internals {
+    pub fn new(backend: &str) -> Self {
+        Self {
+            backend: backend.to_string(),
+            ..Self::default()
+        }
+    }
+
+    /// Note the file changes a tick took from the watcher.
+    pub fn applied(&mut self, changes: usize, now: Instant) {
+        self.backlog = changes;
+        if changes > 0 {
+            self.recent.push_back((now, changes));
+        }
+        while let Some((at, _)) = self.recent.front() {
+            if now.duration_since(*at) <= WINDOW {
+                break;
+            }
+            self.recent.pop_front();
+        }
+    }
+
+    /// File changes applied per second, over the last few seconds.
+    pub fn rate(&self, now: Instant) -> f64 {
+        let changes: usize = self
+            .recent
+            .iter()
+            .filter(|(at, _)| now.duration_since(*at) <= WINDOW)
+            .map(|(_, changes)| changes)
+            .sum();
+        changes as f64 / WINDOW.as_secs_f64()
+    }
+}
+
+/// This process's resident memory in bytes, where /proc has it.
+pub fn resident_memory() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let kb = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+    Some(kb * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rate_over_the_window() {
+        let start = Instant::now();
+        let mut internals = Internals::new("local");
+        internals.applied(20, start);
+        internals.applied(0, start + Duration::from_secs(1));
+        internals.applied(5, start + Duration::from_secs(3));
+        assert_eq!(internals.backlog, 5);
+        assert_eq!(internals.rate(start + Duration::from_secs(3)), 5.0);
+        internals.applied(0, start + Duration::from_secs(7));
+        assert_eq!(internals.rate(start + Duration::from_secs(7)), 1.0);
+        assert_eq!(internals.recent.len(), 1);
+    }
+}
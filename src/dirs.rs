@@ -0,0 +1,20 @@
+use std::path::PathBuf;
+
+use directories::ProjectDirs;
+
+/// Where file_task keeps its own files when not told otherwise, following each platform's
+/// conventions: XDG base directories on Linux, `~/Library/Application Support` on macOS, and
+/// `%APPDATA%` on Windows.
+fn project() -> Option<ProjectDirs> {
+    ProjectDirs::from("", "", "file_task")
+}
+
+/// The config file used when `--config` isn't given (it doesn't necessarily exist).
+pub fn config_file() -> Option<PathBuf> {
+    project().map(|dirs| dirs.config_dir().join("config.toml"))
+}
+
+/// Where the dashboard's state is kept between runs when `--state` isn't given.
+pub fn state_file() -> Option<PathBuf> {
+    project().map(|dirs| dirs.data_local_dir().join("state.json"))
+}
@@ -0,0 +1,302 @@
+use std::collections::HashSet;
+use std::ffi::CString;
+use std::io;
+use std::mem::MaybeUninit;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::Duration;
+
+use crate::config::FreeLimits;
+use crate::events::Event;
+use crate::filesystem::FileGroup;
+use crate::health::Check;
+use crate::reactor::Waker;
+
+/// How often the filesystems are looked at again.
+const CHECK_EVERY: Duration = Duration::from_secs(30);
+
+/// How much room is left on the filesystem holding a group's root, as `statvfs` reports it. That
+/// includes any limit the filesystem applies to the directory itself, like an XFS project quota.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Usage {
+    pub group: String,
+    /// Identifies the filesystem, so groups sharing one are only warned about once
+    pub filesystem: u64,
+    pub free_bytes: u64,
+    pub total_bytes: u64,
+    pub free_inodes: u64,
+    /// Zero for filesystems without a fixed number of inodes, like btrfs
+    pub total_inodes: u64,
+}
+
+impl Usage {
+    // the fields' types differ between platforms
+    #[allow(clippy::unnecessary_cast)]
+    fn read(group: String, root: &Path) -> io::Result<Self> {
+        let path = CString::new(root.as_os_str().as_bytes())?;
+        let mut stat = MaybeUninit::<libc::statvfs>::uninit();
+        if unsafe { libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let stat = unsafe { stat.assume_init() };
+        let block = stat.f_frsize as u64;
+        Ok(Self {
+            group,
+            filesystem: stat.f_fsid as u64,
+            free_bytes: stat.f_bavail as u64 * block,
+            total_bytes: stat.f_blocks as u64 * block,
+            free_inodes: stat.f_favail as u64,
+            total_inodes: stat.f_files as u64,
+        })
+    }
+
+    /// The percentage of bytes left.
+    pub fn free_space(&self) -> f64 {
+        percent(self.free_bytes, self.total_bytes)
+    }
+
+    /// The percentage of inodes left, if the filesystem has a fixed number of them.
+    pub fn free_inodes(&self) -> Option<f64> {
+        (self.total_inodes > 0).then(|| percent(self.free_inodes, self.total_inodes))
+    }
+
+    /// What's running low, if anything, as it would be said in a warning.
+    pub fn low(&self, limits: &FreeLimits) -> Option<String> {
+        let space = limits
+            .space
+            .filter(|limit| self.free_space() < *limit)
+            .map(|_| format!("{:.0}% space free", self.free_space()));
+        let inodes = limits
+            .inodes
+            .zip(self.free_inodes())
+            .filter(|(limit, free)| free < limit)
+            .map(|(_, free)| format!("{:.0}% inodes free", free));
+        let low = space.into_iter().chain(inodes).collect::<Vec<_>>();
+        (!low.is_empty()).then(|| low.join(", "))
+    }
+}
+
+fn percent(part: u64, total: u64) -> f64 {
+    if total == 0 {
+        return 100.0;
+    }
+    part as f64 * 100.0 / total as f64
+}
+
+/// Keeps an eye on the free space and inodes of the filesystems the groups are on. `statvfs` is
+/// called in the background, since a hung network filesystem would otherwise hold up the
+/// dashboard.
+pub struct Disks {
+    limits: FreeLimits,
+    roots: Sender<Vec<(String, PathBuf)>>,
+    read: Receiver<Vec<Usage>>,
+    usage: Vec<Usage>,
+    /// The roots last sent to be looked at
+    watched: Vec<(String, PathBuf)>,
+    /// Filesystems running low, each warned about once until there's room again
+    low: HashSet<u64>,
+}
+
+impl Disks {
+    pub fn new(limits: Option<FreeLimits>, waker: Waker) -> Self {
+        let (roots, rx) = channel::<Vec<(String, PathBuf)>>();
+        let (tx, read) = channel();
+        thread::spawn(move || {
+            let mut watched = vec![];
+            let mut last = vec![];
+            loop {
+                match rx.recv_timeout(CHECK_EVERY) {
+                    Ok(roots) => watched = roots,
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+                let usage = watched
+                    .iter()
+                    .filter_map(|(group, root): &(String, PathBuf)| {
+                        Usage::read(group.clone(), root).ok()
+                    })
+                    .collect::<Vec<_>>();
+                // the loop is only woken for what's changed
+                if usage == last {
+                    continue;
+                }
+                if tx.send(usage.clone()).is_err() {
+                    return;
+                }
+                last = usage;
+                waker.wake();
+            }
+        });
+        Self {
+            limits: limits.unwrap_or_default(),
+            roots,
+            read,
+            usage: vec![],
+            watched: vec![],
+            low: HashSet::new(),
+        }
+    }
+
+    /// Use new limits, e.g. after the config is reloaded.
+    pub fn set_limits(&mut self, limits: Option<FreeLimits>) {
+        self.limits = limits.unwrap_or_default();
+        self.low.clear();
+    }
+
+    /// Have the filesystems looked at again if the groups have changed, and take in what was
+    /// found since the last call, raising an alert for each that has started running low.
+    pub fn update(&mut self, groups: &[FileGroup]) -> Vec<Event> {
+        let watched = groups
+            .iter()
+            .map(|group| (crate::group_title(group).to_string(), group.root.clone()))
+            .collect::<Vec<_>>();
+        if watched != self.watched {
+            let _ = self.roots.send(watched.clone());
+            self.watched = watched;
+        }
+        if let Some(usage) = self.read.try_iter().last() {
+            self.usage = usage;
+        }
+        self.check()
+    }
+
+    fn check(&mut self) -> Vec<Event> {
+        let mut alerts = vec![];
+        let mut seen = HashSet::new();
+        for usage in &self.usage {
+            if !seen.insert(usage.filesystem) {
+                continue;
+            }
+            match usage.low(&self.limits) {
+                Some(low) if self.low.insert(usage.filesystem) => alerts.push(Event::Alert {
+                    rule: "space".to_string(),
+                    path: None,
+                    message: format!("{} is running low, with {}", usage.group, low),
+                }),
+                Some(_) => {}
+                None => {
+                    self.low.remove(&usage.filesystem);
+                }
+            }
+        }
+        alerts
+    }
+
+    /// What was found when the filesystems were last looked at, a group at a time.
+    pub fn usage(&self) -> &[Usage] {
+        &self.usage
+    }
+
+    /// The groups on filesystems running low, with what's low, one group per filesystem.
+    pub fn warnings(&self) -> Vec<(String, String)> {
+        let mut seen = HashSet::new();
+        self.usage
+            .iter()
+            .filter(|usage| seen.insert(usage.filesystem))
+            .filter_map(|usage| Some((usage.group.clone(), usage.low(&self.limits)?)))
+            .collect()
+    }
+
+    /// Each filesystem, if there are limits on them, and whether it has room.
+    pub fn health(&self) -> Vec<Check> {
+        if self.limits.space.is_none() && self.limits.inodes.is_none() {
+            return vec![];
+        }
+        let mut seen = HashSet::new();
+        self.usage
+            .iter()
+            .filter(|usage| seen.insert(usage.filesystem))
+            .map(|usage| {
+                let low = usage.low(&self.limits);
+                Check::new(
+                    format!("space {}", usage.group),
+                    low.is_none(),
+                    low.unwrap_or_else(|| format!("{:.0}% space free", usage.free_space())),
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reactor::Reactor;
+
+    fn usage(group: &str, filesystem: u64, free_bytes: u64, free_inodes: u64) -> Usage {
+        Usage {
+            group: group.to_string(),
+            filesystem,
+            free_bytes,
+            total_bytes: 1000,
+            free_inodes,
+            total_inodes: 100,
+        }
+    }
+
+    #[test]
+    fn warn_once_per_filesystem() {
+        let waker = Reactor::new(Duration::from_secs(60)).unwrap().waker();
+        let mut disks = Disks::new(
+            Some(FreeLimits {
+                space: Some(10.0),
+                inodes: Some(5.0),
+            }),
+            waker,
+        );
+        disks.usage = vec![
+            usage("ingest", 1, 500, 2),
+            usage("staging", 1, 500, 2),
+            usage("archive", 2, 50, 50),
+            usage("reports", 3, 500, 50),
+        ];
+        let alerts = disks.check();
+        assert_eq!(alerts.len(), 2);
+        assert_eq!(
+            disks.warnings(),
+            [
+                ("ingest".to_string(), "2% inodes free".to_string()),
+                ("archive".to_string(), "5% space free".to_string()),
+            ]
+        );
+        assert!(disks.check().is_empty());
+
+        disks.usage[0].free_inodes = 50;
+        disks.usage[1].free_inodes = 50;
+        assert!(disks.check().is_empty());
+        disks.usage[0].free_inodes = 1;
+        assert_eq!(disks.check().len(), 1);
+
+        // no fixed number of inodes
+        let btrfs = Usage {
+            total_inodes: 0,
+            ..usage("btrfs", 4, 500, 0)
+        };
+        assert_eq!(btrfs.free_inodes(), None);
+    }
+
+    #[test]
+    fn read_in_the_background() {
+        let mut reactor = Reactor::new(Duration::from_secs(60)).unwrap();
+        let mut disks = Disks::new(None, reactor.waker());
+        let groups = [FileGroup::new(
+            std::env::temp_dir(),
+            Some("tmp".to_string()),
+            vec![],
+        )];
+        disks.update(&groups);
+        reactor.wait();
+        disks.update(&groups);
+        assert_eq!(disks.usage()[0].group, "tmp");
+    }
+
+    #[test]
+    fn read_a_real_filesystem() {
+        let usage = Usage::read("tmp".to_string(), &std::env::temp_dir()).unwrap();
+        assert!(usage.total_bytes > 0);
+        assert!(usage.free_bytes <= usage.total_bytes);
+        assert!(Usage::read("missing".to_string(), Path::new("/no/such/dir")).is_err());
+    }
+}
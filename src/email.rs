@@ -0,0 +1,127 @@
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::Local;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport};
+
+use crate::config::{EmailConfig, EmailTls};
+use crate::events::{Event, EventFilter, Receipt, Sink};
+
+/// Emails selected events, gathering any that arrive close together into one digest.
+pub struct Email {
+    filter: EventFilter,
+    /// Each event's time and summary
+    tx: Sender<(String, String, Receipt)>,
+}
+
+impl Email {
+    pub fn new(
+        config: &EmailConfig,
+        diagnostics: Sender<String>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let filter = EventFilter::new(&config.events)?;
+
+        let mut builder = match config.tls {
+            EmailTls::Starttls => SmtpTransport::starttls_relay(&config.server)?,
+            EmailTls::Tls => SmtpTransport::relay(&config.server)?,
+            EmailTls::None => SmtpTransport::builder_dangerous(&config.server),
+        };
+        if let Some(port) = config.port {
+            builder = builder.port(port);
+        }
+        if let Some(username) = &config.username {
+            let password = config.password.clone().unwrap_or_default();
+            builder = builder.credentials(Credentials::new(username.clone(), password));
+        }
+        let transport = builder.build();
+
+        let from = config
+            .from
+            .parse::<Mailbox>()
+            .map_err(|e| format!("invalid email from address {}: {}", config.from, e))?;
+        let to = config
+            .to
+            .iter()
+            .map(|to| {
+                to.parse::<Mailbox>()
+                    .map_err(|e| format!("invalid email to address {}: {}", to, e))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let digest = config.digest.0;
+
+        let (tx, rx) = channel::<(String, String, Receipt)>();
+        // sending can take a while, and digests wait for more events, so keep it off the UI thread
+        thread::spawn(move || {
+            while let Some(events) = gather(&rx, digest) {
+                let subject = match events.as_slice() {
+                    [(_, summary, _)] => format!("file_task: {}", summary),
+                    events => format!("file_task: {} events", events.len()),
+                };
+                let body = events
+                    .iter()
+                    .map(|(time, summary, _)| format!("{} {}", time, summary))
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                let mut message = Message::builder().from(from.clone()).subject(subject);
+                for to in &to {
+                    message = message.to(to.clone());
+                }
+                let sent = message
+                    .body(body)
+                    .map_err(|e| e.to_string())
+                    .and_then(|m| transport.send(&m).map(|_| ()).map_err(|e| e.to_string()));
+                if let Err(e) = &sent {
+                    let _ = diagnostics.send(format!("email failed: {}", e));
+                }
+                for (_, _, receipt) in events {
+                    receipt.result(&sent);
+                }
+            }
+        });
+
+        Ok(Self { filter, tx })
+    }
+}
+
+/// Wait for an event, then collect any more that arrive within the digest window.
+fn gather<T>(rx: &Receiver<T>, digest: Duration) -> Option<Vec<T>> {
+    let mut events = vec![rx.recv().ok()?];
+    let deadline = Instant::now() + digest;
+    loop {
+        match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+            Ok(event) => events.push(event),
+            Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => return Some(events),
+        }
+    }
+}
+
+impl Sink for Email {
+    fn send(&mut self, event: &Event, receipt: Receipt) {
+        if self.filter.matches(event) {
+            let time = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+            let _ = self.tx.send((time, event.summary(), receipt));
+        }
+    }
+
+    fn name(&self) -> String {
+        "email".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gather_collects_a_burst() {
+        let (tx, rx) = channel();
+        tx.send(1).unwrap();
+        tx.send(2).unwrap();
+        assert_eq!(gather(&rx, Duration::from_millis(10)), Some(vec![1, 2]));
+        drop(tx);
+        assert_eq!(gather(&rx, Duration::from_millis(10)), None);
+    }
+}
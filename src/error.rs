@@ -0,0 +1,51 @@
+use std::io;
+use std::path::PathBuf;
+
+/// What can go wrong finding, reading, or watching the directories to track, and setting up
+/// everything around that: the config, the terminal, and the files and sockets kept alongside.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("no paths to watch")]
+    NoPaths,
+    /// A path that was asked for isn't a directory that can be watched
+    #[error("path {} {reason}", path.display())]
+    NotWatchable { path: PathBuf, reason: &'static str },
+    /// Reading what's in a directory, or resolving a path, failed
+    #[error("could not scan {}: {source}", path.display())]
+    Scan { path: PathBuf, source: io::Error },
+    /// A list of paths to watch couldn't be read
+    #[error("could not read {}: {source}", path.display())]
+    PathList { path: PathBuf, source: io::Error },
+    /// A glob picking which files to show is invalid
+    #[error("invalid glob {pattern}: {source}")]
+    Glob {
+        pattern: String,
+        source: globset::Error,
+    },
+    /// The watcher couldn't be started
+    #[error("could not start watching: {0}")]
+    Start(#[source] notify::Error),
+    /// Starting or stopping watching a directory failed, e.g. when out of inotify watches
+    #[error("could not watch {}: {source}", path.display())]
+    Watch {
+        path: PathBuf,
+        source: notify::Error,
+    },
+    /// A config file couldn't be read, understood, or written
+    #[error("config {}: {source}", path.display())]
+    Config {
+        path: PathBuf,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    /// The terminal couldn't be set up for the dashboard, or given back
+    #[error("could not set up the terminal: {0}")]
+    Terminal(#[source] io::Error),
+    /// What's tracked, or a checkpoint of the dashboard, couldn't be saved
+    #[error("could not save {}: {source}", path.display())]
+    Save { path: PathBuf, source: io::Error },
+    /// A socket for control or an API couldn't be listened on
+    #[error("could not listen on {address}: {source}")]
+    Listen { address: String, source: io::Error },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
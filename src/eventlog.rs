@@ -0,0 +1,121 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use std::time::{Duration, SystemTime};
+
+use chrono::Local;
+use serde_json::json;
+
+use crate::config::EventLogConfig;
+use crate::events::{Event, Receipt, Sink};
+
+const CURRENT: &str = "events.jsonl";
+
+/// Appends every event as a line of JSON to `events.jsonl` in a directory, moving it aside to a
+/// timestamped name when it gets too big or too old and deleting the oldest of those beyond
+/// the number to keep.
+pub struct EventLog {
+    dir: PathBuf,
+    file: File,
+    size: u64,
+    opened: SystemTime,
+    max_bytes: u64,
+    rotate_after: Duration,
+    keep: usize,
+    diagnostics: Sender<String>,
+}
+
+impl EventLog {
+    pub fn open(
+        dir: &Path,
+        config: &EventLogConfig,
+        diagnostics: Sender<String>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        fs::create_dir_all(dir)
+            .map_err(|e| format!("could not create event log {}: {}", dir.display(), e))?;
+        let (file, size, opened) = open_current(dir)
+            .map_err(|e| format!("could not open event log in {}: {}", dir.display(), e))?;
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            file,
+            size,
+            opened,
+            max_bytes: config.max_bytes,
+            rotate_after: config.rotate_after.0,
+            keep: config.keep,
+            diagnostics,
+        })
+    }
+
+    fn due(&self) -> bool {
+        let age = self.opened.elapsed().unwrap_or_default();
+        self.size > 0 && (self.size >= self.max_bytes || age >= self.rotate_after)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        let stamp = Local::now().format("%Y%m%dT%H%M%S%.3f");
+        fs::rename(
+            self.dir.join(CURRENT),
+            self.dir.join(format!("events-{}.jsonl", stamp)),
+        )?;
+        (self.file, self.size, self.opened) = open_current(&self.dir)?;
+
+        // the timestamps sort, so the oldest come first
+        let mut rotated = fs::read_dir(&self.dir)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| {
+                let name = path
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .unwrap_or_default();
+                name.starts_with("events-") && name.ends_with(".jsonl")
+            })
+            .collect::<Vec<_>>();
+        rotated.sort();
+        let excess = rotated.len().saturating_sub(self.keep);
+        for path in &rotated[..excess] {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, event: &Event) -> io::Result<()> {
+        if self.due() {
+            self.rotate()?;
+        }
+        let mut line = json!(event.payload()).to_string();
+        line.push('\n');
+        self.file.write_all(line.as_bytes())?;
+        self.file.sync_data()?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+}
+
+fn open_current(dir: &Path) -> io::Result<(File, u64, SystemTime)> {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dir.join(CURRENT))?;
+    let metadata = file.metadata()?;
+    let opened = metadata.created().unwrap_or_else(|_| SystemTime::now());
+    Ok((file, metadata.len(), opened))
+}
+
+impl Sink for EventLog {
+    fn send(&mut self, event: &Event, receipt: Receipt) {
+        let written = self.write(event);
+        if let Err(e) = &written {
+            let _ =
+                self.diagnostics
+                    .send(format!("event log {} failed: {}", self.dir.display(), e));
+        }
+        receipt.result(&written);
+    }
+
+    fn name(&self) -> String {
+        "event log".to_string()
+    }
+}
@@ -0,0 +1,478 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+use chrono::Utc;
+use globset::{Glob, GlobMatcher};
+use serde::{Deserialize, Serialize};
+use toml::Spanned;
+
+use crate::filesystem::{FileChange, FileGroup};
+use crate::reactor::Waker;
+use crate::service::ServiceState;
+
+/// Something that happened that may be worth telling the outside world about.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    FileAdded {
+        group: String,
+        path: PathBuf,
+    },
+    FileRemoved {
+        group: String,
+        path: PathBuf,
+    },
+    ServiceChanged {
+        service: String,
+        active: bool,
+        status: String,
+    },
+    /// A condition that needs attention, raised by one of the configured alert rules
+    Alert {
+        rule: String,
+        /// The file the alert is about, if it's about one
+        path: Option<PathBuf>,
+        message: String,
+    },
+    /// Something one of the configured actions did (or would have done, in a dry run) to a file
+    Action {
+        rule: String,
+        path: PathBuf,
+        message: String,
+    },
+}
+
+/// The kinds of event that can be selected in the config.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    Added,
+    Removed,
+    ServiceDown,
+    ServiceUp,
+    Alert,
+    Action,
+}
+
+impl EventKind {
+    pub const ALL: &'static [EventKind] = &[
+        EventKind::Added,
+        EventKind::Removed,
+        EventKind::ServiceDown,
+        EventKind::ServiceUp,
+        EventKind::Alert,
+        EventKind::Action,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Added => "added",
+            Self::Removed => "removed",
+            Self::ServiceDown => "service_down",
+            Self::ServiceUp => "service_up",
+            Self::Alert => "alert",
+            Self::Action => "action",
+        }
+    }
+}
+
+impl Event {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            Self::FileAdded { .. } => EventKind::Added,
+            Self::FileRemoved { .. } => EventKind::Removed,
+            Self::ServiceChanged { active: true, .. } => EventKind::ServiceUp,
+            Self::ServiceChanged { active: false, .. } => EventKind::ServiceDown,
+            Self::Alert { .. } => EventKind::Alert,
+            Self::Action { .. } => EventKind::Action,
+        }
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Self::FileAdded { path, .. }
+            | Self::FileRemoved { path, .. }
+            | Self::Action { path, .. } => Some(path),
+            Self::Alert { path, .. } => path.as_deref(),
+            Self::ServiceChanged { .. } => None,
+        }
+    }
+
+    /// The machine readable form of the event, stamped with the current time.
+    pub fn payload(&self) -> Payload<'_> {
+        let mut payload = Payload {
+            event: self.kind(),
+            time: Utc::now().to_rfc3339(),
+            group: None,
+            path: None,
+            service: None,
+            status: None,
+            rule: None,
+            message: None,
+        };
+        match self {
+            Self::FileAdded { group, path } | Self::FileRemoved { group, path } => {
+                payload.group = Some(group);
+                payload.path = Some(path);
+            }
+            Self::ServiceChanged {
+                service, status, ..
+            } => {
+                payload.service = Some(service);
+                payload.status = Some(status.trim());
+            }
+            Self::Alert {
+                rule,
+                path,
+                message,
+            } => {
+                payload.rule = Some(rule);
+                payload.path = path.as_deref();
+                payload.message = Some(message);
+            }
+            Self::Action {
+                rule,
+                path,
+                message,
+            } => {
+                payload.rule = Some(rule);
+                payload.path = Some(path);
+                payload.message = Some(message);
+            }
+        }
+        payload
+    }
+
+    /// Fill in `{path}`, `{group}`, `{event}`, `{service}`, `{status}`, `{rule}`, and `{message}`
+    /// in a template.
+    pub fn expand(&self, template: &str) -> String {
+        let payload = self.payload();
+        let path = payload.path.map(|p| p.to_string_lossy().into_owned());
+        let values = [
+            ("{event}", Some(self.kind().name())),
+            ("{path}", path.as_deref()),
+            ("{group}", payload.group),
+            ("{service}", payload.service),
+            ("{status}", payload.status),
+            ("{rule}", payload.rule),
+            ("{message}", payload.message),
+        ];
+
+        values
+            .iter()
+            .fold(template.to_string(), |text, (placeholder, value)| {
+                text.replace(placeholder, value.unwrap_or(""))
+            })
+    }
+
+    /// A one-line human readable description.
+    pub fn summary(&self) -> String {
+        match self {
+            Self::FileAdded { group, path } => {
+                format!("{}: added {}", group, crate::display_name(path))
+            }
+            Self::FileRemoved { group, path } => {
+                format!("{}: removed {}", group, crate::display_name(path))
+            }
+            Self::ServiceChanged {
+                service, status, ..
+            } => format!("service {} is {}", service, status.trim()),
+            Self::Alert { message, .. } => message.clone(),
+            Self::Action { rule, message, .. } => format!("{}: {}", rule, message),
+        }
+    }
+}
+
+/// An event as sent to external consumers, e.g. as JSON.
+#[derive(Debug, Serialize)]
+pub struct Payload<'a> {
+    pub event: EventKind,
+    pub time: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<&'a Path>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub service: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rule: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<&'a str>,
+}
+
+/// Whether events of one kind should be passed on, as configured per sink.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+
+    /// Only pass on file events for names matching this glob
+    #[serde(rename = "match")]
+    pub pattern: Option<Spanned<String>>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl Rule {
+    pub fn matcher(&self) -> Result<Option<GlobMatcher>, globset::Error> {
+        self.pattern
+            .as_ref()
+            .map(|p| Glob::new(p.get_ref()).map(|g| g.compile_matcher()))
+            .transpose()
+    }
+}
+
+/// The compiled form of a set of rules; event kinds without a rule are not passed on.
+#[derive(Debug, Clone, Default)]
+pub struct EventFilter {
+    rules: HashMap<EventKind, Option<GlobMatcher>>,
+}
+
+impl EventFilter {
+    pub fn new(rules: &HashMap<EventKind, Rule>) -> Result<Self, globset::Error> {
+        let rules = rules
+            .iter()
+            .filter(|(_, rule)| rule.enabled)
+            .map(|(kind, rule)| Ok((*kind, rule.matcher()?)))
+            .collect::<Result<_, globset::Error>>()?;
+        Ok(Self { rules })
+    }
+
+    pub fn matches(&self, event: &Event) -> bool {
+        match (self.rules.get(&event.kind()), event.path()) {
+            (None, _) => false,
+            (Some(Some(matcher)), Some(path)) => {
+                path.file_name().is_some_and(|name| matcher.is_match(name))
+            }
+            (Some(_), _) => true,
+        }
+    }
+}
+
+/// Something that wants to hear about events.
+pub trait Sink {
+    /// Pass an event on, saying what became of it through the receipt.
+    fn send(&mut self, event: &Event, receipt: Receipt);
+
+    /// How the sink is referred to when saying what it did with an event.
+    fn name(&self) -> String;
+}
+
+/// What a sink did with an event.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Delivery {
+    /// Not heard back about yet
+    Pending,
+    Sent,
+    Failed(String),
+    /// The sink doesn't pass on events like this one
+    Skipped,
+}
+
+/// Where sinks report back on events, by the event's number and the sink's index, waking the
+/// main loop to hear it.
+#[derive(Clone)]
+pub struct Deliveries {
+    tx: Sender<(u64, usize, Delivery)>,
+    waker: Waker,
+}
+
+impl Deliveries {
+    pub fn new(waker: Waker) -> (Self, Receiver<(u64, usize, Delivery)>) {
+        let (tx, rx) = channel();
+        (Self { tx, waker }, rx)
+    }
+}
+
+/// A sink's way of reporting back on one event, if anyone asked to hear. It can be carried off
+/// to wherever the event is delivered from; dropping it without saying means the event was
+/// skipped.
+pub struct Receipt(Option<(u64, usize, Deliveries)>);
+
+impl Receipt {
+    pub fn new(event: u64, sink: usize, deliveries: Deliveries) -> Self {
+        Self(Some((event, sink, deliveries)))
+    }
+
+    /// For events nobody needs to hear back about.
+    pub fn none() -> Self {
+        Self(None)
+    }
+
+    pub fn sent(mut self) {
+        self.report(Delivery::Sent);
+    }
+
+    pub fn failed(mut self, error: impl std::fmt::Display) {
+        self.report(Delivery::Failed(error.to_string()));
+    }
+
+    /// Sent or failed, from how delivering it went.
+    pub fn result<E: std::fmt::Display>(self, result: &std::result::Result<(), E>) {
+        match result {
+            Ok(()) => self.sent(),
+            Err(e) => self.failed(e),
+        }
+    }
+
+    fn report(&mut self, delivery: Delivery) {
+        if let Some((event, sink, deliveries)) = self.0.take() {
+            if deliveries.tx.send((event, sink, delivery)).is_ok() {
+                deliveries.waker.wake();
+            }
+        }
+    }
+}
+
+impl Drop for Receipt {
+    fn drop(&mut self) {
+        self.report(Delivery::Skipped);
+    }
+}
+
+pub fn file_events(changes: &[FileChange], groups: &[FileGroup]) -> Vec<Event> {
+    let group_name = |path: &Path| {
+        groups
+            .iter()
+            .find(|g| path.starts_with(&g.root))
+            .map(|g| crate::group_title(g).to_string())
+    };
+    let added = |path: &Path| {
+        group_name(path).map(|group| Event::FileAdded {
+            group,
+            path: path.to_path_buf(),
+        })
+    };
+    let removed = |path: &Path| {
+        group_name(path).map(|group| Event::FileRemoved {
+            group,
+            path: path.to_path_buf(),
+        })
+    };
+
+    changes
+        .iter()
+        .flat_map(|change| match change {
+            FileChange::Added(path) => vec![added(path)],
+            FileChange::Removed(path) => vec![removed(path)],
+            FileChange::Moved(from, to) => vec![removed(from), added(to)],
+            FileChange::Modified(_) => vec![],
+        })
+        .flatten()
+        .collect()
+}
+
+/// An event if the service went up or down; a service found to be up at startup isn't news.
+pub fn service_event(previous: Option<&ServiceState>, current: &ServiceState) -> Option<Event> {
+    let was_active = previous.and_then(ServiceState::active);
+    match (was_active, current) {
+        (None, ServiceState::Details(details)) if details.active => None,
+        (_, ServiceState::Details(details)) if was_active != Some(details.active) => {
+            Some(Event::ServiceChanged {
+                service: current.name().to_string(),
+                active: details.active,
+                status: details.status.clone(),
+            })
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn added(path: &str) -> Event {
+        Event::FileAdded {
+            group: "root".to_string(),
+            path: PathBuf::from(path),
+        }
+    }
+
+    #[test]
+    fn filter_by_kind_and_pattern() {
+        let rules: HashMap<EventKind, Rule> = toml::from_str(
+            "added = { match = \"*.pdf\" }\nremoved = { enabled = false }\nservice_down = {}",
+        )
+        .unwrap();
+        let filter = EventFilter::new(&rules).unwrap();
+
+        assert!(filter.matches(&added("/root/a.pdf")));
+        assert!(!filter.matches(&added("/root/a.txt")));
+        assert!(!filter.matches(&Event::FileRemoved {
+            group: "root".to_string(),
+            path: PathBuf::from("/root/a.pdf"),
+        }));
+        assert!(filter.matches(&Event::ServiceChanged {
+            service: "foo".to_string(),
+            active: false,
+            status: "failed".to_string(),
+        }));
+    }
+
+    #[test]
+    fn receipts_report_back() {
+        let reactor = crate::reactor::Reactor::new(std::time::Duration::from_secs(60)).unwrap();
+        let (deliveries, rx) = Deliveries::new(reactor.waker());
+
+        Receipt::new(1, 0, deliveries.clone()).sent();
+        Receipt::new(1, 1, deliveries.clone()).result(&Err("refused"));
+        // a sink that drops it without saying didn't pass the event on
+        drop(Receipt::new(1, 2, deliveries));
+        Receipt::none().sent();
+
+        assert_eq!(
+            rx.try_iter().collect::<Vec<_>>(),
+            [
+                (1, 0, Delivery::Sent),
+                (1, 1, Delivery::Failed("refused".to_string())),
+                (1, 2, Delivery::Skipped),
+            ]
+        );
+    }
+
+    #[test]
+    fn payload_json() {
+        let json = serde_json::to_value(added("/root/a.pdf").payload()).unwrap();
+        assert_eq!(json["event"], "added");
+        assert_eq!(json["group"], "root");
+        assert_eq!(json["path"], "/root/a.pdf");
+        assert!(json.get("service").is_none());
+        assert!(json["time"].is_string());
+    }
+
+    #[test]
+    fn file_events_for_moves() {
+        let groups = vec![FileGroup::new(
+            PathBuf::from("/root"),
+            Some("Inbox".to_string()),
+            vec![],
+        )];
+        let changes = vec![FileChange::Moved(
+            PathBuf::from("/root/a.tmp"),
+            PathBuf::from("/root/a.pdf"),
+        )];
+
+        let events = file_events(&changes, &groups);
+
+        assert_eq!(
+            events,
+            vec![
+                Event::FileRemoved {
+                    group: "Inbox".to_string(),
+                    path: PathBuf::from("/root/a.tmp"),
+                },
+                Event::FileAdded {
+                    group: "Inbox".to_string(),
+                    path: PathBuf::from("/root/a.pdf"),
+                },
+            ]
+        );
+    }
+}
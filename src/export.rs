@@ -0,0 +1,89 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::ExitCode;
+use std::time::SystemTime;
+
+use chrono::{DateTime, Local, SecondsFormat};
+
+use crate::cli::ExportArgs;
+use crate::filesystem::FileGroup;
+use crate::watchlist::watch_paths;
+
+/// Write what's currently tracked as CSV, one row per file.
+pub fn write_csv(groups: &[FileGroup], mut out: impl Write) -> io::Result<()> {
+    writeln!(out, "group,path,size,observed_at,removed_at")?;
+    for group in groups {
+        for item in group.items() {
+            // a removed file has no size any more
+            let size = match item.removed {
+                None => fs::metadata(group.path(item))
+                    .map(|m| m.len().to_string())
+                    .ok(),
+                Some(_) => None,
+            };
+            let row = [
+                field(crate::group_title(group)),
+                field(&group.path(item).to_string_lossy()),
+                size.unwrap_or_default(),
+                timestamp(item.observed),
+                item.removed_at().map(timestamp).unwrap_or_default(),
+            ];
+            writeln!(out, "{}", row.join(","))?;
+        }
+    }
+    out.flush()
+}
+
+/// Quote a field if it contains anything that would otherwise break the row.
+fn field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn timestamp(time: SystemTime) -> String {
+    DateTime::<Local>::from(time).to_rfc3339_opts(SecondsFormat::Secs, false)
+}
+
+/// Export the watched paths' current contents, as `file_task export` does.
+pub fn export(args: ExportArgs) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let source = args.watch.source.unwrap_or_default();
+    let groups = source.scan(watch_paths(&args.watch)?)?;
+    match args.output.as_deref() {
+        None | Some("-") => write_csv(&groups, io::stdout().lock())?,
+        Some(path) => write_to(&groups, Path::new(path))?,
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+pub fn write_to(groups: &[FileGroup], path: &Path) -> io::Result<()> {
+    write_csv(groups, io::BufWriter::new(File::create(path)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::filesystem::FileItem;
+
+    #[test]
+    fn csv_quotes_fields() {
+        let groups = vec![FileGroup::new(
+            PathBuf::from("/nonexistent"),
+            Some("Inbox, \"A\"".to_string()),
+            vec![FileItem::new(PathBuf::from("a.pdf"))],
+        )];
+        let mut out = vec![];
+
+        write_csv(&groups, &mut out).unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        let row = out.lines().nth(1).unwrap();
+        assert!(row.starts_with("\"Inbox, \"\"A\"\"\",/nonexistent/a.pdf,,"));
+        assert!(row.ends_with(','));
+    }
+}
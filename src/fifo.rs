@@ -0,0 +1,120 @@
+use std::ffi::CString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{FileTypeExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+
+use crate::events::{Event, Receipt, Sink};
+
+/// Writes each event as a tab separated line to a named pipe, for scripts to `read`.
+///
+/// Nothing waits on a reader: events are dropped while nobody has the pipe open or when the
+/// reader falls so far behind that the pipe is full.
+pub struct Fifo {
+    path: PathBuf,
+    file: Option<File>,
+}
+
+impl Fifo {
+    /// Use the FIFO at `path`, creating it if needed.
+    pub fn open(path: &Path) -> Result<Self, String> {
+        match fs::metadata(path) {
+            Ok(metadata) if metadata.file_type().is_fifo() => {}
+            Ok(_) => return Err(format!("{} exists and is not a FIFO", path.display())),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => mkfifo(path)
+                .map_err(|e| format!("could not create FIFO {}: {}", path.display(), e))?,
+            Err(e) => return Err(format!("{}: {}", path.display(), e)),
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            file: None,
+        })
+    }
+
+    fn write(&mut self, line: &str) -> io::Result<()> {
+        let file = match self.file.as_mut() {
+            Some(file) => file,
+            None => self.file.insert(
+                OpenOptions::new()
+                    .write(true)
+                    .custom_flags(libc::O_NONBLOCK)
+                    .open(&self.path)?,
+            ),
+        };
+        // lines shorter than PIPE_BUF (all but pathological ones) are written whole or not at all
+        file.write_all(line.as_bytes())
+    }
+}
+
+fn mkfifo(path: &Path) -> io::Result<()> {
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    // SAFETY: the path is a valid NUL terminated string for the duration of the call
+    if unsafe { libc::mkfifo(path.as_ptr(), 0o600) } == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+/// The event kind followed by the fields it has, e.g. "added\tInbox\t/in/a.pdf".
+fn line(event: &Event) -> String {
+    let payload = event.payload();
+    let fields = [
+        payload.group,
+        payload.path.and_then(|p| p.to_str()),
+        payload.service,
+        payload.status,
+        payload.rule,
+        payload.message,
+    ];
+    let mut line = event.kind().name().to_string();
+    for field in fields.into_iter().flatten() {
+        line.push('\t');
+        // keep each event on one line with the fields intact
+        line.extend(
+            field
+                .chars()
+                .map(|c| if c == '\t' || c == '\n' { ' ' } else { c }),
+        );
+    }
+    line.push('\n');
+    line
+}
+
+impl Sink for Fifo {
+    fn send(&mut self, event: &Event, receipt: Receipt) {
+        // no reader (ENXIO), a reader that went away (EPIPE), or a full pipe: try again with a
+        // fresh open for the next event
+        let written = self.write(&line(event));
+        if written.is_err() {
+            self.file = None;
+        }
+        receipt.result(&written);
+    }
+
+    fn name(&self) -> String {
+        format!("fifo {}", self.path.display())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tab_separated_lines() {
+        let event = Event::FileAdded {
+            group: "In\tbox".to_string(),
+            path: PathBuf::from("/in/a.pdf"),
+        };
+        assert_eq!(line(&event), "added\tIn box\t/in/a.pdf\n");
+
+        let event = Event::ServiceChanged {
+            service: "web".to_string(),
+            active: false,
+            status: "failed\n".to_string(),
+        };
+        assert_eq!(line(&event), "service_down\tweb\tfailed\n");
+    }
+}
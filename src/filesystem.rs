@@ -1,187 +1,1438 @@
-use std::sync::mpsc::{Receiver, Sender};
-use std::time::{Duration, Instant};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::{BufRead, IsTerminal};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{Receiver, SendError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 use std::{fs, io};
 
 use std::path::{Path, PathBuf};
 
-use itertools::Itertools;
+use clap::builder::PossibleValue;
+use clap::ValueEnum;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use itertools::{Either, Itertools};
 use notify::event::{ModifyKind, RenameMode};
-use notify::{EventKind, RecommendedWatcher, RecursiveMode};
-use notify_debouncer_full::{new_debouncer, DebouncedEvent, Debouncer, NoCache};
+use notify::{EventKind, PollWatcher, RecommendedWatcher, RecursiveMode, Watcher};
+use notify_debouncer_full::{new_debouncer_opt, DebouncedEvent, Debouncer, NoCache};
+use rayon::prelude::*;
+use serde::{Deserialize, Deserializer, Serialize};
 
+use crate::clock::Clock;
 use crate::DELETED_RETENTION;
+use crate::{Error, Result};
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug)]
 pub struct FileItem {
-    pub path: PathBuf,
+    /// Relative to the group's root, or absolute if it resolved to somewhere outside it
+    pub name: Arc<Path>,
     pub removed: Option<Instant>,
+    /// When the file was first seen, for reporting
+    pub observed: SystemTime,
+    /// Labels given to the file, which follow it when it's renamed
+    pub tags: Vec<String>,
+    /// Whether the file matched the checksum in its sidecar file, once that's been checked
+    pub verified: Option<bool>,
+    /// What its detached signature showed, once that's been checked
+    pub signature: Option<Signature>,
+    /// The process that wrote the file, when that's being found out
+    pub writer: Option<Writer>,
+    /// Its size in bytes when it was last read, if it's a plain file and could be read
+    pub size: Option<u64>,
+    /// Its modified time when it was last read, if it could be read
+    pub modified: Option<SystemTime>,
+    /// Whether `size` and `modified` have been read, as a scan leaves that until they're wanted
+    pub metadata_read: bool,
+}
+
+/// A process that wrote a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Writer {
+    pub pid: u32,
+    /// Unknown if the process had already exited
+    pub uid: Option<u32>,
+    pub command: String,
+}
+
+/// Whether a file comes with a good detached signature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signature {
+    Signed,
+    Unsigned,
+    /// The signature doesn't match the file, or isn't from a trusted key
+    Invalid,
+}
+
+impl Signature {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Signed => "signed",
+            Self::Unsigned => "unsigned",
+            Self::Invalid => "invalid",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [Self::Signed, Self::Unsigned, Self::Invalid]
+            .into_iter()
+            .find(|s| s.name() == name)
+    }
 }
 
 impl FileItem {
-    pub fn new(path: PathBuf) -> Self {
+    pub fn new(name: impl Into<Arc<Path>>) -> Self {
         Self {
-            path,
+            name: name.into(),
             removed: None,
+            observed: SystemTime::now(),
+            tags: vec![],
+            verified: None,
+            signature: None,
+            writer: None,
+            size: None,
+            modified: None,
+            metadata_read: false,
+        }
+    }
+
+    /// Read the file's size and modified time again, as after it's been added or written to.
+    pub fn refresh(&mut self, path: &Path) {
+        self.set_metadata(read_metadata(path));
+    }
+
+    /// Take a size and modified time read elsewhere, e.g. on another thread.
+    pub fn set_metadata(&mut self, (size, modified): (Option<u64>, Option<SystemTime>)) {
+        self.size = size;
+        self.modified = modified;
+        self.metadata_read = true;
+    }
+
+    /// How the item is shown: by its path under the root, or just by its file name if it
+    /// resolved to somewhere outside the root.
+    pub fn shown_name(&self) -> &str {
+        if self.name.is_relative() {
+            return self.name.to_str().unwrap_or_default();
+        }
+        self.name
+            .file_name()
+            .and_then(|f| f.to_str())
+            .or_else(|| self.name.to_str())
+            .unwrap_or_default()
+    }
+
+    /// When the file was removed, as a wall clock time.
+    pub fn removed_at(&self) -> Option<SystemTime> {
+        self.removed
+            .map(|removed| SystemTime::now() - removed.elapsed())
+    }
+}
+
+/// A file's size, if it's a plain file, and modified time, as far as they can be read.
+pub fn read_metadata(path: &Path) -> (Option<u64>, Option<SystemTime>) {
+    let metadata = fs::metadata(path).ok();
+    let size = metadata
+        .as_ref()
+        .filter(|m| m.is_file())
+        .map(fs::Metadata::len);
+    (size, metadata.and_then(|metadata| metadata.modified().ok()))
+}
+
+/// The order a group's files are listed in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Sort {
+    /// In the order they were discovered, by the first scan or the watcher since, which isn't
+    /// necessarily the order they were created in
+    #[default]
+    #[value(alias = "created")]
+    #[serde(alias = "created")]
+    Found,
+    Name,
+    /// Newest modified first
+    Mtime,
+    /// Largest first
+    Size,
+}
+
+impl Sort {
+    /// The order after this one, cycling back round to the order they were found in.
+    pub fn next(self) -> Self {
+        match self {
+            Self::Found => Self::Name,
+            Self::Name => Self::Mtime,
+            Self::Mtime => Self::Size,
+            Self::Size => Self::Found,
+        }
+    }
+
+    /// How a group's title describes the order, unless it's the order they were found in.
+    pub fn describe(self) -> Option<&'static str> {
+        match self {
+            Self::Found => None,
+            Self::Name => Some("by name"),
+            Self::Mtime => Some("newest first"),
+            Self::Size => Some("largest first"),
+        }
+    }
+
+    /// Which of two items comes first. Files whose size or modified time is unknown go last,
+    /// and it's left to the order they were found in between equals.
+    fn compare(self, a: &FileItem, b: &FileItem) -> std::cmp::Ordering {
+        match self {
+            Self::Found => std::cmp::Ordering::Equal,
+            Self::Name => a.shown_name().cmp(b.shown_name()),
+            Self::Mtime => b.modified.cmp(&a.modified),
+            Self::Size => b.size.cmp(&a.size),
         }
     }
 }
 
+// when an item happened to be first seen doesn't make it a different item
+impl PartialEq for FileItem {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.removed == other.removed
+    }
+}
+
+impl Eq for FileItem {}
+
 #[derive(Debug, PartialEq, Eq)]
 pub struct FileGroup {
     pub root: PathBuf,
-    pub items: Vec<FileItem>,
+    pub title: Option<String>,
+    /// Shown under the title, e.g. to say why the group is quiet
+    pub note: Option<String>,
+    /// In display order, with at most one item per path
+    items: Vec<FileItem>,
+    /// Where each name is in `items`, so large groups can be updated without scanning them
+    index: HashMap<Arc<Path>, usize>,
+    /// The order `sorted` lists the items in
+    sort: Sort,
+    /// Positions in `items` in `sort`'s order, kept as items come and go so huge groups never
+    /// have to be sorted to be shown; empty while they're listed as found
+    order: Vec<usize>,
+    /// How many files were dropped to stay within the item limit, as far as we know still there
+    pub hidden: usize,
+    /// Whether files in subdirectories are tracked too, named by their path under the root
+    pub recursive: bool,
+    /// Which files are tracked at all
+    pub filter: NameFilter,
 }
 
-#[cfg(test)]
 impl FileGroup {
-    fn new(root: PathBuf) -> FileGroup {
-        FileGroup {
+    pub fn new(root: PathBuf, title: Option<String>, items: Vec<FileItem>) -> FileGroup {
+        let mut group = FileGroup {
             root,
+            title,
+            note: None,
             items: vec![],
+            index: HashMap::new(),
+            sort: Sort::default(),
+            order: vec![],
+            hidden: 0,
+            recursive: false,
+            filter: NameFilter::default(),
+        };
+        for item in items {
+            group.insert(item);
+        }
+        group
+    }
+
+    pub fn items(&self) -> &[FileItem] {
+        &self.items
+    }
+
+    /// The items in the group's order.
+    pub fn sorted(&self) -> impl Iterator<Item = &FileItem> {
+        match self.sort {
+            Sort::Found => Either::Left(self.items.iter()),
+            _ => Either::Right(self.order.iter().map(|&i| &self.items[i])),
+        }
+    }
+
+    pub fn sort(&self) -> Sort {
+        self.sort
+    }
+
+    /// List the items in another order, sorting them once now if it's changed.
+    pub fn set_sort(&mut self, sort: Sort) {
+        if sort == self.sort {
+            return;
+        }
+        self.sort = sort;
+        self.order.clear();
+        if sort != Sort::Found {
+            let mut order = (0..self.items.len()).collect::<Vec<_>>();
+            order.sort_by(|&a, &b| self.compare(a, b));
+            self.order = order;
+        }
+    }
+
+    /// Which of the items at two positions comes first.
+    fn compare(&self, a: usize, b: usize) -> std::cmp::Ordering {
+        self.sort
+            .compare(&self.items[a], &self.items[b])
+            .then(a.cmp(&b))
+    }
+
+    /// Move the item at a position to where the order puts it, after it's been added or changed.
+    fn place(&mut self, i: usize) {
+        if self.sort == Sort::Found {
+            return;
+        }
+        if let Some(at) = self.order.iter().position(|&j| j == i) {
+            self.order.remove(at);
+        }
+        let at = self
+            .order
+            .partition_point(|&j| self.compare(j, i) == std::cmp::Ordering::Less);
+        self.order.insert(at, i);
+    }
+
+    /// The full path of one of this group's items.
+    pub fn path(&self, item: &FileItem) -> PathBuf {
+        self.root.join(&item.name)
+    }
+
+    /// What an item at a path would be named in this group.
+    pub fn name(&self, path: &Path) -> Arc<Path> {
+        path.strip_prefix(&self.root).unwrap_or(path).into()
+    }
+
+    pub fn get(&self, path: &Path) -> Option<&FileItem> {
+        let name = path.strip_prefix(&self.root).unwrap_or(path);
+        self.index.get(name).map(|&i| &self.items[i])
+    }
+
+    /// The item for a path, which must not be given a different name this way.
+    pub fn get_mut(&mut self, path: &Path) -> Option<&mut FileItem> {
+        let name = path.strip_prefix(&self.root).unwrap_or(path);
+        self.index.get(name).map(|&i| &mut self.items[i])
+    }
+
+    /// Add an item at the end, or in place of the one with the same name.
+    pub fn insert(&mut self, item: FileItem) {
+        let i = match self.index.get(&item.name) {
+            Some(&i) => {
+                self.items[i] = item;
+                i
+            }
+            None => {
+                self.index.insert(Arc::clone(&item.name), self.items.len());
+                self.items.push(item);
+                self.items.len() - 1
+            }
+        };
+        self.place(i);
+    }
+
+    /// Start tracking a file at a path, in place of any item already there, unless the group's
+    /// globs leave it out. Its size and modified time aren't read until they're wanted.
+    pub fn add(&mut self, path: &Path) {
+        if self.admits(path) {
+            self.insert(FileItem::new(self.name(path)));
+        }
+    }
+
+    /// Read the size and modified time of the item at a path again, if there is one.
+    pub fn refresh(&mut self, path: &Path) {
+        let name = path.strip_prefix(&self.root).unwrap_or(path);
+        if let Some(&i) = self.index.get(name) {
+            self.items[i].refresh(path);
+            self.place(i);
+        }
+    }
+
+    /// Take a size and modified time read in the background for the item at a path, unless
+    /// they've been read again since.
+    pub fn fill_metadata(&mut self, path: &Path, metadata: (Option<u64>, Option<SystemTime>)) {
+        let name = path.strip_prefix(&self.root).unwrap_or(path);
+        if let Some(&i) = self.index.get(name) {
+            if !self.items[i].metadata_read {
+                self.items[i].set_metadata(metadata);
+                self.place(i);
+            }
+        }
+    }
+
+    /// Read the size and modified time of every present item that hasn't had them read yet.
+    pub fn read_metadata(&mut self) {
+        let unread = self.unread().collect::<Vec<_>>();
+        for path in unread {
+            self.refresh(&path);
+        }
+    }
+
+    /// The paths of present items whose size and modified time haven't been read yet.
+    pub fn unread(&self) -> impl Iterator<Item = PathBuf> + '_ {
+        self.items
+            .iter()
+            .filter(|item| !item.metadata_read && item.removed.is_none())
+            .map(|item| self.path(item))
+    }
+
+    /// Whether a file at a path is one the group's globs let in.
+    pub fn admits(&self, path: &Path) -> bool {
+        self.filter.matches(&self.name(path))
+    }
+
+    /// Whether a change at a path could matter to the group: not one to a file its globs leave
+    /// out, unless it's a directory with tracked files under it.
+    fn concerns(&self, path: &Path) -> bool {
+        if self.filter.is_empty() || self.admits(path) {
+            return true;
         }
+        let name = self.name(path);
+        self.recursive && self.items.iter().any(|item| item.name.starts_with(&name))
+    }
+
+    /// Give an item a new path, keeping its place and replacing any item already at that path.
+    pub fn rename(&mut self, from: &Path, to: &Path) -> Option<&mut FileItem> {
+        let (from, to) = (self.name(from), self.name(to));
+        if from != to && self.index.contains_key(&from) && self.index.contains_key(&to) {
+            self.retain(|item| item.name != to);
+        }
+        let i = self.index.remove(&from)?;
+        self.index.insert(Arc::clone(&to), i);
+        self.items[i].name = to;
+        self.place(i);
+        Some(&mut self.items[i])
+    }
+
+    /// Move everything under a directory, as when it's renamed in a recursive group.
+    fn rename_under(&mut self, from: &Path, to: &Path) {
+        let moved = self
+            .items
+            .iter()
+            .filter_map(|item| {
+                let rest = self.path(item).strip_prefix(from).ok()?.to_path_buf();
+                Some((self.path(item), to.join(rest)))
+            })
+            .collect::<Vec<_>>();
+        for (from, to) in moved {
+            self.rename(&from, &to);
+        }
+    }
+
+    /// Forget removed items now rather than when they expire, returning how many there were.
+    pub fn clear_removed(&mut self) -> usize {
+        let before = self.items.len();
+        self.retain(|item| item.removed.is_none());
+        before - self.items.len()
+    }
+
+    /// Drop the oldest items beyond `max`: removed ones first, then the files seen earliest.
+    fn truncate(&mut self, max: usize) {
+        let excess = self.items.len().saturating_sub(max);
+        if excess == 0 {
+            return;
+        }
+        let mut removed = self
+            .items
+            .iter()
+            .filter_map(|item| Some((item.removed?, &item.name)))
+            .collect::<Vec<_>>();
+        removed.sort();
+        let mut dropped = removed
+            .into_iter()
+            .take(excess)
+            .map(|(_, name)| Arc::clone(name))
+            .collect::<HashSet<_>>();
+        let present = excess - dropped.len();
+        dropped.extend(
+            self.items
+                .iter()
+                .filter(|item| item.removed.is_none())
+                .take(present)
+                .map(|item| Arc::clone(&item.name)),
+        );
+        self.hidden += present;
+        self.retain(|item| !dropped.contains(&item.name));
+    }
+
+    pub fn retain(&mut self, keep: impl FnMut(&FileItem) -> bool) {
+        let kept = self.items.iter().map(keep).collect::<Vec<_>>();
+        if kept.iter().all(|&kept| kept) {
+            return;
+        }
+        // where each item that's kept ends up
+        let moved = kept
+            .iter()
+            .scan(0, |next, &kept| {
+                let at = kept.then_some(*next);
+                *next += kept as usize;
+                Some(at)
+            })
+            .collect::<Vec<_>>();
+        let mut kept = kept.into_iter();
+        self.items.retain(|_| kept.next().unwrap_or_default());
+        self.order = self.order.iter().filter_map(|&i| moved[i]).collect();
+        self.index = self
+            .items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| (Arc::clone(&item.name), i))
+            .collect();
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+/// A directory to watch, along with any per-path options.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchPath {
+    pub path: PathBuf,
+    pub title: Option<String>,
+    pub note: Option<String>,
+    /// Whether to watch the files in its subdirectories too
+    pub recursive: bool,
+    /// Globs for the files to show, or empty for all of them
+    pub include: Vec<String>,
+    /// Globs for files not to show, even if included
+    pub exclude: Vec<String>,
+}
+
+impl From<PathBuf> for WatchPath {
+    fn from(path: PathBuf) -> Self {
+        Self {
+            path,
+            title: None,
+            note: None,
+            recursive: false,
+            include: vec![],
+            exclude: vec![],
+        }
+    }
+}
+
+/// Globs picking which of a directory's files are tracked, matched against their names (their
+/// path under it, in a recursive group) so that e.g. a producer's partial files stay out of view.
+#[derive(Debug, Clone, Default)]
+pub struct NameFilter {
+    include: Vec<String>,
+    exclude: Vec<String>,
+    included: GlobSet,
+    excluded: GlobSet,
+}
+
+impl NameFilter {
+    pub fn new(include: &[String], exclude: &[String]) -> Result<Self> {
+        Ok(Self {
+            include: include.to_vec(),
+            exclude: exclude.to_vec(),
+            included: glob_set(include)?,
+            excluded: glob_set(exclude)?,
+        })
+    }
+
+    /// Whether every file gets through.
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    pub fn matches(&self, name: &Path) -> bool {
+        (self.include.is_empty() || self.included.is_match(name)) && !self.excluded.is_match(name)
+    }
+}
+
+impl PartialEq for NameFilter {
+    fn eq(&self, other: &Self) -> bool {
+        self.include == other.include && self.exclude == other.exclude
+    }
+}
+
+impl Eq for NameFilter {}
+
+fn glob_set(patterns: &[String]) -> Result<GlobSet> {
+    let error = |pattern: &str| {
+        let pattern = pattern.to_string();
+        move |source| Error::Glob { pattern, source }
+    };
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern).map_err(error(pattern))?);
+    }
+    builder.build().map_err(error(&patterns.join(" ")))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum FileChange {
     Added(PathBuf),
     Removed(PathBuf),
     Moved(PathBuf, PathBuf),
+    /// A file's contents changed, which leaves what's tracked as it is
+    Modified(PathBuf),
+}
+
+/// Where watched files come from: something that can list directories and report changes to
+/// them as `FileChange`s on the channel it was created with.
+pub trait WatchSource {
+    /// Check that a path can be watched, before anything is read from it.
+    fn check(&self, path: &Path) -> Result<()>;
+
+    /// The form of a watched path that its listings and changes are reported under.
+    fn root(&self, path: &Path) -> io::Result<PathBuf>;
+
+    /// Everything directly in a root, or if recursive, every file anywhere under it.
+    fn list(&self, root: &Path, recursive: bool) -> io::Result<Vec<PathBuf>>;
+
+    fn watch(&mut self, root: &Path, recursive: bool) -> Result<()>;
+
+    fn unwatch(&mut self, root: &Path) -> Result<()>;
+
+    /// How many files have been listed so far, for sources that keep count.
+    fn progress(&self) -> Option<Arc<AtomicUsize>> {
+        None
+    }
+}
+
+/// Starts a watch source sending changes on the first channel, and anything that goes wrong
+/// while watching on the second.
+type CreateSource = fn(Sender<FileChange>, Sender<String>) -> Result<Box<dyn WatchSource>>;
+
+/// A kind of watch source that can be picked by name with `--source`.
+#[derive(Clone, Copy)]
+pub struct SourceKind {
+    pub name: &'static str,
+    pub description: &'static str,
+    create: CreateSource,
+}
+
+/// Every source there is, the first being the default. A new backend only needs an entry here
+/// (behind a feature if it brings in dependencies).
+pub const SOURCES: &[SourceKind] = &[
+    SourceKind {
+        name: "local",
+        description: "the platform's file events (inotify, FSEvents, ...)",
+        create: |tx, problems| {
+            let config = notify::Config::default();
+            let source = NotifySource::<RecommendedWatcher>::new(tx, problems, config);
+            Ok(Box::new(source.map_err(Error::Start)?))
+        },
+    },
+    SourceKind {
+        name: "poll",
+        description: "listing the directories every 2 seconds, for network filesystems that don't send events",
+        create: |tx, problems| {
+            let config = notify::Config::default().with_poll_interval(Duration::from_secs(2));
+            let source = NotifySource::<PollWatcher>::new(tx, problems, config);
+            Ok(Box::new(source.map_err(Error::Start)?))
+        },
+    },
+];
+
+impl SourceKind {
+    pub fn create(
+        &self,
+        tx: Sender<FileChange>,
+        problems: Sender<String>,
+    ) -> Result<Box<dyn WatchSource>> {
+        (self.create)(tx, problems)
+    }
+
+    /// Read the paths once, without watching them.
+    pub fn scan(&self, paths: Vec<WatchPath>) -> Result<Vec<FileGroup>> {
+        let (tx, _) = std::sync::mpsc::channel();
+        let (problems, _) = std::sync::mpsc::channel();
+        get_initial_state(paths, &*self.create(tx, problems)?)
+    }
+}
+
+impl Default for SourceKind {
+    fn default() -> Self {
+        SOURCES[0]
+    }
+}
+
+impl fmt::Debug for SourceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name)
+    }
+}
+
+impl PartialEq for SourceKind {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+impl Eq for SourceKind {}
+
+impl FromStr for SourceKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        SOURCES
+            .iter()
+            .find(|kind| kind.name == s)
+            .copied()
+            .ok_or_else(|| {
+                let names = SOURCES.iter().map(|kind| kind.name).join(", ");
+                format!("unknown watch source \"{}\" (expected one of {})", s, names)
+            })
+    }
+}
+
+impl ValueEnum for SourceKind {
+    fn value_variants<'a>() -> &'a [Self] {
+        SOURCES
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        Some(PossibleValue::new(self.name).help(self.description))
+    }
+}
+
+impl<'de> Deserialize<'de> for SourceKind {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
 }
 
 pub fn get_initial_state(
-    paths: Vec<PathBuf>,
-) -> Result<Vec<FileGroup>, Box<dyn std::error::Error>> {
+    paths: Vec<WatchPath>,
+    source: &dyn WatchSource,
+) -> Result<Vec<FileGroup>> {
+    if paths.is_empty() {
+        return Err(Error::NoPaths);
+    }
+
+    check_paths(&paths, source)?;
+
+    paths
+        .iter()
+        .map(|path| read_initial_contents(path, source))
+        .collect::<Result<Vec<_>, _>>()
+}
+
+fn check_paths(paths: &[WatchPath], source: &dyn WatchSource) -> Result<()> {
+    for WatchPath { path, .. } in paths.iter() {
+        source.check(path)?;
+    }
+
+    Ok(())
+}
+
+/// Start watching every group's root.
+pub fn watch_groups(groups: &[FileGroup], source: &mut dyn WatchSource) -> Result<()> {
+    for group in groups.iter() {
+        source.watch(&group.root, group.recursive)?;
+    }
+
+    Ok(())
+}
+
+/// Bring the watched groups in line with a new set of paths (e.g. after a watch list changes),
+/// keeping the tracked state of any groups that are still wanted.
+pub fn sync_groups(
+    groups: &mut Vec<FileGroup>,
+    paths: Vec<WatchPath>,
+    source: &mut dyn WatchSource,
+) -> Result<()> {
+    check_paths(&paths, source)?;
+
+    let mut old = std::mem::take(groups);
+    let mut added = vec![];
+
     for path in paths.iter() {
-        if !path.exists() {
-            return Err(format!("path {} does not exist", path.display()).into());
+        let root = source
+            .root(&path.path)
+            .map_err(|e| scan_error(&path.path, e))?;
+        let filter = NameFilter::new(&path.include, &path.exclude)?;
+        // a group that's started or stopped being recursive, or has new globs, is read afresh
+        match old
+            .iter()
+            .position(|g| g.root == root && g.recursive == path.recursive && g.filter == filter)
+        {
+            Some(index) => {
+                let mut group = old.remove(index);
+                group.title = path.title.clone();
+                group.note = path.note.clone();
+                groups.push(group);
+            }
+            None => {
+                let group = read_initial_contents(path, source)?;
+                added.push((group.root.clone(), group.recursive));
+                groups.push(group);
+            }
         }
-        if !path.is_dir() {
-            return Err(format!("path {} is not a directory", path.display()).into());
+    }
+
+    for group in old {
+        if !groups.iter().any(|g| g.root == group.root) {
+            source.unwatch(&group.root)?;
         }
     }
+    for (root, recursive) in added {
+        source.watch(&root, recursive)?;
+    }
 
-    paths
+    Ok(())
+}
+
+/// Directories read from a list, and the entries that were left out of it.
+#[derive(Debug, Default)]
+pub struct PathList {
+    pub paths: Vec<PathBuf>,
+    /// Entries that aren't directories or that duplicate an already known path, with why
+    pub skipped: Vec<(PathBuf, &'static str)>,
+}
+
+/// Read a list of directories to watch, one per line, from a file or stdin (`-`).
+///
+/// Entries that aren't directories or that duplicate an already known path are skipped rather
+/// than failing the whole list, since lists like this usually come from tools like `find`.
+pub fn read_paths_from(source: &Path, known: &[PathBuf]) -> Result<PathList> {
+    let read_error = |e| Error::PathList {
+        path: source.to_path_buf(),
+        source: e,
+    };
+    let lines = if source == Path::new("-") {
+        parse_path_list(io::stdin().lock())
+    } else {
+        fs::File::open(source).and_then(|file| parse_path_list(io::BufReader::new(file)))
+    }
+    .map_err(read_error)?;
+
+    let mut seen = known
         .iter()
-        .map(|path| read_initial_contents(path))
-        .collect::<Result<Vec<_>, _>>()
+        .filter_map(|p| p.canonicalize().ok())
+        .collect::<HashSet<_>>();
+    let mut list = PathList::default();
+
+    for path in lines {
+        if !path.is_dir() {
+            list.skipped.push((path, "not a directory"));
+            continue;
+        }
+        if !seen.insert(path.canonicalize().map_err(|e| scan_error(&path, e))?) {
+            list.skipped.push((path, "already watched"));
+            continue;
+        }
+        list.paths.push(path);
+    }
+
+    Ok(list)
+}
+
+fn parse_path_list(reader: impl BufRead) -> io::Result<Vec<PathBuf>> {
+    reader
+        .lines()
+        .filter_map(|line| match line {
+            Ok(line) if line.trim().is_empty() => None,
+            Ok(line) => Some(Ok(PathBuf::from(line.trim_end_matches(['\r', '\n'])))),
+            Err(e) => Some(Err(e)),
+        })
+        .collect()
+}
+
+fn read_initial_contents(path: &WatchPath, source: &dyn WatchSource) -> Result<FileGroup> {
+    let root = source
+        .root(&path.path)
+        .map_err(|e| scan_error(&path.path, e))?;
+    let files = source
+        .list(&root, path.recursive)
+        .map_err(|e| scan_error(&root, e))?;
+    let mut group = FileGroup::new(root, path.title.clone(), vec![]);
+    group.note = path.note.clone();
+    group.recursive = path.recursive;
+    group.filter = NameFilter::new(&path.include, &path.exclude)?;
+    for file in files {
+        group.add(&file);
+    }
+
+    Ok(group)
+}
+
+/// What a watch source has worked out while listing directories, shared with its event handler
+/// so what's changed on disk can be forgotten.
+#[derive(Default)]
+struct Scanner {
+    /// Canonical forms of the directories resolved so far, so siblings share the work
+    dirs: Mutex<HashMap<PathBuf, PathBuf>>,
+    /// Files listed so far, for showing how a long scan is getting on
+    scanned: Arc<AtomicUsize>,
+}
+
+impl Scanner {
+    /// List a canonical directory, with each entry resolved.
+    fn read_contents(&self, root: &Path) -> io::Result<Vec<PathBuf>> {
+        let entries = fs::read_dir(root)?.collect::<io::Result<Vec<_>>>()?;
+        self.resolve_entries(root, &entries)
+    }
+
+    /// Every file under a canonical directory, with each resolved. Links to directories aren't
+    /// followed, so a link back up the tree can't loop, and subdirectories that can't be read
+    /// are skipped.
+    fn read_tree(&self, root: &Path) -> io::Result<Vec<PathBuf>> {
+        let mut files = vec![];
+        let mut dirs = vec![root.to_path_buf()];
+        while let Some(dir) = dirs.pop() {
+            let entries: Vec<fs::DirEntry> = match fs::read_dir(&dir).and_then(Iterator::collect) {
+                Ok(entries) => entries,
+                Err(_) if dir != root => continue,
+                Err(e) => return Err(e),
+            };
+            let (subdirs, entries): (Vec<_>, Vec<_>) = entries
+                .into_iter()
+                .partition(|entry| entry.file_type().is_ok_and(|kind| kind.is_dir()));
+            dirs.extend(subdirs.iter().map(fs::DirEntry::path));
+            files.extend(self.resolve_entries(&dir, &entries)?);
+        }
+        Ok(files)
+    }
+
+    fn resolve_entries(&self, root: &Path, entries: &[fs::DirEntry]) -> io::Result<Vec<PathBuf>> {
+        // resolving each entry is what's slow on network filesystems, so do many at once
+        entries
+            .par_iter()
+            .map(|entry| {
+                let path = self.resolve_entry(root, entry);
+                self.scanned.fetch_add(1, Ordering::Relaxed);
+                path
+            })
+            .collect()
+    }
+
+    /// The canonical path of an entry in a canonical directory. Only symlinks need resolving,
+    /// and one pointing at a plain file only needs the directory it's in resolved.
+    fn resolve_entry(&self, root: &Path, entry: &fs::DirEntry) -> io::Result<PathBuf> {
+        if !entry.file_type()?.is_symlink() {
+            return Ok(entry.path());
+        }
+        let target = root.join(fs::read_link(entry.path())?);
+        match (target.parent(), target.file_name()) {
+            (Some(dir), Some(name)) if !fs::symlink_metadata(&target)?.is_symlink() => {
+                Ok(self.canonical_dir(dir)?.join(name))
+            }
+            _ => target.canonicalize(),
+        }
+    }
+
+    fn canonical_dir(&self, dir: &Path) -> io::Result<PathBuf> {
+        let cached = self
+            .dirs
+            .lock()
+            .ok()
+            .and_then(|dirs| dirs.get(dir).cloned());
+        match cached {
+            Some(canonical) => Ok(canonical),
+            None => self.remember_dir(dir),
+        }
+    }
+
+    /// Resolve a directory afresh, updating the cache.
+    fn remember_dir(&self, dir: &Path) -> io::Result<PathBuf> {
+        let canonical = dir.canonicalize()?;
+        if let Ok(mut dirs) = self.dirs.lock() {
+            dirs.insert(dir.to_path_buf(), canonical.clone());
+        }
+        Ok(canonical)
+    }
+
+    /// Forget the directories at or under a path the watcher has seen appear, go, or move, as
+    /// they may resolve somewhere else now.
+    fn forget(&self, path: &Path) {
+        if let Ok(mut dirs) = self.dirs.lock() {
+            dirs.retain(|dir, canonical| !dir.starts_with(path) && !canonical.starts_with(path));
+        }
+    }
 }
 
-fn read_initial_contents(path: &Path) -> Result<FileGroup, Box<dyn std::error::Error>> {
-    let root = path.canonicalize()?;
-    let contents = fs::read_dir(&root)?
-        .map(|fr| fr.and_then(|f| f.path().canonicalize()).map(FileItem::new))
-        .collect::<Result<Vec<_>, io::Error>>()?;
+/// Run a scan, counting the files it finds on stderr if it takes a while and that's a terminal,
+/// for sources that keep count.
+pub fn with_progress<T>(scanned: Option<&AtomicUsize>, scan: impl FnOnce() -> T) -> T {
+    const QUIET_FOR: Duration = Duration::from_millis(500);
+    const EVERY: Duration = Duration::from_millis(100);
 
-    Ok(FileGroup {
-        root,
-        items: contents,
+    let Some(scanned) = scanned.filter(|_| io::stderr().is_terminal()) else {
+        return scan();
+    };
+    let done = AtomicBool::new(false);
+    thread::scope(|s| {
+        s.spawn(|| {
+            let start = Instant::now();
+            let mut shown = false;
+            while !done.load(Ordering::Relaxed) {
+                if start.elapsed() >= QUIET_FOR {
+                    eprint!("\rscanning: {} files", scanned.load(Ordering::Relaxed));
+                    shown = true;
+                }
+                thread::sleep(EVERY);
+            }
+            if shown {
+                // clear the line for whatever comes next
+                eprint!("\r\x1b[K");
+            }
+        });
+        let result = scan();
+        done.store(true, Ordering::Relaxed);
+        result
     })
 }
 
-pub fn update_file_items(rx: &Receiver<FileChange>, file_items: &mut Vec<FileGroup>) {
-    let now = Instant::now();
-    // get any observed file changes
-    let changes = rx.try_iter().collect::<Vec<_>>();
+/// The differences between what's tracked and what's on disk, e.g. changes the watcher missed.
+pub fn rescan(groups: &[FileGroup], source: &dyn WatchSource) -> Result<Vec<FileChange>> {
+    let mut changes = vec![];
+
+    for group in groups {
+        let present = source
+            .list(&group.root, group.recursive)
+            .map_err(|e| scan_error(&group.root, e))?;
+        let tracked = group
+            .items()
+            .iter()
+            .filter(|f| f.removed.is_none())
+            .map(|f| group.path(f))
+            .collect::<HashSet<_>>();
+
+        changes.extend(
+            present
+                .iter()
+                .filter(|p| !tracked.contains(*p) && group.admits(p))
+                .map(|p| FileChange::Added(p.clone())),
+        );
+        let present = present.iter().collect::<HashSet<_>>();
+        changes.extend(
+            tracked
+                .into_iter()
+                .filter(|p| !present.contains(p))
+                .map(FileChange::Removed),
+        );
+    }
+
+    Ok(changes)
+}
+
+/// Apply any observed file changes, returning them so they can be reported elsewhere. Removed
+/// files are forgotten once they've been gone for `retention`.
+pub fn update_file_items(
+    rx: &Receiver<FileChange>,
+    file_items: &mut Vec<FileGroup>,
+    clock: &dyn Clock,
+    retention: Duration,
+) -> Vec<FileChange> {
+    apply_file_changes(rx.try_iter(), file_items, clock, retention)
+}
+
+/// Apply file changes already taken from the watcher, e.g. to keep a record of them first.
+pub fn apply_file_changes(
+    changes: impl IntoIterator<Item = FileChange>,
+    file_items: &mut Vec<FileGroup>,
+    clock: &dyn Clock,
+    retention: Duration,
+) -> Vec<FileChange> {
+    let now = clock.now();
+    let roots = GroupRoots::new(file_items);
+
+    // changes only to files every group's globs leave out aren't worth applying or reporting
+    let concerned = |path: &Path| roots.find(path).any(|i| file_items[i].concerns(path));
+    let changes = coalesce(changes)
+        .into_iter()
+        .filter(|change| match change {
+            FileChange::Added(path) | FileChange::Removed(path) | FileChange::Modified(path) => {
+                concerned(path)
+            }
+            FileChange::Moved(from, to) => concerned(from) || concerned(to),
+        })
+        .collect::<Vec<_>>();
 
     // apply file changes
     for change in changes.iter() {
         match change {
             FileChange::Added(path) => {
-                for group in find_groups(path, file_items) {
-                    group.items.push(FileItem::new(path.to_path_buf()));
+                // one file at a time is cheap enough to read straight away
+                for i in roots.find(path) {
+                    file_items[i].add(path);
+                    file_items[i].refresh(path);
                 }
             }
             FileChange::Removed(path) => {
-                for group in find_groups(path, file_items) {
-                    if let Some(existing) = group.items.iter_mut().find(|f| f.path == *path) {
+                for i in roots.find(path) {
+                    let group = &mut file_items[i];
+                    if let Some(existing) = group.get_mut(path) {
                         existing.removed = Some(now);
+                    } else if group.recursive {
+                        // a directory, so everything that was under it
+                        let dir = group.name(path);
+                        for item in group.items.iter_mut() {
+                            if item.removed.is_none() && item.name.starts_with(&dir) {
+                                item.removed = Some(now);
+                            }
+                        }
                     }
                 }
             }
             FileChange::Moved(from, to) => {
+                // moved around within a recursive group, e.g. from one subdirectory to another
+                let within = roots
+                    .find(from)
+                    .filter(|&i| file_items[i].recursive && to.starts_with(&file_items[i].root))
+                    .collect::<Vec<_>>();
+                for &i in &within {
+                    let group = &mut file_items[i];
+                    if !group.admits(to) && group.get(from).is_some() {
+                        hide(group, from, now);
+                        continue;
+                    }
+                    let left_out = !group.admits(from);
+                    match group.rename(from, to) {
+                        Some(existing) => {
+                            existing.removed = None;
+                            group.refresh(to);
+                        }
+                        None if left_out && !to.is_dir() => {
+                            group.add(to);
+                            group.refresh(to);
+                        }
+                        None => group.rename_under(from, to),
+                    }
+                }
+                let find = |path| roots.find(path).filter(|i| !within.contains(i));
                 if from.parent() == to.parent() {
                     // rename in same monitored group
-                    for group in find_groups(from, file_items) {
-                        if let Some(existing) = group.items.iter_mut().find(|f| f.path == *from) {
-                            existing.path = to.to_path_buf();
+                    for i in find(from) {
+                        let group = &mut file_items[i];
+                        if !group.admits(to) {
+                            hide(group, from, now);
+                        } else if let Some(existing) = group.rename(from, to) {
                             // we might have already handled the "move from" part of this as a
                             // "remove", so fix up the removed state just in case
                             existing.removed = None;
+                            group.refresh(to);
+                        } else if !group.admits(from) {
+                            // e.g. a partial file renamed once it's complete
+                            group.add(to);
+                            group.refresh(to);
                         }
                     }
                 } else {
                     // was it moved to another tracked group?
                     let mut moved = false;
 
-                    for group in find_groups(to, file_items) {
+                    for i in find(to) {
                         moved = true;
-                        group.items.push(FileItem::new(to.to_path_buf()));
+                        file_items[i].add(to);
+                        file_items[i].refresh(to);
                     }
 
                     // if it was moved to another tracked group immediately remove it from the old one
                     // by abusing the standard cleanup; otherwise (i.e. it was moved out of tracking
                     // entirely) treat it as a normal deletion
-                    let removed = if moved { now - DELETED_RETENTION } else { now };
-
-                    for group in find_groups(from, file_items) {
-                        if let Some(existing) = group.items.iter_mut().find(|f| f.path == *from) {
+                    let removed = match now.checked_sub(retention) {
+                        Some(expired) if moved => expired,
+                        _ => now,
+                    };
+
+                    for i in find(from) {
+                        let group = &mut file_items[i];
+                        if let Some(existing) = group.get_mut(from) {
                             existing.removed = Some(removed);
                         }
                     }
                 }
             }
+            FileChange::Modified(path) => {
+                for i in roots.find(path) {
+                    file_items[i].refresh(path);
+                }
+            }
         }
     }
 
     // clean up any expired removed files
     for group in file_items {
-        group.items.retain(|f| {
+        group.retain(|f| {
             f.removed
-                .map_or(true, |removed| removed.elapsed() <= DELETED_RETENTION)
+                .is_none_or(|removed| now.saturating_duration_since(removed) < retention)
         });
     }
+
+    changes
 }
 
-fn find_groups<'a>(
-    path: &'a Path,
-    file_items: &'a mut [FileGroup],
-) -> impl Iterator<Item = &'a mut FileGroup> + 'a {
-    file_items
-        .iter_mut()
-        .filter(|f| path.starts_with(f.root.as_path()))
+/// Treat a file renamed to something the group's globs leave out as removed.
+fn hide(group: &mut FileGroup, from: &Path, now: Instant) {
+    if let Some(existing) = group.get_mut(from) {
+        existing.removed = Some(now);
+    }
 }
 
-pub fn init_file_watch(
-    tx: Sender<FileChange>,
-    paths: &[FileGroup],
-) -> Result<Debouncer<RecommendedWatcher, NoCache>, Box<dyn std::error::Error>> {
-    let mut debouncer = new_debouncer(Duration::from_secs(2), None, move |res| match res {
-        Ok(events) => handle_events(&tx, events),
-        Err(e) => println!("watch error: {:?}", e),
-    })?;
+/// When the soonest removed file is due to be forgotten.
+pub fn next_expiry(groups: &[FileGroup], retention: Duration) -> Option<Instant> {
+    groups
+        .iter()
+        .flat_map(|group| group.items.iter())
+        .filter_map(|item| Some(item.removed? + retention))
+        .min()
+}
 
-    for path in paths.iter() {
-        debouncer
-            .watch(&path.root, RecursiveMode::NonRecursive)?;
+/// How much to keep track of, so watching a directory that keeps growing can't use up memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Items per group, including removed ones
+    pub items: usize,
+    /// Removed items across all groups
+    pub removed: usize,
+    /// How long removed items are kept for
+    pub retention: Duration,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            items: 100_000,
+            removed: 10_000,
+            retention: DELETED_RETENTION,
+        }
+    }
+}
+
+/// Drop the oldest items from groups that have grown past the limits.
+pub fn enforce_limits(groups: &mut [FileGroup], limits: Limits) {
+    let mut removed = groups
+        .iter()
+        .enumerate()
+        .flat_map(|(i, group)| {
+            group
+                .items
+                .iter()
+                .filter_map(move |item| Some((item.removed?, i, Arc::clone(&item.name))))
+        })
+        .collect::<Vec<_>>();
+    if removed.len() > limits.removed {
+        removed.sort();
+        let excess = removed.len() - limits.removed;
+        let mut dropped = vec![HashSet::new(); groups.len()];
+        for (_, i, name) in removed.into_iter().take(excess) {
+            dropped[i].insert(name);
+        }
+        for (group, dropped) in groups.iter_mut().zip(dropped) {
+            if !dropped.is_empty() {
+                group.retain(|item| !dropped.contains(&item.name));
+            }
+        }
+    }
+
+    for group in groups {
+        group.truncate(limits.items);
+    }
+}
+
+/// Reduce a batch of changes to their net effect on each path, so a file that comes and goes
+/// within one batch (e.g. a temporary file) or is renamed several times is only applied once.
+fn coalesce(changes: impl IntoIterator<Item = FileChange>) -> Vec<FileChange> {
+    let mut net: Vec<Option<FileChange>> = vec![];
+    // which change last left a file at each path
+    let mut latest = HashMap::<PathBuf, usize>::new();
+    let mut modified = HashSet::new();
+
+    for change in changes {
+        let change = match change {
+            // only worth hearing about once per batch
+            FileChange::Modified(path) if !modified.insert(path.clone()) => continue,
+            FileChange::Modified(path) => FileChange::Modified(path),
+            FileChange::Added(path) => match latest.get(&path).map(|&i| &net[i]) {
+                Some(Some(FileChange::Added(_))) => continue,
+                _ => FileChange::Added(path),
+            },
+            FileChange::Removed(path) => match latest.remove(&path) {
+                Some(i) => match net[i].take() {
+                    // came and went
+                    Some(FileChange::Added(_)) => continue,
+                    Some(FileChange::Moved(from, _)) => FileChange::Removed(from),
+                    _ => FileChange::Removed(path),
+                },
+                None => FileChange::Removed(path),
+            },
+            FileChange::Moved(from, to) => match latest.remove(&from) {
+                Some(i) => match net[i].take() {
+                    Some(FileChange::Added(_)) => FileChange::Added(to),
+                    // renamed back to where it started
+                    Some(FileChange::Moved(first, _)) if first == to => continue,
+                    Some(FileChange::Moved(first, _)) => FileChange::Moved(first, to),
+                    _ => FileChange::Moved(from, to),
+                },
+                None => FileChange::Moved(from, to),
+            },
+        };
+        match &change {
+            FileChange::Added(path) | FileChange::Moved(_, path) => {
+                latest.insert(path.clone(), net.len());
+            }
+            FileChange::Removed(_) | FileChange::Modified(_) => {}
+        }
+        net.push(Some(change));
+    }
+
+    net.into_iter().flatten().collect()
+}
+
+/// The groups watching each root, to find a path's groups by its ancestors rather than by
+/// checking every group.
+struct GroupRoots(HashMap<PathBuf, Vec<usize>>);
+
+impl GroupRoots {
+    fn new(groups: &[FileGroup]) -> Self {
+        let mut roots = HashMap::<_, Vec<_>>::new();
+        for (i, group) in groups.iter().enumerate() {
+            roots.entry(group.root.clone()).or_default().push(i);
+        }
+        Self(roots)
+    }
+
+    /// Indexes of the groups a path is in.
+    fn find<'a>(&'a self, path: &'a Path) -> impl Iterator<Item = usize> + 'a {
+        path.ancestors()
+            .filter_map(|ancestor| self.0.get(ancestor))
+            .flatten()
+            .copied()
+    }
+}
+
+/// How many errors the file watcher has reported.
+pub static WATCH_ERRORS: AtomicU64 = AtomicU64::new(0);
+
+/// Local directories watched through `notify`, with either native events or polling.
+struct NotifySource<W: Watcher> {
+    debouncer: Debouncer<W, NoCache>,
+    /// The roots watched recursively, shared with the event handler
+    recursive: Arc<Mutex<HashSet<PathBuf>>>,
+    scanner: Arc<Scanner>,
+}
+
+impl<W: Watcher> NotifySource<W> {
+    fn new(
+        tx: Sender<FileChange>,
+        problems: Sender<String>,
+        config: notify::Config,
+    ) -> notify::Result<Self> {
+        let recursive = Arc::new(Mutex::new(HashSet::new()));
+        let scanner = Arc::new(Scanner::default());
+        let (roots, scanning) = (Arc::clone(&recursive), Arc::clone(&scanner));
+        let handler = move |res: notify_debouncer_full::DebounceEventResult| match res {
+            Ok(events) => handle_events(&tx, events, &roots, &scanning),
+            Err(e) => {
+                WATCH_ERRORS.fetch_add(1, Ordering::Relaxed);
+                let _ = problems.send(format!("watch error: {:?}", e));
+            }
+        };
+        let debouncer = new_debouncer_opt(Duration::from_secs(2), None, handler, NoCache, config)?;
+        Ok(Self {
+            debouncer,
+            recursive,
+            scanner,
+        })
+    }
+}
+
+impl<W: Watcher> WatchSource for NotifySource<W> {
+    fn check(&self, path: &Path) -> Result<()> {
+        let reason = if !path.exists() {
+            "does not exist"
+        } else if !path.is_dir() {
+            "is not a directory"
+        } else {
+            return Ok(());
+        };
+        Err(Error::NotWatchable {
+            path: path.to_path_buf(),
+            reason,
+        })
+    }
+
+    fn root(&self, path: &Path) -> io::Result<PathBuf> {
+        self.scanner.remember_dir(path)
+    }
+
+    fn list(&self, root: &Path, recursive: bool) -> io::Result<Vec<PathBuf>> {
+        if recursive {
+            self.scanner.read_tree(root)
+        } else {
+            self.scanner.read_contents(root)
+        }
     }
 
-    Ok(debouncer)
+    fn watch(&mut self, root: &Path, recursive: bool) -> Result<()> {
+        if let Ok(mut roots) = self.recursive.lock() {
+            if recursive {
+                roots.insert(root.to_path_buf());
+            } else {
+                roots.remove(root);
+            }
+        }
+        let mode = if recursive {
+            RecursiveMode::Recursive
+        } else {
+            RecursiveMode::NonRecursive
+        };
+        self.debouncer
+            .watch(root, mode)
+            .map_err(|e| watch_error(root, e))
+    }
+
+    fn unwatch(&mut self, root: &Path) -> Result<()> {
+        if let Ok(mut roots) = self.recursive.lock() {
+            roots.remove(root);
+        }
+        self.debouncer
+            .unwatch(root)
+            .map_err(|e| watch_error(root, e))
+    }
+
+    fn progress(&self) -> Option<Arc<AtomicUsize>> {
+        Some(Arc::clone(&self.scanner.scanned))
+    }
+}
+
+fn scan_error(path: &Path, source: io::Error) -> Error {
+    Error::Scan {
+        path: path.to_path_buf(),
+        source,
+    }
+}
+
+fn watch_error(path: &Path, source: notify::Error) -> Error {
+    Error::Watch {
+        path: path.to_path_buf(),
+        source,
+    }
 }
 
-fn handle_events(tx: &Sender<FileChange>, events: Vec<DebouncedEvent>) {
+fn handle_events(
+    tx: &Sender<FileChange>,
+    events: Vec<DebouncedEvent>,
+    recursive: &Mutex<HashSet<PathBuf>>,
+    scanner: &Scanner,
+) {
     for dbe in events {
-        handle_event(tx, dbe.event);
+        handle_event(tx, dbe.event, recursive, scanner);
     }
 }
 
-fn handle_event(tx: &Sender<FileChange>, event: notify::Event) {
-    // println!("{:?}", event);
+fn handle_event(
+    tx: &Sender<FileChange>,
+    event: notify::Event,
+    recursive: &Mutex<HashSet<PathBuf>>,
+    scanner: &Scanner,
+) {
+    if matches!(
+        event.kind,
+        EventKind::Create(_) | EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(_))
+    ) {
+        for path in &event.paths {
+            scanner.forget(path);
+        }
+    }
     match event.kind {
         EventKind::Create(_) => event
             .paths
             .first()
-            .map(|f| tx.send(FileChange::Added(f.to_path_buf()))),
+            .map(|f| send_added(tx, f, recursive, scanner)),
         EventKind::Remove(_) => event
             .paths
             .first()
@@ -200,11 +1451,35 @@ fn handle_event(tx: &Sender<FileChange>, event: notify::Event) {
             .paths
             .first()
             // RenameMode::To means moved in to tracking; treat as a create
-            .map(|f| tx.send(FileChange::Added(f.to_owned()))),
+            .map(|f| send_added(tx, f, recursive, scanner)),
+        EventKind::Modify(ModifyKind::Data(_)) => event
+            .paths
+            .first()
+            .map(|f| tx.send(FileChange::Modified(f.to_owned()))),
         _ => None,
     };
 }
 
+/// Report a path that's appeared. A directory under a recursive root isn't tracked itself, but
+/// the files already in it are, since they may have been written before it was being watched.
+fn send_added(
+    tx: &Sender<FileChange>,
+    path: &Path,
+    recursive: &Mutex<HashSet<PathBuf>>,
+    scanner: &Scanner,
+) -> std::result::Result<(), SendError<FileChange>> {
+    let in_tree = recursive
+        .lock()
+        .is_ok_and(|roots| path.ancestors().skip(1).any(|dir| roots.contains(dir)));
+    if !(in_tree && path.is_dir()) {
+        return tx.send(FileChange::Added(path.to_path_buf()));
+    }
+    for file in scanner.read_tree(path).unwrap_or_default() {
+        tx.send(FileChange::Added(file))?;
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use std::sync::mpsc::channel;
@@ -212,138 +1487,289 @@ mod tests {
     use itertools::assert_equal;
 
     use super::*;
+    use crate::clock::{MockClock, SystemClock};
+
+    /// A source with a fixed listing, standing in for a remote backend.
+    struct FixedSource(Vec<PathBuf>);
+
+    impl WatchSource for FixedSource {
+        fn check(&self, _: &Path) -> Result<()> {
+            Ok(())
+        }
+
+        fn root(&self, path: &Path) -> io::Result<PathBuf> {
+            Ok(path.to_path_buf())
+        }
+
+        fn list(&self, root: &Path, _: bool) -> io::Result<Vec<PathBuf>> {
+            Ok(self
+                .0
+                .iter()
+                .filter(|p| p.starts_with(root))
+                .cloned()
+                .collect())
+        }
+
+        fn watch(&mut self, _: &Path, _: bool) -> Result<()> {
+            Ok(())
+        }
+
+        fn unwatch(&mut self, _: &Path) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn rescan_through_source() {
+        let mut source = FixedSource(vec![PathBuf::from("/root/bar")]);
+        let groups = get_initial_state(vec![PathBuf::from("/root").into()], &source).unwrap();
+        assert_equal(groups[0].items(), &[FileItem::new(PathBuf::from("bar"))]);
+
+        source.0 = vec![PathBuf::from("/root/foo")];
+        assert_eq!(
+            rescan(&groups, &source).unwrap(),
+            vec![
+                FileChange::Added(PathBuf::from("/root/foo")),
+                FileChange::Removed(PathBuf::from("/root/bar")),
+            ]
+        );
+    }
+
+    #[test]
+    fn filter_by_name() {
+        let path = |name: &str| PathBuf::from("/root").join(name);
+        let source = FixedSource(
+            ["a.csv", "b.tmp", "skip.csv", "notes.txt"]
+                .map(path)
+                .to_vec(),
+        );
+        let watch = WatchPath {
+            include: vec!["*.csv".to_string(), "*.tmp".to_string()],
+            exclude: vec!["skip*".to_string(), "*.tmp".to_string()],
+            ..PathBuf::from("/root").into()
+        };
+        let mut groups = get_initial_state(vec![watch], &source).unwrap();
+        let names = |groups: &[FileGroup]| {
+            groups[0]
+                .items()
+                .iter()
+                .filter(|item| item.removed.is_none())
+                .map(|item| item.name.to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(&groups), ["a.csv"]);
+        assert!(rescan(&groups, &source).unwrap().is_empty());
+
+        // a partial file is only seen once it's renamed to its final name
+        let changes = apply_file_changes(
+            [
+                FileChange::Added(path("c.tmp")),
+                FileChange::Modified(path("c.tmp")),
+                FileChange::Moved(path("b.tmp"), path("b.csv")),
+                FileChange::Moved(path("a.csv"), path("a.tmp")),
+            ],
+            &mut groups,
+            &SystemClock,
+            DELETED_RETENTION,
+        );
+        assert_eq!(
+            changes,
+            [
+                FileChange::Moved(path("b.tmp"), path("b.csv")),
+                FileChange::Moved(path("a.csv"), path("a.tmp")),
+            ]
+        );
+        assert_eq!(names(&groups), ["b.csv"]);
+
+        assert!(matches!(
+            NameFilter::new(&["[".to_string()], &[]),
+            Err(Error::Glob { .. })
+        ));
+    }
+
+    #[test]
+    fn keep_sorted() {
+        let path = |name: &str| PathBuf::from("/root").join(name);
+        let mut group = FileGroup::new(
+            PathBuf::from("/root"),
+            None,
+            ["c", "a", "d"]
+                .into_iter()
+                .map(|name| FileItem::new(PathBuf::from(name)))
+                .collect(),
+        );
+        let names = |group: &FileGroup| {
+            group
+                .sorted()
+                .map(|item| item.shown_name().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(&group), ["c", "a", "d"]);
+
+        group.set_sort(Sort::Name);
+        assert_eq!(names(&group), ["a", "c", "d"]);
+        group.add(&path("b"));
+        group.rename(&path("a"), &path("e"));
+        assert_eq!(names(&group), ["b", "c", "d", "e"]);
+        group.retain(|item| item.shown_name() != "c");
+        assert_eq!(names(&group), ["b", "d", "e"]);
+
+        // sizes not read yet go last, in the order they were found
+        group.set_sort(Sort::Size);
+        group.fill_metadata(&path("e"), (Some(10), None));
+        group.fill_metadata(&path("b"), (Some(5), None));
+        assert_eq!(names(&group), ["e", "b", "d"]);
+        assert_eq!(Sort::from_str("created", false), Ok(Sort::Found));
+    }
+
+    #[test]
+    fn source_names() {
+        assert_eq!("poll".parse::<SourceKind>().unwrap().name, "poll");
+        assert!("sftp".parse::<SourceKind>().is_err());
+    }
+
+    #[test]
+    fn parse_path_list_skips_blank_lines() {
+        let input = io::Cursor::new("/a\n\n  \n/b c\r\n");
+        assert_eq!(
+            parse_path_list(input).unwrap(),
+            vec![PathBuf::from("/a"), PathBuf::from("/b c")]
+        );
+    }
 
     #[test]
     fn update_file_items_new() {
         let (tx, rx) = channel();
-        let mut paths = vec![FileGroup::new(PathBuf::from("/root"))];
+        let mut paths = vec![FileGroup::new(PathBuf::from("/root"), None, vec![])];
 
         tx.send(FileChange::Added(PathBuf::from("/root/foo")))
             .unwrap();
 
-        update_file_items(&rx, &mut paths);
+        update_file_items(&rx, &mut paths, &SystemClock, DELETED_RETENTION);
 
         assert_eq!(paths.len(), 1);
-        assert_equal(
-            &paths[0].items,
-            &vec![FileItem::new(PathBuf::from("/root/foo"))],
-        );
+        assert_equal(paths[0].items(), &vec![FileItem::new(PathBuf::from("foo"))]);
     }
 
     #[test]
     fn update_file_items_new_duplicate() {
         let (tx, rx) = channel();
         let mut paths = vec![
-            FileGroup::new(PathBuf::from("/root")),
-            FileGroup::new(PathBuf::from("/root")),
+            FileGroup::new(PathBuf::from("/root"), None, vec![]),
+            FileGroup::new(PathBuf::from("/root"), None, vec![]),
         ];
 
         tx.send(FileChange::Added(PathBuf::from("/root/foo")))
             .unwrap();
 
-        update_file_items(&rx, &mut paths);
+        update_file_items(&rx, &mut paths, &SystemClock, DELETED_RETENTION);
 
         assert_eq!(paths.len(), 2);
-        let expected_items = vec![FileItem::new(PathBuf::from("/root/foo"))];
-        assert_equal(&paths[0].items, &expected_items);
-        assert_equal(&paths[1].items, &expected_items);
+        let expected_items = vec![FileItem::new(PathBuf::from("foo"))];
+        assert_equal(paths[0].items(), &expected_items);
+        assert_equal(paths[1].items(), &expected_items);
     }
 
     #[test]
     fn update_file_items_add() {
         let (tx, rx) = channel();
-        let mut paths = vec![FileGroup {
-            root: PathBuf::from("/root"),
-            items: vec![FileItem::new(PathBuf::from("/root/bar"))],
-        }];
+        let mut paths = vec![FileGroup::new(
+            PathBuf::from("/root"),
+            None,
+            vec![FileItem::new(PathBuf::from("bar"))],
+        )];
 
         tx.send(FileChange::Added(PathBuf::from("/root/foo")))
             .unwrap();
 
-        update_file_items(&rx, &mut paths);
+        update_file_items(&rx, &mut paths, &SystemClock, DELETED_RETENTION);
 
         assert_eq!(paths.len(), 1);
         let expected_items = vec![
-            FileItem::new(PathBuf::from("/root/bar")),
-            FileItem::new(PathBuf::from("/root/foo")),
+            FileItem::new(PathBuf::from("bar")),
+            FileItem::new(PathBuf::from("foo")),
         ];
-        assert_equal(&paths[0].items, &expected_items);
+        assert_equal(paths[0].items(), &expected_items);
     }
 
     #[test]
     fn update_file_items_add_duplicate() {
         let (tx, rx) = channel();
         let mut paths = vec![
-            FileGroup {
-                root: PathBuf::from("/root"),
-                items: vec![FileItem::new(PathBuf::from("/root/bar"))],
-            },
-            FileGroup {
-                root: PathBuf::from("/root"),
-                items: vec![FileItem::new(PathBuf::from("/root/bar"))],
-            },
+            FileGroup::new(
+                PathBuf::from("/root"),
+                None,
+                vec![FileItem::new(PathBuf::from("bar"))],
+            ),
+            FileGroup::new(
+                PathBuf::from("/root"),
+                None,
+                vec![FileItem::new(PathBuf::from("bar"))],
+            ),
         ];
 
         tx.send(FileChange::Added(PathBuf::from("/root/foo")))
             .unwrap();
 
-        update_file_items(&rx, &mut paths);
+        update_file_items(&rx, &mut paths, &SystemClock, DELETED_RETENTION);
 
         assert_eq!(paths.len(), 2);
         let expected_items = vec![
-            FileItem::new(PathBuf::from("/root/bar")),
-            FileItem::new(PathBuf::from("/root/foo")),
+            FileItem::new(PathBuf::from("bar")),
+            FileItem::new(PathBuf::from("foo")),
         ];
-        assert_equal(&paths[0].items, &expected_items);
-        assert_equal(&paths[1].items, &expected_items);
+        assert_equal(paths[0].items(), &expected_items);
+        assert_equal(paths[1].items(), &expected_items);
     }
 
     #[test]
     fn update_file_items_delete_first() {
         let (tx, rx) = channel();
-        let mut paths = vec![FileGroup {
-            root: PathBuf::from("/root"),
-            items: vec![
-                FileItem::new(PathBuf::from("/root/bar")),
-                FileItem::new(PathBuf::from("/root/foo")),
+        let mut paths = vec![FileGroup::new(
+            PathBuf::from("/root"),
+            None,
+            vec![
+                FileItem::new(PathBuf::from("bar")),
+                FileItem::new(PathBuf::from("foo")),
             ],
-        }];
+        )];
 
         tx.send(FileChange::Removed(PathBuf::from("/root/bar")))
             .unwrap();
 
-        update_file_items(&rx, &mut paths);
+        update_file_items(&rx, &mut paths, &SystemClock, DELETED_RETENTION);
 
         assert_eq!(paths.len(), 1);
-        let items = &paths[0].items;
+        let items = paths[0].items();
         assert_eq!(items.len(), 2);
-        assert_eq!(items[0].path, PathBuf::from("/root/bar"));
+        assert_eq!(&*items[0].name, Path::new("bar"));
         assert!(items[0].removed.is_some());
-        assert_eq!(items[1].path, PathBuf::from("/root/foo"));
+        assert_eq!(&*items[1].name, Path::new("foo"));
         assert!(items[1].removed.is_none());
     }
 
     #[test]
     fn update_file_items_delete_second() {
         let (tx, rx) = channel();
-        let mut paths = vec![FileGroup {
-            root: PathBuf::from("/root"),
-            items: vec![
-                FileItem::new(PathBuf::from("/root/bar")),
-                FileItem::new(PathBuf::from("/root/foo")),
+        let mut paths = vec![FileGroup::new(
+            PathBuf::from("/root"),
+            None,
+            vec![
+                FileItem::new(PathBuf::from("bar")),
+                FileItem::new(PathBuf::from("foo")),
             ],
-        }];
+        )];
 
         tx.send(FileChange::Removed(PathBuf::from("/root/foo")))
             .unwrap();
 
-        update_file_items(&rx, &mut paths);
+        update_file_items(&rx, &mut paths, &SystemClock, DELETED_RETENTION);
         assert_eq!(paths.len(), 1);
-        let items = &paths[0].items;
+        let items = paths[0].items();
         assert_eq!(items.len(), 2);
-        assert_eq!(items[0].path, PathBuf::from("/root/bar"));
+        assert_eq!(&*items[0].name, Path::new("bar"));
         assert!(items[0].removed.is_none());
-        assert_eq!(items[1].path, PathBuf::from("/root/foo"));
+        assert_eq!(&*items[1].name, Path::new("foo"));
         assert!(items[1].removed.is_some());
     }
 
@@ -351,86 +1777,91 @@ mod tests {
     fn update_file_items_delete_first_duplicate() {
         let (tx, rx) = channel();
         let mut paths = vec![
-            FileGroup {
-                root: PathBuf::from("/root"),
-                items: vec![
-                    FileItem::new(PathBuf::from("/root/bar")),
-                    FileItem::new(PathBuf::from("/root/foo")),
+            FileGroup::new(
+                PathBuf::from("/root"),
+                None,
+                vec![
+                    FileItem::new(PathBuf::from("bar")),
+                    FileItem::new(PathBuf::from("foo")),
                 ],
-            },
-            FileGroup {
-                root: PathBuf::from("/root"),
-                items: vec![
-                    FileItem::new(PathBuf::from("/root/bar")),
-                    FileItem::new(PathBuf::from("/root/foo")),
+            ),
+            FileGroup::new(
+                PathBuf::from("/root"),
+                None,
+                vec![
+                    FileItem::new(PathBuf::from("bar")),
+                    FileItem::new(PathBuf::from("foo")),
                 ],
-            },
+            ),
         ];
 
         tx.send(FileChange::Removed(PathBuf::from("/root/bar")))
             .unwrap();
 
-        update_file_items(&rx, &mut paths);
+        update_file_items(&rx, &mut paths, &SystemClock, DELETED_RETENTION);
 
         assert_eq!(paths.len(), 2);
-        let assert_items = |items: &Vec<FileItem>| {
+        let assert_items = |items: &[FileItem]| {
             assert_eq!(items.len(), 2);
-            assert_eq!(items[0].path, PathBuf::from("/root/bar"));
+            assert_eq!(&*items[0].name, Path::new("bar"));
             assert!(items[0].removed.is_some());
-            assert_eq!(items[1].path, PathBuf::from("/root/foo"));
+            assert_eq!(&*items[1].name, Path::new("foo"));
             assert!(items[1].removed.is_none());
         };
-        assert_items(&paths[0].items);
-        assert_items(&paths[1].items);
+        assert_items(paths[0].items());
+        assert_items(paths[1].items());
     }
 
     #[test]
     fn update_file_items_delete_second_duplicate() {
         let (tx, rx) = channel();
         let mut paths = vec![
-            FileGroup {
-                root: PathBuf::from("/root"),
-                items: vec![
-                    FileItem::new(PathBuf::from("/root/bar")),
-                    FileItem::new(PathBuf::from("/root/foo")),
+            FileGroup::new(
+                PathBuf::from("/root"),
+                None,
+                vec![
+                    FileItem::new(PathBuf::from("bar")),
+                    FileItem::new(PathBuf::from("foo")),
                 ],
-            },
-            FileGroup {
-                root: PathBuf::from("/root"),
-                items: vec![
-                    FileItem::new(PathBuf::from("/root/bar")),
-                    FileItem::new(PathBuf::from("/root/foo")),
+            ),
+            FileGroup::new(
+                PathBuf::from("/root"),
+                None,
+                vec![
+                    FileItem::new(PathBuf::from("bar")),
+                    FileItem::new(PathBuf::from("foo")),
                 ],
-            },
+            ),
         ];
 
         tx.send(FileChange::Removed(PathBuf::from("/root/foo")))
             .unwrap();
 
-        update_file_items(&rx, &mut paths);
+        update_file_items(&rx, &mut paths, &SystemClock, DELETED_RETENTION);
 
         assert_eq!(paths.len(), 2);
-        let assert_items = |items: &Vec<FileItem>| {
+        let assert_items = |items: &[FileItem]| {
             assert_eq!(items.len(), 2);
-            assert_eq!(items[0].path, PathBuf::from("/root/bar"));
+            assert_eq!(&*items[0].name, Path::new("bar"));
             assert!(items[0].removed.is_none());
-            assert_eq!(items[1].path, PathBuf::from("/root/foo"));
+            assert_eq!(&*items[1].name, Path::new("foo"));
             assert!(items[1].removed.is_some());
         };
-        assert_items(&paths[0].items);
-        assert_items(&paths[1].items);
+        assert_items(paths[0].items());
+        assert_items(paths[1].items());
     }
 
     #[test]
     fn update_file_items_move_first() {
         let (tx, rx) = channel();
-        let mut paths = vec![FileGroup {
-            root: PathBuf::from("/root"),
-            items: vec![
-                FileItem::new(PathBuf::from("/root/bar")),
-                FileItem::new(PathBuf::from("/root/foo")),
+        let mut paths = vec![FileGroup::new(
+            PathBuf::from("/root"),
+            None,
+            vec![
+                FileItem::new(PathBuf::from("bar")),
+                FileItem::new(PathBuf::from("foo")),
             ],
-        }];
+        )];
 
         tx.send(FileChange::Moved(
             PathBuf::from("/root/bar"),
@@ -438,26 +1869,27 @@ mod tests {
         ))
         .unwrap();
 
-        update_file_items(&rx, &mut paths);
+        update_file_items(&rx, &mut paths, &SystemClock, DELETED_RETENTION);
 
         assert_eq!(paths.len(), 1);
         let expected_items = vec![
-            FileItem::new(PathBuf::from("/root/new")),
-            FileItem::new(PathBuf::from("/root/foo")),
+            FileItem::new(PathBuf::from("new")),
+            FileItem::new(PathBuf::from("foo")),
         ];
-        assert_equal(&paths[0].items, &expected_items);
+        assert_equal(paths[0].items(), &expected_items);
     }
 
     #[test]
     fn update_file_items_move_second() {
         let (tx, rx) = channel();
-        let mut paths = vec![FileGroup {
-            root: PathBuf::from("/root"),
-            items: vec![
-                FileItem::new(PathBuf::from("/root/bar")),
-                FileItem::new(PathBuf::from("/root/foo")),
+        let mut paths = vec![FileGroup::new(
+            PathBuf::from("/root"),
+            None,
+            vec![
+                FileItem::new(PathBuf::from("bar")),
+                FileItem::new(PathBuf::from("foo")),
             ],
-        }];
+        )];
 
         tx.send(FileChange::Moved(
             PathBuf::from("/root/foo"),
@@ -465,34 +1897,36 @@ mod tests {
         ))
         .unwrap();
 
-        update_file_items(&rx, &mut paths);
+        update_file_items(&rx, &mut paths, &SystemClock, DELETED_RETENTION);
 
         assert_eq!(paths.len(), 1);
         let expected_items = vec![
-            FileItem::new(PathBuf::from("/root/bar")),
-            FileItem::new(PathBuf::from("/root/new")),
+            FileItem::new(PathBuf::from("bar")),
+            FileItem::new(PathBuf::from("new")),
         ];
-        assert_equal(&paths[0].items, &expected_items);
+        assert_equal(paths[0].items(), &expected_items);
     }
 
     #[test]
     fn update_file_items_move_first_duplicate() {
         let (tx, rx) = channel();
         let mut paths = vec![
-            FileGroup {
-                root: PathBuf::from("/root"),
-                items: vec![
-                    FileItem::new(PathBuf::from("/root/bar")),
-                    FileItem::new(PathBuf::from("/root/foo")),
+            FileGroup::new(
+                PathBuf::from("/root"),
+                None,
+                vec![
+                    FileItem::new(PathBuf::from("bar")),
+                    FileItem::new(PathBuf::from("foo")),
                 ],
-            },
-            FileGroup {
-                root: PathBuf::from("/root"),
-                items: vec![
-                    FileItem::new(PathBuf::from("/root/bar")),
-                    FileItem::new(PathBuf::from("/root/foo")),
+            ),
+            FileGroup::new(
+                PathBuf::from("/root"),
+                None,
+                vec![
+                    FileItem::new(PathBuf::from("bar")),
+                    FileItem::new(PathBuf::from("foo")),
                 ],
-            },
+            ),
         ];
 
         tx.send(FileChange::Moved(
@@ -501,35 +1935,37 @@ mod tests {
         ))
         .unwrap();
 
-        update_file_items(&rx, &mut paths);
+        update_file_items(&rx, &mut paths, &SystemClock, DELETED_RETENTION);
 
         assert_eq!(paths.len(), 2);
         let expected_items = vec![
-            FileItem::new(PathBuf::from("/root/new")),
-            FileItem::new(PathBuf::from("/root/foo")),
+            FileItem::new(PathBuf::from("new")),
+            FileItem::new(PathBuf::from("foo")),
         ];
-        assert_equal(&paths[0].items, &expected_items);
-        assert_equal(&paths[1].items, &expected_items);
+        assert_equal(paths[0].items(), &expected_items);
+        assert_equal(paths[1].items(), &expected_items);
     }
 
     #[test]
     fn update_file_items_move_second_duplicate() {
         let (tx, rx) = channel();
         let mut paths = vec![
-            FileGroup {
-                root: PathBuf::from("/root"),
-                items: vec![
-                    FileItem::new(PathBuf::from("/root/bar")),
-                    FileItem::new(PathBuf::from("/root/foo")),
+            FileGroup::new(
+                PathBuf::from("/root"),
+                None,
+                vec![
+                    FileItem::new(PathBuf::from("bar")),
+                    FileItem::new(PathBuf::from("foo")),
                 ],
-            },
-            FileGroup {
-                root: PathBuf::from("/root"),
-                items: vec![
-                    FileItem::new(PathBuf::from("/root/bar")),
-                    FileItem::new(PathBuf::from("/root/foo")),
+            ),
+            FileGroup::new(
+                PathBuf::from("/root"),
+                None,
+                vec![
+                    FileItem::new(PathBuf::from("bar")),
+                    FileItem::new(PathBuf::from("foo")),
                 ],
-            },
+            ),
         ];
 
         tx.send(FileChange::Moved(
@@ -538,27 +1974,28 @@ mod tests {
         ))
         .unwrap();
 
-        update_file_items(&rx, &mut paths);
+        update_file_items(&rx, &mut paths, &SystemClock, DELETED_RETENTION);
 
         assert_eq!(paths.len(), 2);
         let expected_items = vec![
-            FileItem::new(PathBuf::from("/root/bar")),
-            FileItem::new(PathBuf::from("/root/new")),
+            FileItem::new(PathBuf::from("bar")),
+            FileItem::new(PathBuf::from("new")),
         ];
-        assert_equal(&paths[0].items, &expected_items);
-        assert_equal(&paths[1].items, &expected_items);
+        assert_equal(paths[0].items(), &expected_items);
+        assert_equal(paths[1].items(), &expected_items);
     }
 
     #[test]
     fn update_file_items_move_out() {
         let (tx, rx) = channel();
-        let mut paths = vec![FileGroup {
-            root: PathBuf::from("/root"),
-            items: vec![
-                FileItem::new(PathBuf::from("/root/bar")),
-                FileItem::new(PathBuf::from("/root/foo")),
+        let mut paths = vec![FileGroup::new(
+            PathBuf::from("/root"),
+            None,
+            vec![
+                FileItem::new(PathBuf::from("bar")),
+                FileItem::new(PathBuf::from("foo")),
             ],
-        }];
+        )];
 
         tx.send(FileChange::Moved(
             PathBuf::from("/root/bar"),
@@ -566,24 +2003,25 @@ mod tests {
         ))
         .unwrap();
 
-        update_file_items(&rx, &mut paths);
+        update_file_items(&rx, &mut paths, &SystemClock, DELETED_RETENTION);
 
         assert_eq!(paths.len(), 1);
-        let items = &paths[0].items;
+        let items = paths[0].items();
         assert_eq!(items.len(), 2);
-        assert_eq!(items[0].path, PathBuf::from("/root/bar"));
+        assert_eq!(&*items[0].name, Path::new("bar"));
         assert!(items[0].removed.is_some());
-        assert_eq!(items[1].path, PathBuf::from("/root/foo"));
+        assert_eq!(&*items[1].name, Path::new("foo"));
         assert!(items[1].removed.is_none());
     }
 
     #[test]
     fn update_file_items_move_in() {
         let (tx, rx) = channel();
-        let mut paths = vec![FileGroup {
-            root: PathBuf::from("/root"),
-            items: vec![FileItem::new(PathBuf::from("/root/bar"))],
-        }];
+        let mut paths = vec![FileGroup::new(
+            PathBuf::from("/root"),
+            None,
+            vec![FileItem::new(PathBuf::from("bar"))],
+        )];
 
         tx.send(FileChange::Moved(
             PathBuf::from("/other/new"),
@@ -591,31 +2029,117 @@ mod tests {
         ))
         .unwrap();
 
-        update_file_items(&rx, &mut paths);
+        update_file_items(&rx, &mut paths, &SystemClock, DELETED_RETENTION);
 
         assert_eq!(paths.len(), 1);
         let expected_items = vec![
-            FileItem::new(PathBuf::from("/root/bar")),
-            FileItem::new(PathBuf::from("/root/foo")),
+            FileItem::new(PathBuf::from("bar")),
+            FileItem::new(PathBuf::from("foo")),
+        ];
+        assert_eq!(paths[0].items(), &expected_items);
+    }
+
+    #[test]
+    fn removed_files_expire() {
+        let clock = MockClock::new();
+        let (tx, rx) = channel();
+        let mut paths = vec![FileGroup::new(
+            PathBuf::from("/root"),
+            None,
+            vec![
+                FileItem::new(PathBuf::from("bar")),
+                FileItem::new(PathBuf::from("foo")),
+            ],
+        )];
+
+        tx.send(FileChange::Removed(PathBuf::from("/root/bar")))
+            .unwrap();
+        update_file_items(&rx, &mut paths, &clock, DELETED_RETENTION);
+        assert_eq!(paths[0].items()[0].removed, Some(clock.now()));
+        assert_eq!(
+            next_expiry(&paths, DELETED_RETENTION),
+            Some(clock.now() + DELETED_RETENTION)
+        );
+
+        clock.advance(DELETED_RETENTION - Duration::from_secs(1));
+        update_file_items(&rx, &mut paths, &clock, DELETED_RETENTION);
+        assert_eq!(paths[0].items().len(), 2);
+        assert_eq!(
+            next_expiry(&paths, DELETED_RETENTION),
+            Some(clock.now() + Duration::from_secs(1))
+        );
+
+        clock.advance(Duration::from_secs(1));
+        update_file_items(&rx, &mut paths, &clock, DELETED_RETENTION);
+        assert_eq!(paths[0].items(), &[FileItem::new(PathBuf::from("foo"))]);
+        assert_eq!(next_expiry(&paths, DELETED_RETENTION), None);
+    }
+
+    #[test]
+    fn removed_files_expire_sooner() {
+        let clock = MockClock::new();
+        let retention = Duration::from_secs(30 * 60);
+        let mut paths = vec![FileGroup::new(
+            PathBuf::from("/root"),
+            None,
+            vec![FileItem::new(PathBuf::from("bar"))],
+        )];
+
+        let removed = FileChange::Removed(PathBuf::from("/root/bar"));
+        apply_file_changes([removed], &mut paths, &clock, retention);
+        assert_eq!(
+            next_expiry(&paths, retention),
+            Some(clock.now() + retention)
+        );
+
+        clock.advance(retention);
+        apply_file_changes([], &mut paths, &clock, retention);
+        assert!(paths[0].items().is_empty());
+    }
+
+    #[test]
+    fn moved_between_groups_leaves_no_ghost() {
+        // the old item is dropped straight away even though no time passes
+        let clock = MockClock::new();
+        let (tx, rx) = channel();
+        let mut paths = vec![
+            FileGroup::new(
+                PathBuf::from("/root"),
+                None,
+                vec![FileItem::new(PathBuf::from("move"))],
+            ),
+            FileGroup::new(PathBuf::from("/other"), None, vec![]),
         ];
-        assert_eq!(&paths[0].items, &expected_items);
+
+        tx.send(FileChange::Moved(
+            PathBuf::from("/root/move"),
+            PathBuf::from("/other/move"),
+        ))
+        .unwrap();
+        update_file_items(&rx, &mut paths, &clock, DELETED_RETENTION);
+
+        assert!(paths[0].items().is_empty());
+        assert_eq!(paths[1].items(), &[FileItem::new(PathBuf::from("move"))]);
+        assert_eq!(next_expiry(&paths, DELETED_RETENTION), None);
     }
 
     #[test]
     fn update_file_items_move_between() {
         let (tx, rx) = channel();
         let mut paths = vec![
-            FileGroup {
-                root: PathBuf::from("/root"),
-                items: vec![
-                    FileItem::new(PathBuf::from("/root/bar")),
-                    FileItem::new(PathBuf::from("/root/move")),
+            FileGroup::new(
+                PathBuf::from("/root"),
+                None,
+                vec![
+                    FileItem::new(PathBuf::from("bar")),
+                    FileItem::new(PathBuf::from("move")),
                 ],
-            },
-            FileGroup {
-                root: PathBuf::from("/other"),
-                items: vec![FileItem::new(PathBuf::from("/other/foo"))],
-            },
+            ),
+            FileGroup::new(
+                PathBuf::from("/other"),
+                None,
+                vec![FileItem::new(PathBuf::from("foo"))],
+            ),
         ];
 
         tx.send(FileChange::Moved(
@@ -624,19 +2148,278 @@ mod tests {
         ))
         .unwrap();
 
-        update_file_items(&rx, &mut paths);
+        update_file_items(&rx, &mut paths, &SystemClock, DELETED_RETENTION);
 
         assert_eq!(paths.len(), 2);
 
-        assert_eq!(paths[0].items.len(), 1);
-        let items = &paths[0].items;
-        assert_eq!(items[0].path, PathBuf::from("/root/bar"));
+        assert_eq!(paths[0].items().len(), 1);
+        let items = paths[0].items();
+        assert_eq!(&*items[0].name, Path::new("bar"));
         assert!(items[0].removed.is_none());
 
         let expected_items_2 = vec![
-            FileItem::new(PathBuf::from("/other/foo")),
-            FileItem::new(PathBuf::from("/other/move")),
+            FileItem::new(PathBuf::from("foo")),
+            FileItem::new(PathBuf::from("move")),
         ];
-        assert_eq!(&paths[1].items, &expected_items_2);
+        assert_eq!(paths[1].items(), &expected_items_2);
+    }
+
+    #[test]
+    fn update_file_items_readd_and_rename_over() {
+        let (tx, rx) = channel();
+        let mut paths = vec![FileGroup::new(
+            PathBuf::from("/root"),
+            None,
+            vec![
+                FileItem::new(PathBuf::from("bar")),
+                FileItem::new(PathBuf::from("foo")),
+                FileItem::new(PathBuf::from("baz")),
+            ],
+        )];
+
+        tx.send(FileChange::Removed(PathBuf::from("/root/bar")))
+            .unwrap();
+        tx.send(FileChange::Added(PathBuf::from("/root/bar")))
+            .unwrap();
+        tx.send(FileChange::Moved(
+            PathBuf::from("/root/baz"),
+            PathBuf::from("/root/foo"),
+        ))
+        .unwrap();
+
+        update_file_items(&rx, &mut paths, &SystemClock, DELETED_RETENTION);
+
+        // each path is only shown once
+        let expected_items = vec![
+            FileItem::new(PathBuf::from("bar")),
+            FileItem::new(PathBuf::from("foo")),
+        ];
+        assert_eq!(paths[0].items(), &expected_items);
+        assert!(paths[0].get(Path::new("/root/baz")).is_none());
+        assert!(paths[0].get(Path::new("/root/foo")).is_some());
+    }
+
+    #[test]
+    fn update_recursive_group() {
+        let (tx, rx) = channel();
+        let mut group = FileGroup::new(
+            PathBuf::from("/drop"),
+            None,
+            vec![
+                FileItem::new(PathBuf::from("2024-06-01/a")),
+                FileItem::new(PathBuf::from("2024-06-01/b")),
+                FileItem::new(PathBuf::from("2024-06-02/c")),
+            ],
+        );
+        group.recursive = true;
+        group.get_mut(Path::new("/drop/2024-06-01/a")).unwrap().tags = vec!["late".to_string()];
+        let mut paths = vec![group];
+
+        // between subdirectories, and a whole subdirectory renamed
+        tx.send(FileChange::Moved(
+            PathBuf::from("/drop/2024-06-02/c"),
+            PathBuf::from("/drop/done/c"),
+        ))
+        .unwrap();
+        tx.send(FileChange::Moved(
+            PathBuf::from("/drop/2024-06-01"),
+            PathBuf::from("/drop/june-1"),
+        ))
+        .unwrap();
+        update_file_items(&rx, &mut paths, &SystemClock, DELETED_RETENTION);
+        let names = |group: &FileGroup| {
+            group
+                .items()
+                .iter()
+                .filter(|item| item.removed.is_none())
+                .map(|item| item.name.to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(&paths[0]), ["june-1/a", "june-1/b", "done/c"]);
+        assert_eq!(paths[0].items()[0].tags, ["late"]);
+
+        tx.send(FileChange::Removed(PathBuf::from("/drop/june-1")))
+            .unwrap();
+        update_file_items(&rx, &mut paths, &SystemClock, DELETED_RETENTION);
+        assert_eq!(names(&paths[0]), ["done/c"]);
+    }
+
+    #[test]
+    fn read_nested_files() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        fs::create_dir_all(dir.join("a/b")).unwrap();
+        fs::write(dir.join("top"), "").unwrap();
+        fs::write(dir.join("a/b/deep"), "").unwrap();
+        std::os::unix::fs::symlink("b", dir.join("a/link")).unwrap();
+
+        let scanner = Scanner::default();
+        let root = scanner.remember_dir(dir).unwrap();
+        let mut files = scanner.read_tree(&root).unwrap();
+        files.sort();
+
+        // a link to a directory is listed like any other entry, but not followed
+        assert_eq!(
+            files,
+            [root.join("a/b"), root.join("a/b/deep"), root.join("top")]
+        );
+    }
+
+    #[test]
+    fn refresh_size_and_modified() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let path = dir.join("queued");
+        fs::write(&path, "abc").unwrap();
+        let mut groups = vec![FileGroup::new(dir.to_path_buf(), None, vec![])];
+
+        // as scanned, it's left to be read once it's wanted
+        groups[0].add(&path);
+        assert!(!groups[0].get(&path).unwrap().metadata_read);
+        assert_equal(groups[0].unread(), [path.clone()]);
+
+        apply_file_changes(
+            [FileChange::Added(path.clone())],
+            &mut groups,
+            &SystemClock,
+            DELETED_RETENTION,
+        );
+        let item = groups[0].get(&path).unwrap();
+        assert_eq!(item.size, Some(3));
+        assert_eq!(item.modified, fs::metadata(&path).unwrap().modified().ok());
+
+        fs::write(&path, "abcdef").unwrap();
+        apply_file_changes(
+            [FileChange::Modified(path.clone())],
+            &mut groups,
+            &SystemClock,
+            DELETED_RETENTION,
+        );
+        assert_eq!(groups[0].get(&path).unwrap().size, Some(6));
+    }
+
+    #[test]
+    fn resolve_symlinked_entries() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        fs::create_dir_all(dir.join("watched")).unwrap();
+        fs::create_dir_all(dir.join("elsewhere")).unwrap();
+        fs::write(dir.join("elsewhere/a"), "a").unwrap();
+        fs::write(dir.join("watched/b"), "b").unwrap();
+        std::os::unix::fs::symlink("../elsewhere/a", dir.join("watched/a")).unwrap();
+        std::os::unix::fs::symlink("a", dir.join("watched/c")).unwrap();
+
+        let scanner = Scanner::default();
+        let root = scanner.remember_dir(&dir.join("watched")).unwrap();
+        let mut contents = scanner.read_contents(&root).unwrap();
+        contents.sort();
+        let mut expected = ["a", "b", "c"]
+            .iter()
+            .map(|name| root.join(name).canonicalize().unwrap())
+            .collect::<Vec<_>>();
+        expected.sort();
+
+        assert_eq!(contents, expected);
+        assert_eq!(scanner.scanned.load(Ordering::Relaxed), 3);
+
+        // once the link's target has moved, it's resolved again rather than from before
+        assert!(scanner
+            .dirs
+            .lock()
+            .unwrap()
+            .contains_key(&root.join("../elsewhere")));
+        scanner.forget(&dir.join("elsewhere").canonicalize().unwrap());
+        assert_eq!(scanner.dirs.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn coalesce_changes() {
+        let path = |name: &str| PathBuf::from("/root").join(name);
+        let changes = vec![
+            FileChange::Added(path("tmp")),
+            FileChange::Removed(path("tmp")),
+            FileChange::Added(path("a.part")),
+            FileChange::Moved(path("a.part"), path("a")),
+            FileChange::Moved(path("b"), path("c")),
+            FileChange::Moved(path("c"), path("d")),
+            FileChange::Moved(path("e"), path("f")),
+            FileChange::Moved(path("f"), path("e")),
+            FileChange::Moved(path("g"), path("h")),
+            FileChange::Removed(path("h")),
+            FileChange::Modified(path("log")),
+            FileChange::Modified(path("log")),
+        ];
+
+        assert_eq!(
+            coalesce(changes),
+            vec![
+                FileChange::Added(path("a")),
+                FileChange::Moved(path("b"), path("d")),
+                FileChange::Removed(path("g")),
+                FileChange::Modified(path("log")),
+            ]
+        );
+    }
+
+    #[test]
+    fn skip_listed_non_directories() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let (a, b, file) = (dir.join("a"), dir.join("b"), dir.join("c.csv"));
+        fs::create_dir(&a).unwrap();
+        fs::create_dir(&b).unwrap();
+        fs::write(&file, "").unwrap();
+        let list = dir.join("paths.txt");
+        let lines = [&a, &b, &file, &b].map(|p| p.display().to_string());
+        fs::write(&list, lines.join("\n")).unwrap();
+
+        let read = read_paths_from(&list, std::slice::from_ref(&a)).unwrap();
+        assert_eq!(read.paths, std::slice::from_ref(&b));
+        assert_eq!(
+            read.skipped,
+            [
+                (a, "already watched"),
+                (file, "not a directory"),
+                (b, "already watched"),
+            ]
+        );
+    }
+
+    #[test]
+    fn limit_tracked_items() {
+        let path = |name: &str| PathBuf::from(name);
+        let removed = |name: &str, secs| FileItem {
+            removed: Some(Instant::now() - Duration::from_secs(secs)),
+            ..FileItem::new(path(name))
+        };
+        let mut groups = vec![FileGroup::new(
+            PathBuf::from("/root"),
+            None,
+            vec![
+                FileItem::new(path("a")),
+                removed("b", 10),
+                FileItem::new(path("c")),
+                removed("d", 20),
+                removed("e", 30),
+                FileItem::new(path("f")),
+            ],
+        )];
+
+        enforce_limits(
+            &mut groups,
+            Limits {
+                items: 2,
+                removed: 2,
+                retention: DELETED_RETENTION,
+            },
+        );
+
+        // the oldest ghost goes to meet the removed limit, then the other ghosts and the file
+        // seen earliest to meet the item limit
+        assert_eq!(
+            groups[0].items(),
+            &[FileItem::new(path("c")), FileItem::new(path("f"))]
+        );
+        assert_eq!(groups[0].hidden, 1);
     }
 }
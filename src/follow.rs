@@ -0,0 +1,111 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::PathBuf;
+
+/// The most lines kept from a followed file.
+const FOLLOW_LINES: usize = 1000;
+
+/// How far from the end of a file to start following it, so it opens with its last few lines.
+const START_BYTES: u64 = 8 * 1024;
+
+/// Lines appended to a file, read as it's modified, like `tail -f`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Follow {
+    pub path: PathBuf,
+    /// How much of the file has been read
+    offset: u64,
+    /// The end of the file, when it doesn't end with a newline yet
+    partial: String,
+    pub lines: VecDeque<String>,
+}
+
+impl Follow {
+    /// Start following a file from its last few lines.
+    pub fn new(path: PathBuf) -> io::Result<Self> {
+        let start = path.metadata()?.len().saturating_sub(START_BYTES);
+        let mut follow = Self {
+            path,
+            offset: start,
+            partial: String::new(),
+            lines: VecDeque::new(),
+        };
+        follow.read()?;
+        // starting part way through a line leaves half of it at the top
+        if start > 0 {
+            follow.lines.pop_front();
+        }
+        Ok(follow)
+    }
+
+    /// Read whatever has been appended since last time, starting again from the top if the
+    /// file has been truncated.
+    pub fn read(&mut self) -> io::Result<()> {
+        let mut file = File::open(&self.path)?;
+        if file.metadata()?.len() < self.offset {
+            self.offset = 0;
+            self.partial.clear();
+            self.lines.clear();
+        }
+        file.seek(SeekFrom::Start(self.offset))?;
+        let mut bytes = vec![];
+        self.offset += file.read_to_end(&mut bytes)? as u64;
+
+        self.partial.push_str(&String::from_utf8_lossy(&bytes));
+        if let Some(end) = self.partial.rfind('\n') {
+            let rest = self.partial.split_off(end + 1);
+            let complete = std::mem::replace(&mut self.partial, rest);
+            self.lines.extend(
+                complete
+                    .lines()
+                    .map(|line| line.trim_end_matches('\r').to_string()),
+            );
+        }
+        let excess = self.lines.len().saturating_sub(FOLLOW_LINES);
+        self.lines.drain(..excess);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::Write;
+
+    use super::*;
+
+    #[test]
+    fn appended_lines() {
+        let temp = tempfile::NamedTempFile::new().unwrap();
+        let path = temp.path();
+        fs::write(path, "one\ntwo\n").unwrap();
+        let mut follow = Follow::new(path.to_path_buf()).unwrap();
+        assert_eq!(follow.lines, ["one", "two"]);
+
+        let mut file = fs::OpenOptions::new().append(true).open(path).unwrap();
+        write!(file, "three\nfo").unwrap();
+        follow.read().unwrap();
+        assert_eq!(follow.lines, ["one", "two", "three"]);
+        writeln!(file, "ur").unwrap();
+        follow.read().unwrap();
+        assert_eq!(follow.lines, ["one", "two", "three", "four"]);
+
+        // truncated, e.g. by log rotation
+        fs::write(path, "five\n").unwrap();
+        follow.read().unwrap();
+        assert_eq!(follow.lines, ["five"]);
+    }
+
+    #[test]
+    fn start_near_the_end() {
+        let temp = tempfile::NamedTempFile::new().unwrap();
+        let lines = (0..10_000).map(|i| i.to_string()).collect::<Vec<_>>();
+        fs::write(temp.path(), lines.join("\n") + "\n").unwrap();
+
+        let follow = Follow::new(temp.path().to_path_buf()).unwrap();
+        assert_eq!(follow.lines.back().map(String::as_str), Some("9999"));
+        // no cut off line at the top
+        let first = follow.lines.front().unwrap();
+        assert!(lines.contains(first) && first.len() == 4);
+    }
+}
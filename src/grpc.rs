@@ -0,0 +1,232 @@
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+
+use serde_json::Value;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status};
+
+use crate::api::Api;
+
+mod proto {
+    tonic::include_proto!("file_task.v1");
+}
+
+use proto::file_task_server::{FileTask, FileTaskServer};
+use proto::{
+    Event, EventsRequest, File, GetEventsRequest, GetEventsResponse, GetGroupsRequest,
+    GetGroupsResponse, GetItemsRequest, GetItemsResponse, GetServiceRequest, Group, Item,
+    ListServicesRequest, ListServicesResponse, Service,
+};
+
+/// How many events a slow `Events` client can fall behind before it's dropped.
+const STREAM_BUFFER: usize = 100;
+
+/// The HTTP API's view, converted from its JSON.
+struct Server {
+    api: Arc<Mutex<Api>>,
+}
+
+impl Server {
+    fn api(&self) -> Result<MutexGuard<'_, Api>, Status> {
+        self.api
+            .lock()
+            .map_err(|_| Status::internal("API state is unavailable"))
+    }
+}
+
+#[tonic::async_trait]
+impl FileTask for Server {
+    async fn get_groups(
+        &self,
+        _: Request<GetGroupsRequest>,
+    ) -> Result<Response<GetGroupsResponse>, Status> {
+        let groups = array(self.api()?.groups()).map(group).collect();
+        Ok(Response::new(GetGroupsResponse { groups }))
+    }
+
+    async fn get_items(
+        &self,
+        _: Request<GetItemsRequest>,
+    ) -> Result<Response<GetItemsResponse>, Status> {
+        let items = array(&self.api()?.items())
+            .map(|item| Item {
+                group: text(&item["group"]),
+                path: text(&item["path"]),
+                removed: item["removed"].as_bool().unwrap_or_default(),
+            })
+            .collect();
+        Ok(Response::new(GetItemsResponse { items }))
+    }
+
+    async fn get_service(
+        &self,
+        _: Request<GetServiceRequest>,
+    ) -> Result<Response<Service>, Status> {
+        let api = self.api()?;
+        let service = api.service();
+        if service.is_null() {
+            return Err(Status::not_found("no service is monitored"));
+        }
+        Ok(Response::new(self::service(service)))
+    }
+
+    async fn list_services(
+        &self,
+        _: Request<ListServicesRequest>,
+    ) -> Result<Response<ListServicesResponse>, Status> {
+        let services = self.api()?.services().iter().map(service).collect();
+        Ok(Response::new(ListServicesResponse { services }))
+    }
+
+    async fn get_events(
+        &self,
+        request: Request<GetEventsRequest>,
+    ) -> Result<Response<GetEventsResponse>, Status> {
+        let page = self.api()?.events_since(request.get_ref().since);
+        Ok(Response::new(GetEventsResponse {
+            cursor: page["cursor"].as_u64().unwrap_or_default(),
+            events: array(&page["events"]).map(event).collect(),
+        }))
+    }
+
+    type EventsStream = ReceiverStream<Result<Event, Status>>;
+
+    async fn events(
+        &self,
+        _: Request<EventsRequest>,
+    ) -> Result<Response<Self::EventsStream>, Status> {
+        let records = self.api()?.subscribe();
+        let (tx, rx) = mpsc::channel(STREAM_BUFFER);
+        // the API hands out blocking receivers; forward from one until the client goes away
+        tokio::task::spawn_blocking(move || {
+            for record in records {
+                if tx.blocking_send(Ok(event(&record))).is_err() {
+                    return;
+                }
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+}
+
+fn array(value: &Value) -> impl Iterator<Item = &Value> {
+    value.as_array().into_iter().flatten()
+}
+
+fn text(value: &Value) -> String {
+    value.as_str().unwrap_or_default().to_string()
+}
+
+fn optional(value: &Value) -> Option<String> {
+    value.as_str().map(str::to_string)
+}
+
+fn group(group: &Value) -> Group {
+    Group {
+        title: text(&group["title"]),
+        root: text(&group["root"]),
+        files: array(&group["files"])
+            .map(|file| File {
+                path: text(&file["path"]),
+                removed: file["removed"].as_bool().unwrap_or_default(),
+            })
+            .collect(),
+        hidden: group["hidden"].as_u64().unwrap_or_default(),
+    }
+}
+
+fn service(service: &Value) -> Service {
+    Service {
+        name: text(&service["name"]),
+        active: service["active"].as_bool(),
+        status: optional(&service["status"]),
+    }
+}
+
+fn event(record: &Value) -> Event {
+    Event {
+        id: record["id"].as_u64().unwrap_or_default(),
+        event: text(&record["event"]),
+        time: text(&record["time"]),
+        group: optional(&record["group"]),
+        path: optional(&record["path"]),
+        service: optional(&record["service"]),
+        status: optional(&record["status"]),
+        rule: optional(&record["rule"]),
+        message: optional(&record["message"]),
+    }
+}
+
+/// Serve the gRPC API on a background thread.
+pub fn serve(addr: SocketAddr, api: Arc<Mutex<Api>>) -> Result<(), Box<dyn std::error::Error>> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    // bind up front so a bad address is reported at startup
+    let listener = runtime
+        .block_on(tokio::net::TcpListener::bind(addr))
+        .map_err(|source| file_task::Error::Listen {
+            address: addr.to_string(),
+            source,
+        })?;
+
+    thread::spawn(move || {
+        let incoming = tokio_stream::wrappers::TcpListenerStream::new(listener);
+        let served = runtime.block_on(
+            tonic::transport::Server::builder()
+                .add_service(FileTaskServer::new(Server { api }))
+                .serve_with_incoming(incoming),
+        );
+        if let Err(e) = served {
+            eprintln!("gRPC server on {} stopped: {}", addr, e);
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::events::Event as FileEvent;
+    use crate::AppState;
+
+    #[test]
+    fn events_after_cursor() {
+        let api = Arc::new(Mutex::new(Api::default()));
+        let added = FileEvent::FileAdded {
+            group: "in".to_string(),
+            path: PathBuf::from("/in/a"),
+        };
+        api.lock()
+            .unwrap()
+            .update(&AppState::default(), &[added.clone(), added]);
+        let server = Server { api };
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+
+        let page = runtime
+            .block_on(server.get_events(Request::new(GetEventsRequest { since: 1 })))
+            .unwrap()
+            .into_inner();
+
+        assert_eq!(page.cursor, 2);
+        assert_eq!(page.events.len(), 1);
+        assert_eq!(page.events[0].event, "added");
+        assert_eq!(page.events[0].path.as_deref(), Some("/in/a"));
+        assert_eq!(page.events[0].service, None);
+        assert!(runtime
+            .block_on(server.get_service(Request::new(GetServiceRequest {})))
+            .is_err());
+        let services = runtime
+            .block_on(server.list_services(Request::new(ListServicesRequest {})))
+            .unwrap()
+            .into_inner();
+        assert!(services.services.is_empty());
+    }
+}
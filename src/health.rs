@@ -0,0 +1,65 @@
+use crate::service::ServiceState;
+
+/// One of the things being monitored, and whether it's as it should be.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    pub name: String,
+    pub healthy: bool,
+    /// What it's at right now, e.g. "active (running)" or "3 files"
+    pub detail: String,
+}
+
+impl Check {
+    pub fn new(name: String, healthy: bool, detail: String) -> Self {
+        Self {
+            name,
+            healthy,
+            detail,
+        }
+    }
+}
+
+/// Whether the service is running, counted as unhealthy until it's been checked.
+pub fn service(service: &ServiceState) -> Check {
+    let (healthy, detail) = match service {
+        ServiceState::Details(details) => (details.active, details.status.trim().to_string()),
+        ServiceState::Unknown(_) => (false, "not checked yet".to_string()),
+    };
+    Check::new(format!("service {}", service.name()), healthy, detail)
+}
+
+/// How many of the checks are healthy, out of how many there are.
+pub fn tally(checks: &[Check]) -> (usize, usize) {
+    let healthy = checks.iter().filter(|check| check.healthy).count();
+    (healthy, checks.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::ServiceDetails;
+
+    #[test]
+    fn tally_checks() {
+        let nginx = ServiceState::Details(ServiceDetails::new(
+            "nginx".to_string(),
+            true,
+            "active (running)\n".to_string(),
+        ));
+        let checks = [
+            service(&nginx),
+            service(&ServiceState::Unknown("cron".to_string())),
+            Check::new("count inbox".to_string(), true, "3 files".to_string()),
+        ];
+        assert_eq!(
+            checks[0],
+            Check::new(
+                "service nginx".to_string(),
+                true,
+                "active (running)".to_string()
+            )
+        );
+        assert!(!checks[1].healthy);
+        assert_eq!(tally(&checks), (2, 3));
+    }
+}
@@ -0,0 +1,105 @@
+use chrono::{DateTime, Duration, NaiveDate, Timelike, Utc};
+
+use crate::timezone::Timezone;
+
+/// How many days back the heatmap goes, including today.
+pub const HEATMAP_DAYS: usize = 14;
+
+/// How many files arrived in a group in each hour of each recent day.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Heatmap {
+    pub title: String,
+    /// The oldest day shown, the first row of counts
+    pub first_day: NaiveDate,
+    pub counts: Vec<[u32; 24]>,
+}
+
+impl Heatmap {
+    /// The busiest hour's count, for scaling the rest against.
+    pub fn most(&self) -> u32 {
+        self.counts
+            .iter()
+            .flatten()
+            .copied()
+            .max()
+            .unwrap_or_default()
+    }
+}
+
+/// The moment the heatmap for the days up to today starts, for fetching the history to fill it.
+pub fn since(today: NaiveDate) -> DateTime<Utc> {
+    // a day more than needed covers the zone being ahead of UTC
+    (today - Duration::days(HEATMAP_DAYS as i64))
+        .and_hms_opt(0, 0, 0)
+        .unwrap_or_default()
+        .and_utc()
+}
+
+/// Count arrivals by the day and hour they happened in a zone, with a heatmap for each of the
+/// titles, in order, even if nothing arrived in it.
+pub fn build(
+    titles: &[&str],
+    arrivals: &[(String, DateTime<Utc>)],
+    timezone: Timezone,
+    today: NaiveDate,
+) -> Vec<Heatmap> {
+    let first_day = today - Duration::days(HEATMAP_DAYS as i64 - 1);
+    let mut heatmaps = titles
+        .iter()
+        .map(|title| Heatmap {
+            title: title.to_string(),
+            first_day,
+            counts: vec![[0; 24]; HEATMAP_DAYS],
+        })
+        .collect::<Vec<_>>();
+    for (title, time) in arrivals {
+        let Some(heatmap) = heatmaps.iter_mut().find(|h| h.title == *title) else {
+            continue;
+        };
+        let local = timezone.local(*time);
+        let day = (local.date() - first_day).num_days();
+        if let Some(hours) = usize::try_from(day)
+            .ok()
+            .and_then(|day| heatmap.counts.get_mut(day))
+        {
+            hours[local.hour() as usize] += 1;
+        }
+    }
+    heatmaps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(time: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(time).unwrap().into()
+    }
+
+    #[test]
+    fn arrivals_by_day_and_hour() {
+        let today = NaiveDate::from_ymd_opt(2024, 5, 14).unwrap();
+        let timezone = "America/New_York".parse().unwrap();
+        let arrivals = [
+            ("nightly".to_string(), at("2024-05-14T06:10:00Z")),
+            ("nightly".to_string(), at("2024-05-14T06:50:00Z")),
+            ("nightly".to_string(), at("2024-05-01T06:00:00Z")),
+            // too long ago, and a group that isn't shown
+            ("nightly".to_string(), at("2024-04-01T06:00:00Z")),
+            ("gone".to_string(), at("2024-05-14T06:00:00Z")),
+        ];
+
+        let heatmaps = build(&["nightly", "quiet"], &arrivals, timezone, today);
+        assert_eq!(heatmaps.len(), 2);
+        let nightly = &heatmaps[0];
+        assert_eq!(
+            nightly.first_day,
+            NaiveDate::from_ymd_opt(2024, 5, 1).unwrap()
+        );
+        // 06:00 UTC is 02:00 in New York
+        assert_eq!(nightly.counts[13][2], 2);
+        assert_eq!(nightly.counts[0][2], 1);
+        assert_eq!(nightly.most(), 2);
+        assert_eq!(heatmaps[1].most(), 0);
+    }
+}
@@ -0,0 +1,106 @@
+use regex::RegexSet;
+use tui::style::{Color, Modifier, Style};
+
+/// Styles for file names matching regular expressions, all matched in one pass.
+#[derive(Debug, Clone)]
+pub struct Highlights {
+    patterns: RegexSet,
+    styles: Vec<Style>,
+}
+
+impl Highlights {
+    /// Rules as pairs of a regex and a style like "bold red on black", later rules winning where
+    /// they overlap.
+    pub fn new<'a>(rules: impl IntoIterator<Item = (&'a str, &'a str)>) -> Result<Self, String> {
+        let (patterns, styles): (Vec<_>, Vec<_>) = rules.into_iter().unzip();
+        let styles = styles
+            .into_iter()
+            .map(parse_style)
+            .collect::<Result<_, _>>()?;
+        let patterns = RegexSet::new(patterns).map_err(|e| e.to_string())?;
+        Ok(Self { patterns, styles })
+    }
+
+    /// The style of every rule matching a name layered over a base style, e.g. one for whether
+    /// the file has been removed.
+    pub fn style(&self, name: &str, base: Style) -> Style {
+        if self.styles.is_empty() {
+            return base;
+        }
+        self.patterns
+            .matches(name)
+            .into_iter()
+            .fold(base, |style, i| style.patch(self.styles[i]))
+    }
+}
+
+impl Default for Highlights {
+    fn default() -> Self {
+        Self {
+            patterns: RegexSet::empty(),
+            styles: vec![],
+        }
+    }
+}
+
+/// Read a style from words: modifiers like "bold" or "underlined", a foreground color, and "on"
+/// followed by a background color. Colors are names like "red" or "lightblue", or "#rrggbb".
+pub fn parse_style(s: &str) -> Result<Style, String> {
+    let mut style = Style::default();
+    let mut words = s.split_whitespace();
+    while let Some(word) = words.next() {
+        style = match word.to_lowercase().as_str() {
+            "bold" => style.add_modifier(Modifier::BOLD),
+            "dim" => style.add_modifier(Modifier::DIM),
+            "italic" => style.add_modifier(Modifier::ITALIC),
+            "underlined" => style.add_modifier(Modifier::UNDERLINED),
+            "reversed" => style.add_modifier(Modifier::REVERSED),
+            "crossed_out" => style.add_modifier(Modifier::CROSSED_OUT),
+            "on" => {
+                let color = words.next().ok_or("\"on\" needs a background color")?;
+                style.bg(parse_color(color)?)
+            }
+            _ => style.fg(parse_color(word)?),
+        };
+    }
+    Ok(style)
+}
+
+fn parse_color(s: &str) -> Result<Color, String> {
+    s.parse()
+        .map_err(|_| format!("unknown color or style \"{}\"", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layered_styles() {
+        let highlights =
+            Highlights::new([("FAIL", "bold red"), (r"\.tmp$", "dim"), ("^x", "on blue")]).unwrap();
+        let base = Style::default().fg(Color::Green);
+
+        assert_eq!(highlights.style("ok.txt", base), base);
+        assert_eq!(
+            highlights.style("report-FAIL.tmp", base),
+            Style::default()
+                .fg(Color::Red)
+                .add_modifier(Modifier::BOLD | Modifier::DIM)
+        );
+        assert_eq!(
+            highlights.style("x", base),
+            Style::default().fg(Color::Green).bg(Color::Blue)
+        );
+    }
+
+    #[test]
+    fn bad_rules() {
+        assert_eq!(
+            parse_style("bold sparkly").unwrap_err(),
+            "unknown color or style \"sparkly\""
+        );
+        assert!(parse_style("red on").is_err());
+        assert!(Highlights::new([("(", "red")]).is_err());
+    }
+}
@@ -0,0 +1,132 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// How a process has a file open.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+impl Access {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Read => "reading",
+            Self::Write => "writing",
+            Self::ReadWrite => "reading and writing",
+        }
+    }
+
+    /// From the flags in a file descriptor's fdinfo, written in octal.
+    fn from_flags(flags: &str) -> Option<Self> {
+        match i32::from_str_radix(flags, 8).ok()? & libc::O_ACCMODE {
+            libc::O_RDONLY => Some(Self::Read),
+            libc::O_WRONLY => Some(Self::Write),
+            _ => Some(Self::ReadWrite),
+        }
+    }
+}
+
+/// A process with a file open, and how far through it that process is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Holder {
+    pub pid: u32,
+    pub command: String,
+    pub access: Access,
+    /// The offset the descriptor is at
+    pub position: u64,
+}
+
+/// The processes with a file open, found like `fuser` does, by looking through each process's
+/// descriptors under `/proc`. Only processes this user is allowed to look at are found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Holders {
+    pub path: PathBuf,
+    pub processes: Vec<Holder>,
+}
+
+impl Holders {
+    pub fn find(path: PathBuf) -> Result<Self, String> {
+        let processes = scan(Path::new("/proc"), &path)
+            .map_err(|e| format!("could not look for {}: {}", path.display(), e))?;
+        Ok(Self { path, processes })
+    }
+}
+
+fn scan(proc: &Path, path: &Path) -> io::Result<Vec<Holder>> {
+    let target = path.canonicalize()?;
+    let mut holders = vec![];
+    for entry in fs::read_dir(proc)? {
+        let entry = entry?;
+        let Some(pid) = entry.file_name().to_str().and_then(|s| s.parse().ok()) else {
+            continue;
+        };
+        // processes come and go, and others' descriptors can't be read
+        let Ok(fds) = fs::read_dir(entry.path().join("fd")) else {
+            continue;
+        };
+        for fd in fds.flatten() {
+            if fs::read_link(fd.path()).ok().as_deref() != Some(&target) {
+                continue;
+            }
+            let info = fs::read_to_string(entry.path().join("fdinfo").join(fd.file_name()))
+                .unwrap_or_default();
+            let field = |name: &str| {
+                info.lines()
+                    .find_map(|line| line.strip_prefix(name))
+                    .map(str::trim)
+            };
+            let command = fs::read_to_string(entry.path().join("comm")).unwrap_or_default();
+            holders.push(Holder {
+                pid,
+                command: command.trim().to_string(),
+                access: field("flags:")
+                    .and_then(Access::from_flags)
+                    .unwrap_or(Access::Read),
+                position: field("pos:")
+                    .and_then(|pos| pos.parse().ok())
+                    .unwrap_or_default(),
+            });
+        }
+    }
+    holders.sort_by_key(|holder| holder.pid);
+    Ok(holders)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::io::Read;
+
+    use super::*;
+
+    #[test]
+    fn find_this_process() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let path = dir.join("feed.csv");
+        fs::write(&path, "a,b\n1,2\n").unwrap();
+
+        assert_eq!(Holders::find(path.clone()).unwrap().processes, []);
+        let mut file = File::open(&path).unwrap();
+        file.read_exact(&mut [0; 4]).unwrap();
+        let holders = Holders::find(path.clone()).unwrap();
+        assert_eq!(
+            holders.processes,
+            [Holder {
+                pid: std::process::id(),
+                command: fs::read_to_string("/proc/self/comm")
+                    .unwrap()
+                    .trim()
+                    .to_string(),
+                access: Access::Read,
+                position: 4,
+            }]
+        );
+        drop(file);
+
+        assert!(Holders::find(dir.join("missing")).is_err());
+    }
+}
@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::config::HooksConfig;
+use crate::events::{Event, EventKind, Receipt, Sink};
+use crate::otlp;
+use crate::watchlist::tokenize;
+
+/// Runs the configured command for each event, a limited number at a time.
+pub struct Hooks {
+    commands: HashMap<EventKind, Vec<String>>,
+    tx: Sender<(Vec<String>, Receipt)>,
+}
+
+impl Hooks {
+    pub fn new(config: &HooksConfig, diagnostics: Sender<String>) -> Result<Self, String> {
+        let commands = config
+            .commands()
+            .map(|(kind, template)| match tokenize(template) {
+                Ok(words) if !words.is_empty() => Ok((kind, words)),
+                Ok(_) => Err(format!("hook for {} is empty", kind.name())),
+                Err(e) => Err(format!("hook for {}: {}", kind.name(), e)),
+            })
+            .collect::<Result<_, _>>()?;
+
+        let (tx, rx) = channel::<(Vec<String>, Receipt)>();
+        let rx = Arc::new(Mutex::new(rx));
+        for _ in 0..config.max_concurrent.max(1) {
+            let rx = Arc::clone(&rx);
+            let diagnostics = diagnostics.clone();
+            thread::spawn(move || worker(&rx, &diagnostics));
+        }
+
+        Ok(Self { commands, tx })
+    }
+}
+
+fn worker(rx: &Mutex<Receiver<(Vec<String>, Receipt)>>, diagnostics: &Sender<String>) {
+    loop {
+        // the lock is only held while waiting, so commands still run in parallel
+        let next = match rx.lock() {
+            Ok(rx) => rx.recv(),
+            Err(_) => return,
+        };
+        let Ok((command, receipt)) = next else {
+            return;
+        };
+        let attributes = vec![("command", command.join(" "))];
+        let ran = otlp::traced("hook", attributes, || run(&command));
+        if let Err(e) = &ran {
+            let _ = diagnostics.send(format!("hook {} failed: {}", command[0], e));
+        }
+        receipt.result(&ran);
+    }
+}
+
+pub fn run(command: &[String]) -> Result<(), String> {
+    let status = Command::new(&command[0])
+        .args(&command[1..])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map_err(|e| e.to_string())?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(status.to_string())
+    }
+}
+
+impl Sink for Hooks {
+    fn send(&mut self, event: &Event, receipt: Receipt) {
+        if let Some(template) = self.commands.get(&event.kind()) {
+            let command = template.iter().map(|word| event.expand(word)).collect();
+            let _ = self.tx.send((command, receipt));
+        }
+    }
+
+    fn name(&self) -> String {
+        "hooks".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn expand_placeholders() {
+        let template = tokenize("process-file {path} \"{group} {event}\" {service}").unwrap();
+        let event = Event::FileAdded {
+            group: "Inbox".to_string(),
+            path: PathBuf::from("/in/a b.pdf"),
+        };
+        assert_eq!(
+            template.iter().map(|w| event.expand(w)).collect::<Vec<_>>(),
+            vec!["process-file", "/in/a b.pdf", "Inbox added", ""]
+        );
+    }
+}
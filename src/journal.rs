@@ -0,0 +1,275 @@
+use std::collections::VecDeque;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use file_task::service::{journal_lines, JournalFrom, JournalLine};
+use regex::Regex;
+
+use crate::config::JournalAlert;
+use crate::events::Event;
+use crate::health::Check;
+use crate::reactor::Waker;
+
+/// How often the services' journals are read.
+const CHECK_EVERY: Duration = Duration::from_secs(10);
+
+/// How long a matching line counts against its service's health.
+const RECENT: Duration = Duration::from_secs(5 * 60);
+
+/// Syslog priority names, most severe first.
+const PRIORITIES: [&str; 8] = [
+    "emerg", "alert", "crit", "err", "warning", "notice", "info", "debug",
+];
+
+/// The priority a name like "err" stands for.
+pub fn priority(name: &str) -> Option<u8> {
+    PRIORITIES
+        .iter()
+        .position(|priority| *priority == name)
+        .map(|priority| priority as u8)
+}
+
+/// A pattern the services' journals are checked against, at a priority or more severe.
+#[derive(Debug)]
+pub struct JournalRule {
+    pattern: Regex,
+    pub priority: u8,
+}
+
+impl JournalRule {
+    /// The rule, unless its pattern or priority is invalid, which is reported when the config is
+    /// loaded.
+    pub fn new(config: &JournalAlert) -> Option<Self> {
+        Some(Self {
+            pattern: Regex::new(config.pattern.get_ref()).ok()?,
+            priority: match &config.priority {
+                Some(name) => priority(name.get_ref())?,
+                None => 3,
+            },
+        })
+    }
+
+    fn is_match(&self, line: &JournalLine) -> bool {
+        line.priority <= self.priority && self.pattern.is_match(&line.message)
+    }
+}
+
+/// Reads the services' journals for lines matching the `[[alerts.journal]]` rules, so a service
+/// that's active but failing on everything it does gets noticed. journalctl is run in the
+/// background so it never holds up the dashboard.
+pub struct Journal {
+    rules: Vec<JournalRule>,
+    units: Sender<Option<(Vec<String>, u8)>>,
+    found: Receiver<(String, Vec<JournalLine>)>,
+    /// The units being read and the least severe priority wanted from them, if any
+    watched: Option<(Vec<String>, u8)>,
+    /// How many lines each unit matched at each recent read
+    matched: VecDeque<(Instant, String, usize)>,
+}
+
+impl Journal {
+    pub fn new(rules: Vec<JournalRule>, waker: Waker) -> Self {
+        let (units, rx) = channel::<Option<(Vec<String>, u8)>>();
+        let (tx, found) = channel();
+        thread::spawn(move || {
+            // each unit with where to pick up reading it
+            let mut watched: Vec<(String, JournalFrom)> = vec![];
+            let mut priority = 0;
+            loop {
+                match rx.recv_timeout(CHECK_EVERY) {
+                    Ok(units) => {
+                        let (units, least_severe) = units.unwrap_or_default();
+                        watched = units.into_iter().map(|unit| (unit, since_now())).collect();
+                        priority = least_severe;
+                    }
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+                for (unit, from) in watched.iter_mut() {
+                    // without journald there's nothing to read
+                    let Ok((lines, cursor)) = journal_lines(unit, priority, from) else {
+                        continue;
+                    };
+                    if let Some(cursor) = cursor {
+                        *from = JournalFrom::After(cursor);
+                    }
+                    if lines.is_empty() {
+                        continue;
+                    }
+                    if tx.send((unit.clone(), lines)).is_err() {
+                        return;
+                    }
+                    waker.wake();
+                }
+            }
+        });
+        Self {
+            rules,
+            units,
+            found,
+            watched: None,
+            matched: VecDeque::new(),
+        }
+    }
+
+    /// Use new rules, as after the config is reloaded.
+    pub fn set_rules(&mut self, rules: Vec<JournalRule>) {
+        self.rules = rules;
+    }
+
+    /// Start reading other units' journals if the services have changed, and raise an alert for
+    /// each rule that lines a unit logged since the last call match.
+    pub fn update(&mut self, services: &[&str]) -> Vec<Event> {
+        let least_severe = self.rules.iter().map(|rule| rule.priority).max();
+        let watched = least_severe
+            .filter(|_| !services.is_empty())
+            .map(|priority| (services.iter().map(|s| s.to_string()).collect(), priority));
+        if watched != self.watched {
+            let _ = self.units.send(watched.clone());
+            self.watched = watched;
+            self.matched.clear();
+        }
+
+        let mut alerts = vec![];
+        for (unit, lines) in self.found.try_iter() {
+            let mut matched = 0;
+            for rule in &self.rules {
+                let matching = lines
+                    .iter()
+                    .filter(|line| rule.is_match(line))
+                    .collect::<Vec<_>>();
+                let Some(last) = matching.last() else {
+                    continue;
+                };
+                matched += matching.len();
+                let message = if matching.len() == 1 {
+                    format!("{} logged: {}", unit, last.message.trim())
+                } else {
+                    format!(
+                        "{} logged {} lines matching {}, the last: {}",
+                        unit,
+                        matching.len(),
+                        rule.pattern,
+                        last.message.trim()
+                    )
+                };
+                alerts.push(Event::Alert {
+                    rule: "journal".to_string(),
+                    path: None,
+                    message,
+                });
+            }
+            if matched > 0 {
+                self.matched.push_back((Instant::now(), unit, matched));
+            }
+        }
+        while self
+            .matched
+            .front()
+            .is_some_and(|(at, _, _)| at.elapsed() > RECENT)
+        {
+            self.matched.pop_front();
+        }
+        alerts
+    }
+
+    /// Whether each service whose journal is being read has logged anything matching lately.
+    pub fn health(&self) -> Vec<Check> {
+        let Some((units, _)) = &self.watched else {
+            return vec![];
+        };
+        units
+            .iter()
+            .map(|unit| {
+                let matched: usize = self
+                    .matched
+                    .iter()
+                    .filter(|(_, logged, _)| logged == unit)
+                    .map(|(_, _, lines)| lines)
+                    .sum();
+                let detail = if matched == 0 {
+                    "nothing matching logged".to_string()
+                } else {
+                    format!(
+                        "{} matching lines in the last {}",
+                        matched,
+                        humantime::format_duration(RECENT)
+                    )
+                };
+                Check::new(format!("journal {}", unit), matched == 0, detail)
+            })
+            .collect()
+    }
+}
+
+fn since_now() -> JournalFrom {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    JournalFrom::Since(now.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alert_on_matching_lines() {
+        let (units, requested) = channel();
+        let (tx, found) = channel();
+        let rule = |pattern, priority| JournalRule {
+            pattern: Regex::new(pattern).unwrap(),
+            priority,
+        };
+        let mut journal = Journal {
+            rules: vec![rule("failed to process", 3), rule("retrying", 4)],
+            units,
+            found,
+            watched: None,
+            matched: VecDeque::new(),
+        };
+        let line = |priority, message: &str| JournalLine {
+            priority,
+            message: message.to_string(),
+        };
+        let messages = |alerts: Vec<Event>| {
+            alerts
+                .into_iter()
+                .map(|alert| alert.summary())
+                .collect::<Vec<_>>()
+        };
+
+        let units = ["ingest.service", "upload.service"];
+        assert!(journal.update(&[]).is_empty());
+        assert!(journal.health().is_empty());
+        assert!(journal.update(&units).is_empty());
+        assert_eq!(
+            requested.try_iter().last(),
+            Some(Some((units.map(str::to_string).to_vec(), 4)))
+        );
+        assert!(journal.health().iter().all(|check| check.healthy));
+
+        tx.send((
+            "ingest.service".to_string(),
+            vec![
+                line(3, "failed to process a.csv\n"),
+                line(6, "retrying b.csv"),
+                line(3, "failed to process b.csv"),
+                line(4, "retrying c.csv"),
+            ],
+        ))
+        .unwrap();
+        assert_eq!(
+            messages(journal.update(&units)),
+            [
+                "ingest.service logged 2 lines matching failed to process, the last: failed to process b.csv",
+                "ingest.service logged: retrying c.csv",
+            ]
+        );
+        let health = journal.health();
+        assert!(!health[0].healthy);
+        assert_eq!(health[0].detail, "3 matching lines in the last 5m");
+        assert!(health[1].healthy);
+    }
+}
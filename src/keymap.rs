@@ -0,0 +1,364 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Quit,
+    SaveSession,
+    Export,
+    Pause,
+    Rescan,
+    Suspend,
+    ClearRemoved,
+    ClearGroup,
+    Undo,
+    FilterTag,
+    Tag,
+    Untag,
+    EditNote,
+    Follow,
+    Peek,
+    Stats,
+    Heatmap,
+    Extensions,
+    Holders,
+    Notifications,
+    Settings,
+    Debug,
+    Health,
+    Down,
+    Up,
+    PageDown,
+    PageUp,
+    NextGroup,
+    Columns,
+    Sort,
+}
+
+impl Action {
+    pub const ALL: &'static [Action] = &[
+        Action::Quit,
+        Action::SaveSession,
+        Action::Export,
+        Action::Pause,
+        Action::Rescan,
+        Action::Suspend,
+        Action::ClearRemoved,
+        Action::ClearGroup,
+        Action::Undo,
+        Action::FilterTag,
+        Action::Tag,
+        Action::Untag,
+        Action::EditNote,
+        Action::Follow,
+        Action::Peek,
+        Action::Stats,
+        Action::Heatmap,
+        Action::Extensions,
+        Action::Holders,
+        Action::Notifications,
+        Action::Settings,
+        Action::Debug,
+        Action::Health,
+        Action::Down,
+        Action::Up,
+        Action::PageDown,
+        Action::PageUp,
+        Action::NextGroup,
+        Action::Columns,
+        Action::Sort,
+    ];
+
+    /// Name used for this action in the `[keys]` config table
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Quit => "quit",
+            Self::SaveSession => "save",
+            Self::Export => "export",
+            Self::Pause => "pause",
+            Self::Rescan => "rescan",
+            Self::Suspend => "suspend",
+            Self::ClearRemoved => "clear",
+            Self::ClearGroup => "clear_group",
+            Self::Undo => "undo",
+            Self::FilterTag => "filter",
+            Self::Tag => "tag",
+            Self::Untag => "untag",
+            Self::EditNote => "note",
+            Self::Follow => "follow",
+            Self::Peek => "peek",
+            Self::Stats => "stats",
+            Self::Heatmap => "heatmap",
+            Self::Extensions => "extensions",
+            Self::Holders => "holders",
+            Self::Notifications => "alerts",
+            Self::Settings => "settings",
+            Self::Debug => "debug",
+            Self::Health => "health",
+            Self::Down => "down",
+            Self::Up => "up",
+            Self::PageDown => "page_down",
+            Self::PageUp => "page_up",
+            Self::NextGroup => "next_group",
+            Self::Columns => "columns",
+            Self::Sort => "sort",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::Quit => "Quit",
+            Self::SaveSession => "Save the current settings to the config file",
+            Self::Export => "Export the watched files as CSV to the current directory",
+            Self::Pause => "Pause applying file and service changes, or resume",
+            Self::Rescan => "Pick up any changes the watcher missed",
+            Self::Suspend => "Suspend to the shell, until resumed with fg",
+            Self::ClearRemoved => "Stop showing removed files now instead of when they expire",
+            Self::ClearGroup => "Stop showing the focused group's removed files now",
+            Self::Undo => "Undo the most recent move, copy, tag, or trashing done by an action",
+            Self::FilterTag => "Show only files with the next tag, cycling back round to all files",
+            Self::Tag => "Give the selected file a tag",
+            Self::Untag => "Take a tag away from the selected file",
+            Self::EditNote => "Edit the note shown under a group's title, saved with the profile",
+            Self::Follow => "Show lines as they're written to the selected or newest file, or stop",
+            Self::Peek => {
+                "List what's inside the selected or newest .zip, .tar, or .tar.gz file, or close it"
+            }
+            Self::Stats => {
+                "Show how big the files in each group are instead of the files, or go back"
+            }
+            Self::Heatmap => "Show when files arrived in each group by day and hour, or go back",
+            Self::Extensions => "Switch the stats between file sizes and a breakdown by extension",
+            Self::Holders => {
+                "List the processes that have the selected or newest file open, or close the list"
+            }
+            Self::Notifications => {
+                "Show the alerts raised and what each sink did with them, or go back"
+            }
+            Self::Settings => "Look at and change the settings in the config file",
+            Self::Debug => {
+                "Show the dashboard's internals over it, e.g. to see why it lags, or hide them"
+            }
+            Self::Health => "List each check summed up in the status bar and whether it's healthy",
+            Self::Down => "Select the next file in the group, scrolling it into view",
+            Self::Up => "Select the previous file in the group, scrolling it into view",
+            Self::PageDown => "Move the selection down a page of the group",
+            Self::PageUp => "Move the selection up a page of the group",
+            Self::NextGroup => "Move the selection to the next group",
+            Self::Columns => "Show or hide each file's size, modified time, and age",
+            Self::Sort => "List the focused group's files by name, newest, largest, or as found",
+        }
+    }
+
+    /// The keys bound to the action unless the `[keys]` table binds another.
+    pub fn default_keys(&self) -> Vec<Key> {
+        match self {
+            Self::Quit => vec![Key::char('q')],
+            Self::SaveSession => vec![Key::char('S')],
+            Self::Export => vec![Key::char('E')],
+            Self::Pause => vec![Key::char('p')],
+            Self::Rescan => vec![Key::char('r')],
+            Self::Suspend => vec![Key::ctrl('z')],
+            Self::ClearRemoved => vec![Key::char('c')],
+            Self::ClearGroup => vec![Key::char('C')],
+            Self::Undo => vec![Key::char('u')],
+            Self::FilterTag => vec![Key::char('t')],
+            Self::Tag => vec![Key::char('T')],
+            Self::Untag => vec![Key::char('U')],
+            Self::EditNote => vec![Key::char('n')],
+            Self::Follow => vec![Key::char('f')],
+            Self::Peek => vec![Key::char('a')],
+            Self::Stats => vec![Key::char('s')],
+            Self::Heatmap => vec![Key::char('h')],
+            Self::Extensions => vec![Key::char('x')],
+            Self::Holders => vec![Key::char('o')],
+            Self::Notifications => vec![Key::char('N')],
+            Self::Settings => vec![Key::char(',')],
+            Self::Debug => vec![Key::char('D')],
+            Self::Health => vec![Key::char('H')],
+            Self::Down => vec![Key::char('j'), Key::code(KeyCode::Down)],
+            Self::Up => vec![Key::char('k'), Key::code(KeyCode::Up)],
+            Self::PageDown => vec![Key::code(KeyCode::PageDown)],
+            Self::PageUp => vec![Key::code(KeyCode::PageUp)],
+            Self::NextGroup => vec![Key::code(KeyCode::Tab)],
+            Self::Columns => vec![Key::char('i')],
+            Self::Sort => vec![Key::char('O')],
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        Self::ALL.iter().copied().find(|a| a.name() == name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Key {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl Key {
+    const fn code(code: KeyCode) -> Self {
+        Self {
+            code,
+            modifiers: KeyModifiers::NONE,
+        }
+    }
+
+    const fn char(c: char) -> Self {
+        Self {
+            code: KeyCode::Char(c),
+            modifiers: KeyModifiers::NONE,
+        }
+    }
+
+    const fn ctrl(c: char) -> Self {
+        Self {
+            code: KeyCode::Char(c),
+            modifiers: KeyModifiers::CONTROL,
+        }
+    }
+}
+
+impl From<KeyEvent> for Key {
+    fn from(event: KeyEvent) -> Self {
+        // shift is already reflected in the character for printable keys
+        let modifiers = match event.code {
+            KeyCode::Char(_) => event.modifiers - KeyModifiers::SHIFT,
+            _ => event.modifiers,
+        };
+        Self {
+            code: event.code,
+            modifiers,
+        }
+    }
+}
+
+impl From<Key> for KeyEvent {
+    fn from(key: Key) -> Self {
+        KeyEvent::new(key.code, key.modifiers)
+    }
+}
+
+const NAMED_KEYS: &[(&str, KeyCode)] = &[
+    ("tab", KeyCode::Tab),
+    ("backtab", KeyCode::BackTab),
+    ("enter", KeyCode::Enter),
+    ("esc", KeyCode::Esc),
+    ("space", KeyCode::Char(' ')),
+    ("backspace", KeyCode::Backspace),
+    ("delete", KeyCode::Delete),
+    ("up", KeyCode::Up),
+    ("down", KeyCode::Down),
+    ("left", KeyCode::Left),
+    ("right", KeyCode::Right),
+    ("home", KeyCode::Home),
+    ("end", KeyCode::End),
+    ("pageup", KeyCode::PageUp),
+    ("pagedown", KeyCode::PageDown),
+];
+
+impl FromStr for Key {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (modifiers, name) = match s.strip_prefix("ctrl-") {
+            Some(rest) => (KeyModifiers::CONTROL, rest),
+            None => (KeyModifiers::NONE, s),
+        };
+
+        let mut chars = name.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => NAMED_KEYS
+                .iter()
+                .find(|(n, _)| n.eq_ignore_ascii_case(name))
+                .map(|(_, code)| *code)
+                .ok_or_else(|| format!("unknown key \"{}\"", s))?,
+        };
+
+        Ok(Self { code, modifiers })
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "ctrl-")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            code => match NAMED_KEYS.iter().find(|(_, c)| *c == code) {
+                Some((name, _)) => write!(f, "{}", name),
+                None => write!(f, "{:?}", code),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct KeyMap {
+    bindings: HashMap<Key, Action>,
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        Self {
+            bindings: Action::ALL
+                .iter()
+                .flat_map(|a| a.default_keys().into_iter().map(|key| (key, *a)))
+                .collect(),
+        }
+    }
+}
+
+impl KeyMap {
+    /// Build a keymap from the defaults with any overrides applied on top.
+    pub fn with_overrides(overrides: impl IntoIterator<Item = (Action, Key)>) -> Self {
+        let mut keymap = Self::default();
+        for (action, key) in overrides {
+            keymap.bindings.retain(|_, a| *a != action);
+            keymap.bindings.insert(key, action);
+        }
+        keymap
+    }
+
+    pub fn action(&self, key: Key) -> Option<Action> {
+        self.bindings.get(&key).copied()
+    }
+
+    /// Every key that's bound, with its action.
+    pub fn bindings(&self) -> impl Iterator<Item = (Key, Action)> + '_ {
+        self.bindings.iter().map(|(key, action)| (*key, *action))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_round_trip() {
+        for name in ["q", "ctrl-c", "tab", "space", "pagedown", "G"] {
+            let key = name.parse::<Key>().unwrap();
+            assert_eq!(key.to_string(), name);
+        }
+    }
+
+    #[test]
+    fn key_unknown() {
+        assert!("nope".parse::<Key>().is_err());
+        assert!("ctrl-".parse::<Key>().is_err());
+    }
+
+    #[test]
+    fn keymap_override_replaces_default() {
+        let keymap = KeyMap::with_overrides([(Action::Quit, Key::char('x'))]);
+        assert_eq!(keymap.action(Key::char('x')), Some(Action::Quit));
+        assert_eq!(keymap.action(Key::char('q')), None);
+    }
+}
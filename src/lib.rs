@@ -0,0 +1,18 @@
+//! Tracking the files in a set of directories and the status of a service, as used by the
+//! `file_task` dashboard, for embedding elsewhere.
+//!
+//! Scan directories into [`filesystem::FileGroup`]s with [`filesystem::get_initial_state`],
+//! start watching them with [`filesystem::watch_groups`], and fold the changes the watcher
+//! sends into the groups with [`filesystem::update_file_items`].
+
+use std::time::Duration;
+
+pub use error::{Error, Result};
+
+pub mod clock;
+mod error;
+pub mod filesystem;
+pub mod service;
+
+/// How long a removed file is still shown for, unless asked otherwise.
+pub const DELETED_RETENTION: Duration = Duration::from_secs(60 * 60 * 24); // one day
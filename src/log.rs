@@ -0,0 +1,208 @@
+use std::env;
+use std::io::{self, Write};
+use std::os::unix::net::UnixDatagram;
+
+use chrono::NaiveDateTime;
+use clap::ValueEnum;
+
+use crate::events::{Event, Receipt, Sink};
+use crate::reactor::{Signal, Wake, Waker};
+use crate::timezone::Timezone;
+
+const JOURNAL_SOCKET: &str = "/run/systemd/journal/socket";
+
+/// Syslog priorities, as used by journald.
+#[derive(Debug, Clone, Copy)]
+pub enum Priority {
+    Warning = 4,
+    Notice = 5,
+    Info = 6,
+}
+
+/// How events are printed to stdout when asked for with --no-tui or --output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Output {
+    /// Lines starting with the time
+    Text,
+    /// An object per line, with the same fields as webhooks get
+    Json,
+}
+
+/// Where daemon mode reports events and messages: journald when running under systemd, with
+/// each event's details as separate fields, or plain lines on stderr otherwise. With --no-tui
+/// or --output they're printed to stdout instead, for whatever it's piped into, stopping the
+/// loop through `Waker` once that goes away.
+pub enum Logger {
+    Journal(UnixDatagram),
+    Stderr,
+    Stdout(Timezone, Waker),
+    /// Events as JSON on stdout, with other messages going to stderr to keep it parseable
+    Json(Waker),
+}
+
+impl Logger {
+    /// Where events go in daemon mode, or printed to stdout in the given format.
+    pub fn new(output: Option<Output>, timezone: Timezone, waker: Waker) -> Self {
+        match output {
+            Some(Output::Text) => return Self::Stdout(timezone, waker),
+            Some(Output::Json) => return Self::Json(waker),
+            None => {}
+        }
+        // systemd sets this when stderr is connected to the journal
+        if env::var_os("JOURNAL_STREAM").is_none() {
+            return Self::Stderr;
+        }
+        UnixDatagram::unbound()
+            .and_then(|socket| socket.connect(JOURNAL_SOCKET).map(|()| socket))
+            .map_or(Self::Stderr, Self::Journal)
+    }
+
+    pub fn message(&self, message: &str) {
+        self.log(Priority::Notice, message, &[]);
+    }
+
+    fn log(&self, priority: Priority, message: &str, fields: &[(&str, &str)]) {
+        match self {
+            Self::Journal(socket) => {
+                let priority = (priority as u8).to_string();
+                let mut entry = vec![
+                    ("MESSAGE", message),
+                    ("PRIORITY", priority.as_str()),
+                    ("SYSLOG_IDENTIFIER", "file_task"),
+                ];
+                entry.extend_from_slice(fields);
+                if socket.send(&encode(&entry)).is_err() {
+                    eprintln!("{}", message);
+                }
+            }
+            Self::Stderr | Self::Json(_) => eprintln!("{}", message),
+            Self::Stdout(timezone, waker) => print(waker, &timestamped(timezone.now(), message)),
+        }
+    }
+}
+
+/// Print a line to stdout, stopping when whatever it's piped into has closed it, e.g. `head`,
+/// rather than panicking the way `println!` does.
+fn print(waker: &Waker, line: &str) {
+    match writeln!(io::stdout().lock(), "{}", line) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => {
+            waker.send(Wake::Signal(Signal::Stop));
+        }
+        Err(e) => eprintln!("could not write to stdout: {}", e),
+    }
+}
+
+/// An event as one line of JSON, for --output json.
+fn json_line(event: &Event) -> serde_json::Result<String> {
+    serde_json::to_string(&event.payload())
+}
+
+fn timestamped(time: NaiveDateTime, message: &str) -> String {
+    format!("{} {}", time.format("%Y-%m-%d %H:%M:%S"), message)
+}
+
+impl Sink for Logger {
+    fn send(&mut self, event: &Event, receipt: Receipt) {
+        if let Self::Json(waker) = self {
+            match json_line(event) {
+                Ok(json) => print(waker, &json),
+                Err(e) => eprintln!("could not write {} as JSON: {}", event.summary(), e),
+            }
+            receipt.sent();
+            return;
+        }
+        let payload = event.payload();
+        let path = payload.path.map(|p| p.to_string_lossy().into_owned());
+        let fields = [
+            ("FILE_TASK_EVENT", Some(event.kind().name())),
+            ("FILE_TASK_GROUP", payload.group),
+            ("FILE_TASK_PATH", path.as_deref()),
+            ("FILE_TASK_SERVICE", payload.service),
+            ("FILE_TASK_STATUS", payload.status),
+            ("FILE_TASK_RULE", payload.rule),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| Some((name, value?)))
+        .collect::<Vec<_>>();
+
+        let priority = match event {
+            Event::Alert { .. } => Priority::Warning,
+            _ => Priority::Info,
+        };
+        self.log(priority, &event.summary(), &fields);
+        // anything the journal doesn't take goes to stderr instead
+        receipt.sent();
+    }
+
+    fn name(&self) -> String {
+        match self {
+            Self::Journal(_) => "journal".to_string(),
+            Self::Stderr => "stderr".to_string(),
+            Self::Stdout(..) | Self::Json(_) => "stdout".to_string(),
+        }
+    }
+}
+
+/// The journal's native protocol: `NAME=value` lines, with a length-prefixed form for values
+/// that contain newlines.
+fn encode(fields: &[(&str, &str)]) -> Vec<u8> {
+    let mut out = vec![];
+    for (name, value) in fields {
+        out.extend_from_slice(name.as_bytes());
+        if value.contains('\n') {
+            out.push(b'\n');
+            out.extend_from_slice(&(value.len() as u64).to_le_bytes());
+        } else {
+            out.push(b'=');
+        }
+        out.extend_from_slice(value.as_bytes());
+        out.push(b'\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+
+    use super::*;
+
+    #[test]
+    fn encode_fields() {
+        assert_eq!(
+            encode(&[("MESSAGE", "a: added x"), ("FILE_TASK_PATH", "two\nlines")]),
+            b"MESSAGE=a: added x\nFILE_TASK_PATH\n\x09\0\0\0\0\0\0\0two\nlines\n"
+        );
+    }
+
+    #[test]
+    fn timestamped_lines() {
+        let time =
+            NaiveDateTime::parse_from_str("2024-06-01 09:41:05", "%Y-%m-%d %H:%M:%S").unwrap();
+        assert_eq!(
+            timestamped(time, "Inbox: added a.csv"),
+            "2024-06-01 09:41:05 Inbox: added a.csv"
+        );
+    }
+
+    #[test]
+    fn json_lines() {
+        let event = Event::FileAdded {
+            group: "Inbox".to_string(),
+            path: "/srv/inbox/a\nb.csv".into(),
+        };
+        let line = json_line(&event).unwrap();
+        assert!(!line.contains('\n'));
+        let json = serde_json::from_str::<serde_json::Value>(&line).unwrap();
+        let fields = json.as_object().unwrap();
+        itertools::assert_equal(fields.keys(), ["event", "group", "path", "time"]);
+        assert_eq!(json["event"], "added");
+        assert_eq!(json["group"], "Inbox");
+        assert_eq!(json["path"], "/srv/inbox/a\nb.csv");
+        assert!(json["time"]
+            .as_str()
+            .and_then(|time| DateTime::parse_from_rfc3339(time).ok())
+            .is_some());
+    }
+}
@@ -1,48 +1,302 @@
-mod filesystem;
-mod service;
+mod actions;
+mod alerts;
+mod api;
+mod app;
+mod archive;
+mod attach;
+mod attribution;
+mod bar;
+mod brief;
+mod checksum;
+mod cli;
+mod compare;
+mod config;
+mod control;
+mod daemon;
+mod dbus;
+mod debug;
+mod dirs;
+mod disk;
+mod email;
+mod eventlog;
+mod events;
+mod export;
+mod fifo;
+mod follow;
+mod grpc;
+mod health;
+mod heatmap;
+mod highlight;
+mod holders;
+mod hooks;
+mod journal;
+mod keymap;
+mod log;
+mod manpage;
+mod metadata;
+mod metrics;
+mod mounts;
+mod mqtt;
+mod notifications;
+mod notifier;
+mod otlp;
+mod probe;
+mod reactor;
+mod record;
+mod recovery;
+mod script;
+mod settings;
+mod settling;
+mod signature;
+mod snapshot;
+mod stats;
+mod store;
+mod syslog;
+mod systemd;
 mod terminal;
+mod timezone;
+mod view;
+mod watchlist;
+mod webhook;
+mod wizard;
 
 use std::{
+    collections::{HashMap, HashSet},
+    io,
     path::{Path, PathBuf},
-    sync::mpsc::{channel, Receiver},
-    time::Duration,
+    process::ExitCode,
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant, SystemTime},
 };
 
-use chrono::Local;
-use clap::Parser;
-
-use filesystem::{get_initial_state, update_file_items, FileGroup};
-use service::{update_service_status, ServiceState};
-use tui::{
-    backend::Backend,
-    layout::{Constraint, Layout},
-    style::{Color, Modifier, Style},
-    text::{Line, Span},
-    widgets::{Block, Borders, List, ListItem, Paragraph},
-    Frame, Terminal,
+use file_task::clock::{Clock, SystemClock};
+use file_task::{filesystem, service};
+
+use actions::Actions;
+use alerts::Alerts;
+use api::Api;
+use app::{Cmd, Msg, NoteInput, TagInput};
+use archive::Peek;
+use attribution::Attribution;
+use bar::BarFormat;
+use checksum::Checksums;
+use cli::{BarArgs, Cli, Command, ConfigCommand, WatchArgs};
+use compare::{Comparisons, Lag};
+use config::ConfigFile;
+use control::{Command as ControlCommand, ControlSocket};
+use debug::Internals;
+use disk::{Disks, Usage};
+use email::Email;
+use eventlog::EventLog;
+use events::{file_events, service_event, Deliveries, Delivery, Event, EventFilter, Receipt, Sink};
+use filesystem::{
+    apply_file_changes, get_initial_state, sync_groups, FileChange, FileGroup, FileItem, Limits,
+    Sort, WatchPath, WatchSource,
 };
+use follow::Follow;
+use health::Check;
+use heatmap::Heatmap;
+use highlight::Highlights;
+use holders::Holders;
+use hooks::Hooks;
+use journal::Journal;
+use keymap::KeyMap;
+use log::Logger;
+use metadata::Metadata;
+use metrics::Metrics;
+use mounts::Mounts;
+use mqtt::MqttPublisher;
+use notifications::Notifications;
+use notifier::DesktopNotifier;
+use reactor::{Reactor, Signal, Wake};
+use record::{Playback, Recorder, Recording};
+use recovery::{Checkpoints, Recovery};
+use script::Script;
+use service::ServiceState;
+use settings::SettingsEditor;
+use signature::Signatures;
+use snapshot::Snapshot;
+use stats::GroupStats;
+use store::Store;
+use tui::{backend::Backend, widgets::ListState, Terminal};
 
-use service::ServiceDetails;
+use timezone::Timezone;
+use view::Column;
+use watchlist::{watch_paths, with_listed, Watchlist};
+use webhook::Webhook;
 
-const DELETED_RETENTION: Duration = Duration::from_secs(60 * 60 * 24); // one day
-const INPUT_POLL: Duration = Duration::from_secs(5);
+/// How often to check the services when systemd can't be asked over D-Bus, and the watch list if
+/// there is one
+const SERVICE_POLL: Duration = Duration::from_secs(5);
 
-#[derive(Parser, Debug)]
-#[clap(author, version, about)]
-struct Args {
-    /// Paths to watch
-    #[clap(required = true)]
-    paths: Vec<PathBuf>,
+#[derive(Debug, Default)]
+struct AppState {
+    file_groups: Vec<FileGroup>,
+    services: Vec<ServiceState>,
+    /// Feedback from the last action, shown in the status bar
+    message: Option<String>,
+    timezone: Timezone,
+    /// Whether changes are being held back, from the control socket
+    paused: bool,
+    /// Only files with this tag are shown
+    tag_filter: Option<String>,
+    /// The group the selection keys move in
+    focus: usize,
+    /// The selected file and how far the list is scrolled in each group, by root
+    selections: HashMap<PathBuf, ListState>,
+    /// How many files fit in the focused group when it was last drawn, to page by
+    page: usize,
+    /// What's shown about each file before its name, when shown
+    columns: Vec<Column>,
+    /// Whether the columns are shown
+    show_columns: bool,
+    /// The order files are listed in
+    sort: Sort,
+    /// Groups listed in another order, by root
+    sorts: HashMap<PathBuf, Sort>,
+    /// A group's note while it's being edited
+    note_input: Option<NoteInput>,
+    /// A tag for the selected file while it's being typed
+    tag_input: Option<TagInput>,
+    /// Styles for file names, from the config
+    highlights: Highlights,
+    /// Roots of the groups with more or fewer files than expected
+    breached: HashSet<PathBuf>,
+    /// Files without a match in the group they're compared with
+    out_of_sync: HashSet<PathBuf>,
+    /// How far behind each compared mirror is
+    lags: Vec<Lag>,
+    /// Room left on each group's filesystem
+    disks: Vec<Usage>,
+    /// Groups on filesystems running low, with what's low
+    low_space: Vec<(String, String)>,
+    /// Roots of groups whose filesystem isn't mounted, with its mount unit
+    unmounted: HashMap<PathBuf, String>,
+    /// A file whose new lines are shown as they're written
+    follow: Option<Follow>,
+    /// Styles for the lines of a followed file, from the config
+    follow_highlights: Highlights,
+    /// The contents of an archive, while they're being looked at
+    peek: Option<Peek>,
+    /// The processes with a file open, while they're being looked at
+    holders: Option<Holders>,
+    /// File sizes in each group, shown in place of the groups while they're being looked at
+    stats: Option<Vec<GroupStats>>,
+    /// Whether the stats show extensions instead of sizes
+    by_extension: bool,
+    /// When files have been arriving in each group, from the history, while it's being looked at
+    heatmap: Option<Vec<Heatmap>>,
+    /// The alerts raised and what each sink did with them
+    notifications: Notifications,
+    /// Whether the alerts are shown in place of the groups
+    show_notifications: bool,
+    /// The config's settings, while they're being looked at or changed
+    settings: Option<SettingsEditor>,
+    /// Whether the dashboard's internals are shown over it
+    show_debug: bool,
+    internals: Internals,
+    /// Each of the things being monitored, and whether it's healthy
+    health: Vec<Check>,
+    /// Whether the checks are listed
+    show_health: bool,
+}
 
-    /// Systemd service to monitor
-    #[clap(long)]
-    service: Option<String>,
+/// How watching is presented.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Dashboard,
+    Daemon,
+    Bar(BarFormat),
 }
 
-#[derive(Debug)]
-struct AppState {
-    file_groups: Vec<FileGroup>,
-    service: Option<ServiceState>,
+/// Everything the dashboard needs to know about how it was started.
+struct Session {
+    keymap: KeyMap,
+    sinks: Vec<Box<dyn Sink>>,
+    alerts: Alerts,
+    /// Things done automatically with new files, if any are configured
+    actions: Option<Actions>,
+    /// New files being checked against their sidecar checksums
+    checksums: Checksums,
+    /// New files having their signatures checked, if a keyring is configured
+    signatures: Option<Signatures>,
+    /// Groups that should hold the same files
+    comparisons: Comparisons,
+    /// Free space and inodes on the groups' filesystems
+    disks: Disks,
+    /// Files' sizes and modified times, read in the background once they're wanted
+    metadata: Metadata,
+    /// The mount units the groups' roots are on, unless playing back
+    mounts: Option<Mounts>,
+    /// The services' journals, read for lines that should raise alerts, unless playing back
+    journal: Option<Journal>,
+    /// Which process wrote each new file, with --attribute
+    attribution: Option<Attribution>,
+    /// Failures reported in the background by sinks and the file watcher
+    diagnostics: (Sender<String>, Receiver<String>),
+    /// Where sinks report what they did with each alert
+    deliveries: Deliveries,
+    delivered: Receiver<(u64, usize, Delivery)>,
+    metrics: Option<Arc<Mutex<Metrics>>>,
+    api: Option<Arc<Mutex<Api>>>,
+    /// History database, if one is in use
+    store: Option<Store>,
+    script: Option<Script>,
+    control: Option<ControlSocket>,
+    /// What the main loop waits on between ticks
+    reactor: Reactor,
+    config: Option<ConfigFile>,
+    config_path: Option<PathBuf>,
+    profile: Option<String>,
+    /// Settings from the command line and environment, before any from the config
+    base_args: WatchArgs,
+    args: WatchArgs,
+    watchlist: Option<Watchlist>,
+    /// Where tracked files are kept between runs, unless disabled
+    state_file: Option<PathBuf>,
+    /// Where everything the dashboard reacts to is written, with --record
+    recorder: Option<Recorder>,
+    /// The session played back instead of watching, with --play
+    playback: Option<Recording>,
+    /// Where the dashboard's state is checkpointed, so it can be restored if it's cut off
+    checkpoints: Option<Checkpoints>,
+}
+
+impl Session {
+    fn save(&self) -> String {
+        let Some(path) = &self.config_path else {
+            return "no config file location found (use --config)".to_string();
+        };
+
+        match config::save_profile(path, self.profile.as_deref(), &self.args) {
+            Ok(()) => match &self.profile {
+                Some(profile) => format!("saved profile {} to {}", profile, path.display()),
+                None => format!("saved settings to {}", path.display()),
+            },
+            Err(e) => format!("could not save settings: {}", e),
+        }
+    }
+
+    /// The settings to use after switching to another profile.
+    fn profile_args(&self, name: &str) -> Result<WatchArgs, String> {
+        let config = self.config.as_ref().ok_or("no config file loaded")?;
+        if !config.has_profile(name) {
+            return Err(format!("no profile {} in config", name));
+        }
+        let mut args = self.base_args.clone();
+        config.merge_into(&mut args, Some(name));
+        Ok(args)
+    }
+
+    /// All paths to watch if the watch list has changed since it was last read.
+    fn reload_watchlist(&mut self) -> Option<Result<Vec<WatchPath>, String>> {
+        let watchlist = self.watchlist.as_mut().filter(|w| w.changed())?;
+        let listed = watchlist.read();
+        Some(listed.map(|listed| with_listed(&self.args, listed)))
+    }
 }
 
 fn display_name(path: &Path) -> &str {
@@ -52,138 +306,1322 @@ fn display_name(path: &Path) -> &str {
         .unwrap_or_default()
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args = Args::parse();
+/// Whether an item is shown, given the tag files are being filtered by if any.
+fn is_shown(item: &FileItem, filter: Option<&str>) -> bool {
+    filter.is_none_or(|tag| item.tags.iter().any(|t| t == tag))
+}
+
+fn group_title(group: &FileGroup) -> &str {
+    group
+        .title
+        .as_deref()
+        .unwrap_or_else(|| display_name(&group.root))
+}
+
+/// The columns the columns key shows: those asked for, or all of them.
+fn columns(args: &WatchArgs) -> Vec<Column> {
+    if args.columns.is_empty() {
+        Column::ALL.to_vec()
+    } else {
+        args.columns.clone()
+    }
+}
+
+/// The services to monitor, before anything's known about them.
+fn unknown_services(names: &[String]) -> Vec<ServiceState> {
+    names.iter().cloned().map(ServiceState::Unknown).collect()
+}
+
+fn main() -> ExitCode {
+    match try_main() {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            if let Some(hint) = hint(&*e) {
+                eprintln!("hint: {}", hint);
+            }
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Advice for failures that usually have an obvious fix.
+fn hint(error: &(dyn std::error::Error + 'static)) -> Option<&'static str> {
+    match error.downcast_ref::<file_task::Error>()? {
+        file_task::Error::NoPaths => {
+            Some("pass directories to watch as arguments, or list them in the config file")
+        }
+        file_task::Error::NotWatchable { .. } => {
+            Some("check the paths given as arguments, in the config file, and in any watch list")
+        }
+        file_task::Error::Scan { source, .. } | file_task::Error::PathList { source, .. }
+            if source.kind() == io::ErrorKind::PermissionDenied =>
+        {
+            Some("check that this user can read it")
+        }
+        file_task::Error::Watch { source, .. }
+            if matches!(source.kind, notify::ErrorKind::MaxFilesWatch) =>
+        {
+            Some("the system's limit on watches was reached; raise fs.inotify.max_user_watches with sysctl, or use --source poll")
+        }
+        file_task::Error::Config { .. } => {
+            Some("check the config file, or run `file_task config validate`")
+        }
+        file_task::Error::Terminal(_) => {
+            Some("without a terminal, run `file_task daemon` or pass --no-tui")
+        }
+        file_task::Error::Listen { source, .. } if source.kind() == io::ErrorKind::AddrInUse => {
+            Some("another instance may be running; stop it or choose another address")
+        }
+        _ => None,
+    }
+}
+
+fn try_main() -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let cli = Cli::parse_args();
+    // without --config, use the platform config location if there's anything there
+    let save_path = cli.config.clone().or_else(dirs::config_file);
+    let config_path = cli
+        .config
+        .clone()
+        .or_else(|| dirs::config_file().filter(|p| p.exists()));
+    let profile = cli.profile.clone();
+    let command = cli.into_command();
 
-    let app_state = AppState {
-        file_groups: get_initial_state(args.paths)?,
-        service: args.service.map(ServiceState::Unknown),
+    // on the first run, with nothing saying what to watch, ask and keep the answers
+    let config_path = match (config_path, &save_path) {
+        (None, Some(path)) if wizard::wanted() => match wizard::run(path)? {
+            true => Some(path.clone()),
+            false => return Ok(ExitCode::SUCCESS),
+        },
+        (config_path, _) => config_path,
     };
 
-    let (tx, rx) = channel();
+    // saving is allowed to create the config file
+    let config = match &config_path {
+        Some(path)
+            if matches!(command, Command::Config(ConfigCommand::Save(_))) && !path.exists() =>
+        {
+            None
+        }
+        Some(path) => Some(ConfigFile::load(path)?),
+        None => None,
+    };
+
+    // settings from the command line win over the selected profile, which wins over the top level
+    let merge_config = |args: &mut WatchArgs| -> Result<(), Box<dyn std::error::Error>> {
+        match (&config, profile.as_deref()) {
+            (Some(config), Some(name)) if !config.has_profile(name) => {
+                Err(format!("no profile {} in config", name).into())
+            }
+            (None, Some(_)) => Err("--profile requires a config file".into()),
+            (Some(config), profile) => {
+                config.merge_into(args, profile);
+                Ok(())
+            }
+            (None, None) => Ok(()),
+        }
+    };
+    let merge = |args: &mut WatchArgs| -> Result<(), Box<dyn std::error::Error>> {
+        args.apply_env();
+        expand_paths_from(args)?;
+        merge_config(args)
+    };
 
+    let mode = match &command {
+        Command::Daemon(_) => Mode::Daemon,
+        Command::Watch(args) if args.no_tui || args.output.is_some() => Mode::Daemon,
+        Command::Bar(args) => Mode::Bar(args.format),
+        _ => Mode::Dashboard,
+    };
+    match command {
+        Command::Watch(args) if args.play.is_some() => play(args).map(|_| ExitCode::SUCCESS),
+        Command::Watch(mut args)
+        | Command::Daemon(mut args)
+        | Command::Bar(BarArgs {
+            watch: mut args, ..
+        }) => {
+            args.apply_env();
+            expand_paths_from(&mut args)?;
+            let base_args = args.clone();
+            merge_config(&mut args)?;
+            let diagnostics = channel();
+            let reactor = Reactor::new(SERVICE_POLL)?;
+            let (deliveries, delivered) = Deliveries::new(reactor.waker());
+            let otlp = config.as_ref().and_then(|c| c.config.otlp.as_ref());
+            let metrics = (args.metrics.is_some() || otlp.is_some())
+                .then(|| Arc::new(Mutex::new(Metrics::default())));
+            if let (Some(addr), Some(metrics)) = (args.metrics, &metrics) {
+                metrics::serve(addr, Arc::clone(metrics))?;
+            }
+            if let (Some(otlp), Some(metrics)) = (otlp, &metrics) {
+                otlp::start(otlp, Arc::clone(metrics), diagnostics.0.clone());
+            }
+            let api = (args.listen.is_some() || args.grpc.is_some())
+                .then(|| Arc::new(Mutex::new(Api::default())));
+            if let (Some(addr), Some(api)) = (args.listen, &api) {
+                api::serve(addr, Arc::clone(api))?;
+            }
+            if let (Some(addr), Some(api)) = (args.grpc, &api) {
+                grpc::serve(addr, Arc::clone(api))?;
+            }
+            let session = Session {
+                keymap: config
+                    .as_ref()
+                    .map_or_else(|| Ok(KeyMap::default()), |c| c.keymap())?,
+                sinks: build_sinks(config.as_ref(), &args, diagnostics.0.clone())?,
+                alerts: config
+                    .as_ref()
+                    .map(ConfigFile::alerts)
+                    .transpose()?
+                    .unwrap_or_default(),
+                actions: config
+                    .as_ref()
+                    .filter(|c| !c.config.actions.is_empty())
+                    .map(|c| {
+                        Actions::new(
+                            &c.config.actions,
+                            args.dry_run,
+                            c.config.delete_permanently,
+                            reactor.waker(),
+                        )
+                    })
+                    .transpose()?,
+                checksums: Checksums::new(reactor.waker()),
+                signatures: config
+                    .as_ref()
+                    .and_then(|c| c.config.signatures.as_ref())
+                    .map(|s| Signatures::new(s, reactor.waker())),
+                comparisons: Comparisons::new(
+                    config.as_ref().map_or(&[][..], |c| &c.config.compare),
+                    reactor.waker(),
+                ),
+                disks: Disks::new(
+                    config.as_ref().and_then(|c| c.config.alerts.free),
+                    reactor.waker(),
+                ),
+                metadata: Metadata::new(reactor.waker()),
+                mounts: Some(Mounts::new(reactor.waker())),
+                journal: Some(Journal::new(
+                    config
+                        .as_ref()
+                        .map(ConfigFile::journal_rules)
+                        .transpose()?
+                        .unwrap_or_default(),
+                    reactor.waker(),
+                )),
+                attribution: args
+                    .attribute
+                    .then(|| Attribution::start(reactor.waker()))
+                    .transpose()
+                    .map_err(|e| {
+                        format!(
+                            "could not start --attribute (it needs CAP_SYS_ADMIN): {}",
+                            e
+                        )
+                    })?,
+                diagnostics,
+                deliveries,
+                delivered,
+                metrics,
+                api,
+                store: args.db.as_deref().map(Store::open).transpose()?,
+                script: args.script.as_deref().map(Script::load).transpose()?,
+                control: ControlSocket::bind(
+                    args.control.as_deref(),
+                    args.control_addr,
+                    reactor.waker(),
+                )?,
+                reactor,
+                config,
+                config_path: save_path,
+                profile,
+                base_args,
+                watchlist: args.watchlist.clone().map(Watchlist::new),
+                state_file: if args.no_state {
+                    None
+                } else {
+                    args.state.clone().or_else(dirs::state_file)
+                },
+                recorder: None,
+                playback: None,
+                checkpoints: None,
+                args,
+            };
+            watch(session, mode).map(|_| ExitCode::SUCCESS)
+        }
+        Command::Status(mut args) => {
+            merge(&mut args)?;
+            probe::status(args)
+        }
+        Command::StatusBrief(mut args) => {
+            merge(&mut args.watch)?;
+            brief::status_brief(args)
+        }
+        Command::Attach(args) => {
+            let keymap = config
+                .as_ref()
+                .map_or_else(|| Ok(KeyMap::default()), |c| c.keymap())?;
+            let highlights = config
+                .as_ref()
+                .map(ConfigFile::highlights)
+                .transpose()?
+                .unwrap_or_default();
+            attach::attach(args, &keymap, &highlights).map(|_| ExitCode::SUCCESS)
+        }
+        Command::Check(mut args) => {
+            args.apply_env();
+            merge(&mut args.watch)?;
+            probe::check(args)
+        }
+        Command::Export(mut args) => {
+            merge(&mut args.watch)?;
+            export::export(args)
+        }
+        Command::Config(ConfigCommand::Validate) => {
+            let config = config.ok_or("no config file found (use --config)")?;
+            Ok(validate_config(&config))
+        }
+        Command::Config(ConfigCommand::Save(mut args)) => {
+            let path = save_path.ok_or("no config file location found (use --config)")?;
+            args.apply_env();
+            expand_paths_from(&mut args)?;
+            if let Some(config) = &config {
+                // a profile that doesn't exist yet is fine here, it's about to be created
+                config.merge_into(&mut args, profile.as_deref());
+            }
+            config::save_profile(&path, profile.as_deref(), &args)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Man => {
+            manpage::render(&mut std::io::stdout())?;
+            Ok(ExitCode::SUCCESS)
+        }
+    }
+}
+
+/// Play back a recorded session in the dashboard, with nothing from the config and nothing done
+/// besides showing it: no sinks, actions, or state saved.
+fn play(mut args: WatchArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let recording = Recording::load(args.play.as_deref().unwrap_or(Path::new("")))?;
+    args.services = recording.services();
+    args.timezone = Some(recording.timezone()?);
+    let reactor = Reactor::new(SERVICE_POLL)?;
+    let (deliveries, delivered) = Deliveries::new(reactor.waker());
+    let session = Session {
+        keymap: recording.keymap(),
+        sinks: vec![],
+        alerts: Alerts::default(),
+        actions: None,
+        checksums: Checksums::new(reactor.waker()),
+        signatures: None,
+        comparisons: Comparisons::new(&[], reactor.waker()),
+        disks: Disks::new(None, reactor.waker()),
+        metadata: Metadata::new(reactor.waker()),
+        mounts: None,
+        journal: None,
+        attribution: None,
+        diagnostics: channel(),
+        deliveries,
+        delivered,
+        metrics: None,
+        api: None,
+        store: None,
+        script: None,
+        control: None,
+        reactor,
+        config: None,
+        config_path: None,
+        profile: None,
+        base_args: args.clone(),
+        watchlist: None,
+        state_file: None,
+        recorder: None,
+        playback: Some(recording),
+        checkpoints: None,
+        args,
+    };
+    watch(session, Mode::Dashboard)
+}
+
+fn build_sinks(
+    config: Option<&ConfigFile>,
+    args: &WatchArgs,
+    diagnostics: Sender<String>,
+) -> Result<Vec<Box<dyn Sink>>, Box<dyn std::error::Error>> {
+    let mut sinks: Vec<Box<dyn Sink>> = vec![];
+
+    if let Some(dir) = &args.event_log {
+        let settings = config.and_then(|c| c.config.event_log.as_ref());
+        let log = EventLog::open(
+            dir,
+            settings.unwrap_or(&Default::default()),
+            diagnostics.clone(),
+        )?;
+        sinks.push(Box::new(log));
+    }
+
+    if let Some(path) = &args.fifo {
+        sinks.push(Box::new(fifo::Fifo::open(path)?));
+    }
+
+    let Some(config) = config.map(|c| &c.config) else {
+        return Ok(sinks);
+    };
+
+    if !config.desktop_notifications.is_empty() {
+        let filter = EventFilter::new(&config.desktop_notifications)?;
+        sinks.push(Box::new(DesktopNotifier::new(filter)));
+    }
+
+    for webhook in &config.webhooks {
+        sinks.push(Box::new(Webhook::new(webhook, diagnostics.clone())?));
+    }
+
+    if let Some(mqtt) = &config.mqtt {
+        sinks.push(Box::new(MqttPublisher::new(mqtt, diagnostics.clone())?));
+    }
+
+    if let Some(email) = &config.email {
+        sinks.push(Box::new(Email::new(email, diagnostics.clone())?));
+    }
+
+    if let Some(syslog) = &config.syslog {
+        sinks.push(Box::new(syslog::Syslog::new(syslog, diagnostics.clone())?));
+    }
+
+    if let Some(hooks) = &config.hooks {
+        sinks.push(Box::new(Hooks::new(hooks, diagnostics)?));
+    }
+
+    Ok(sinks)
+}
+
+fn expand_paths_from(args: &mut WatchArgs) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(source) = args.paths_from.take() {
+        let list = filesystem::read_paths_from(&source, &args.paths)?;
+        for (path, reason) in list.skipped {
+            Logger::Stderr.message(&format!("skipping {}: {}", path.display(), reason));
+        }
+        args.paths.extend(list.paths);
+    }
+    Ok(())
+}
+
+fn validate_config(config: &ConfigFile) -> ExitCode {
+    let problems = config.validate();
+    for problem in problems.iter() {
+        println!("{}", config.describe(problem));
+    }
+
+    if problems.is_empty() {
+        println!("{}: OK", config.path.display());
+        ExitCode::SUCCESS
+    } else {
+        println!("{} problem(s) found", problems.len());
+        ExitCode::FAILURE
+    }
+}
+
+fn watch(mut session: Session, mode: Mode) -> Result<(), Box<dyn std::error::Error>> {
+    let (tx, rx) = channel();
     // NOTE: need to hold on to this so file watches continue to run
-    let _watcher = filesystem::init_file_watch(tx, &app_state.file_groups)?;
+    let mut source = match &session.playback {
+        Some(_) => Box::new(Playback),
+        None => session.args.source.unwrap_or_default().create(
+            session.reactor.forward_changes(tx.clone()),
+            session.diagnostics.0.clone(),
+        )?,
+    };
+
+    let mut file_groups = match &session.playback {
+        Some(recording) => recording.groups(),
+        None => {
+            let paths = watch_paths(&session.args)?;
+            otlp::traced("initial_scan", vec![], || {
+                let scanned = source.progress();
+                filesystem::with_progress(scanned.as_deref(), || get_initial_state(paths, &*source))
+            })?
+        }
+    };
+    if let Some(path) = &session.state_file {
+        Snapshot::load(path)?.restore(&mut file_groups, session.args.limits().retention);
+    }
+    filesystem::enforce_limits(&mut file_groups, session.args.limits());
+    if let Some(script) = session.script.as_mut() {
+        script.title_groups(&mut file_groups)?;
+    }
+    if let Some(store) = session.store.as_mut() {
+        store
+            .record_items(&file_groups)
+            .and_then(|()| store.load_tags(&mut file_groups))
+            .map_err(|e| {
+                format!(
+                    "could not record files in {}: {}",
+                    store.path().display(),
+                    e
+                )
+            })?;
+    }
+
+    let display = session
+        .config
+        .as_ref()
+        .map(|c| c.config.display)
+        .unwrap_or_default();
+    let mut app_state = AppState {
+        file_groups,
+        services: unknown_services(&session.args.services),
+        message: session.playback.as_ref().map(|recording| {
+            format!(
+                "playing back {}s of recorded input",
+                recording.length().as_secs()
+            )
+        }),
+        timezone: session.args.timezone.unwrap_or_default(),
+        paused: false,
+        tag_filter: None,
+        focus: 0,
+        selections: HashMap::new(),
+        page: 0,
+        columns: columns(&session.args),
+        show_columns: !session.args.columns.is_empty(),
+        sort: session.args.sort.unwrap_or_default(),
+        sorts: HashMap::new(),
+        note_input: None,
+        tag_input: None,
+        highlights: session
+            .config
+            .as_ref()
+            .map(ConfigFile::highlights)
+            .transpose()?
+            .unwrap_or_default(),
+        breached: HashSet::new(),
+        out_of_sync: HashSet::new(),
+        lags: vec![],
+        disks: vec![],
+        low_space: vec![],
+        unmounted: HashMap::new(),
+        follow: None,
+        follow_highlights: session
+            .config
+            .as_ref()
+            .map(ConfigFile::follow_highlights)
+            .transpose()?
+            .unwrap_or_default(),
+        peek: None,
+        holders: None,
+        stats: None,
+        by_extension: false,
+        heatmap: None,
+        notifications: Notifications::default(),
+        show_notifications: display.notifications,
+        settings: None,
+        show_debug: false,
+        internals: Internals::new(match &session.playback {
+            Some(_) => "playback",
+            None => session.args.source.unwrap_or_default().name,
+        }),
+        health: vec![],
+        show_health: display.health,
+    };
+
+    if let Some(path) = &session.args.record {
+        session.recorder = Some(Recorder::create(path, &app_state, &session.keymap)?);
+    }
+
+    filesystem::watch_groups(&app_state.file_groups, &mut *source)?;
+    // the initial state already covers the watch list as it is now
+    if let Some(watchlist) = session.watchlist.as_mut() {
+        watchlist.read()?;
+    }
+
+    session.reactor.handle_signals()?;
+    match mode {
+        Mode::Dashboard => {}
+        Mode::Daemon => return daemon::run(app_state, (tx, rx), &mut *source, &mut session),
+        Mode::Bar(format) => {
+            return bar::run(app_state, (tx, rx), &mut *source, &mut session, format)
+        }
+    }
+
+    if let Some(path) = &session.state_file {
+        let checkpoints = Checkpoints::new(path);
+        if let Some(recovery) = Recovery::load(checkpoints.path())? {
+            if recovery.offer()? {
+                recovery.restore(&mut app_state);
+            }
+        }
+        session.checkpoints = Some(checkpoints);
+    }
 
     // setup terminal
     let mut state = terminal::TerminalState::init()?;
 
-    run(&mut state.terminal, app_state, rx)?;
+    run(
+        &mut state.terminal,
+        app_state,
+        (tx, rx),
+        &mut *source,
+        &mut session,
+    )?;
 
     Ok(())
 }
 
-fn update_state(rx: &Receiver<filesystem::FileChange>, state: &mut AppState) {
-    update_file_items(rx, &mut state.file_groups);
-    let status = update_service_status(state.service.as_ref());
-    state.service = status;
+fn update_state(
+    changes: Vec<FileChange>,
+    state: &mut AppState,
+    statuses: Vec<ServiceState>,
+    limits: Limits,
+    clock: &dyn Clock,
+) -> Vec<Event> {
+    let changes = apply_file_changes(changes, &mut state.file_groups, clock, limits.retention);
+    if let Some(follow) = state.follow.as_mut() {
+        let modified = changes
+            .iter()
+            .any(|change| matches!(change, FileChange::Modified(path) if *path == follow.path));
+        if let Err(e) = modified.then(|| follow.read()).transpose() {
+            state.message = Some(format!("could not read {}: {}", follow.path.display(), e));
+        }
+    }
+    let mut events = file_events(&changes, &state.file_groups);
+    filesystem::enforce_limits(&mut state.file_groups, limits);
+
+    // a status checked before switching services is dropped
+    for current in statuses {
+        if let Some(service) = state
+            .services
+            .iter_mut()
+            .find(|s| s.name() == current.name())
+        {
+            events.extend(service_event(Some(service), &current));
+            *service = current;
+        }
+    }
+
+    events
 }
 
 fn run<B: Backend>(
     terminal: &mut Terminal<B>,
     mut data: AppState,
-    rx: Receiver<filesystem::FileChange>,
+    changes: (Sender<FileChange>, Receiver<FileChange>),
+    source: &mut dyn WatchSource,
+    session: &mut Session,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    session.reactor.read_input();
     loop {
-        update_state(&rx, &mut data);
-        terminal.draw(|f| ui(f, &data))?;
+        tick(&mut data, &changes, source, session);
+        if let Some(cmd) = app::update(&mut data, Msg::Tick) {
+            perform(cmd, &mut data, &changes, source, session);
+        }
+        let drawing = Instant::now();
+        terminal.draw(|f| view::ui(f, &mut data))?;
+        data.internals.draw = drawing.elapsed();
+        session.reactor.wake_at(next_minute());
 
-        if terminal::should_quit()? {
-            return Ok(());
+        if let Some(due) = session.checkpoints.as_ref().map(Checkpoints::due) {
+            if due <= Instant::now() {
+                if let Err(e) = checkpoint(&data, session) {
+                    data.message = Some(e.to_string());
+                }
+            }
+            if let Some(checkpoints) = &session.checkpoints {
+                session.reactor.wake_at(checkpoints.due());
+            }
+        }
+        // keep the internals current while they're shown
+        if data.show_debug {
+            session
+                .reactor
+                .wake_at(Instant::now() + Duration::from_secs(1));
+        }
+        // a recording plays back at the pace it was recorded
+        if let Some(due) = session.playback.as_ref().and_then(Recording::due) {
+            session.reactor.wake_at(due);
+        }
+        let mut wakes = session.reactor.wait();
+        let played = session.playback.as_mut().map(|r| r.input(Instant::now()));
+        wakes.extend(played.flatten().map(Wake::Input));
+        data.internals.wakes = wakes.len();
+        for wake in wakes {
+            if let (Some(recorder), Wake::Input(event)) = (session.recorder.as_mut(), &wake) {
+                if let Err(e) = recorder.input(event) {
+                    data.message = Some(e);
+                }
+            }
+            let editing =
+                data.note_input.is_some() || data.tag_input.is_some() || data.settings.is_some();
+            let mut next = Msg::from_wake(wake, &session.keymap, editing);
+            while let Some(msg) = next.take() {
+                next = match app::update(&mut data, msg) {
+                    Some(Cmd::Quit) => {
+                        save_state(&data, session)?;
+                        if let Some(checkpoints) = &session.checkpoints {
+                            checkpoints.finish()?;
+                        }
+                        return Ok(());
+                    }
+                    Some(Cmd::Suspend) => {
+                        terminal::suspend(terminal)?;
+                        None
+                    }
+                    Some(cmd) => perform(cmd, &mut data, &changes, source, session),
+                    None => None,
+                };
+            }
         }
     }
 }
 
-fn ui<B: Backend>(frame: &mut Frame<B>, state: &AppState) {
-    const STATUS_BAR_HEIGHT: u16 = 1;
-    let screen_area = frame.size();
-    let file_group_count = state.file_groups.len() as u32;
-    let file_group_space = screen_area.height - STATUS_BAR_HEIGHT;
-
-    let layout_areas = Layout::default()
-        .constraints([
-            Constraint::Length(file_group_space),  // file list area
-            Constraint::Length(STATUS_BAR_HEIGHT), // status bar
-        ])
-        .split(screen_area);
+/// When the minute next changes, to redraw the clock then.
+fn next_minute() -> Instant {
+    let second = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        % 60;
+    Instant::now() + Duration::from_secs(60 - second)
+}
 
-    let total_group_space = file_group_space as u32;
-    let per_group_space = total_group_space / file_group_count;
-    let extra_space = total_group_space % file_group_count;
+/// Do what `update` asked for, returning anything to tell it about the outcome.
+fn perform(
+    cmd: Cmd,
+    data: &mut AppState,
+    (tx, _): &(Sender<FileChange>, Receiver<FileChange>),
+    source: &mut dyn WatchSource,
+    session: &mut Session,
+) -> Option<Msg> {
+    let message = match cmd {
+        Cmd::Quit | Cmd::Suspend => return None,
+        Cmd::SaveSession => session.save(),
+        Cmd::Export => export_csv(data),
+        Cmd::Reload => reload_config(data, source, session),
+        Cmd::SaveNote(group) => save_note(&data.file_groups[group], session),
+        Cmd::Follow => follow_file(data),
+        Cmd::Peek => peek_archive(data),
+        Cmd::Holders => find_holders(data),
+        Cmd::SaveTag => save_tag(data, session),
+        Cmd::Heatmap => show_heatmap(data, session),
+        Cmd::Settings => open_settings(data, session),
+        Cmd::SaveSetting => save_setting(data, source, session),
+        Cmd::Stats => {
+            // anything not read yet is read once, and kept up to date from then on
+            for group in data.file_groups.iter_mut() {
+                group.read_metadata();
+            }
+            data.stats = Some(data.file_groups.iter().map(GroupStats::of).collect());
+            if data.by_extension {
+                "showing files by extension".to_string()
+            } else {
+                "showing file sizes".to_string()
+            }
+        }
+        Cmd::ReadVisible => {
+            read_visible(data);
+            return None;
+        }
+        Cmd::Undo => match session.actions.as_mut() {
+            Some(actions) => actions.undo(),
+            None => "there are no actions to undo".to_string(),
+        },
+        // whatever rescanning found was recorded along with everything else
+        Cmd::Rescan if session.playback.is_some() => return None,
+        Cmd::Rescan => rescan(&data.file_groups, tx, source)
+            .err()
+            .map(|e| format!("could not rescan: {}", e))?,
+    };
+    Some(Msg::Done(message))
+}
 
-    // divide the file list area up into even parts
-    let constraints = (0..file_group_count)
-        .map(|i| {
-            Constraint::Ratio(
-                // if there's any extra space give it to the first item
-                per_group_space + (if i == 0 { extra_space } else { 0 }),
-                total_group_space,
-            )
+/// Read the files on screen that haven't been yet, so their columns can be filled in.
+fn read_visible(data: &mut AppState) {
+    let unread = data
+        .file_groups
+        .iter()
+        .enumerate()
+        .flat_map(|(i, group)| {
+            view::on_screen(data, group)
+                .filter(|item| !item.metadata_read)
+                .map(move |item| (i, group.path(item)))
         })
         .collect::<Vec<_>>();
-    let file_list_areas = Layout::default()
-        .constraints(constraints)
-        .split(layout_areas[0]);
+    for (i, path) in unread {
+        data.file_groups[i].refresh(&path);
+    }
+}
+
+/// Open the settings screen on the config file, which is written once something is changed if
+/// it doesn't exist yet.
+fn open_settings(data: &mut AppState, session: &Session) -> String {
+    let Some(path) = &session.config_path else {
+        return "no config file location found (use --config)".to_string();
+    };
+    match SettingsEditor::open(path, session.profile.as_deref()) {
+        Ok(editor) => {
+            data.settings = Some(editor);
+            format!("editing the settings in {}", path.display())
+        }
+        Err(e) => e,
+    }
+}
+
+/// Write the setting being typed to the config file, then apply the config with it.
+fn save_setting(
+    data: &mut AppState,
+    source: &mut dyn WatchSource,
+    session: &mut Session,
+) -> String {
+    let Some(editor) = data.settings.as_mut() else {
+        return "no setting is being changed".to_string();
+    };
+    let key = editor.setting().key();
+    if let Err(e) = editor.save() {
+        editor.error = Some(e);
+        return format!("{} was not changed", key);
+    }
+    // a config written for the first time is only reloaded once it's been loaded
+    if session.config.is_none() {
+        session.config = ConfigFile::load(&editor.path).ok();
+    }
+    match reload_config(data, source, session) {
+        message if message.starts_with("reloaded") => format!("changed {}", key),
+        message => message,
+    }
+}
+
+/// Keep a group's note with the rest of the profile's settings in the config.
+fn save_note(group: &FileGroup, session: &mut Session) -> String {
+    let path = session
+        .args
+        .paths
+        .iter()
+        .find(|path| path.canonicalize().is_ok_and(|path| path == group.root))
+        .cloned();
+    let Some(path) = path else {
+        return format!(
+            "the note for {} is only kept until exit, as it isn't a configured path",
+            group_title(group)
+        );
+    };
+    match &group.note {
+        Some(note) => session.args.notes.insert(path, note.clone()),
+        None => session.args.notes.remove(&path),
+    };
+    session.save()
+}
+
+/// Give the selected file the tag typed for it, or take it away.
+fn save_tag(data: &mut AppState, session: &mut Session) -> String {
+    let Some(input) = data.tag_input.take() else {
+        return "no tag is being typed".to_string();
+    };
+    let path = input.path.display();
+    match set_tag(data, session, &input.path, input.text.clone(), input.on) {
+        Ok(()) if input.on => format!("tagged {} {}", path, input.text),
+        Ok(()) => format!("took {} away from {}", input.text, path),
+        Err(e) => e.to_string(),
+    }
+}
+
+/// Follow the selected file, or the one most recently seen arriving in any group.
+fn follow_file(data: &mut AppState) -> String {
+    let Some(path) = chosen_file(data, |_| true) else {
+        return "there are no files to follow".to_string();
+    };
+    match Follow::new(path.clone()) {
+        Ok(follow) => {
+            data.follow = Some(follow);
+            format!("following {}", path.display())
+        }
+        Err(e) => format!("could not follow {}: {}", path.display(), e),
+    }
+}
+
+/// Count when files arrived in each group over the last couple of weeks, from the history.
+fn show_heatmap(data: &mut AppState, session: &Session) -> String {
+    let Some(store) = &session.store else {
+        return "the heatmap needs the history kept with --db".to_string();
+    };
+    let today = data.timezone.now().date();
+    match store.arrivals(heatmap::since(today)) {
+        Ok(arrivals) => {
+            let titles = data.file_groups.iter().map(group_title).collect::<Vec<_>>();
+            data.heatmap = Some(heatmap::build(&titles, &arrivals, data.timezone, today));
+            format!(
+                "showing arrivals over the last {} days",
+                heatmap::HEATMAP_DAYS
+            )
+        }
+        Err(e) => format!("could not read history: {}", e),
+    }
+}
 
-    for (group, rect) in state.file_groups.iter().zip(file_list_areas.iter()) {
-        let list_items = group.items.iter().map(draw_file_item).collect::<Vec<_>>();
-        let block = Block::default()
-            .title(display_name(&group.root))
-            .borders(Borders::ALL);
-        let list = List::new(list_items).block(block).style(Style::default());
-        frame.render_widget(list, *rect)
+/// List what's in the selected archive, or the one most recently seen arriving in any group.
+fn peek_archive(data: &mut AppState) -> String {
+    let Some(path) = chosen_file(data, archive::is_archive) else {
+        return "there are no archives to look inside".to_string();
+    };
+    if !archive::is_archive(&path) {
+        return format!("{} is not an archive", path.display());
     }
+    match Peek::open(path) {
+        Ok(peek) => {
+            let message = format!("{} holds {} files", peek.path.display(), peek.entries.len());
+            data.peek = Some(peek);
+            message
+        }
+        Err(e) => e,
+    }
+}
 
-    let time = draw_time();
-    let service_status = draw_service_status(state);
-    let content = Line::from(time.into_iter().chain(service_status).collect::<Vec<_>>());
+fn find_holders(data: &mut AppState) -> String {
+    let Some(path) = chosen_file(data, |_| true) else {
+        return "there are no files to look for".to_string();
+    };
+    match Holders::find(path) {
+        Ok(holders) if holders.processes.is_empty() => {
+            format!("nothing has {} open", holders.path.display())
+        }
+        Ok(holders) => {
+            let message = format!(
+                "{} is open in {} processes",
+                holders.path.display(),
+                holders.processes.len()
+            );
+            data.holders = Some(holders);
+            message
+        }
+        Err(e) => e,
+    }
+}
+
+/// The selected file, or if nothing's selected the newest a filter accepts.
+fn chosen_file(data: &AppState, filter: impl Fn(&Path) -> bool) -> Option<PathBuf> {
+    match app::selected_item(data) {
+        Some((group, item)) => Some(group.path(item)),
+        None => newest_file(data, filter),
+    }
+}
 
-    let bar = Paragraph::new(content).style(Style::default().bg(Color::Blue));
+/// The present file seen arriving most recently, out of those a filter accepts.
+fn newest_file(data: &AppState, filter: impl Fn(&Path) -> bool) -> Option<PathBuf> {
+    data.file_groups
+        .iter()
+        .flat_map(|group| group.items().iter().map(move |item| (group, item)))
+        .filter(|(_, item)| item.removed.is_none() && filter(&item.name))
+        .max_by_key(|(_, item)| item.observed)
+        .map(|(group, item)| group.path(item))
+}
 
-    frame.render_widget(bar, layout_areas[1]);
+fn export_csv(data: &AppState) -> String {
+    let name = data.timezone.now().format("file_task-%Y%m%d-%H%M%S.csv");
+    let path = PathBuf::from(name.to_string());
+    match export::write_to(&data.file_groups, &path) {
+        Ok(()) => format!("exported to {}", path.display()),
+        Err(e) => format!("could not export to {}: {}", path.display(), e),
+    }
 }
 
-fn draw_time<'a>() -> Vec<Span<'a>> {
-    let now = Local::now().format("%H:%M").to_string();
-    let time = vec![
-        Span::styled("[", Style::default().fg(Color::Cyan)),
-        Span::styled(now, Style::default()),
-        Span::styled("]", Style::default().fg(Color::Cyan)),
-    ];
-    time
+/// Whether anything shown needs every file's size and modified time, not just the rows on screen.
+fn wants_metadata(data: &AppState) -> bool {
+    let sorted = data.file_groups.iter().any(|group| {
+        let sort = data.sorts.get(&group.root).copied().unwrap_or(data.sort);
+        matches!(sort, Sort::Mtime | Sort::Size)
+    });
+    // the age column puts the age of each group's oldest file in its title
+    sorted || (data.show_columns && data.columns.contains(&Column::Age))
 }
 
-fn draw_service_status(state: &AppState) -> Vec<Span> {
-    if let Some(status) = &state.service {
-        let (active, status_desc): (bool, &str) = match status {
-            ServiceState::Details(ServiceDetails { active, status, .. }) => (*active, status),
-            ServiceState::Unknown(_) => (false, "----"),
+/// Take in everything that happened since the last tick and pass on any resulting events.
+fn tick(
+    data: &mut AppState,
+    (tx, rx): &(Sender<FileChange>, Receiver<FileChange>),
+    source: &mut dyn WatchSource,
+    session: &mut Session,
+) {
+    if let Some(control) = &session.control {
+        let requests = control.requests.try_iter().collect::<Vec<_>>();
+        for request in requests {
+            let reply = match request.command {
+                ControlCommand::State => Ok(control::state_json(data, session)),
+                command => {
+                    handle_control(command, data, tx, source, session).map(|()| "ok".to_string())
+                }
+            };
+            let _ = request
+                .reply
+                .send(reply.unwrap_or_else(|e| format!("error: {}", e)));
+        }
+    }
+    if let Some(paths) = session.reload_watchlist() {
+        let synced = paths.and_then(|paths| {
+            sync_groups(&mut data.file_groups, paths, source).map_err(|e| e.to_string())
+        });
+        data.message = Some(match synced {
+            Ok(()) => "reloaded watch list".to_string(),
+            Err(e) => format!("could not reload watch list: {}", e),
+        });
+    }
+    // a recording supplies the services' statuses when playing back
+    if session.playback.is_none() {
+        session.reactor.watch_services(&data.services);
+    }
+    let mut events = if data.paused {
+        vec![]
+    } else {
+        let (changes, statuses) = match session.playback.as_mut() {
+            Some(recording) => recording.changes(Instant::now()),
+            None => (
+                rx.try_iter().collect::<Vec<_>>(),
+                session.reactor.take_service_statuses(),
+            ),
         };
-        let status_style = if active {
-            Style::default().fg(Color::Green)
-        } else {
-            Style::default().bg(Color::Red)
+        data.internals.applied(changes.len(), Instant::now());
+        if let Some(recorder) = session.recorder.as_mut() {
+            if let Err(e) = recorder.update(&changes, &statuses) {
+                data.message = Some(e);
+            }
+        }
+        let clock = match &session.playback {
+            Some(recording) => recording.clock(),
+            None => &SystemClock,
         };
-        vec![
-            Span::styled("[", Style::default().fg(Color::Cyan)),
-            Span::styled(
-                status_desc,
-                Style::default()
-                    .add_modifier(Modifier::BOLD)
-                    .patch(status_style),
-            ),
-            Span::styled("]", Style::default().fg(Color::Cyan)),
-        ]
-    } else {
-        vec![]
+        update_state(changes, data, statuses, session.args.limits(), clock)
+    };
+    if data.show_debug {
+        data.internals.memory = debug::resident_memory();
+    }
+    let alerts = session.alerts.check(data, &events);
+    events.extend(alerts);
+    let mismatches = session.checksums.update(&events, &mut data.file_groups);
+    events.extend(mismatches);
+    if let Some(signatures) = session.signatures.as_mut() {
+        let invalid = signatures.update(&events, &mut data.file_groups);
+        events.extend(invalid);
+    }
+    if let Some(attribution) = session.attribution.as_mut() {
+        attribution.update(&mut data.file_groups);
+    }
+    let wanted = wants_metadata(data);
+    session.metadata.update(&mut data.file_groups, wanted);
+    data.breached = session.alerts.breached().clone();
+    if let Some(out_of_sync) = session
+        .comparisons
+        .update(&data.file_groups, SystemTime::now())
+    {
+        data.out_of_sync = out_of_sync;
+    }
+    data.lags = session.comparisons.lags().to_vec();
+    if !data.paused {
+        let lagging = session.comparisons.check_lag();
+        events.extend(lagging);
     }
+    let low = session.disks.update(&data.file_groups);
+    events.extend(low);
+    data.disks = session.disks.usage().to_vec();
+    data.low_space = session.disks.warnings();
+    if let Some(mounts) = session.mounts.as_mut() {
+        let changed = mounts.update(&data.file_groups);
+        events.extend(changed);
+        data.unmounted = mounts.unmounted();
+    }
+    if let Some(journal) = session.journal.as_mut() {
+        let units = data
+            .services
+            .iter()
+            .map(ServiceState::name)
+            .collect::<Vec<_>>();
+        let logged = journal.update(&units);
+        events.extend(logged);
+    }
+    data.health = data
+        .services
+        .iter()
+        .map(health::service)
+        .chain(session.alerts.health(&data.file_groups))
+        .chain(session.disks.health())
+        .chain(session.comparisons.health())
+        .chain(session.mounts.iter().flat_map(Mounts::health))
+        .chain(session.journal.iter().flat_map(Journal::health))
+        .collect();
+    if let Some(actions) = session.actions.as_mut().filter(|_| !data.paused) {
+        let done = actions.update(&events);
+        events.extend(done);
+    }
+    if let Some(script) = session.script.as_mut() {
+        if let Err(e) = script.title_groups(&mut data.file_groups) {
+            data.message = Some(e);
+        }
+        let problems;
+        (events, problems) = script.process(events, data);
+        if let Some(problem) = problems.into_iter().last() {
+            data.message = Some(problem);
+        }
+    }
+    if let Some(metrics) = &session.metrics {
+        if let Ok(mut metrics) = metrics.lock() {
+            metrics.update(data, &events);
+        }
+    }
+    if let Some(api) = &session.api {
+        if let Ok(mut api) = api.lock() {
+            api.update(data, &events);
+        }
+    }
+    if let Some(store) = session.store.as_mut() {
+        if let Err(e) = store.update(data, &events) {
+            data.message = Some(format!(
+                "could not record history in {}: {}",
+                store.path().display(),
+                e
+            ));
+        }
+    }
+    for event in &events {
+        let names = session.sinks.iter().map(|sink| sink.name()).collect();
+        let id = data.notifications.raise(event, names, chrono::Utc::now());
+        for (i, sink) in session.sinks.iter_mut().enumerate() {
+            let receipt = match id {
+                Some(id) => Receipt::new(id, i, session.deliveries.clone()),
+                None => Receipt::none(),
+            };
+            sink.send(event, receipt);
+        }
+    }
+    for (id, sink, delivery) in session.delivered.try_iter() {
+        data.notifications.deliver(id, sink, delivery);
+    }
+    if let Some(problem) = session.diagnostics.1.try_iter().last() {
+        data.message = Some(problem);
+    }
+    schedule(data, session);
 }
 
-fn draw_file_item(file: &filesystem::FileItem) -> ListItem {
-    let color = if file.removed.is_none() {
-        Color::Green
-    } else {
-        Color::LightBlue
+/// Ask to be woken when anything time-based is next due; otherwise the loop sleeps until
+/// something happens.
+fn schedule(data: &AppState, session: &mut Session) {
+    let now = Instant::now();
+    let mut due = vec![];
+    if !data.paused {
+        let retention = session.args.limits().retention;
+        due.extend(filesystem::next_expiry(&data.file_groups, retention));
+        due.extend(session.actions.as_ref().and_then(Actions::next_check));
+        due.extend(session.checksums.next_check());
+        due.extend(session.signatures.as_ref().and_then(Signatures::next_check));
+        due.extend(session.comparisons.next_check().map(|wait| now + wait));
+    }
+    due.extend(
+        session
+            .alerts
+            .next_check(data.timezone.now())
+            .map(|wait| now + wait),
+    );
+    // a watch list is only noticed changing by checking it
+    if session.watchlist.is_some() {
+        due.push(now + SERVICE_POLL);
+    }
+    for at in due {
+        session.reactor.wake_at(at);
+    }
+}
+
+/// Compare what's tracked with what's on disk, queueing up any differences as changes.
+fn rescan(
+    groups: &[FileGroup],
+    tx: &Sender<FileChange>,
+    source: &dyn WatchSource,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for change in otlp::traced("rescan", vec![], || filesystem::rescan(groups, source))? {
+        let _ = tx.send(change);
+    }
+    Ok(())
+}
+
+fn handle_control(
+    command: ControlCommand,
+    data: &mut AppState,
+    tx: &Sender<FileChange>,
+    source: &mut dyn WatchSource,
+    session: &mut Session,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        ControlCommand::State => {}
+        ControlCommand::Rescan => rescan(&data.file_groups, tx, source)?,
+        ControlCommand::Pause => data.paused = true,
+        ControlCommand::Resume => data.paused = false,
+        ControlCommand::Add(path) => {
+            let mut args = session.args.clone();
+            args.paths.push(path);
+            sync_groups(&mut data.file_groups, watch_paths(&args)?, source)?;
+            session.args = args;
+        }
+        ControlCommand::Remove(path) => {
+            let root = path
+                .canonicalize()
+                .map_err(|e| format!("{}: {}", path.display(), e))?;
+            let mut args = session.args.clone();
+            args.paths
+                .retain(|p| p.canonicalize().map_or(true, |p| p != root));
+            if args.paths.len() == session.args.paths.len() {
+                return Err(format!("{} is not a watched path", path.display()).into());
+            }
+            sync_groups(&mut data.file_groups, watch_paths(&args)?, source)?;
+            session.args = args;
+        }
+        ControlCommand::Clear(None) => {
+            for group in data.file_groups.iter_mut() {
+                group.clear_removed();
+            }
+        }
+        ControlCommand::Clear(Some(path)) => {
+            let root = path
+                .canonicalize()
+                .map_err(|e| format!("{}: {}", path.display(), e))?;
+            let group = data
+                .file_groups
+                .iter_mut()
+                .find(|group| group.root == root)
+                .ok_or_else(|| format!("{} is not a watched path", path.display()))?;
+            group.clear_removed();
+        }
+        ControlCommand::Tag(path, tag) => set_tag(data, session, &path, tag, true)?,
+        ControlCommand::Untag(path, tag) => set_tag(data, session, &path, tag, false)?,
+        ControlCommand::Profile(name) => {
+            let args = session.profile_args(&name)?;
+            switch_args(args, data, source, session)?;
+            session.profile = Some(name);
+        }
+    }
+    Ok(())
+}
+
+/// Give a tracked file a tag or take it away, remembering its tags in the store if there is one.
+fn set_tag(
+    data: &mut AppState,
+    session: &mut Session,
+    path: &Path,
+    tag: String,
+    on: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let path = path
+        .canonicalize()
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    let item = data
+        .file_groups
+        .iter_mut()
+        .find_map(|group| group.get_mut(&path))
+        .ok_or_else(|| format!("{} is not a tracked file", path.display()))?;
+    let tagged = item.tags.contains(&tag);
+    if on && !tagged {
+        item.tags.push(tag);
+    } else if !on {
+        item.tags.retain(|t| *t != tag);
+    }
+    if let Some(store) = session.store.as_mut() {
+        store.save_tags(&path, &item.tags)?;
+    }
+    Ok(())
+}
+
+/// Start using different settings, e.g. those of another profile.
+fn switch_args(
+    args: WatchArgs,
+    data: &mut AppState,
+    source: &mut dyn WatchSource,
+    session: &mut Session,
+) -> Result<(), Box<dyn std::error::Error>> {
+    sync_groups(&mut data.file_groups, watch_paths(&args)?, source)?;
+    if args.services != session.args.services {
+        data.services = unknown_services(&args.services);
+    }
+    if args.columns != session.args.columns {
+        data.columns = columns(&args);
+        data.show_columns = !args.columns.is_empty();
+    }
+    if args.sort != session.args.sort {
+        data.sort = args.sort.unwrap_or_default();
+        data.sorts.clear();
+    }
+    data.timezone = args.timezone.unwrap_or_default();
+    session.watchlist = args.watchlist.clone().map(Watchlist::new);
+    if let Some(watchlist) = session.watchlist.as_mut() {
+        watchlist.read()?;
+    }
+    session.args = args;
+    Ok(())
+}
+
+/// Read the config file again and switch to what it now says, e.g. on SIGHUP.
+fn reload_config(
+    data: &mut AppState,
+    source: &mut dyn WatchSource,
+    session: &mut Session,
+) -> String {
+    let Some(path) = session
+        .config_path
+        .clone()
+        .filter(|_| session.config.is_some())
+    else {
+        return "no config file to reload".to_string();
     };
-    ListItem::new(display_name(&file.path)).style(Style::default().fg(color))
+    let reloaded = ConfigFile::load(&path)
+        .map_err(Box::<dyn std::error::Error>::from)
+        .and_then(|config| {
+            let mut args = session.base_args.clone();
+            config.merge_into(&mut args, session.profile.as_deref());
+            session.keymap = config.keymap()?;
+            data.highlights = config.highlights()?;
+            data.follow_highlights = config.follow_highlights()?;
+            session.alerts = config.alerts()?;
+            if let Some(journal) = session.journal.as_mut() {
+                journal.set_rules(config.journal_rules()?);
+            }
+            session.comparisons = Comparisons::new(&config.config.compare, session.reactor.waker());
+            session.disks.set_limits(config.config.alerts.free);
+            session.config = Some(config);
+            switch_args(args, data, source, session)
+        });
+    match reloaded {
+        Ok(()) => format!("reloaded {}", path.display()),
+        Err(e) => format!("could not reload {}: {}", path.display(), e),
+    }
+}
+
+/// Act on a signal, returning whether to stop.
+fn handle_signal(
+    signal: Signal,
+    data: &mut AppState,
+    source: &mut dyn WatchSource,
+    session: &mut Session,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    match signal {
+        Signal::Stop => {
+            save_state(data, session)?;
+            Ok(true)
+        }
+        Signal::Reload => {
+            data.message = Some(reload_config(data, source, session));
+            Ok(false)
+        }
+    }
+}
+
+/// Save the tracked files and what the dashboard is in the middle of, to pick up from if it's
+/// cut off.
+fn checkpoint(data: &AppState, session: &mut Session) -> file_task::Result<()> {
+    save_state(data, session)?;
+    if let Some(checkpoints) = session.checkpoints.as_mut() {
+        checkpoints.save(data)?;
+    }
+    Ok(())
+}
+
+/// Keep what's tracked for next time, unless that's disabled.
+fn save_state(data: &AppState, session: &Session) -> file_task::Result<()> {
+    if let Some(path) = &session.state_file {
+        Snapshot::save(path, &data.file_groups)?;
+    }
+    Ok(())
 }
@@ -0,0 +1,485 @@
+use std::io::Write;
+
+use clap::CommandFactory;
+use clap_mangen::Man;
+use itertools::Itertools;
+use roff::{bold, italic, roman, Roff};
+
+use crate::cli::Cli;
+use crate::keymap::Action;
+
+const CONFIG_KEYS: &[(&str, &str)] = &[
+    (
+        "paths",
+        "List of directories to watch, relative to the config file. Each entry is either a path or a table like { path = \"/mnt/x\", title = \"Inbox\", note = \"Paused until Monday\", recursive = true, include = [\"*.csv\"], exclude = [\"*.tmp\"] }, the note being shown under the title and the globs adding to those for every path",
+    ),
+    (
+        "watchlist",
+        "Watch list file to read more paths from, relative to the config file, like --watchlist. Paths given on the command line replace it along with the paths",
+    ),
+    (
+        "state",
+        "File to keep the tracked files in between runs, relative to the config file, like --state",
+    ),
+    (
+        "display",
+        "Table with notifications and health, set to true to open those panes when the dashboard starts rather than toggling them each time",
+    ),
+    (
+        "recursive",
+        "Set to true to watch the files in every path's subdirectories too, like --recursive. They're shown by their path under the watched directory, and a new subdirectory's files are picked up as soon as it appears",
+    ),
+    (
+        "include",
+        "Array of globs for the files to show in every path, like --include. Files are matched by name, or by their path under the watched directory if recursive",
+    ),
+    (
+        "exclude",
+        "Array of globs for the files not to show in any path, like --exclude, e.g. the partial files a producer writes before renaming them into place",
+    ),
+    (
+        "service",
+        "Systemd unit to monitor: a service, or a .mount or .path unit, shown as mounted or armed. Its state is read from systemd over D-Bus as soon as it changes, or with systemctl every 5 seconds without a system bus. Separately, the mount unit under each watched directory is looked at every 30 seconds, and one that isn't mounted raises an alert and marks the group",
+    ),
+    (
+        "services",
+        "Array of systemd units to monitor alongside service, each with its own status in the status bar and its own health check",
+    ),
+    (
+        "timezone",
+        "Time zone for displayed times: an IANA name, UTC, or local",
+    ),
+    (
+        "columns",
+        "Array of columns shown before each file's name, like --columns: size, mtime, and age. With age, each group's title also gives the age of its oldest file",
+    ),
+    (
+        "sort",
+        "Order to list each group's files in, like --sort: found (as discovered), name, mtime (newest first), or size (largest first)",
+    ),
+    (
+        "metrics",
+        "Address to serve Prometheus metrics on, like --metrics",
+    ),
+    (
+        "listen",
+        "Address to serve the JSON API and web dashboard on, like --listen",
+    ),
+    (
+        "grpc",
+        "Address to serve the gRPC API on, like --grpc",
+    ),
+    (
+        "otlp",
+        "Table with endpoint, the base URL of an OpenTelemetry collector's OTLP/HTTP receiver (e.g. \"http://localhost:4318\"), and optional interval (default \"30s\"), service_name (default file_task), and headers. The same metrics as --metrics are exported, along with spans for scans and hook commands",
+    ),
+    (
+        "tmux",
+        "Set to true to keep the tmux option @file_task up to date in daemon mode, like --tmux",
+    ),
+    (
+        "dbus",
+        "Message bus to register on in daemon mode, session or system, like --dbus",
+    ),
+    (
+        "control",
+        "Path of a Unix socket to accept commands on, like --control",
+    ),
+    (
+        "control_addr",
+        "Address to accept state, pause, resume, rescan, and clear on over TCP, like --control-addr",
+    ),
+    (
+        "desktop_notifications.<event>",
+        "Show desktop notifications for an event kind (added, removed, service_down, service_up, alert, action). Each is a table with optional enabled = false and match = \"<glob>\" to only notify for matching file names",
+    ),
+    (
+        "[[webhooks]]",
+        "A URL to POST events to as JSON, with an events table of rules like desktop_notifications, and optional format (json, the default; slack; or discord), timeout_secs (default 10), and retries (default 3)",
+    ),
+    (
+        "mqtt",
+        "Table with host, and optional port (default 1883), client_id, username, password, topic (default \"file_task/{event}\", with the same placeholders as hooks), and retain, plus an events table of rules like desktop_notifications. Events are published as JSON",
+    ),
+    (
+        "alerts.service_down_for",
+        "Raise an alert when a service has been down this long, like \"10m\"",
+    ),
+    (
+        "[[alerts.expect]]",
+        "A file that should exist by a time of day: path (relative to the config file) and by = \"HH:MM\" in the configured time zone. An alert is raised if it's missing then",
+    ),
+    (
+        "[[alerts.counts]]",
+        "How many present files a group should have: group (its title), and min and/or max. An alert is raised when the count goes outside the range, and the group's border is red until it's back within it",
+    ),
+    (
+        "[[alerts.idle]]",
+        "A group that should keep getting new files: group (its title), for (like \"2h\"), and optional from and until (\"HH:MM\" in the configured time zone, all day by default). An alert is raised when no file has been added for that long within those hours",
+    ),
+    (
+        "[[alerts.names]]",
+        "A regular expression for paths that shouldn't turn up: match, and optional group (its title, all groups by default). An alert is raised for each new file whose full path matches, with the path in the event",
+    ),
+    (
+        "[[alerts.journal]]",
+        "A regular expression for lines the services shouldn't log: match, and optional priority, the least severe syslog priority to look at (\"err\" by default, or emerg, alert, crit, warning, notice, info, debug). Each service's journal is read every 10 seconds, and lines matching raise an alert and count against its health check for 5 minutes",
+    ),
+    (
+        "alerts.free",
+        "Table with space and/or inodes, percentages of each group's filesystem that should stay free, like 10. The filesystems are looked at every 30 seconds, and one running low raises an alert and is shown in the status bar until there's room again",
+    ),
+    (
+        "signatures",
+        "Table with keyring (a keyring file as read by gpgv, relative to the config file) and optional groups (titles, all groups by default). Each new file in those groups is checked against a detached FILE.sig or FILE.asc with gpgv once it stops changing, and shown as signed, unsigned, or invalid. A bad signature raises an alert",
+    ),
+    (
+        "attribute",
+        "Set to true to find out which process wrote each new file, like --attribute",
+    ),
+    (
+        "[[compare]]",
+        "Two groups that should hold the same files: source and mirror (their titles), and optional by (name, the default; size; or hash, which also compares SHA-256 digests of files whose names and sizes match). Files in one group without a match in the other are marked with ≠, and each group's title says how many there are. How many source files are waiting to reach the mirror, and for how long, is shown in the status bar and exported as metrics; with max_lag (like \"15m\") an alert is raised when a file has waited longer than that",
+    ),
+    (
+        "email",
+        "Table with server, from, to (a list), and an events table of rules like desktop_notifications, plus optional port, tls (starttls, the default; tls; or none), username, password, and digest (default \"1m\"): events arriving within the digest are sent together as one email",
+    ),
+    (
+        "syslog",
+        "Table with an events table of rules like desktop_notifications, and optional server (udp://host:port or tcp://host:port; the local syslog when not given), facility (default user; e.g. daemon or local0), and severity, a table from event kind to severity (emerg, alert, crit, err, warning, notice, info, or debug) overriding the defaults of info for files, notice for service_up, err for service_down, warning for alert, and info for action",
+    ),
+    (
+        "event_log",
+        "Table with dir (like --event-log, relative to the config file), and optional max_bytes (default 10485760), rotate_after (default \"1d\"), and keep (default 30 old files)",
+    ),
+    (
+        "source",
+        "How to watch the paths, like --source: local (the default) or poll (for network filesystems that don't send events)",
+    ),
+    (
+        "max_items",
+        "Most files to track per group, like --max-items (default 100000). Removed files are dropped first, then the files seen earliest, which are counted as not shown",
+    ),
+    (
+        "max_removed",
+        "Most removed files to keep showing across all groups, like --max-removed (default 10000)",
+    ),
+    (
+        "retention",
+        "How long to keep showing removed files, like --retention, e.g. \"30m\" (default \"1day\")",
+    ),
+    (
+        "script",
+        "Rhai script to filter events, raise alerts, and title groups with, like --script (relative to the config file). See SCRIPTING",
+    ),
+    (
+        "fifo",
+        "Named pipe to write events to, like --fifo (relative to the config file). Each line is the event kind followed by its fields, separated by tabs: group and path for files, service and status for the service, rule and message for alerts, and rule, path, and message for actions",
+    ),
+    (
+        "database",
+        "SQLite database to record history in, like --db (relative to the config file)",
+    ),
+    (
+        "hooks.on_<event>",
+        "Command to run for an event kind (on_added, on_removed, on_service_down, on_service_up, on_alert, on_action). {path}, {group}, {event}, {service}, {status}, {rule}, and {message} are replaced in each word; the command is not run through a shell",
+    ),
+    (
+        "hooks.max_concurrent",
+        "How many hook commands may run at once (default 4)",
+    ),
+    (
+        "[[actions]]",
+        "Something to do with each new file whose name matches a glob (match), once its size and modification time have stayed the same for stable_for (default \"5s\"): exactly one of move or copy (into a directory, with the same placeholders as hooks; relative to the config file), run (a command like a hook), tag (added to the file's user.xdg.tags attribute), or delete = true (to the trash, unless delete_permanently is set). Optional name (default the glob), group (only files in the group with this title), and dry_run = true to only record what would be done, as --dry-run does for all of them. Only the first matching action is used, and each one done is an action event",
+    ),
+    (
+        "delete_permanently",
+        "Delete files outright instead of moving them to the trash (default false)",
+    ),
+    (
+        "highlights",
+        "Array of tables styling file names that match a regular expression (match) with words like \"bold red on black\" (style): modifiers bold, dim, italic, underlined, reversed, and crossed_out, a color name or #rrggbb for the text, and \"on\" a color for the background. Matching rules are layered over the usual colors in order",
+    ),
+    (
+        "follow_highlights",
+        "Array of tables styling the lines of a followed file, like highlights",
+    ),
+    (
+        "profiles.<name>",
+        "Table of paths, service, services, and timezone overriding the top level when selected with --profile",
+    ),
+    (
+        "keys.<action>",
+        "Key to use for an action (see KEYBINDINGS for action names)",
+    ),
+];
+
+const CONTROL_COMMANDS: &[(&str, &str)] = &[
+    (
+        "state",
+        "Reply with the current groups, files, and service as JSON",
+    ),
+    (
+        "rescan",
+        "Compare the watched directories with what's shown and pick up any missed changes",
+    ),
+    ("pause", "Stop applying file and service changes"),
+    (
+        "resume",
+        "Apply changes again, including any held back while paused",
+    ),
+    ("add <path>", "Start watching a directory"),
+    ("remove <path>", "Stop watching a directory"),
+    (
+        "tag <path> <tag>",
+        "Give a tracked file a tag, kept in the --db database if there is one",
+    ),
+    ("untag <path> <tag>", "Take a tag off a tracked file"),
+    (
+        "clear [path]",
+        "Stop showing removed files in a watched directory, or in all of them, without waiting for them to expire",
+    ),
+    (
+        "profile <name>",
+        "Switch to another profile from the config",
+    ),
+];
+
+const API_ENDPOINTS: &[(&str, &str)] = &[
+    (
+        "/",
+        "An HTML dashboard of the groups, service, and recent events, updated live from /api/stream, for a browser",
+    ),
+    (
+        "/api/groups",
+        "Watched groups with their files, and how many more were dropped to stay within max_items",
+    ),
+    ("/api/items", "All files, each with the title of its group"),
+    ("/api/service", "State of the first monitored service, or null"),
+    ("/api/services", "States of all the monitored services"),
+    (
+        "/api/events?since=<cursor>",
+        "Events after the cursor, and the cursor to pass next time. Only recent events are kept",
+    ),
+    (
+        "/api/stream",
+        "A WebSocket sending each new event as a JSON text message, as they happen",
+    ),
+];
+
+const DBUS_MEMBERS: &[(&str, &str)] = &[
+    (
+        "State() -> s",
+        "The current groups, files, and service as JSON, like the control socket's state",
+    ),
+    ("FileAdded(s group, s path)", "Signal for a new file"),
+    ("FileRemoved(s group, s path)", "Signal for a removed file"),
+    (
+        "ServiceChanged(s service, b active, s status)",
+        "Signal for the service starting or stopping",
+    ),
+    ("Alert(s rule, s message)", "Signal for a raised alert"),
+    (
+        "Action(s rule, s path, s message)",
+        "Signal for something an automatic action did",
+    ),
+];
+
+const SCRIPT_FUNCTIONS: &[(&str, &str)] = &[
+    (
+        "filter(event, state)",
+        "Return false to drop the event before it reaches any notification, hook, or sink",
+    ),
+    (
+        "alert(event, state)",
+        "Return a string to raise an alert (rule \"script\") with it as the message",
+    ),
+    (
+        "group_title(root, title)",
+        "Return the title to show for the group watching root, called when the watched paths change",
+    ),
+];
+
+pub fn render(w: &mut dyn Write) -> Result<(), std::io::Error> {
+    let cmd = Cli::command();
+    let man = Man::new(cmd.clone());
+
+    man.render_title(w)?;
+    man.render_name_section(w)?;
+    man.render_synopsis_section(w)?;
+    man.render_description_section(w)?;
+    man.render_options_section(w)?;
+
+    let mut roff = Roff::new();
+    render_subcommand_options(&mut roff, &cmd);
+    render_config(&mut roff);
+    render_keybindings(&mut roff);
+    render_control(&mut roff);
+    render_signals(&mut roff);
+    render_api(&mut roff);
+    render_dbus(&mut roff);
+    render_scripting(&mut roff);
+    roff.to_writer(w)?;
+
+    man.render_version_section(w)?;
+    man.render_authors_section(w)
+}
+
+fn render_subcommand_options(roff: &mut Roff, cmd: &clap::Command) {
+    roff.control("SH", ["COMMANDS"]);
+    for sub in cmd.get_subcommands().filter(|s| !s.is_hide_set()) {
+        roff.control("SS", [sub.get_name()]);
+        if let Some(about) = sub.get_about() {
+            roff.text([roman(about.to_string())]);
+        }
+        for arg in sub.get_arguments().filter(|a| !a.is_hide_set()) {
+            roff.control("TP", []);
+            roff.text(arg_synopsis(arg));
+            if let Some(help) = arg.get_help() {
+                roff.text([roman(help.to_string())]);
+            }
+        }
+    }
+}
+
+fn arg_synopsis(arg: &clap::Arg) -> Vec<roff::Inline> {
+    let value_name = arg
+        .get_value_names()
+        .and_then(|names| names.first())
+        .map(|name| name.to_string())
+        .unwrap_or_else(|| arg.get_id().to_string().to_uppercase());
+
+    let flag = match (arg.get_short(), arg.get_long()) {
+        (Some(short), Some(long)) => format!("-{}, --{}", short, long),
+        (None, Some(long)) => format!("--{}", long),
+        (Some(short), None) => format!("-{}", short),
+        (None, None) => return vec![italic(format!("<{}>", value_name))],
+    };
+
+    if arg.get_action().takes_values() {
+        vec![bold(flag), roman(" "), italic(value_name)]
+    } else {
+        vec![bold(flag)]
+    }
+}
+
+fn render_config(roff: &mut Roff) {
+    roff.control("SH", ["CONFIGURATION"]);
+    roff.text([
+        roman("A TOML config file can be given with "),
+        bold("--config"),
+        roman(", otherwise "),
+        italic("$XDG_CONFIG_HOME/file_task/config.toml"),
+        roman(" (or the platform equivalent) is used if it exists. "),
+        roman("Started in a terminal with no arguments and no config, file_task asks which directories to watch, a service, and how many files to track, and writes them there. "),
+        roman("Command line arguments take precedence over the config. Keys:"),
+    ]);
+    for (key, description) in CONFIG_KEYS {
+        roff.control("TP", []);
+        roff.text([bold(*key)]);
+        roff.text([roman(*description)]);
+    }
+}
+
+fn render_keybindings(roff: &mut Roff) {
+    roff.control("SH", ["KEYBINDINGS"]);
+    for action in Action::ALL {
+        roff.control("TP", []);
+        roff.text([bold(action.default_keys().iter().join(", "))]);
+        roff.text([roman(format!(
+            "{} (action name: {})",
+            action.description(),
+            action.name()
+        ))]);
+    }
+}
+
+fn render_control(roff: &mut Roff) {
+    roff.control("SH", ["CONTROL SOCKET"]);
+    roff.text([
+        roman("With "),
+        bold("--control"),
+        roman(", commands are accepted one per line on a Unix socket (or over TCP with "),
+        bold("--control-addr"),
+        roman("), and each gets a one line reply: ok, error: followed by a reason, or JSON. "),
+        roman("TCP is unauthenticated, so only state, pause, resume, rescan, and clear are "),
+        roman("accepted there. "),
+        roman("Commands:"),
+    ]);
+    for (command, description) in CONTROL_COMMANDS {
+        roff.control("TP", []);
+        roff.text([bold(*command)]);
+        roff.text([roman(*description)]);
+    }
+}
+
+fn render_signals(roff: &mut Roff) {
+    roff.control("SH", ["SIGNALS"]);
+    roff.control("TP", []);
+    roff.text([bold("SIGINT, SIGTERM")]);
+    roff.text([roman(
+        "Save the tracked files (unless --no-state), restore the terminal, and exit cleanly",
+    )]);
+    roff.control("TP", []);
+    roff.text([bold("SIGHUP")]);
+    roff.text([roman(
+        "Read the config file again and switch to its settings for the current profile",
+    )]);
+}
+
+fn render_api(roff: &mut Roff) {
+    roff.control("SH", ["HTTP API"]);
+    roff.text([
+        roman("With "),
+        bold("--listen"),
+        roman(", these read-only endpoints are served, all but / as JSON:"),
+    ]);
+    for (endpoint, description) in API_ENDPOINTS {
+        roff.control("TP", []);
+        roff.text([bold(*endpoint)]);
+        roff.text([roman(*description)]);
+    }
+    roff.control("PP", []);
+    roff.text([
+        roman("With "),
+        bold("--grpc"),
+        roman(", the same is served as the gRPC service file_task.v1.FileTask defined in "),
+        roman("proto/file_task.proto, whose Events method streams each new event."),
+    ]);
+}
+
+fn render_dbus(roff: &mut Roff) {
+    roff.control("SH", ["D-BUS"]);
+    roff.text([
+        roman("With "),
+        bold("--dbus"),
+        roman(" in daemon mode, the name org.file_task.FileTask is owned and the object "),
+        roman("/org/file_task/FileTask implements org.file_task.FileTask1:"),
+    ]);
+    for (member, description) in DBUS_MEMBERS {
+        roff.control("TP", []);
+        roff.text([bold(*member)]);
+        roff.text([roman(*description)]);
+    }
+}
+
+fn render_scripting(roff: &mut Roff) {
+    roff.control("SH", ["SCRIPTING"]);
+    roff.text([
+        roman("A "),
+        bold("--script"),
+        roman(
+            " is written in Rhai and may define any of these functions. An event has the fields ",
+        ),
+        roman(
+            "of its JSON payload, plus size (bytes, for an added file), hour (0-23), and weekday ",
+        ),
+        roman("(1 for Monday to 7). The state is a map like the control socket's state reply:"),
+    ]);
+    for (function, description) in SCRIPT_FUNCTIONS {
+        roff.control("TP", []);
+        roff.text([bold(*function)]);
+        roff.text([roman(*description)]);
+    }
+}
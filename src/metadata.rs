@@ -0,0 +1,74 @@
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use std::time::SystemTime;
+
+use crate::filesystem::{read_metadata, FileGroup};
+use crate::reactor::Waker;
+
+/// How many files are read in one go, so what's read shows up bit by bit on huge groups.
+const BATCH: usize = 1_000;
+
+type Read = (PathBuf, (Option<u64>, Option<SystemTime>));
+
+/// Reads the sizes and modified times a scan leaves out, on a worker thread, so starting on a
+/// huge directory doesn't wait for every file in it to be stat'd. The rows on screen are read
+/// before they're drawn; this fills in the rest while something needs them, like sorting by size.
+pub struct Metadata {
+    paths: Sender<Vec<PathBuf>>,
+    read: Receiver<Vec<Read>>,
+    /// Whether a batch is out being read
+    reading: bool,
+}
+
+impl Metadata {
+    pub fn new(waker: Waker) -> Self {
+        let (paths, rx) = channel::<Vec<PathBuf>>();
+        let (tx, read) = channel();
+        thread::spawn(move || {
+            for batch in rx {
+                let read = batch
+                    .into_iter()
+                    .map(|path| {
+                        let metadata = read_metadata(&path);
+                        (path, metadata)
+                    })
+                    .collect::<Vec<_>>();
+                if tx.send(read).is_err() {
+                    return;
+                }
+                waker.wake();
+            }
+        });
+        Self {
+            paths,
+            read,
+            reading: false,
+        }
+    }
+
+    /// Take in what's been read since the last call, then, if it's `wanted`, send off the next
+    /// batch of files that haven't been read.
+    pub fn update(&mut self, groups: &mut [FileGroup], wanted: bool) {
+        for read in self.read.try_iter() {
+            self.reading = false;
+            for (path, metadata) in read {
+                let group = groups.iter_mut().find(|group| group.get(&path).is_some());
+                if let Some(group) = group {
+                    group.fill_metadata(&path, metadata);
+                }
+            }
+        }
+        if !wanted || self.reading {
+            return;
+        }
+        let batch = groups
+            .iter()
+            .flat_map(FileGroup::unread)
+            .take(BATCH)
+            .collect::<Vec<_>>();
+        if !batch.is_empty() {
+            self.reading = self.paths.send(batch).is_ok();
+        }
+    }
+}
@@ -0,0 +1,285 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use tiny_http::{Header, Response, Server};
+
+use crate::events::{Event, EventKind};
+use crate::filesystem::WATCH_ERRORS;
+use crate::service::ServiceState;
+use crate::AppState;
+
+/// Counts and gauges shared with the metrics server.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    /// Per group title: (files present, removed files still shown)
+    pub files: Vec<(String, usize, usize)>,
+    pub events: HashMap<EventKind, u64>,
+    /// Per service whose status is known: (name, active)
+    pub services: Vec<(String, bool)>,
+    /// Per compared pair: (source, mirror, files waiting, seconds the oldest has waited)
+    pub lags: Vec<(String, String, usize, u64)>,
+    /// Per group title: (free bytes, free inodes if the filesystem counts them)
+    pub free: Vec<(String, u64, Option<u64>)>,
+}
+
+impl Metrics {
+    pub fn update(&mut self, state: &AppState, events: &[Event]) {
+        self.files = state
+            .file_groups
+            .iter()
+            .map(|group| {
+                let removed = group.items().iter().filter(|i| i.removed.is_some()).count();
+                (
+                    crate::group_title(group).to_string(),
+                    group.items().len() - removed,
+                    removed,
+                )
+            })
+            .collect();
+        for event in events {
+            *self.events.entry(event.kind()).or_default() += 1;
+        }
+        self.lags = state
+            .lags
+            .iter()
+            .map(|lag| {
+                (
+                    lag.source.clone(),
+                    lag.mirror.clone(),
+                    lag.pending,
+                    lag.oldest.as_secs(),
+                )
+            })
+            .collect();
+        self.free = state
+            .disks
+            .iter()
+            .map(|usage| {
+                let inodes = (usage.total_inodes > 0).then_some(usage.free_inodes);
+                (usage.group.clone(), usage.free_bytes, inodes)
+            })
+            .collect();
+        self.services = state
+            .services
+            .iter()
+            .filter_map(|service| match service {
+                ServiceState::Details(details) => {
+                    Some((service.name().to_string(), details.active))
+                }
+                ServiceState::Unknown(_) => None,
+            })
+            .collect();
+    }
+
+    /// The metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        let _ = writeln!(
+            out,
+            "# HELP file_task_files Files present in a watched group."
+        );
+        let _ = writeln!(out, "# TYPE file_task_files gauge");
+        for (group, present, _) in &self.files {
+            let _ = writeln!(
+                out,
+                "file_task_files{{group=\"{}\"}} {}",
+                escape(group),
+                present
+            );
+        }
+
+        let _ = writeln!(
+            out,
+            "# HELP file_task_removed_pending Removed files still shown in a group."
+        );
+        let _ = writeln!(out, "# TYPE file_task_removed_pending gauge");
+        for (group, _, removed) in &self.files {
+            let _ = writeln!(
+                out,
+                "file_task_removed_pending{{group=\"{}\"}} {}",
+                escape(group),
+                removed
+            );
+        }
+
+        let _ = writeln!(out, "# HELP file_task_events_total Events seen, by type.");
+        let _ = writeln!(out, "# TYPE file_task_events_total counter");
+        for kind in EventKind::ALL {
+            let count = self.events.get(kind).copied().unwrap_or(0);
+            let _ = writeln!(
+                out,
+                "file_task_events_total{{type=\"{}\"}} {}",
+                kind.name(),
+                count
+            );
+        }
+
+        if !self.services.is_empty() {
+            let _ = writeln!(
+                out,
+                "# HELP file_task_service_up Whether the service is active."
+            );
+            let _ = writeln!(out, "# TYPE file_task_service_up gauge");
+            for (service, active) in &self.services {
+                let _ = writeln!(
+                    out,
+                    "file_task_service_up{{service=\"{}\"}} {}",
+                    escape(service),
+                    u8::from(*active)
+                );
+            }
+        }
+
+        if !self.lags.is_empty() {
+            let _ = writeln!(
+                out,
+                "# HELP file_task_mirror_pending Files in a source group not yet in its mirror."
+            );
+            let _ = writeln!(out, "# TYPE file_task_mirror_pending gauge");
+            for (source, mirror, pending, _) in &self.lags {
+                let _ = writeln!(
+                    out,
+                    "file_task_mirror_pending{{source=\"{}\",mirror=\"{}\"}} {}",
+                    escape(source),
+                    escape(mirror),
+                    pending
+                );
+            }
+            let _ = writeln!(
+                out,
+                "# HELP file_task_mirror_lag_seconds How long the oldest file waiting for a mirror has waited."
+            );
+            let _ = writeln!(out, "# TYPE file_task_mirror_lag_seconds gauge");
+            for (source, mirror, _, lag) in &self.lags {
+                let _ = writeln!(
+                    out,
+                    "file_task_mirror_lag_seconds{{source=\"{}\",mirror=\"{}\"}} {}",
+                    escape(source),
+                    escape(mirror),
+                    lag
+                );
+            }
+        }
+
+        if !self.free.is_empty() {
+            let _ = writeln!(
+                out,
+                "# HELP file_task_free_bytes Bytes free on a group's filesystem."
+            );
+            let _ = writeln!(out, "# TYPE file_task_free_bytes gauge");
+            for (group, bytes, _) in &self.free {
+                let _ = writeln!(
+                    out,
+                    "file_task_free_bytes{{group=\"{}\"}} {}",
+                    escape(group),
+                    bytes
+                );
+            }
+            let _ = writeln!(
+                out,
+                "# HELP file_task_free_inodes Inodes free on a group's filesystem."
+            );
+            let _ = writeln!(out, "# TYPE file_task_free_inodes gauge");
+            for (group, _, inodes) in &self.free {
+                if let Some(inodes) = inodes {
+                    let _ = writeln!(
+                        out,
+                        "file_task_free_inodes{{group=\"{}\"}} {}",
+                        escape(group),
+                        inodes
+                    );
+                }
+            }
+        }
+
+        let _ = writeln!(
+            out,
+            "# HELP file_task_watcher_errors_total Errors reported by the file watcher."
+        );
+        let _ = writeln!(out, "# TYPE file_task_watcher_errors_total counter");
+        let _ = writeln!(
+            out,
+            "file_task_watcher_errors_total {}",
+            WATCH_ERRORS.load(Ordering::Relaxed)
+        );
+
+        out
+    }
+}
+
+fn escape(label: &str) -> String {
+    label
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Serve `/metrics` on a background thread.
+pub fn serve(
+    addr: SocketAddr,
+    shared: Arc<Mutex<Metrics>>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let server =
+        Server::http(addr).map_err(|e| format!("could not serve metrics on {}: {}", addr, e))?;
+
+    thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let response = if request.url() == "/metrics" {
+                let body = shared.lock().map(|m| m.render()).unwrap_or_default();
+                let content_type = Header::from_bytes("Content-Type", "text/plain; version=0.0.4")
+                    .expect("valid header");
+                Response::from_string(body).with_header(content_type)
+            } else {
+                Response::from_string("not found").with_status_code(404)
+            };
+            let _ = request.respond(response);
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_counts_events() {
+        let mut metrics = Metrics {
+            files: vec![("In \"box\"".to_string(), 2, 1)],
+            ..Default::default()
+        };
+        *metrics.events.entry(EventKind::Added).or_default() += 3;
+
+        let text = metrics.render();
+
+        assert!(text.contains("file_task_files{group=\"In \\\"box\\\"\"} 2\n"));
+        assert!(text.contains("file_task_removed_pending{group=\"In \\\"box\\\"\"} 1\n"));
+        assert!(text.contains("file_task_events_total{type=\"added\"} 3\n"));
+        assert!(text.contains("file_task_events_total{type=\"removed\"} 0\n"));
+        assert!(!text.contains("file_task_service_up"));
+        assert!(!text.contains("file_task_mirror_pending"));
+
+        metrics.lags = vec![("src".to_string(), "dst".to_string(), 4, 90)];
+        let text = metrics.render();
+        assert!(text.contains("file_task_mirror_pending{source=\"src\",mirror=\"dst\"} 4\n"));
+        assert!(!text.contains("file_task_free_bytes"));
+
+        metrics.free = vec![("in".to_string(), 4096, None)];
+        let text = metrics.render();
+        assert!(text.contains("file_task_free_bytes{group=\"in\"} 4096\n"));
+        assert!(!text.contains("file_task_free_inodes{"));
+        assert!(text.contains("file_task_mirror_lag_seconds{source=\"src\",mirror=\"dst\"} 90\n"));
+
+        metrics.services = vec![("fetch".to_string(), true), ("upload".to_string(), false)];
+        let text = metrics.render();
+        assert_eq!(text.matches("# TYPE file_task_service_up").count(), 1);
+        assert!(text.contains("file_task_service_up{service=\"fetch\"} 1\n"));
+        assert!(text.contains("file_task_service_up{service=\"upload\"} 0\n"));
+    }
+}
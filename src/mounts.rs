@@ -0,0 +1,191 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::Duration;
+
+use file_task::service::{backing_mount, mount_units, MountUnit};
+
+use crate::events::Event;
+use crate::filesystem::FileGroup;
+use crate::health::Check;
+use crate::reactor::Waker;
+
+/// How often the mount units are looked at.
+const CHECK_EVERY: Duration = Duration::from_secs(30);
+
+/// Keeps an eye on the systemd mount units the groups' roots are on, so a filesystem unmounted
+/// from under a group doesn't just look like an empty, quiet group. systemctl is run in the
+/// background so it never holds up the dashboard.
+pub struct Mounts {
+    roots: Sender<Vec<(String, PathBuf)>>,
+    found: Receiver<Vec<(String, PathBuf, MountUnit)>>,
+    /// Each group's title and root, with the mount unit it's on, for those on one
+    backing: Vec<(String, PathBuf, MountUnit)>,
+    /// The roots last sent to be looked up
+    watched: Vec<(String, PathBuf)>,
+}
+
+impl Mounts {
+    pub fn new(waker: Waker) -> Self {
+        let (roots, rx) = channel::<Vec<(String, PathBuf)>>();
+        let (tx, found) = channel();
+        thread::spawn(move || {
+            let mut watched = vec![];
+            loop {
+                match rx.recv_timeout(CHECK_EVERY) {
+                    Ok(roots) => watched = roots,
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+                // without systemd there's nothing to report
+                let Ok(units) = mount_units() else {
+                    continue;
+                };
+                let backing = watched
+                    .iter()
+                    .filter_map(|(title, root): &(String, PathBuf)| {
+                        let unit = backing_mount(&units, root)?;
+                        Some((title.clone(), root.clone(), unit.clone()))
+                    })
+                    .collect();
+                if tx.send(backing).is_err() {
+                    return;
+                }
+                waker.wake();
+            }
+        });
+        Self {
+            roots,
+            found,
+            backing: vec![],
+            watched: vec![],
+        }
+    }
+
+    /// Look the groups up again if they've changed, take in anything found since the last call,
+    /// and raise an alert for each mount that's gone away or come back.
+    pub fn update(&mut self, groups: &[FileGroup]) -> Vec<Event> {
+        let watched = groups
+            .iter()
+            .map(|group| (crate::group_title(group).to_string(), group.root.clone()))
+            .collect::<Vec<_>>();
+        if watched != self.watched {
+            let _ = self.roots.send(watched.clone());
+            self.watched = watched;
+        }
+
+        let mut alerts = vec![];
+        for backing in self.found.try_iter() {
+            let before = self
+                .backing
+                .iter()
+                .map(|(_, _, unit)| (unit.name.as_str(), unit.mounted))
+                .collect::<HashMap<_, _>>();
+            for (title, _, unit) in &backing {
+                let message = match (before.get(unit.name.as_str()), unit.mounted) {
+                    (Some(true) | None, false) => {
+                        format!(
+                            "{} is not mounted, so {} can't see its files",
+                            unit.name, title
+                        )
+                    }
+                    (Some(false), true) => format!("{} is mounted again", unit.name),
+                    _ => continue,
+                };
+                alerts.push(Event::Alert {
+                    rule: "mount".to_string(),
+                    path: Some(unit.mount_point.clone()),
+                    message,
+                });
+            }
+            self.backing = backing;
+        }
+        alerts
+    }
+
+    /// The roots of groups whose filesystem isn't mounted, with the mount unit.
+    pub fn unmounted(&self) -> HashMap<PathBuf, String> {
+        self.backing
+            .iter()
+            .filter(|(_, _, unit)| !unit.mounted)
+            .map(|(_, root, unit)| (root.clone(), unit.name.clone()))
+            .collect()
+    }
+
+    /// Each mount unit a group is on, and whether it's mounted.
+    pub fn health(&self) -> Vec<Check> {
+        let mut checks: Vec<Check> = vec![];
+        for (_, _, unit) in &self.backing {
+            let name = format!("mount {}", unit.name);
+            if checks.iter().any(|check| check.name == name) {
+                continue;
+            }
+            let detail = if unit.mounted {
+                format!("mounted at {}", unit.mount_point.display())
+            } else {
+                "not mounted".to_string()
+            };
+            checks.push(Check::new(name, unit.mounted, detail));
+        }
+        checks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alert_when_unmounted() {
+        let (roots, _) = channel();
+        let (tx, found) = channel();
+        let mut mounts = Mounts {
+            roots,
+            found,
+            backing: vec![],
+            watched: vec![],
+        };
+        let data = |mounted| {
+            vec![(
+                "Reports".to_string(),
+                PathBuf::from("/srv/data/reports"),
+                MountUnit {
+                    name: "srv-data.mount".to_string(),
+                    mount_point: PathBuf::from("/srv/data"),
+                    mounted,
+                },
+            )]
+        };
+        let messages = |alerts: Vec<Event>| {
+            alerts
+                .into_iter()
+                .map(|alert| alert.summary())
+                .collect::<Vec<_>>()
+        };
+
+        tx.send(data(true)).unwrap();
+        assert!(mounts.update(&[]).is_empty());
+        assert!(mounts.unmounted().is_empty());
+        tx.send(data(false)).unwrap();
+        assert_eq!(
+            messages(mounts.update(&[])),
+            ["srv-data.mount is not mounted, so Reports can't see its files"]
+        );
+        assert_eq!(
+            mounts.unmounted(),
+            HashMap::from([(
+                PathBuf::from("/srv/data/reports"),
+                "srv-data.mount".to_string()
+            )])
+        );
+        assert!(!mounts.health()[0].healthy);
+        tx.send(data(false)).unwrap();
+        assert!(mounts.update(&[]).is_empty());
+        tx.send(data(true)).unwrap();
+        assert_eq!(
+            messages(mounts.update(&[])),
+            ["srv-data.mount is mounted again"]
+        );
+    }
+}
@@ -0,0 +1,78 @@
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::Duration;
+
+use rumqttc::{Client, MqttOptions, QoS};
+
+use crate::config::MqttConfig;
+use crate::events::{Event, EventFilter, Receipt, Sink};
+
+/// Publishes selected events as JSON to an MQTT broker.
+pub struct MqttPublisher {
+    filter: EventFilter,
+    client: Client,
+    topic: String,
+    retain: bool,
+}
+
+impl MqttPublisher {
+    pub fn new(config: &MqttConfig, diagnostics: Sender<String>) -> Result<Self, globset::Error> {
+        let filter = EventFilter::new(&config.events)?;
+
+        let mut options = MqttOptions::new(&config.client_id, &config.host, config.port);
+        options.set_keep_alive(Duration::from_secs(30));
+        if let Some(username) = &config.username {
+            options.set_credentials(username, config.password.as_deref().unwrap_or(""));
+        }
+        let (client, mut connection) = Client::new(options, 64);
+
+        // the connection only makes progress while something polls it
+        let broker = format!("{}:{}", config.host, config.port);
+        thread::spawn(move || {
+            let mut connected = true;
+            for notification in connection.iter() {
+                match notification {
+                    Ok(_) => connected = true,
+                    Err(e) => {
+                        // only report once per outage, it keeps retrying
+                        if connected {
+                            let _ = diagnostics.send(format!("mqtt {} failed: {}", broker, e));
+                        }
+                        connected = false;
+                        thread::sleep(Duration::from_secs(5));
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            filter,
+            client,
+            topic: config.topic.clone(),
+            retain: config.retain,
+        })
+    }
+}
+
+impl Sink for MqttPublisher {
+    fn send(&mut self, event: &Event, receipt: Receipt) {
+        if !self.filter.matches(event) {
+            return;
+        }
+        match serde_json::to_vec(&event.payload()) {
+            // don't block the UI if the broker is unreachable and the queue is full; once it's
+            // queued, the client takes care of getting it to the broker
+            Ok(body) => receipt.result(&self.client.try_publish(
+                event.expand(&self.topic),
+                QoS::AtLeastOnce,
+                self.retain,
+                body,
+            )),
+            Err(e) => receipt.failed(e),
+        }
+    }
+
+    fn name(&self) -> String {
+        "mqtt".to_string()
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::VecDeque;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::events::{Delivery, Event};
+
+/// How many alerts are kept, dropping the oldest past it.
+const KEEP: usize = 500;
+
+/// An alert that was raised, and what each sink did with it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Notification {
+    pub id: u64,
+    pub time: DateTime<Utc>,
+    pub rule: String,
+    pub message: String,
+    /// Each sink's name and what became of the alert there, in the order the sinks were set up
+    pub deliveries: Vec<(String, Delivery)>,
+}
+
+/// The alerts raised since starting, the oldest first.
+#[derive(Debug, Default)]
+pub struct Notifications {
+    raised: VecDeque<Notification>,
+    next: u64,
+}
+
+impl Notifications {
+    /// Keep an alert that's about to go to the named sinks, with the number they report back on
+    /// it by. Other events aren't kept.
+    pub fn raise(&mut self, event: &Event, sinks: Vec<String>, time: DateTime<Utc>) -> Option<u64> {
+        let Event::Alert { rule, message, .. } = event else {
+            return None;
+        };
+        let id = self.next;
+        self.next += 1;
+        if self.raised.len() == KEEP {
+            self.raised.pop_front();
+        }
+        self.raised.push_back(Notification {
+            id,
+            time,
+            rule: rule.clone(),
+            message: message.clone(),
+            deliveries: sinks
+                .into_iter()
+                .map(|sink| (sink, Delivery::Pending))
+                .collect(),
+        });
+        Some(id)
+    }
+
+    /// Note what a sink did with an alert, if it's still kept.
+    pub fn deliver(&mut self, id: u64, sink: usize, delivery: Delivery) {
+        if let Some((_, outcome)) = self
+            .raised
+            .iter_mut()
+            .rev()
+            .find(|n| n.id == id)
+            .and_then(|n| n.deliveries.get_mut(sink))
+        {
+            *outcome = delivery;
+        }
+    }
+
+    /// Bring back alerts kept by an earlier session, numbering new ones after them.
+    pub fn restore(&mut self, alerts: impl IntoIterator<Item = Notification>) {
+        self.raised = alerts.into_iter().collect();
+        while self.raised.len() > KEEP {
+            self.raised.pop_front();
+        }
+        self.next = self.raised.iter().map(|n| n.id + 1).max().unwrap_or(0);
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &Notification> {
+        self.raised.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.raised.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn alert(message: &str) -> Event {
+        Event::Alert {
+            rule: "count".to_string(),
+            path: None,
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn alerts_and_their_deliveries() {
+        let mut notifications = Notifications::default();
+        let sinks = || vec!["webhook".to_string(), "email".to_string()];
+        let added = Event::FileAdded {
+            group: "inbox".to_string(),
+            path: PathBuf::from("/in/a.csv"),
+        };
+        assert_eq!(notifications.raise(&added, sinks(), Utc::now()), None);
+
+        let id = notifications
+            .raise(&alert("too many files"), sinks(), Utc::now())
+            .unwrap();
+        notifications.deliver(id, 1, Delivery::Failed("refused".to_string()));
+        notifications.deliver(id + 1, 0, Delivery::Sent);
+        let deliveries = &notifications.iter().next().unwrap().deliveries;
+        assert_eq!(
+            deliveries,
+            &[
+                ("webhook".to_string(), Delivery::Pending),
+                ("email".to_string(), Delivery::Failed("refused".to_string())),
+            ]
+        );
+
+        for i in 0..KEEP {
+            notifications.raise(&alert(&i.to_string()), vec![], Utc::now());
+        }
+        assert_eq!(notifications.len(), KEEP);
+        assert_eq!(notifications.iter().next().unwrap().message, "0");
+    }
+}
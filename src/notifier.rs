@@ -0,0 +1,44 @@
+use std::sync::mpsc::{channel, Sender};
+use std::thread;
+
+use notify_rust::Notification;
+
+use crate::events::{Event, EventFilter, Receipt, Sink};
+
+/// Shows desktop notifications for selected events.
+pub struct DesktopNotifier {
+    filter: EventFilter,
+    tx: Sender<(Event, Receipt)>,
+}
+
+impl DesktopNotifier {
+    pub fn new(filter: EventFilter) -> Self {
+        let (tx, rx) = channel::<(Event, Receipt)>();
+
+        // showing a notification talks to the notification daemon, so keep it off the UI thread
+        thread::spawn(move || {
+            for (event, receipt) in rx {
+                let shown = Notification::new()
+                    .appname("file_task")
+                    .summary(&event.summary())
+                    .show()
+                    .map(|_| ());
+                receipt.result(&shown);
+            }
+        });
+
+        Self { filter, tx }
+    }
+}
+
+impl Sink for DesktopNotifier {
+    fn send(&mut self, event: &Event, receipt: Receipt) {
+        if self.filter.matches(event) {
+            let _ = self.tx.send((event.clone(), receipt));
+        }
+    }
+
+    fn name(&self) -> String {
+        "desktop notifications".to_string()
+    }
+}
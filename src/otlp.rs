@@ -0,0 +1,318 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde_json::{json, Value};
+use ureq::Agent;
+
+use crate::config::OtlpConfig;
+use crate::events::EventKind;
+use crate::filesystem::WATCH_ERRORS;
+use crate::metrics::Metrics;
+
+/// Whether spans are being collected, so nothing piles up when there's no exporter.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static SPANS: Mutex<Vec<Span>> = Mutex::new(vec![]);
+
+/// A finished piece of work to report as a trace.
+#[derive(Debug)]
+pub struct Span {
+    name: &'static str,
+    start: SystemTime,
+    end: SystemTime,
+    attributes: Vec<(&'static str, String)>,
+    error: Option<String>,
+}
+
+/// Run `work`, recording how long it took and whether it failed as a span when exporting.
+pub fn traced<T, E: ToString>(
+    name: &'static str,
+    attributes: Vec<(&'static str, String)>,
+    work: impl FnOnce() -> Result<T, E>,
+) -> Result<T, E> {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return work();
+    }
+    let start = SystemTime::now();
+    let result = work();
+    let span = Span {
+        name,
+        start,
+        end: SystemTime::now(),
+        attributes,
+        error: result.as_ref().err().map(|e| e.to_string()),
+    };
+    if let Ok(mut spans) = SPANS.lock() {
+        spans.push(span);
+    }
+    result
+}
+
+/// Export the metrics and any spans to an OTLP/HTTP collector on a background thread.
+pub fn start(config: &OtlpConfig, metrics: Arc<Mutex<Metrics>>, diagnostics: Sender<String>) {
+    ENABLED.store(true, Ordering::Relaxed);
+
+    let agent: Agent = Agent::config_builder()
+        .timeout_global(Some(Duration::from_secs(10)))
+        .build()
+        .into();
+    let endpoint = config.endpoint.trim_end_matches('/').to_string();
+    let headers = config.headers.clone();
+    let interval = config.interval.0;
+    let resource = json!({
+        "attributes": [attribute("service.name", &config.service_name)],
+    });
+    let started = SystemTime::now();
+
+    thread::spawn(move || {
+        let mut working = true;
+        loop {
+            thread::sleep(interval);
+
+            let now = SystemTime::now();
+            let mut bodies = vec![];
+            if let Ok(metrics) = metrics.lock() {
+                let body = metrics_json(&metrics, &resource, started, now);
+                bodies.push(("metrics", body));
+            }
+            let spans = SPANS.lock().map(|mut s| s.split_off(0)).unwrap_or_default();
+            if !spans.is_empty() {
+                bodies.push(("traces", traces_json(&spans, &resource)));
+            }
+
+            for (signal, body) in bodies {
+                let url = format!("{}/v1/{}", endpoint, signal);
+                let mut request = agent.post(&url).content_type("application/json");
+                for (name, value) in &headers {
+                    request = request.header(name, value);
+                }
+                match request.send(body.to_string()) {
+                    Ok(_) => working = true,
+                    Err(e) => {
+                        // only report once per outage, it tries again next interval
+                        if working {
+                            let _ = diagnostics.send(format!("otlp {} failed: {}", url, e));
+                        }
+                        working = false;
+                    }
+                }
+            }
+        }
+    });
+}
+
+fn attribute(key: &str, value: &str) -> Value {
+    json!({ "key": key, "value": { "stringValue": value } })
+}
+
+// 64 bit integers are strings in OTLP's JSON encoding
+fn nanos(time: SystemTime) -> String {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+        .to_string()
+}
+
+fn scope() -> Value {
+    json!({ "name": "file_task", "version": env!("CARGO_PKG_VERSION") })
+}
+
+/// The same gauges and counters as the Prometheus endpoint, as an OTLP metrics request.
+fn metrics_json(metrics: &Metrics, resource: &Value, start: SystemTime, now: SystemTime) -> Value {
+    let time = nanos(now);
+    let point = |attributes: Vec<Value>, value: u64| {
+        json!({
+            "attributes": attributes,
+            "startTimeUnixNano": nanos(start),
+            "timeUnixNano": time,
+            "asInt": value.to_string(),
+        })
+    };
+    let gauge = |name: &str, description: &str, points: Vec<Value>| {
+        json!({
+            "name": name,
+            "description": description,
+            "gauge": { "dataPoints": points },
+        })
+    };
+    let counter = |name: &str, description: &str, points: Vec<Value>| {
+        json!({
+            "name": name,
+            "description": description,
+            // cumulative
+            "sum": { "dataPoints": points, "aggregationTemporality": 2, "isMonotonic": true },
+        })
+    };
+
+    let mut exported = vec![
+        gauge(
+            "file_task_files",
+            "Files present in a watched group.",
+            metrics
+                .files
+                .iter()
+                .map(|(group, present, _)| point(vec![attribute("group", group)], *present as u64))
+                .collect(),
+        ),
+        gauge(
+            "file_task_removed_pending",
+            "Removed files still shown in a group.",
+            metrics
+                .files
+                .iter()
+                .map(|(group, _, removed)| point(vec![attribute("group", group)], *removed as u64))
+                .collect(),
+        ),
+        counter(
+            "file_task_events_total",
+            "Events seen, by type.",
+            EventKind::ALL
+                .iter()
+                .map(|kind| {
+                    let count = metrics.events.get(kind).copied().unwrap_or(0);
+                    point(vec![attribute("type", kind.name())], count)
+                })
+                .collect(),
+        ),
+        counter(
+            "file_task_watcher_errors_total",
+            "Errors reported by the file watcher.",
+            vec![point(vec![], WATCH_ERRORS.load(Ordering::Relaxed))],
+        ),
+    ];
+    if !metrics.lags.is_empty() {
+        let pair = |source: &str, mirror: &str| {
+            vec![attribute("source", source), attribute("mirror", mirror)]
+        };
+        exported.push(gauge(
+            "file_task_mirror_pending",
+            "Files in a source group not yet in its mirror.",
+            metrics
+                .lags
+                .iter()
+                .map(|(source, mirror, pending, _)| point(pair(source, mirror), *pending as u64))
+                .collect(),
+        ));
+        exported.push(gauge(
+            "file_task_mirror_lag_seconds",
+            "How long the oldest file waiting for a mirror has waited.",
+            metrics
+                .lags
+                .iter()
+                .map(|(source, mirror, _, lag)| point(pair(source, mirror), *lag))
+                .collect(),
+        ));
+    }
+    if !metrics.free.is_empty() {
+        exported.push(gauge(
+            "file_task_free_bytes",
+            "Bytes free on a group's filesystem.",
+            metrics
+                .free
+                .iter()
+                .map(|(group, bytes, _)| point(vec![attribute("group", group)], *bytes))
+                .collect(),
+        ));
+        exported.push(gauge(
+            "file_task_free_inodes",
+            "Inodes free on a group's filesystem.",
+            metrics
+                .free
+                .iter()
+                .filter_map(|(group, _, inodes)| {
+                    Some(point(vec![attribute("group", group)], (*inodes)?))
+                })
+                .collect(),
+        ));
+    }
+    if !metrics.services.is_empty() {
+        exported.push(gauge(
+            "file_task_service_up",
+            "Whether the service is active.",
+            metrics
+                .services
+                .iter()
+                .map(|(service, active)| {
+                    point(vec![attribute("service", service)], u64::from(*active))
+                })
+                .collect(),
+        ));
+    }
+
+    json!({
+        "resourceMetrics": [{
+            "resource": resource,
+            "scopeMetrics": [{ "scope": scope(), "metrics": exported }],
+        }],
+    })
+}
+
+fn traces_json(spans: &[Span], resource: &Value) -> Value {
+    let spans = spans
+        .iter()
+        .map(|span| {
+            let status = match &span.error {
+                Some(message) => json!({ "code": 2, "message": message }),
+                None => json!({ "code": 1 }),
+            };
+            json!({
+                // each span is its own trace, nothing calls anything else worth following
+                "traceId": format!("{:016x}{:016x}", random(), random()),
+                "spanId": format!("{:016x}", random()),
+                "name": span.name,
+                // internal
+                "kind": 1,
+                "startTimeUnixNano": nanos(span.start),
+                "endTimeUnixNano": nanos(span.end),
+                "attributes": span
+                    .attributes
+                    .iter()
+                    .map(|(key, value)| attribute(key, value))
+                    .collect::<Vec<_>>(),
+                "status": status,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    json!({
+        "resourceSpans": [{
+            "resource": resource,
+            "scopeSpans": [{ "scope": scope(), "spans": spans }],
+        }],
+    })
+}
+
+// std seeds every RandomState differently, which is plenty for ids
+fn random() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metrics_request() {
+        let metrics = Metrics {
+            files: vec![("Inbox".to_string(), 2, 1)],
+            ..Default::default()
+        };
+        let json = metrics_json(&metrics, &json!({}), UNIX_EPOCH, UNIX_EPOCH);
+        let exported = &json["resourceMetrics"][0]["scopeMetrics"][0]["metrics"];
+
+        assert_eq!(exported[0]["name"], "file_task_files");
+        let point = &exported[0]["gauge"]["dataPoints"][0];
+        assert_eq!(point["asInt"], "2");
+        assert_eq!(point["attributes"][0], attribute("group", "Inbox"));
+        assert_eq!(
+            exported[2]["sum"]["dataPoints"].as_array().unwrap().len(),
+            6
+        );
+        // no service_up without a service
+        assert_eq!(exported.as_array().unwrap().len(), 4);
+    }
+}
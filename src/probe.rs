@@ -0,0 +1,116 @@
+use std::process::ExitCode;
+
+use crate::cli::{CheckArgs, WatchArgs};
+use crate::filesystem::WatchPath;
+use crate::service::{service_status, ServiceDetails, ServiceState};
+use crate::store::Store;
+use crate::watchlist::watch_paths;
+
+/// How many events from the history database `status` shows.
+const RECENT_EVENTS: usize = 20;
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+pub fn status(args: WatchArgs) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let timezone = args.timezone.unwrap_or_default();
+    let file_groups = args.source.unwrap_or_default().scan(watch_paths(&args)?)?;
+
+    for group in file_groups.iter() {
+        let title = match &group.title {
+            Some(title) => format!("{} [{}]", group.root.display(), title),
+            None => group.root.display().to_string(),
+        };
+        println!("{} ({} items)", title, group.items().len());
+        for item in group.items().iter() {
+            println!("  {}", item.shown_name());
+        }
+    }
+
+    let store = args.db.as_deref().map(Store::open).transpose()?;
+    for service in args.services.iter() {
+        println!("service {}", describe_service(&service_status(service)));
+        if let Some(transition) = store
+            .as_ref()
+            .map(|s| s.last_transition(service))
+            .transpose()?
+            .flatten()
+        {
+            println!(
+                "  {} since {}",
+                if transition.active { "up" } else { "down" },
+                timezone.local(transition.time).format(TIME_FORMAT)
+            );
+        }
+    }
+
+    if let Some(store) = store {
+        println!("recent events:");
+        for event in store.recent_events(RECENT_EVENTS)? {
+            println!(
+                "  {} {}",
+                timezone.local(event.time).format(TIME_FORMAT),
+                event.summary
+            );
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+pub fn check(args: CheckArgs) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let mut problems = vec![];
+
+    for WatchPath { path, .. } in watch_paths(&args.watch)?.iter() {
+        if !path.is_dir() {
+            problems.push(format!(
+                "watched path {} is not a directory",
+                path.display()
+            ));
+        }
+    }
+
+    for path in args.expect.iter() {
+        if !path.exists() {
+            problems.push(format!("expected file {} is missing", path.display()));
+        }
+    }
+
+    for service in args.watch.services.iter() {
+        let state = service_status(service);
+        if !is_active(&state) {
+            problems.push(format!("service {}", describe_service(&state)));
+        }
+    }
+
+    if problems.is_empty() {
+        println!("OK");
+        Ok(ExitCode::SUCCESS)
+    } else {
+        for problem in problems.iter() {
+            println!("FAIL: {}", problem);
+        }
+        Ok(ExitCode::FAILURE)
+    }
+}
+
+fn is_active(state: &ServiceState) -> bool {
+    matches!(
+        state,
+        ServiceState::Details(ServiceDetails { active: true, .. })
+    )
+}
+
+fn describe_service(state: &ServiceState) -> String {
+    match state {
+        ServiceState::Details(ServiceDetails {
+            status,
+            description: Some(description),
+            ..
+        }) if !status.trim().is_empty() => {
+            format!("{} ({}): {}", state.name(), description, status.trim())
+        }
+        ServiceState::Details(ServiceDetails { status, .. }) if !status.trim().is_empty() => {
+            format!("{}: {}", state.name(), status.trim())
+        }
+        _ => format!("{}: unknown", state.name()),
+    }
+}
@@ -0,0 +1,418 @@
+use std::collections::HashMap;
+use std::io;
+use std::pin::Pin;
+use std::sync::mpsc::{channel, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossterm::event::{self, Event};
+use tokio::runtime::Runtime;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::sync::watch;
+use tokio::task;
+use tokio::time::Interval;
+use tokio_stream::{Stream, StreamExt, StreamMap};
+
+use crate::filesystem::FileChange;
+use crate::service::{service_status, ServiceState, Systemd};
+
+/// Something the main loop should wake up for.
+#[derive(Debug)]
+pub enum Wake {
+    /// A key press, resize, etc.
+    Input(Event),
+    /// File changes are waiting to be applied
+    Files,
+    /// A service's status changed, as checked in the background
+    Service(ServiceState),
+    /// Something done in the background wants a reply or has finished, e.g. a control request
+    Work,
+    Signal(Signal),
+    /// A time asked for with `wake_at` came
+    Timer,
+}
+
+/// What a signal asks for: SIGINT and SIGTERM to stop, SIGHUP to reload the config.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    Stop,
+    Reload,
+}
+
+/// Lets background threads wake the main loop once they've handed it something.
+#[derive(Clone)]
+pub struct Waker(UnboundedSender<Wake>);
+
+impl Waker {
+    pub fn wake(&self) {
+        let _ = self.0.send(Wake::Work);
+    }
+
+    /// Wake for something in particular, e.g. input played back from a recording.
+    pub fn send(&self, wake: Wake) -> bool {
+        self.0.send(wake).is_ok()
+    }
+}
+
+/// Waits on everything the main loop reacts to at once, so file changes, key presses, and
+/// services' changes are handled as soon as they happen, checking the services never holds up
+/// the loop, and nothing runs at all while there's nothing to do.
+pub struct Reactor {
+    runtime: Runtime,
+    tx: UnboundedSender<Wake>,
+    rx: UnboundedReceiver<Wake>,
+    services: watch::Sender<Vec<String>>,
+    /// The latest status of each service that's changed, until they're taken to be applied
+    statuses: Vec<ServiceState>,
+    /// The earliest time asked for since the last wait
+    deadline: Option<Instant>,
+}
+
+impl Reactor {
+    /// Follow the services over D-Bus, or without it check them every `period`.
+    pub fn new(period: Duration) -> Result<Self, Box<dyn std::error::Error>> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let (tx, rx) = unbounded_channel();
+        let (services, names) = watch::channel(vec![]);
+        runtime.spawn(check_services(names, period, tx.clone()));
+
+        Ok(Self {
+            runtime,
+            tx,
+            rx,
+            services,
+            statuses: vec![],
+            deadline: None,
+        })
+    }
+
+    pub fn waker(&self) -> Waker {
+        Waker(self.tx.clone())
+    }
+
+    /// Wake by `at` at the latest during the next wait, e.g. when something is due.
+    pub fn wake_at(&mut self, at: Instant) {
+        self.deadline = Some(self.deadline.map_or(at, |deadline| deadline.min(at)));
+    }
+
+    /// A sender for the file watcher to use, which wakes the loop for each change passed on to
+    /// `changes`.
+    pub fn forward_changes(&self, changes: Sender<FileChange>) -> Sender<FileChange> {
+        let (tx, rx) = channel();
+        let wake = self.tx.clone();
+        thread::spawn(move || {
+            for change in rx {
+                if changes.send(change).is_err() || wake.send(Wake::Files).is_err() {
+                    return;
+                }
+            }
+        });
+        tx
+    }
+
+    /// Wake for terminal input too.
+    pub fn read_input(&self) {
+        let wake = self.tx.clone();
+        thread::spawn(move || {
+            while let Ok(event) = event::read() {
+                if wake.send(Wake::Input(event)).is_err() {
+                    return;
+                }
+            }
+        });
+    }
+
+    /// Wake for signals rather than being killed by them, so there's a chance to clean up.
+    pub fn handle_signals(&self) -> io::Result<()> {
+        let _runtime = self.runtime.enter();
+        let mut interrupt = signal(SignalKind::interrupt())?;
+        let mut terminate = signal(SignalKind::terminate())?;
+        let mut hangup = signal(SignalKind::hangup())?;
+        let wake = self.tx.clone();
+        self.runtime.spawn(async move {
+            loop {
+                let signal = tokio::select! {
+                    Some(()) = interrupt.recv() => Signal::Stop,
+                    Some(()) = terminate.recv() => Signal::Stop,
+                    Some(()) = hangup.recv() => Signal::Reload,
+                    else => return,
+                };
+                if wake.send(Wake::Signal(signal)).is_err() {
+                    return;
+                }
+            }
+        });
+        Ok(())
+    }
+
+    /// Which services to check.
+    pub fn watch_services(&self, services: &[ServiceState]) {
+        self.services.send_if_modified(|current| {
+            let changed = !current.iter().eq(services.iter().map(ServiceState::name));
+            if changed {
+                *current = services.iter().map(|s| s.name().to_string()).collect();
+            }
+            changed
+        });
+    }
+
+    /// The status of each service that's changed since this was last called.
+    pub fn take_service_statuses(&mut self) -> Vec<ServiceState> {
+        std::mem::take(&mut self.statuses)
+    }
+
+    /// Block until something happens or the deadline asked for comes, returning it along with
+    /// anything else that happened by then, so a burst of changes is handled in one go.
+    /// Nothing is returned when services' statuses are all that changed.
+    pub fn wait(&mut self) -> Vec<Wake> {
+        let deadline = self.deadline.take();
+        let first = self.runtime.block_on(async {
+            let timer = async {
+                match deadline {
+                    Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+                    None => std::future::pending().await,
+                }
+            };
+            tokio::select! {
+                Some(wake) = self.rx.recv() => wake,
+                () = timer => Wake::Timer,
+            }
+        });
+
+        let mut wakes = vec![first];
+        while let Ok(wake) = self.rx.try_recv() {
+            wakes.push(wake);
+        }
+        // services' statuses are kept for the next tick rather than handed out
+        wakes
+            .into_iter()
+            .filter_map(|wake| match wake {
+                Wake::Service(status) => {
+                    self.statuses.retain(|s| s.name() != status.name());
+                    self.statuses.push(status);
+                    None
+                }
+                wake => Some(wake),
+            })
+            .collect()
+    }
+}
+
+/// Keep track of the services in the background, only waking the loop for those whose status is
+/// different from last time.
+async fn check_services(
+    mut names: watch::Receiver<Vec<String>>,
+    period: Duration,
+    tx: UnboundedSender<Wake>,
+) {
+    let Ok(mut systemd) = Systemd::connect().await else {
+        poll_services(&mut names, period, &tx, false).await;
+        return;
+    };
+    // losing the bus, e.g. to a daemon-reexec, ends systemd's signals, so the services are
+    // checked with systemctl until it's back
+    while follow_services(&systemd, &mut names, period, &tx).await {
+        match poll_services(&mut names, period, &tx, true).await {
+            Some(reconnected) => systemd = reconnected,
+            None => return,
+        }
+    }
+}
+
+/// Pass on a service's status if it's changed, returning false once the loop has gone away.
+fn report(
+    last: &mut HashMap<String, ServiceState>,
+    status: ServiceState,
+    tx: &UnboundedSender<Wake>,
+) -> bool {
+    if last.get(status.name()) == Some(&status) {
+        return true;
+    }
+    last.insert(status.name().to_string(), status.clone());
+    tx.send(Wake::Service(status)).is_ok()
+}
+
+/// What's next for the services being followed.
+#[derive(Debug, PartialEq, Eq)]
+enum Followed {
+    /// systemd says a unit has changed
+    Changed(String),
+    /// The units that couldn't be subscribed to are due to be checked
+    Poll(Vec<String>),
+    /// A unit's changes ended, as they all do when the connection to the bus is lost
+    Lost,
+}
+
+/// Each unit's changes from systemd, with those that couldn't be subscribed to checked every so
+/// often instead.
+struct Subscriptions {
+    /// Each change, then `None` once there won't be any more
+    changes: StreamMap<String, Pin<Box<dyn Stream<Item = Option<()>> + Send>>>,
+    unsubscribed: Vec<String>,
+    poll: Interval,
+}
+
+impl Subscriptions {
+    fn new(period: Duration) -> Self {
+        Self {
+            changes: StreamMap::new(),
+            unsubscribed: vec![],
+            poll: tokio::time::interval_at(tokio::time::Instant::now() + period, period),
+        }
+    }
+
+    /// Follow a unit's changes, or poll it if there's no subscription to them.
+    fn add(&mut self, name: String, changes: Option<impl Stream<Item = ()> + Send + 'static>) {
+        match changes {
+            Some(changes) => {
+                let changes = changes.map(Some).chain(tokio_stream::once(None));
+                self.changes.insert(name, Box::pin(changes));
+            }
+            None => self.unsubscribed.push(name),
+        }
+    }
+
+    async fn next(&mut self) -> Followed {
+        tokio::select! {
+            Some((name, change)) = self.changes.next() => match change {
+                Some(()) => Followed::Changed(name),
+                None => Followed::Lost,
+            },
+            _ = self.poll.tick(), if !self.unsubscribed.is_empty() => {
+                Followed::Poll(self.unsubscribed.clone())
+            }
+            else => std::future::pending().await,
+        }
+    }
+}
+
+/// Read the services' state from systemd as soon as which services change, then again each
+/// time systemd says one of them has changed. Returns true if the connection is lost, or false
+/// once the loop has gone away.
+async fn follow_services(
+    systemd: &Systemd,
+    names: &mut watch::Receiver<Vec<String>>,
+    period: Duration,
+    tx: &UnboundedSender<Wake>,
+) -> bool {
+    let mut last = HashMap::new();
+    loop {
+        let watched = names.borrow_and_update().clone();
+        let mut subscriptions = Subscriptions::new(period);
+        for name in watched {
+            // listening before reading means no change can slip in between
+            subscriptions.add(name.clone(), systemd.changes(&name).await.ok());
+            if !report(&mut last, systemd.status(&name).await, tx) {
+                return false;
+            }
+        }
+        loop {
+            tokio::select! {
+                followed = subscriptions.next() => match followed {
+                    Followed::Changed(name) => {
+                        if !report(&mut last, systemd.status(&name).await, tx) {
+                            return false;
+                        }
+                    }
+                    Followed::Poll(unsubscribed) => {
+                        if !check(&mut last, unsubscribed, tx).await {
+                            return false;
+                        }
+                    }
+                    Followed::Lost => return true,
+                },
+                changed = names.changed() => {
+                    if changed.is_err() {
+                        return false;
+                    }
+                    last.clear();
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Check services with systemctl, returning false once the loop has gone away.
+async fn check(
+    last: &mut HashMap<String, ServiceState>,
+    names: Vec<String>,
+    tx: &UnboundedSender<Wake>,
+) -> bool {
+    for name in names {
+        let Ok(status) = task::spawn_blocking(move || service_status(&name)).await else {
+            return false;
+        };
+        if !report(last, status, tx) {
+            return false;
+        }
+    }
+    true
+}
+
+/// Check the services with systemctl every `period`, and as soon as which services change.
+/// With `reconnect`, try the bus again each time, returning it once it's back; otherwise this
+/// only returns, with nothing, once the loop has gone away.
+async fn poll_services(
+    names: &mut watch::Receiver<Vec<String>>,
+    period: Duration,
+    tx: &UnboundedSender<Wake>,
+    reconnect: bool,
+) -> Option<Systemd> {
+    let mut last = HashMap::new();
+    loop {
+        let watched = names.borrow_and_update().clone();
+        if !check(&mut last, watched, tx).await {
+            return None;
+        }
+        tokio::select! {
+            () = tokio::time::sleep(period) => {}
+            changed = names.changed() => {
+                if changed.is_err() {
+                    return None;
+                }
+                last.clear();
+            }
+        }
+        if reconnect {
+            if let Ok(systemd) = Systemd::connect().await {
+                return Some(systemd);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn lost_connection() {
+        let mut subscriptions = Subscriptions::new(Duration::from_secs(60));
+        subscriptions.add("a.service".to_string(), Some(tokio_stream::iter([()])));
+
+        assert_eq!(
+            subscriptions.next().await,
+            Followed::Changed("a.service".to_string())
+        );
+        // the bus going away ends the stream
+        assert_eq!(subscriptions.next().await, Followed::Lost);
+    }
+
+    #[tokio::test]
+    async fn poll_unsubscribed() {
+        let mut subscriptions = Subscriptions::new(Duration::from_millis(10));
+        subscriptions.add("a.service".to_string(), Some(tokio_stream::pending()));
+        subscriptions.add("b.service".to_string(), None::<tokio_stream::Pending<()>>);
+
+        for _ in 0..2 {
+            assert_eq!(
+                subscriptions.next().await,
+                Followed::Poll(vec!["b.service".to_string()])
+            );
+        }
+    }
+}
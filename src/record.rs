@@ -0,0 +1,492 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use chrono::{DateTime, Utc};
+use crossterm::event::Event as TermEvent;
+use file_task::clock::{Clock, MockClock};
+use serde::{Deserialize, Serialize};
+
+use crate::filesystem::{FileChange, FileGroup, FileItem, WatchSource};
+use crate::keymap::{Action, Key, KeyMap};
+use crate::service::{ServiceDetails, ServiceState};
+use crate::timezone::Timezone;
+use crate::AppState;
+
+/// Bumped whenever a recording written before couldn't be played back the same way.
+const VERSION: u32 = 1;
+
+/// What the dashboard looked like when recording started, on the first line of a recording.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Header {
+    version: u32,
+    started: DateTime<Utc>,
+    timezone: String,
+    /// The one service monitored, in recordings from before several could be
+    #[serde(default, skip_serializing)]
+    service: Option<String>,
+    #[serde(default)]
+    services: Vec<String>,
+    /// Each bound key and the name of its action, so keys do the same when played back
+    keys: BTreeMap<String, String>,
+    groups: Vec<GroupRecord>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct GroupRecord {
+    root: PathBuf,
+    title: Option<String>,
+    note: Option<String>,
+    hidden: usize,
+    items: Vec<ItemRecord>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ItemRecord {
+    path: PathBuf,
+    observed: DateTime<Utc>,
+    /// How many milliseconds before recording started the file was removed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    removed_before: Option<u64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+}
+
+/// Something the dashboard reacted to, and when, in milliseconds since recording started.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Entry {
+    at: u64,
+    #[serde(flatten)]
+    input: Input,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Input {
+    Files(Vec<Change>),
+    Service(Service),
+    Key(String),
+    Resize(u16, u16),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Change {
+    Added(PathBuf),
+    Removed(PathBuf),
+    Moved(PathBuf, PathBuf),
+    Modified(PathBuf),
+}
+
+/// A service's status, which is unknown without one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Service {
+    name: String,
+    status: Option<String>,
+    #[serde(default)]
+    active: bool,
+}
+
+impl From<&FileChange> for Change {
+    fn from(change: &FileChange) -> Self {
+        match change.clone() {
+            FileChange::Added(path) => Self::Added(path),
+            FileChange::Removed(path) => Self::Removed(path),
+            FileChange::Moved(from, to) => Self::Moved(from, to),
+            FileChange::Modified(path) => Self::Modified(path),
+        }
+    }
+}
+
+impl From<Change> for FileChange {
+    fn from(change: Change) -> Self {
+        match change {
+            Change::Added(path) => Self::Added(path),
+            Change::Removed(path) => Self::Removed(path),
+            Change::Moved(from, to) => Self::Moved(from, to),
+            Change::Modified(path) => Self::Modified(path),
+        }
+    }
+}
+
+impl From<&ServiceState> for Service {
+    fn from(state: &ServiceState) -> Self {
+        match state {
+            ServiceState::Unknown(name) => Self {
+                name: name.clone(),
+                status: None,
+                active: false,
+            },
+            ServiceState::Details(details) => Self {
+                name: state.name().to_string(),
+                status: Some(details.status.clone()),
+                active: details.active,
+            },
+        }
+    }
+}
+
+impl From<Service> for ServiceState {
+    fn from(service: Service) -> Self {
+        match service.status {
+            Some(status) => {
+                Self::Details(ServiceDetails::new(service.name, service.active, status))
+            }
+            None => Self::Unknown(service.name),
+        }
+    }
+}
+
+/// Writes everything the dashboard reacts to, with its timing, as JSON lines, so the session
+/// can be played back exactly with `--play`.
+pub struct Recorder {
+    path: PathBuf,
+    file: BufWriter<File>,
+    start: Instant,
+}
+
+impl Recorder {
+    /// Start recording from the dashboard as it is now.
+    pub fn create(path: &Path, data: &AppState, keymap: &KeyMap) -> Result<Self, String> {
+        let now = Instant::now();
+        let groups = data
+            .file_groups
+            .iter()
+            .map(|group| GroupRecord {
+                root: group.root.clone(),
+                title: group.title.clone(),
+                note: group.note.clone(),
+                hidden: group.hidden,
+                items: group
+                    .items()
+                    .iter()
+                    .map(|item| ItemRecord {
+                        path: group.path(item),
+                        observed: item.observed.into(),
+                        removed_before: item.removed.map(|removed| {
+                            now.saturating_duration_since(removed).as_millis() as u64
+                        }),
+                        tags: item.tags.clone(),
+                    })
+                    .collect(),
+            })
+            .collect();
+        let header = Header {
+            version: VERSION,
+            started: Utc::now(),
+            timezone: data.timezone.to_string(),
+            service: None,
+            services: data.services.iter().map(|s| s.name().to_string()).collect(),
+            keys: keymap
+                .bindings()
+                .map(|(key, action)| (key.to_string(), action.name().to_string()))
+                .collect(),
+            groups,
+        };
+        let file = File::create(path)
+            .map_err(|e| format!("could not create recording {}: {}", path.display(), e))?;
+        let mut recorder = Self {
+            path: path.to_path_buf(),
+            file: BufWriter::new(file),
+            start: now,
+        };
+        recorder.write(&header)?;
+        Ok(recorder)
+    }
+
+    /// Note the file changes and services' statuses about to be applied.
+    pub fn update(
+        &mut self,
+        changes: &[FileChange],
+        statuses: &[ServiceState],
+    ) -> Result<(), String> {
+        if !changes.is_empty() {
+            self.entry(Input::Files(changes.iter().map(Change::from).collect()))?;
+        }
+        for status in statuses {
+            self.entry(Input::Service(status.into()))?;
+        }
+        Ok(())
+    }
+
+    /// Note a key press or resize.
+    pub fn input(&mut self, event: &TermEvent) -> Result<(), String> {
+        match event {
+            TermEvent::Key(key) => self.entry(Input::Key(Key::from(*key).to_string())),
+            TermEvent::Resize(width, height) => self.entry(Input::Resize(*width, *height)),
+            _ => Ok(()),
+        }
+    }
+
+    fn entry(&mut self, input: Input) -> Result<(), String> {
+        let at = self.start.elapsed().as_millis() as u64;
+        self.write(&Entry { at, input })
+    }
+
+    /// Write a line, flushing it so a crash keeps everything up to it.
+    fn write(&mut self, line: &impl Serialize) -> Result<(), String> {
+        serde_json::to_writer(&mut self.file, line)
+            .map_err(io::Error::from)
+            .and_then(|()| writeln!(self.file))
+            .and_then(|()| self.file.flush())
+            .map_err(|e| format!("could not record to {}: {}", self.path.display(), e))
+    }
+}
+
+/// A session recorded with `--record`, to be played back in place of watching. Its entries are
+/// handed to the loop one at a time, in the order they were recorded, with its clock moved up to
+/// when each was recorded, so playing back always ends up the same.
+#[derive(Debug)]
+pub struct Recording {
+    header: Header,
+    entries: Vec<Entry>,
+    /// The next entry to play
+    next: usize,
+    /// What time it was when recording started, by `clock`
+    started: Instant,
+    clock: MockClock,
+    /// When playing back started, to keep to the pace it was recorded at
+    playing: Instant,
+}
+
+impl Recording {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let invalid =
+            |e: &dyn std::fmt::Display| format!("invalid recording {}: {}", path.display(), e);
+        let source = fs::read_to_string(path)
+            .map_err(|e| format!("could not read recording {}: {}", path.display(), e))?;
+        let mut lines = source.lines().filter(|line| !line.trim().is_empty());
+        let header: Header =
+            serde_json::from_str(lines.next().unwrap_or_default()).map_err(|e| invalid(&e))?;
+        if header.version != VERSION {
+            return Err(invalid(&format!(
+                "version {} isn't supported",
+                header.version
+            )));
+        }
+        let entries = lines
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()
+            .map_err(|e| invalid(&e))?;
+        let clock = MockClock::new();
+        Ok(Self {
+            header,
+            entries,
+            next: 0,
+            started: clock.now(),
+            clock,
+            playing: Instant::now(),
+        })
+    }
+
+    /// The groups as they were when recording started, with removed files still going to
+    /// expire when they would have.
+    pub fn groups(&self) -> Vec<FileGroup> {
+        let now = self.clock.now();
+        self.header
+            .groups
+            .iter()
+            .map(|record| {
+                let mut group = FileGroup::new(record.root.clone(), record.title.clone(), vec![]);
+                group.note = record.note.clone();
+                group.hidden = record.hidden;
+                for item in &record.items {
+                    group.insert(FileItem {
+                        name: group.name(&item.path),
+                        removed: item.removed_before.map(|before| {
+                            now.checked_sub(Duration::from_millis(before))
+                                .unwrap_or(now)
+                        }),
+                        observed: SystemTime::from(item.observed),
+                        tags: item.tags.clone(),
+                        verified: None,
+                        signature: None,
+                        writer: None,
+                        size: None,
+                        modified: None,
+                        metadata_read: false,
+                    });
+                }
+                group
+            })
+            .collect()
+    }
+
+    pub fn services(&self) -> Vec<String> {
+        match &self.header.service {
+            Some(service) if self.header.services.is_empty() => vec![service.clone()],
+            _ => self.header.services.clone(),
+        }
+    }
+
+    pub fn timezone(&self) -> Result<Timezone, String> {
+        self.header.timezone.parse()
+    }
+
+    /// The keys as they were bound while recording.
+    pub fn keymap(&self) -> KeyMap {
+        KeyMap::with_overrides(
+            self.header
+                .keys
+                .iter()
+                .filter_map(|(key, action)| Some((Action::from_name(action)?, key.parse().ok()?))),
+        )
+    }
+
+    /// How long the recording runs for.
+    pub fn length(&self) -> Duration {
+        Duration::from_millis(self.entries.last().map_or(0, |entry| entry.at))
+    }
+
+    /// The time as it was while recording, for files to be added and removed by.
+    pub fn clock(&self) -> &dyn Clock {
+        &self.clock
+    }
+
+    /// When the next entry is due to be played, at the pace it was recorded.
+    pub fn due(&self) -> Option<Instant> {
+        let entry = self.entries.get(self.next)?;
+        Some(self.playing + Duration::from_millis(entry.at))
+    }
+
+    /// The file changes or service status recorded next, once it's due, leaving key presses for
+    /// `input`.
+    pub fn changes(&mut self, now: Instant) -> (Vec<FileChange>, Vec<ServiceState>) {
+        let played = self.play(now, |input| {
+            matches!(input, Input::Files(_) | Input::Service(_))
+        });
+        match played {
+            Some(Input::Files(files)) => {
+                (files.into_iter().map(FileChange::from).collect(), vec![])
+            }
+            Some(Input::Service(service)) => (vec![], vec![service.into()]),
+            _ => (vec![], vec![]),
+        }
+    }
+
+    /// The key press or resize recorded next, once it's due.
+    pub fn input(&mut self, now: Instant) -> Option<TermEvent> {
+        match self.play(now, |input| {
+            matches!(input, Input::Key(_) | Input::Resize(..))
+        })? {
+            Input::Key(key) => Some(TermEvent::Key(key.parse::<Key>().ok()?.into())),
+            Input::Resize(width, height) => Some(TermEvent::Resize(width, height)),
+            _ => None,
+        }
+    }
+
+    /// Move on past the next entry if it's due and one of the inputs asked for, with the clock
+    /// moved up to when it was recorded.
+    fn play(&mut self, now: Instant, wanted: impl Fn(&Input) -> bool) -> Option<Input> {
+        if self.due()? > now || !wanted(&self.entries[self.next].input) {
+            return None;
+        }
+        let Entry { at, input } = self.entries[self.next].clone();
+        self.next += 1;
+        let at = self.started + Duration::from_millis(at);
+        self.clock
+            .advance(at.saturating_duration_since(self.clock.now()));
+        Some(input)
+    }
+}
+
+/// Stands in for watching while a recording supplies the changes.
+pub struct Playback;
+
+impl WatchSource for Playback {
+    fn check(&self, _: &Path) -> file_task::Result<()> {
+        Ok(())
+    }
+
+    fn root(&self, path: &Path) -> io::Result<PathBuf> {
+        Ok(path.to_path_buf())
+    }
+
+    fn list(&self, _: &Path, _: bool) -> io::Result<Vec<PathBuf>> {
+        Ok(vec![])
+    }
+
+    fn watch(&mut self, _: &Path, _: bool) -> file_task::Result<()> {
+        Ok(())
+    }
+
+    fn unwatch(&mut self, _: &Path) -> file_task::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    use super::*;
+
+    #[test]
+    fn record_and_play_back() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let path = dir.join("session.ft");
+        let mut group = FileGroup::new(
+            PathBuf::from("/srv/inbox"),
+            Some("Inbox".to_string()),
+            vec![FileItem::new(PathBuf::from("a.csv"))],
+        );
+        group
+            .get_mut(Path::new("/srv/inbox/a.csv"))
+            .unwrap()
+            .removed = Some(Instant::now() - Duration::from_secs(60));
+        let data = AppState {
+            file_groups: vec![group],
+            services: vec![ServiceState::Unknown("nginx".to_string())],
+            ..AppState::default()
+        };
+        let keymap = KeyMap::with_overrides([(Action::Pause, "P".parse().unwrap())]);
+
+        let mut recorder = Recorder::create(&path, &data, &keymap).unwrap();
+        let added = FileChange::Added(PathBuf::from("/srv/inbox/b.csv"));
+        let status = ServiceState::Details(ServiceDetails::new(
+            "nginx".to_string(),
+            true,
+            "running".to_string(),
+        ));
+        recorder
+            .update(std::slice::from_ref(&added), std::slice::from_ref(&status))
+            .unwrap();
+        let key = KeyEvent::new(KeyCode::Char('P'), KeyModifiers::SHIFT);
+        recorder.input(&TermEvent::Key(key)).unwrap();
+
+        let mut recording = Recording::load(&path).unwrap();
+        let groups = recording.groups();
+        assert_eq!(groups[0].title.as_deref(), Some("Inbox"));
+        let item = &groups[0].items()[0];
+        assert!(item.removed.unwrap().elapsed() >= Duration::from_secs(60));
+        assert_eq!(recording.services(), ["nginx"]);
+        assert_eq!(
+            recording.keymap().action("P".parse().unwrap()),
+            Some(Action::Pause)
+        );
+
+        for (entry, at) in recording.entries.iter_mut().zip([100, 250, 400]) {
+            entry.at = at;
+        }
+        let start = recording.clock().now();
+        let due = recording.due().unwrap();
+        assert_eq!(
+            recording.changes(due - Duration::from_millis(1)),
+            (vec![], vec![])
+        );
+        assert_eq!(recording.input(due), None);
+        assert_eq!(recording.changes(due), (vec![added], vec![]));
+        assert_eq!(recording.clock().now() - start, Duration::from_millis(100));
+        let due = recording.due().unwrap();
+        assert_eq!(recording.changes(due), (vec![], vec![status]));
+        assert_eq!(recording.clock().now() - start, Duration::from_millis(250));
+        let due = recording.due().unwrap();
+        assert_eq!(recording.changes(due), (vec![], vec![]));
+        assert_eq!(recording.input(due), Some(TermEvent::Key(key)));
+        assert_eq!(recording.clock().now() - start, Duration::from_millis(400));
+        assert_eq!(recording.due(), None);
+    }
+}
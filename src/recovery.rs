@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+use tui::widgets::ListState;
+
+use crate::app::NoteInput;
+use crate::events::Delivery;
+use crate::filesystem::Sort;
+use crate::notifications::Notification;
+use crate::AppState;
+
+/// How often the dashboard's state is checkpointed while it runs.
+pub const CHECKPOINT_EVERY: Duration = Duration::from_secs(15);
+
+/// What the dashboard was in the middle of, beyond the tracked files the state file keeps. It's
+/// checkpointed while the dashboard runs and removed when it exits cleanly, so finding one at
+/// startup means the last session was cut off, e.g. by a dropped SSH connection.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Recovery {
+    saved: DateTime<Utc>,
+    /// The process that saved it, which is still running if this isn't left over
+    pid: u32,
+    paused: bool,
+    tag_filter: Option<String>,
+    show_notifications: bool,
+    /// The group and text of a note that was being typed
+    note: Option<(usize, String)>,
+    alerts: Vec<Notification>,
+    /// The group the selection keys moved in
+    #[serde(default)]
+    focus: usize,
+    /// Each group's selected file and how far it was scrolled, by root
+    #[serde(default)]
+    selections: Vec<(PathBuf, Option<usize>, usize)>,
+    /// Groups listed in another order than the rest, by root
+    #[serde(default)]
+    sorts: HashMap<PathBuf, Sort>,
+}
+
+impl Recovery {
+    fn of(data: &AppState) -> Self {
+        Self {
+            saved: Utc::now(),
+            pid: std::process::id(),
+            paused: data.paused,
+            tag_filter: data.tag_filter.clone(),
+            show_notifications: data.show_notifications,
+            note: data
+                .note_input
+                .as_ref()
+                .map(|input| (input.group, input.text.clone())),
+            alerts: data.notifications.iter().cloned().collect(),
+            focus: data.focus,
+            selections: data
+                .selections
+                .iter()
+                .map(|(root, selection)| (root.clone(), selection.selected(), selection.offset()))
+                .collect(),
+            sorts: data.sorts.clone(),
+        }
+    }
+
+    /// Read a recovery file left by a session that didn't exit cleanly, if there is one.
+    pub fn load(path: &Path) -> Result<Option<Self>, String> {
+        let recovery: Self = match fs::read_to_string(path) {
+            Ok(source) => serde_json::from_str(&source)
+                .map_err(|e| format!("invalid recovery file {}: {}", path.display(), e))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                return Err(format!(
+                    "could not read recovery file {}: {}",
+                    path.display(),
+                    e
+                ))
+            }
+        };
+        // another dashboard using the same state file is still running
+        let running = unsafe { libc::kill(recovery.pid as libc::pid_t, 0) } == 0
+            && recovery.pid != std::process::id();
+        Ok((!running).then_some(recovery))
+    }
+
+    /// Pick up where the session left off.
+    pub fn restore(self, data: &mut AppState) {
+        data.paused = self.paused;
+        data.tag_filter = self.tag_filter;
+        data.show_notifications = self.show_notifications;
+        data.note_input = self
+            .note
+            .filter(|(group, _)| *group < data.file_groups.len())
+            .map(|(group, text)| NoteInput { group, text });
+        let alerts = self.alerts.into_iter().map(|mut alert| {
+            // nothing will report back on these now
+            for (_, delivery) in &mut alert.deliveries {
+                if *delivery == Delivery::Pending {
+                    *delivery = Delivery::Failed("file_task exited first".to_string());
+                }
+            }
+            alert
+        });
+        data.notifications.restore(alerts);
+        data.focus = self.focus;
+        data.selections = self
+            .selections
+            .into_iter()
+            .map(|(root, selected, offset)| {
+                let selection = ListState::default()
+                    .with_selected(selected)
+                    .with_offset(offset);
+                (root, selection)
+            })
+            .collect();
+        data.sorts = self.sorts;
+        data.message = Some(format!(
+            "restored the session from {}",
+            self.saved.with_timezone(&Local).format("%H:%M")
+        ));
+    }
+
+    /// Ask whether to restore, when there's a terminal to ask on.
+    pub fn offer(&self) -> io::Result<bool> {
+        if !io::stdin().is_terminal() {
+            return Ok(false);
+        }
+        print!(
+            "The last session didn't exit cleanly (checkpointed at {}). Restore it? [Y/n] ",
+            self.saved.with_timezone(&Local).format("%H:%M:%S")
+        );
+        io::stdout().flush()?;
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+        Ok(!answer.trim().eq_ignore_ascii_case("n"))
+    }
+}
+
+/// Writes the dashboard's recovery file every so often.
+pub struct Checkpoints {
+    path: PathBuf,
+    due: Instant,
+}
+
+impl Checkpoints {
+    /// Checkpoint to the recovery file kept alongside a state file.
+    pub fn new(state_file: &Path) -> Self {
+        Self {
+            path: state_file.with_file_name("recovery.json"),
+            due: Instant::now() + CHECKPOINT_EVERY,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// When the next checkpoint is.
+    pub fn due(&self) -> Instant {
+        self.due
+    }
+
+    pub fn save(&mut self, data: &AppState) -> file_task::Result<()> {
+        self.due = Instant::now() + CHECKPOINT_EVERY;
+        // write then rename, so a crash mid-write doesn't lose the last checkpoint
+        let temp = self.path.with_extension("json.tmp");
+        serde_json::to_string(&Recovery::of(data))
+            .map_err(io::Error::other)
+            .and_then(|json| fs::write(&temp, json))
+            .and_then(|()| fs::rename(&temp, &self.path))
+            .map_err(|source| file_task::Error::Save {
+                path: self.path.clone(),
+                source,
+            })
+    }
+
+    /// Remove the recovery file, on a clean exit.
+    pub fn finish(&self) -> file_task::Result<()> {
+        match fs::remove_file(&self.path) {
+            Err(source) if source.kind() != io::ErrorKind::NotFound => {
+                Err(file_task::Error::Save {
+                    path: self.path.clone(),
+                    source,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::events::Event;
+    use crate::filesystem::FileGroup;
+
+    #[test]
+    fn checkpoint_and_restore() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let mut checkpoints = Checkpoints::new(&dir.join("state.json"));
+        let mut data = AppState {
+            file_groups: vec![FileGroup::new(PathBuf::from("/srv/inbox"), None, vec![])],
+            paused: true,
+            tag_filter: Some("urgent".to_string()),
+            note_input: Some(NoteInput {
+                group: 0,
+                text: "waiting on the vend".to_string(),
+            }),
+            focus: 1,
+            selections: HashMap::from([(
+                PathBuf::from("/srv/inbox"),
+                ListState::default().with_selected(Some(40)).with_offset(30),
+            )]),
+            sorts: HashMap::from([(PathBuf::from("/srv/inbox"), Sort::Size)]),
+            ..AppState::default()
+        };
+        let event = Event::Alert {
+            rule: "count".to_string(),
+            path: None,
+            message: "inbox has 12 files".to_string(),
+        };
+        let id = data
+            .notifications
+            .raise(
+                &event,
+                vec!["webhook".to_string(), "email".to_string()],
+                Utc::now(),
+            )
+            .unwrap();
+        data.notifications.deliver(id, 0, Delivery::Sent);
+        checkpoints.save(&data).unwrap();
+
+        // it's this process that saved it, so it counts as left over
+        let recovery = Recovery::load(checkpoints.path()).unwrap().unwrap();
+        let mut restored = AppState {
+            file_groups: vec![FileGroup::new(PathBuf::from("/srv/inbox"), None, vec![])],
+            ..AppState::default()
+        };
+        recovery.restore(&mut restored);
+        assert!(restored.paused);
+        assert_eq!(restored.tag_filter.as_deref(), Some("urgent"));
+        assert_eq!(restored.note_input.unwrap().text, "waiting on the vend");
+        assert_eq!(restored.focus, 1);
+        let selection = &restored.selections[Path::new("/srv/inbox")];
+        assert_eq!((selection.selected(), selection.offset()), (Some(40), 30));
+        assert_eq!(restored.sorts[Path::new("/srv/inbox")], Sort::Size);
+        let alert = restored.notifications.iter().next().unwrap();
+        assert_eq!(
+            alert.deliveries[1].1,
+            Delivery::Failed("file_task exited first".to_string())
+        );
+        assert_eq!(
+            restored.notifications.raise(&event, vec![], Utc::now()),
+            Some(id + 1)
+        );
+
+        checkpoints.finish().unwrap();
+        assert!(Recovery::load(checkpoints.path()).unwrap().is_none());
+    }
+}
@@ -0,0 +1,191 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{Datelike, Timelike};
+use rhai::{Dynamic, Engine, Map, Scope, AST};
+use serde_json::json;
+
+use crate::api::{groups_json, service_json, services_json};
+use crate::events::Event;
+use crate::filesystem::FileGroup;
+use crate::AppState;
+
+/// A user script that can drop events, raise alerts for them, and retitle groups. Each of its
+/// functions is optional:
+///
+/// - `filter(event, state)` returns false to drop an event before it reaches any sink
+/// - `alert(event, state)` returns a message to raise an alert about the event
+/// - `group_title(root, title)` returns the title to show for a group
+///
+/// The state is a map like the control socket's `state` reply.
+pub struct Script {
+    path: PathBuf,
+    engine: Engine,
+    ast: AST,
+    /// Roots of the groups as of the last retitling, to only do it again when they change
+    titled: Vec<PathBuf>,
+}
+
+impl Script {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let engine = Engine::new();
+        let ast = engine
+            .compile_file(path.to_path_buf())
+            .map_err(|e| format!("could not load script {}: {}", path.display(), e))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            engine,
+            ast,
+            titled: vec![],
+        })
+    }
+
+    fn has(&self, name: &str) -> bool {
+        self.ast.iter_functions().any(|f| f.name == name)
+    }
+
+    fn call(&self, name: &str, args: impl rhai::FuncArgs) -> Result<Dynamic, String> {
+        self.engine
+            .call_fn::<Dynamic>(&mut Scope::new(), &self.ast, name, args)
+            .map_err(|e| format!("script {}: {}: {}", self.path.display(), name, e))
+    }
+
+    /// Filter the events of one tick and add any alerts the script raises for them.
+    pub fn process(&self, events: Vec<Event>, state: &AppState) -> (Vec<Event>, Vec<String>) {
+        if !self.has("filter") && !self.has("alert") {
+            return (events, vec![]);
+        }
+        let state_map = rhai::serde::to_dynamic(json!({
+            "paused": state.paused,
+            "service": service_json(state.services.first()),
+            "services": services_json(&state.services),
+            "groups": groups_json(&state.file_groups),
+        }))
+        .unwrap_or_default();
+        let mut kept = vec![];
+        let mut alerts = vec![];
+        let mut problems = vec![];
+
+        for event in events {
+            let map = Dynamic::from_map(event_map(&event, state));
+            if self.has("filter") {
+                match self.call("filter", (map.clone(), state_map.clone())) {
+                    Ok(keep) if keep.as_bool() == Ok(false) => continue,
+                    Ok(_) => {}
+                    Err(e) => problems.push(e),
+                }
+            }
+            // alerts are only raised about other events, so they can't feed back into this
+            if self.has("alert") && !matches!(event, Event::Alert { .. }) {
+                match self.call("alert", (map, state_map.clone())) {
+                    Ok(message) if message.is_string() => alerts.push(Event::Alert {
+                        rule: "script".to_string(),
+                        path: None,
+                        message: message.to_string(),
+                    }),
+                    Ok(_) => {}
+                    Err(e) => problems.push(e),
+                }
+            }
+            kept.push(event);
+        }
+
+        kept.extend(alerts);
+        (kept, problems)
+    }
+
+    /// Give each group the title the script picks for it, if the watched groups have changed.
+    pub fn title_groups(&mut self, groups: &mut [FileGroup]) -> Result<(), String> {
+        let roots = groups.iter().map(|g| g.root.clone()).collect::<Vec<_>>();
+        if !self.has("group_title") || roots == self.titled {
+            return Ok(());
+        }
+        self.titled = roots;
+        for group in groups.iter_mut() {
+            let title = self.call(
+                "group_title",
+                (
+                    group.root.to_string_lossy().to_string(),
+                    crate::group_title(group).to_string(),
+                ),
+            )?;
+            if title.is_string() {
+                group.title = Some(title.to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An event as the script sees it: the same fields as its JSON payload, plus the size of an
+/// added file and the hour (0-23) and weekday (1 for Monday to 7) it happened in.
+fn event_map(event: &Event, state: &AppState) -> Map {
+    let mut map = rhai::serde::to_dynamic(event.payload())
+        .ok()
+        .and_then(|d| d.try_cast::<Map>())
+        .unwrap_or_default();
+
+    let size = match event {
+        Event::FileAdded { path, .. } => fs::metadata(path).ok().map(|m| m.len() as i64),
+        _ => None,
+    };
+    map.insert("size".into(), size.map_or(Dynamic::UNIT, Dynamic::from));
+
+    let now = state.timezone.now();
+    map.insert("hour".into(), (now.hour() as i64).into());
+    map.insert(
+        "weekday".into(),
+        (now.weekday().number_from_monday() as i64).into(),
+    );
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    fn script(source: &str) -> Script {
+        let mut file = tempfile::Builder::new().suffix(".rhai").tempfile().unwrap();
+        file.write_all(source.as_bytes()).unwrap();
+        Script::load(file.path()).unwrap()
+    }
+
+    #[test]
+    fn filter_and_alert() {
+        let script = script(
+            r#"
+            fn filter(e, state) { e.event != "removed" }
+            fn alert(e, state) { if e.path.ends_with(".pdf") { `pdf in ${e.group}` } }
+            "#,
+        );
+        let added = |name: &str| Event::FileAdded {
+            group: "In".to_string(),
+            path: PathBuf::from("/nonexistent").join(name),
+        };
+        let removed = Event::FileRemoved {
+            group: "In".to_string(),
+            path: PathBuf::from("/nonexistent/b"),
+        };
+
+        let (events, problems) = script.process(
+            vec![added("a.pdf"), removed, added("c.txt")],
+            &AppState::default(),
+        );
+
+        assert!(problems.is_empty(), "{:?}", problems);
+        assert_eq!(
+            events,
+            vec![
+                added("a.pdf"),
+                added("c.txt"),
+                Event::Alert {
+                    rule: "script".to_string(),
+                    path: None,
+                    message: "pdf in In".to_string(),
+                },
+            ]
+        );
+    }
+}
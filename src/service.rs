@@ -1,6 +1,12 @@
+use std::io;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Output};
 
-#[derive(Debug, PartialEq, Eq)]
+use tokio_stream::{Stream, StreamExt};
+use zbus::zvariant::OwnedObjectPath;
+use zbus::Connection;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ServiceState {
     Unknown(String),
     Details(ServiceDetails),
@@ -14,15 +20,23 @@ impl ServiceState {
             let output = maybe_output?;
             let status = String::from_utf8(output.stdout).ok()?;
             let active = output.status.success();
-            Some(ServiceState::Details(ServiceDetails {
-                name: name.to_string(),
-                status,
+            Some(ServiceState::Details(ServiceDetails::new(
+                name.to_string(),
                 active,
-            }))
+                status,
+            )))
         })()
         .unwrap_or_else(|| ServiceState::Unknown(name.to_string()))
     }
 
+    /// Whether the service is running, if that's known.
+    pub fn active(&self) -> Option<bool> {
+        match self {
+            Self::Unknown(_) => None,
+            Self::Details(ServiceDetails { active, .. }) => Some(*active),
+        }
+    }
+
     pub fn name(&self) -> &str {
         match self {
             Self::Unknown(name) => name,
@@ -31,18 +45,30 @@ impl ServiceState {
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ServiceDetails {
     name: String,
     pub active: bool,
     pub status: String,
+    /// The unit's own description of itself, when it was asked over D-Bus
+    pub description: Option<String>,
 }
 
-pub fn update_service_status(current: Option<&ServiceState>) -> Option<ServiceState> {
-    current.map(|s| service_status(s.name()))
+impl ServiceDetails {
+    pub fn new(name: String, active: bool, status: String) -> Self {
+        Self {
+            name,
+            active,
+            status,
+            description: None,
+        }
+    }
 }
 
 pub fn service_status(unit: &str) -> ServiceState {
+    if unit.ends_with(".mount") || unit.ends_with(".path") {
+        return unit_status(unit);
+    }
     let output = Command::new("systemctl")
         .args(["is-active", unit])
         .output()
@@ -50,3 +76,379 @@ pub fn service_status(unit: &str) -> ServiceState {
 
     ServiceState::from(unit, output)
 }
+
+/// The state of a mount or path unit, which `is-active` alone doesn't tell apart: a path unit
+/// is only watching for its trigger while it's waiting.
+fn unit_status(unit: &str) -> ServiceState {
+    Command::new("systemctl")
+        .args(["show", "--property=ActiveState,SubState", unit])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| {
+            let (active, status) = describe_unit(unit, &String::from_utf8(output.stdout).ok()?)?;
+            Some(ServiceState::Details(ServiceDetails::new(
+                unit.to_string(),
+                active,
+                status,
+            )))
+        })
+        .unwrap_or_else(|| ServiceState::Unknown(unit.to_string()))
+}
+
+/// Whether a mount or path unit is doing its job, and what to call its state, from the
+/// properties `systemctl show` gives.
+fn describe_unit(unit: &str, properties: &str) -> Option<(bool, String)> {
+    let property = |name: &str| {
+        properties
+            .lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix('='))
+    };
+    Some(describe_state(
+        unit,
+        property("ActiveState")?,
+        property("SubState")?,
+    ))
+}
+
+/// Whether a unit is doing its job, and what to call its state, from its active and sub states.
+/// A service is up while it's active, whatever it's doing, but a mount or path unit needs to be
+/// mounted or watching.
+fn describe_state(unit: &str, state: &str, sub: &str) -> (bool, String) {
+    if !unit.ends_with(".mount") && !unit.ends_with(".path") {
+        let active = matches!(state, "active" | "reloading");
+        return (active, format!("{} ({})", state, sub));
+    }
+    match sub {
+        "mounted" if unit.ends_with(".mount") => (true, "mounted".to_string()),
+        "waiting" if unit.ends_with(".path") => (true, "armed".to_string()),
+        "running" if unit.ends_with(".path") => (true, "triggered".to_string()),
+        _ if unit.ends_with(".mount") && state == "inactive" => (false, "not mounted".to_string()),
+        _ => (false, format!("{} ({})", state, sub)),
+    }
+}
+
+#[zbus::proxy(
+    interface = "org.freedesktop.systemd1.Manager",
+    default_service = "org.freedesktop.systemd1",
+    default_path = "/org/freedesktop/systemd1",
+    gen_blocking = false
+)]
+trait Manager {
+    fn load_unit(&self, name: &str) -> zbus::Result<OwnedObjectPath>;
+
+    fn subscribe(&self) -> zbus::Result<()>;
+}
+
+#[zbus::proxy(
+    interface = "org.freedesktop.systemd1.Unit",
+    default_service = "org.freedesktop.systemd1",
+    gen_blocking = false
+)]
+trait Unit {
+    #[zbus(property)]
+    fn active_state(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn sub_state(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn description(&self) -> zbus::Result<String>;
+}
+
+/// systemd's manager on the system bus, which units' state is read from directly rather than by
+/// running systemctl, and which says as soon as a unit's state changes instead of having to be
+/// asked again.
+#[derive(Clone)]
+pub struct Systemd {
+    connection: Connection,
+}
+
+impl Systemd {
+    /// Connect to the system bus, asking systemd to send out signals as units change.
+    pub async fn connect() -> zbus::Result<Self> {
+        let connection = Connection::system().await?;
+        ManagerProxy::new(&connection).await?.subscribe().await?;
+        Ok(Self { connection })
+    }
+
+    /// A unit's object, loaded if it isn't already, with its properties kept up to date from
+    /// the PropertiesChanged signals it sends.
+    async fn unit(&self, unit: &str) -> zbus::Result<UnitProxy<'static>> {
+        let path = ManagerProxy::new(&self.connection)
+            .await?
+            .load_unit(unit)
+            .await?;
+        UnitProxy::builder(&self.connection)
+            .path(path)?
+            .build()
+            .await
+    }
+
+    pub async fn status(&self, unit: &str) -> ServiceState {
+        let details = async {
+            let proxy = self.unit(unit).await?;
+            let (state, sub) = (proxy.active_state().await?, proxy.sub_state().await?);
+            let (active, status) = describe_state(unit, &state, &sub);
+            Ok::<_, zbus::Error>(ServiceDetails {
+                description: proxy.description().await.ok(),
+                ..ServiceDetails::new(unit.to_string(), active, status)
+            })
+        };
+        match details.await {
+            Ok(details) => ServiceState::Details(details),
+            Err(_) => ServiceState::Unknown(unit.to_string()),
+        }
+    }
+
+    /// Yields each time a unit's active or sub state changes, until the connection is lost.
+    pub async fn changes(&self, unit: &str) -> zbus::Result<impl Stream<Item = ()> + Unpin> {
+        let proxy = self.unit(unit).await?;
+        let active = proxy.receive_active_state_changed().await.map(|_| ());
+        let sub = proxy.receive_sub_state_changed().await.map(|_| ());
+        Ok(active.merge(sub))
+    }
+}
+
+/// A systemd mount unit, whether or not it's mounted right now.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MountUnit {
+    pub name: String,
+    pub mount_point: PathBuf,
+    pub mounted: bool,
+}
+
+/// Every mount unit systemd knows about, including those from fstab that aren't mounted.
+pub fn mount_units() -> io::Result<Vec<MountUnit>> {
+    let output = Command::new("systemctl")
+        .args([
+            "list-units",
+            "--type=mount",
+            "--all",
+            "--plain",
+            "--no-legend",
+        ])
+        .output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(io::Error::other(
+            stderr.lines().next().unwrap_or_default().to_string(),
+        ));
+    }
+    Ok(parse_mount_units(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Mount units from `systemctl list-units` lines: the unit, its load, active, and sub states,
+/// then its description.
+fn parse_mount_units(list: &str) -> Vec<MountUnit> {
+    list.lines()
+        .filter_map(|line| {
+            let mut columns = line.split_whitespace();
+            let name = columns.next()?;
+            let sub = columns.nth(2)?;
+            Some(MountUnit {
+                name: name.to_string(),
+                mount_point: unescape_path(name.strip_suffix(".mount")?),
+                mounted: sub == "mounted",
+            })
+        })
+        .collect()
+}
+
+/// The path a unit name stands for, undoing `systemd-escape --path`.
+fn unescape_path(name: &str) -> PathBuf {
+    if name == "-" {
+        return PathBuf::from("/");
+    }
+    let mut bytes = vec![b'/'];
+    let mut rest = name.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        rest = tail;
+        let escaped = (byte == b'\\' && rest.first() == Some(&b'x'))
+            .then(|| std::str::from_utf8(rest.get(1..3)?).ok())
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (byte, escaped) {
+            (_, Some(escaped)) => {
+                bytes.push(escaped);
+                rest = &rest[3..];
+            }
+            (b'-', None) => bytes.push(b'/'),
+            (byte, None) => bytes.push(byte),
+        }
+    }
+    PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// The mount unit a path is on, other than the root filesystem's: the one with the deepest
+/// mount point above it, mounted or not.
+pub fn backing_mount<'a>(units: &'a [MountUnit], path: &Path) -> Option<&'a MountUnit> {
+    units
+        .iter()
+        .filter(|unit| unit.mount_point != Path::new("/") && path.starts_with(&unit.mount_point))
+        .max_by_key(|unit| unit.mount_point.components().count())
+}
+
+/// A line a unit logged to the journal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JournalLine {
+    /// Its syslog priority, 0 (emerg) to 7 (debug)
+    pub priority: u8,
+    pub message: String,
+}
+
+/// Where to pick up reading a unit's journal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JournalFrom {
+    /// Everything logged since a time, as seconds since the epoch
+    Since(u64),
+    /// Everything after the entry with this cursor
+    After(String),
+}
+
+/// The lines a unit has logged at a priority or more severe, with the cursor to read on from,
+/// if anything was logged.
+pub fn journal_lines(
+    unit: &str,
+    priority: u8,
+    from: &JournalFrom,
+) -> io::Result<(Vec<JournalLine>, Option<String>)> {
+    let from = match from {
+        JournalFrom::Since(secs) => format!("--since=@{}", secs),
+        JournalFrom::After(cursor) => format!("--after-cursor={}", cursor),
+    };
+    let output = Command::new("journalctl")
+        .args([
+            &format!("--unit={}", unit),
+            &format!("--priority={}", priority),
+            &from,
+            "--output=json",
+            "--no-pager",
+        ])
+        .output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(io::Error::other(
+            stderr.lines().next().unwrap_or_default().to_string(),
+        ));
+    }
+    Ok(parse_journal(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Lines from `journalctl --output=json`, one entry per line, with the last entry's cursor.
+fn parse_journal(output: &str) -> (Vec<JournalLine>, Option<String>) {
+    let mut lines = vec![];
+    let mut cursor = None;
+    for entry in output.lines() {
+        let Ok(entry) = serde_json::from_str::<serde_json::Value>(entry) else {
+            continue;
+        };
+        if let Some(at) = entry["__CURSOR"].as_str() {
+            cursor = Some(at.to_string());
+        }
+        let message = match &entry["MESSAGE"] {
+            serde_json::Value::String(message) => message.clone(),
+            // messages that aren't UTF-8 come as arrays of bytes
+            serde_json::Value::Array(bytes) => String::from_utf8_lossy(
+                &bytes
+                    .iter()
+                    .filter_map(|byte| u8::try_from(byte.as_u64()?).ok())
+                    .collect::<Vec<_>>(),
+            )
+            .into_owned(),
+            _ => continue,
+        };
+        let priority = entry["PRIORITY"]
+            .as_str()
+            .and_then(|priority| priority.parse().ok())
+            .unwrap_or(6);
+        lines.push(JournalLine { priority, message });
+    }
+    (lines, cursor)
+}
+
+/// Whether systemd knows about the unit at all, regardless of its state.
+pub fn service_exists(unit: &str) -> Result<bool, String> {
+    let output = Command::new("systemctl")
+        .args(["show", "--property=LoadState", "--value", unit])
+        .output()
+        .map_err(|e| e.to_string())?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(stderr.lines().next().unwrap_or_default().to_string());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim() != "not-found")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mount_and_path_units() {
+        let units = parse_mount_units(
+            "-.mount loaded active mounted Root Mount\n\
+             srv-data.mount loaded inactive dead /srv/data\n\
+             srv-data-incoming.mount loaded active mounted /srv/data/incoming\n\
+             mnt-backup\\x2d2024.mount loaded active mounted /mnt/backup-2024\n",
+        );
+        assert_eq!(units[0].mount_point, Path::new("/"));
+        assert_eq!(units[3].mount_point, Path::new("/mnt/backup-2024"));
+        let backing = |path| backing_mount(&units, Path::new(path)).map(|u| u.name.as_str());
+        assert_eq!(backing("/srv/data/reports"), Some("srv-data.mount"));
+        assert_eq!(
+            backing("/srv/data/incoming/a"),
+            Some("srv-data-incoming.mount")
+        );
+        assert_eq!(backing("/home/me"), None);
+        assert!(!units[1].mounted);
+
+        let show = |active, sub| format!("ActiveState={}\nSubState={}\n", active, sub);
+        assert_eq!(
+            describe_unit("inbox.path", &show("active", "waiting")),
+            Some((true, "armed".to_string()))
+        );
+        assert_eq!(
+            describe_unit("inbox.path", &show("failed", "failed")),
+            Some((false, "failed (failed)".to_string()))
+        );
+        assert_eq!(
+            describe_unit("srv-data.mount", &show("inactive", "dead")),
+            Some((false, "not mounted".to_string()))
+        );
+        assert_eq!(
+            describe_state("nginx.service", "reloading", "reload"),
+            (true, "reloading (reload)".to_string())
+        );
+        assert_eq!(
+            describe_state("nginx.service", "activating", "auto-restart"),
+            (false, "activating (auto-restart)".to_string())
+        );
+    }
+
+    #[test]
+    fn parse_journal_entries() {
+        let (lines, cursor) = parse_journal(
+            "{\"__CURSOR\":\"s=1;i=1\",\"PRIORITY\":\"3\",\"MESSAGE\":\"failed to process a.csv\"}\n\
+             not json\n\
+             {\"__CURSOR\":\"s=1;i=2\",\"PRIORITY\":\"2\",\"MESSAGE\":[104,105,255]}\n",
+        );
+        assert_eq!(
+            lines,
+            [
+                JournalLine {
+                    priority: 3,
+                    message: "failed to process a.csv".to_string()
+                },
+                JournalLine {
+                    priority: 2,
+                    message: "hi\u{fffd}".to_string()
+                },
+            ]
+        );
+        assert_eq!(cursor.as_deref(), Some("s=1;i=2"));
+        assert_eq!(parse_journal(""), (vec![], None));
+    }
+}
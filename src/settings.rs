@@ -0,0 +1,406 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+use toml_edit::{Array, DocumentMut, Item, Table};
+
+use crate::config::{ConfigFile, HumanDuration};
+use crate::filesystem::Sort;
+use crate::timezone::Timezone;
+
+/// A setting that can be changed from the settings screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Setting {
+    Paths,
+    Include,
+    Exclude,
+    Service,
+    Services,
+    Timezone,
+    Sort,
+    MaxItems,
+    MaxRemoved,
+    Retention,
+    ServiceDownFor,
+    FreeSpace,
+    FreeInodes,
+}
+
+impl Setting {
+    pub const ALL: &'static [Setting] = &[
+        Setting::Paths,
+        Setting::Include,
+        Setting::Exclude,
+        Setting::Service,
+        Setting::Services,
+        Setting::Timezone,
+        Setting::Sort,
+        Setting::MaxItems,
+        Setting::MaxRemoved,
+        Setting::Retention,
+        Setting::ServiceDownFor,
+        Setting::FreeSpace,
+        Setting::FreeInodes,
+    ];
+
+    /// Where the setting is kept in the config, as a dotted key.
+    pub fn key(&self) -> &'static str {
+        match self {
+            Self::Paths => "paths",
+            Self::Include => "include",
+            Self::Exclude => "exclude",
+            Self::Service => "service",
+            Self::Services => "services",
+            Self::Timezone => "timezone",
+            Self::Sort => "sort",
+            Self::MaxItems => "max_items",
+            Self::MaxRemoved => "max_removed",
+            Self::Retention => "retention",
+            Self::ServiceDownFor => "alerts.service_down_for",
+            Self::FreeSpace => "alerts.free.space",
+            Self::FreeInodes => "alerts.free.inodes",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::Paths => "Directories to watch, separated by commas",
+            Self::Include => "Globs for the files to show, separated by commas",
+            Self::Exclude => "Globs for the files not to show, separated by commas",
+            Self::Service => "Systemd unit to monitor (a service, .mount or .path)",
+            Self::Services => "More units to monitor alongside it, separated by commas",
+            Self::Timezone => "Time zone for displayed times (IANA name, \"UTC\", or \"local\")",
+            Self::Sort => "Order to list files in: name, found, mtime, or size",
+            Self::MaxItems => "Most files to track per group",
+            Self::MaxRemoved => "Most removed files to keep showing across all groups",
+            Self::Retention => "How long to keep showing removed files, e.g. \"1h\"",
+            Self::ServiceDownFor => "Alert when the service has been down this long, e.g. \"5m\"",
+            Self::FreeSpace => "Alert when less than this percentage of space is free",
+            Self::FreeInodes => "Alert when less than this percentage of inodes is free",
+        }
+    }
+
+    /// Whether a profile can have its own value, as `save_profile` writes them.
+    fn per_profile(&self) -> bool {
+        matches!(
+            self,
+            Self::Paths | Self::Service | Self::Services | Self::Timezone
+        )
+    }
+
+    /// Whether it's one of the units to monitor, which a profile sets all of or none of.
+    fn unit(&self) -> bool {
+        matches!(self, Self::Service | Self::Services)
+    }
+
+    /// Check a typed value, giving what to write, or nothing to take the setting out.
+    fn parse(&self, text: &str, existing: Option<&Item>) -> Result<Option<Item>, String> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Ok(None);
+        }
+        let item = match self {
+            Self::Paths => toml_edit::value(paths(text, existing)),
+            Self::Include | Self::Exclude | Self::Services => toml_edit::value(list(text)),
+            Self::Service => toml_edit::value(text),
+            Self::Timezone => toml_edit::value(text.parse::<Timezone>()?.to_string()),
+            Self::Sort => {
+                let sort = Sort::from_str(text, true)
+                    .map_err(|_| format!("{} isn't name, found, mtime, or size", text))?;
+                let name = sort
+                    .to_possible_value()
+                    .map(|value| value.get_name().to_string());
+                toml_edit::value(name.unwrap_or_default())
+            }
+            Self::MaxItems | Self::MaxRemoved => {
+                let count = text
+                    .parse::<i64>()
+                    .ok()
+                    .filter(|count| *count >= 0)
+                    .ok_or_else(|| format!("{} isn't a number of files", text))?;
+                toml_edit::value(count)
+            }
+            Self::Retention | Self::ServiceDownFor => {
+                HumanDuration::try_from(text.to_string())?;
+                toml_edit::value(text)
+            }
+            Self::FreeSpace | Self::FreeInodes => {
+                let percent = text
+                    .trim_end_matches('%')
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|percent| (0.0..=100.0).contains(percent))
+                    .ok_or_else(|| format!("{} isn't a percentage", text))?;
+                toml_edit::value(percent)
+            }
+        };
+        Ok(Some(item))
+    }
+}
+
+/// The watched paths typed, keeping the title and note of any that were already there.
+fn paths(text: &str, existing: Option<&Item>) -> Array {
+    let existing = existing
+        .and_then(Item::as_array)
+        .map(|a| a.iter().collect::<Vec<_>>())
+        .unwrap_or_default();
+    let mut paths = Array::new();
+    for path in text.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        match existing.iter().find(|entry| path_of(entry) == Some(path)) {
+            Some(entry) => paths.push_formatted((*entry).clone().decorated("", "")),
+            None => paths.push(path),
+        }
+    }
+    paths
+}
+
+/// The strings typed, separated by commas.
+fn list(text: &str) -> Array {
+    text.split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// The path of a watched path entry, written either as a string or as a table.
+fn path_of(entry: &toml_edit::Value) -> Option<&str> {
+    match entry {
+        toml_edit::Value::InlineTable(table) => table.get("path")?.as_str(),
+        entry => entry.as_str(),
+    }
+}
+
+/// How a setting's value is shown and typed.
+fn display(item: &Item) -> String {
+    match item.as_value() {
+        Some(toml_edit::Value::String(s)) => s.value().clone(),
+        Some(toml_edit::Value::Array(paths)) => paths
+            .iter()
+            .filter_map(path_of)
+            .collect::<Vec<_>>()
+            .join(", "),
+        Some(value) => value.to_string().trim().to_string(),
+        None => String::new(),
+    }
+}
+
+/// The settings screen: every setting as written in the config, while one of them may be being
+/// changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SettingsEditor {
+    pub path: PathBuf,
+    /// The profile in use, whose paths, service, and time zone are the ones edited
+    pub profile: Option<String>,
+    /// Each setting's value, empty if it isn't set
+    pub values: Vec<(Setting, String)>,
+    pub selected: usize,
+    /// The new value being typed for the selected setting
+    pub input: Option<String>,
+    /// Why the last value typed couldn't be saved
+    pub error: Option<String>,
+}
+
+impl SettingsEditor {
+    pub fn open(path: &Path, profile: Option<&str>) -> Result<Self, String> {
+        let doc = read(path)?;
+        let values = Setting::ALL
+            .iter()
+            .map(|setting| {
+                let value = lookup(&doc, *setting, profile).map(display);
+                (*setting, value.unwrap_or_default())
+            })
+            .collect();
+        Ok(Self {
+            path: path.to_path_buf(),
+            profile: profile.map(str::to_string),
+            values,
+            selected: 0,
+            input: None,
+            error: None,
+        })
+    }
+
+    pub fn setting(&self) -> Setting {
+        self.values[self.selected].0
+    }
+
+    /// Write the value being typed into the config file, as long as the config is still valid
+    /// with it, and read the settings back.
+    pub fn save(&mut self) -> Result<(), String> {
+        let text = self.input.clone().unwrap_or_default();
+        let doc = read(&self.path)?;
+        let before = ConfigFile::parse(&self.path, doc.to_string()).map_err(|e| e.to_string())?;
+        let after = apply(doc, self.setting(), self.profile.as_deref(), &text)?;
+        let checked = ConfigFile::parse(&self.path, after.clone()).map_err(|e| e.to_string())?;
+        // only problems this change brings in stop it being saved
+        let known = before.validate();
+        let problems = checked.validate();
+        if let Some(problem) = problems
+            .iter()
+            .find(|p| known.iter().all(|k| k.message() != p.message()))
+        {
+            return Err(problem.message().to_string());
+        }
+        fs::write(&self.path, after)
+            .map_err(|e| format!("could not write {}: {}", self.path.display(), e))?;
+        let selected = self.selected;
+        *self = Self::open(&self.path, self.profile.as_deref())?;
+        self.selected = selected;
+        Ok(())
+    }
+}
+
+fn read(path: &Path) -> Result<DocumentMut, String> {
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(format!("could not read config {}: {}", path.display(), e)),
+    };
+    source
+        .parse::<DocumentMut>()
+        .map_err(|e| format!("invalid config {}: {}", path.display(), e))
+}
+
+/// A setting's value in the profile if it has its own, otherwise at the top level.
+fn lookup<'a>(doc: &'a DocumentMut, setting: Setting, profile: Option<&str>) -> Option<&'a Item> {
+    let get = |table: &'a Item| {
+        setting
+            .key()
+            .split('.')
+            .try_fold(table, |item, key| item.get(key))
+    };
+    let own = profile
+        .filter(|_| setting.per_profile())
+        .and_then(|name| doc.get("profiles")?.get(name));
+    // a profile with any units of its own monitors only those, as the config reads it
+    let has_units =
+        |table: &Item| table.get("service").is_some() || table.get("services").is_some();
+    match own {
+        Some(own) if setting.unit() && has_units(own) => get(own),
+        own => own.and_then(get).or_else(|| get(doc.as_item())),
+    }
+}
+
+/// The config with one setting changed to a typed value, or taken out if it's empty.
+fn apply(
+    mut doc: DocumentMut,
+    setting: Setting,
+    profile: Option<&str>,
+    text: &str,
+) -> Result<String, String> {
+    let value = setting.parse(text, lookup(&doc, setting, profile))?;
+    let mut table = doc.as_table_mut();
+    let mut keys = setting.key().split('.').collect::<Vec<_>>();
+    let last = keys.pop().unwrap_or_default();
+    if let Some(name) = profile.filter(|_| setting.per_profile()) {
+        keys.splice(0..0, ["profiles", name]);
+    }
+    for key in keys {
+        table = table
+            .entry(key)
+            .or_insert_with(|| {
+                let mut table = Table::new();
+                table.set_implicit(true);
+                Item::Table(table)
+            })
+            .as_table_mut()
+            .ok_or_else(|| format!("{} in config is not a table", key))?;
+    }
+    match value {
+        Some(value) => table[last] = value,
+        None => {
+            table.remove(last);
+        }
+    }
+    Ok(doc.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn change_settings() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        fs::create_dir_all(dir.join("in")).unwrap();
+        fs::create_dir_all(dir.join("out")).unwrap();
+        let path = dir.join("config.toml");
+        fs::write(
+            &path,
+            "# the drop box\npaths = [{ path = \"in\", title = \"Inbox\" }]\nmax_items = 50\n",
+        )
+        .unwrap();
+
+        let mut editor = SettingsEditor::open(&path, None).unwrap();
+        assert_eq!(editor.values[0], (Setting::Paths, "in".to_string()));
+        assert_eq!(editor.values[7], (Setting::MaxItems, "50".to_string()));
+
+        editor.input = Some("in, out".to_string());
+        editor.save().unwrap();
+        editor.selected = 7;
+        editor.input = Some("".to_string());
+        editor.save().unwrap();
+        editor.selected = 11;
+        editor.input = Some("10%".to_string());
+        editor.save().unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "# the drop box\npaths = [{ path = \"in\", title = \"Inbox\" }, \"out\"]\n\n\
+             [alerts.free]\nspace = 10.0\n"
+        );
+        assert_eq!(editor.values[11], (Setting::FreeSpace, "10.0".to_string()));
+
+        editor.selected = 6;
+        editor.input = Some("Created".to_string());
+        editor.save().unwrap();
+        editor.selected = 9;
+        editor.input = Some("ages".to_string());
+        assert!(editor.save().is_err());
+        editor.input = Some("2h".to_string());
+        editor.save().unwrap();
+        editor.selected = 1;
+        editor.input = Some("*.csv, ,*.tsv".to_string());
+        editor.save().unwrap();
+        assert_eq!(
+            editor.values[1],
+            (Setting::Include, "*.csv, *.tsv".to_string())
+        );
+        assert_eq!(editor.values[6], (Setting::Sort, "found".to_string()));
+        assert_eq!(editor.values[9], (Setting::Retention, "2h".to_string()));
+
+        editor.selected = 11;
+        editor.input = Some("120".to_string());
+        assert!(editor.save().is_err());
+        editor.selected = 0;
+        editor.input = Some("missing".to_string());
+        assert!(editor.save().unwrap_err().contains("does not exist"));
+        editor.selected = 5;
+        editor.input = Some("Mars/Olympus".to_string());
+        assert!(editor.save().is_err());
+    }
+
+    #[test]
+    fn profile_settings() {
+        let doc = "service = \"nginx\"\n".parse::<DocumentMut>().unwrap();
+        assert_eq!(
+            lookup(&doc, Setting::Service, Some("night")).map(display),
+            Some("nginx".to_string())
+        );
+        let changed = apply(doc, Setting::Service, Some("night"), "cron").unwrap();
+        assert_eq!(
+            changed,
+            "service = \"nginx\"\n\n[profiles.night]\nservice = \"cron\"\n"
+        );
+
+        let doc = "services = [\"nginx\"]\n\n[profiles.night]\nservice = \"cron\"\n"
+            .parse::<DocumentMut>()
+            .unwrap();
+        assert!(lookup(&doc, Setting::Services, Some("night")).is_none());
+        assert_eq!(
+            lookup(&doc, Setting::Services, None).map(display),
+            Some("nginx".to_string())
+        );
+    }
+}
@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// A file waiting to stop changing, with what's to be done once it has.
+struct Pending<T> {
+    value: T,
+    /// Another file that has to stop changing too, like a checksum's sidecar
+    sidecar: Option<PathBuf>,
+    stable_for: Duration,
+    /// The size and modification time of the file and its sidecar when last looked at
+    seen: Vec<(u64, Option<SystemTime>)>,
+    since: Instant,
+}
+
+/// New files waiting until their size and modification time have stopped changing for a while,
+/// so they aren't acted on while they're still being written.
+pub struct Settling<T> {
+    pending: HashMap<PathBuf, Pending<T>>,
+}
+
+impl<T> Default for Settling<T> {
+    fn default() -> Self {
+        Self {
+            pending: HashMap::new(),
+        }
+    }
+}
+
+impl<T> Settling<T> {
+    /// Wait for a file, and its sidecar if it has one, to go `stable_for` without changing,
+    /// starting over if it was already waiting.
+    pub fn insert(
+        &mut self,
+        path: PathBuf,
+        sidecar: Option<PathBuf>,
+        stable_for: Duration,
+        value: T,
+    ) {
+        self.pending.insert(
+            path,
+            Pending {
+                value,
+                sidecar,
+                stable_for,
+                seen: vec![],
+                since: Instant::now(),
+            },
+        );
+    }
+
+    pub fn remove(&mut self, path: &Path) {
+        self.pending.remove(path);
+    }
+
+    /// Look at the files again, taking out those that have stopped changing for long enough.
+    /// Files that have gone away are forgotten.
+    pub fn settled(&mut self) -> Vec<(PathBuf, T)> {
+        let mut settled = vec![];
+        let pending = std::mem::take(&mut self.pending);
+        for (path, mut pending) in pending {
+            let files = std::iter::once(&path).chain(&pending.sidecar);
+            let Ok(now) = files
+                .map(|file| fs::metadata(file).map(|m| (m.len(), m.modified().ok())))
+                .collect::<Result<Vec<_>, _>>()
+            else {
+                continue;
+            };
+            if now != pending.seen {
+                pending.seen = now;
+                pending.since = Instant::now();
+            }
+            if pending.since.elapsed() < pending.stable_for {
+                self.pending.insert(path, pending);
+            } else {
+                settled.push((path, pending.value));
+            }
+        }
+        settled
+    }
+
+    /// When the soonest pending file could have settled, to check on it then.
+    pub fn next_check(&self) -> Option<Instant> {
+        self.pending
+            .values()
+            .map(|pending| pending.since + pending.stable_for)
+            .min()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wait_for_sidecars() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let file = dir.join("a.csv");
+        let sidecar = dir.join("a.csv.sha256");
+        fs::write(&file, "a").unwrap();
+        fs::write(&sidecar, "b").unwrap();
+
+        let mut settling = Settling::default();
+        settling.insert(file.clone(), Some(sidecar.clone()), Duration::ZERO, 1);
+        settling.insert(dir.join("b.csv"), None, Duration::ZERO, 2);
+        settling.insert(dir.to_path_buf(), None, Duration::from_secs(60), 3);
+        // b.csv isn't there to wait for
+        assert_eq!(settling.settled(), [(file.clone(), 1)]);
+        assert!(settling.next_check().unwrap() > Instant::now());
+
+        settling.insert(file.clone(), Some(sidecar.clone()), Duration::ZERO, 4);
+        fs::remove_file(&sidecar).unwrap();
+        settling.remove(dir);
+        assert!(settling.settled().is_empty());
+        assert_eq!(settling.next_check(), None);
+    }
+}
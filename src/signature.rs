@@ -0,0 +1,184 @@
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::SignaturesConfig;
+use crate::events::Event;
+use crate::filesystem::{FileGroup, Signature};
+use crate::reactor::Waker;
+use crate::settling::Settling;
+
+/// How long a file has to stop changing before its signature is checked.
+const STABLE_FOR: Duration = Duration::from_secs(5);
+
+/// Extensions of detached signatures, in the order they're looked for.
+const SIGNATURES: &[&str] = &["sig", "asc"];
+
+/// Files that come alongside others rather than needing signatures of their own.
+const SIDECARS: &[&str] = &["sig", "asc", "sha256", "md5"];
+
+/// Checks the detached `.sig` or `.asc` signature of each new file in the configured groups with
+/// `gpgv`, once the file has stopped changing. A file without one is marked unsigned until its
+/// signature arrives, which checks it again.
+pub struct Signatures {
+    groups: Vec<String>,
+    /// Files waiting to stop changing
+    pending: Settling<()>,
+    jobs: Sender<PathBuf>,
+    done: Receiver<(PathBuf, Result<Signature, String>)>,
+}
+
+impl Signatures {
+    pub fn new(config: &SignaturesConfig, waker: Waker) -> Self {
+        let keyring = config.keyring.clone();
+        let (jobs, rx) = channel::<PathBuf>();
+        let (tx, done) = channel();
+        thread::spawn(move || {
+            for path in rx {
+                let signature = check(&keyring, &path);
+                if tx.send((path, signature)).is_err() {
+                    return;
+                }
+                waker.wake();
+            }
+        });
+        Self {
+            groups: config.groups.clone(),
+            pending: Settling::default(),
+            jobs,
+            done,
+        }
+    }
+
+    /// Note new files and signatures, start checking any files that have settled, and mark what's
+    /// been checked since the last call on the groups' items, raising an alert for each bad
+    /// signature.
+    pub fn update(&mut self, events: &[Event], groups: &mut [FileGroup]) -> Vec<Event> {
+        for event in events {
+            match event {
+                Event::FileAdded { group, path }
+                    if self.groups.is_empty() || self.groups.contains(group) =>
+                {
+                    let file = if has_extension(path, SIGNATURES) {
+                        path.with_extension("")
+                    } else if has_extension(path, SIDECARS) {
+                        continue;
+                    } else {
+                        path.clone()
+                    };
+                    self.pending.insert(file, None, STABLE_FOR, ());
+                }
+                Event::FileRemoved { path, .. } => {
+                    self.pending.remove(path);
+                }
+                _ => {}
+            }
+        }
+
+        for (path, ()) in self.pending.settled() {
+            let _ = self.jobs.send(path);
+        }
+
+        let mut alerts = vec![];
+        for (path, signature) in self.done.try_iter() {
+            let signature = match signature {
+                Ok(signature) => signature,
+                Err(e) => {
+                    let message = format!("could not check {}: {}", path.display(), e);
+                    alerts.push(alert(path, message));
+                    continue;
+                }
+            };
+            for group in groups.iter_mut() {
+                if let Some(item) = group.get_mut(&path) {
+                    item.signature = Some(signature);
+                }
+            }
+            if signature == Signature::Invalid {
+                let message = format!("{} has a bad signature", path.display());
+                alerts.push(alert(path, message));
+            }
+        }
+        alerts
+    }
+
+    /// When the soonest pending file could have settled, to check on it then.
+    pub fn next_check(&self) -> Option<Instant> {
+        self.pending.next_check()
+    }
+}
+
+fn alert(path: PathBuf, message: String) -> Event {
+    Event::Alert {
+        rule: "signature".to_string(),
+        path: Some(path),
+        message,
+    }
+}
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| extensions.contains(&e))
+}
+
+/// The signature next to a file, if there is one.
+fn signature_path(path: &Path) -> Option<PathBuf> {
+    SIGNATURES
+        .iter()
+        .map(|extension| {
+            let mut name = path.as_os_str().to_owned();
+            name.push(".");
+            name.push(extension);
+            PathBuf::from(name)
+        })
+        .find(|signature| signature.is_file())
+}
+
+/// Verify a file's signature against the keyring, failing only if gpgv couldn't be run.
+fn check(keyring: &Path, path: &Path) -> Result<Signature, String> {
+    let Some(signature) = signature_path(path) else {
+        return Ok(Signature::Unsigned);
+    };
+    let status = Command::new("gpgv")
+        .arg("--keyring")
+        .arg(keyring)
+        .arg(&signature)
+        .arg(path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map_err(|e| format!("could not run gpgv: {}", e))?;
+    Ok(if status.success() {
+        Signature::Signed
+    } else {
+        Signature::Invalid
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn signatures_alongside_files() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let file = dir.join("drop.tar");
+        fs::write(&file, "contents").unwrap();
+        let keyring = dir.join("trusted.gpg");
+
+        assert_eq!(signature_path(&file), None);
+        assert_eq!(check(&keyring, &file), Ok(Signature::Unsigned));
+
+        fs::write(dir.join("drop.tar.asc"), "not really a signature").unwrap();
+        assert_eq!(signature_path(&file), Some(dir.join("drop.tar.asc")));
+        assert!(has_extension(&dir.join("drop.tar.asc"), SIGNATURES));
+        assert!(!has_extension(&file, SIDECARS));
+    }
+}
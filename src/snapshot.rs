@@ -0,0 +1,168 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::filesystem::{FileGroup, FileItem};
+
+/// The tracked files of each group as saved between runs, with times on the wall clock since
+/// `Instant`s don't survive a restart.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Snapshot {
+    groups: Vec<GroupSnapshot>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct GroupSnapshot {
+    root: PathBuf,
+    items: Vec<ItemSnapshot>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ItemSnapshot {
+    path: PathBuf,
+    observed: DateTime<Utc>,
+    removed: Option<DateTime<Utc>>,
+}
+
+impl Snapshot {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        match fs::read_to_string(path) {
+            Ok(source) => serde_json::from_str(&source)
+                .map_err(|e| format!("invalid state file {}: {}", path.display(), e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!(
+                "could not read state file {}: {}",
+                path.display(),
+                e
+            )),
+        }
+    }
+
+    /// Save the given groups, keeping whatever was saved for other groups (e.g. by an instance
+    /// watching something else).
+    pub fn save(path: &Path, groups: &[FileGroup]) -> file_task::Result<()> {
+        let mut snapshot = Self::load(path).unwrap_or_default();
+        snapshot
+            .groups
+            .retain(|saved| !groups.iter().any(|g| g.root == saved.root));
+        snapshot
+            .groups
+            .extend(groups.iter().map(GroupSnapshot::from));
+
+        let failed = |source| file_task::Error::Save {
+            path: path.to_path_buf(),
+            source,
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(failed)?;
+        }
+        let json = serde_json::to_string(&snapshot).map_err(|e| failed(io::Error::other(e)))?;
+        // write then rename, so a crash mid-write doesn't lose the previous state
+        let temp = path.with_extension("json.tmp");
+        fs::write(&temp, json)
+            .and_then(|()| fs::rename(&temp, path))
+            .map_err(failed)
+    }
+
+    /// Bring back what a fresh scan can't know: when files were first seen, and files removed
+    /// within `retention`, so still to be shown. Files that disappeared while we weren't running
+    /// are shown as removed now.
+    pub fn restore(&self, groups: &mut [FileGroup], retention: Duration) {
+        let now = SystemTime::now();
+        for group in groups.iter_mut() {
+            let Some(saved) = self.groups.iter().find(|g| g.root == group.root) else {
+                continue;
+            };
+            for item in &saved.items {
+                match group.get_mut(&item.path) {
+                    Some(current) => current.observed = item.observed.into(),
+                    None => {
+                        let removed_at = item.removed.map_or(now, SystemTime::from);
+                        let ago = now.duration_since(removed_at).unwrap_or_default();
+                        if ago > retention {
+                            continue;
+                        }
+                        group.insert(FileItem {
+                            name: group.name(&item.path),
+                            removed: Some(
+                                Instant::now().checked_sub(ago).unwrap_or_else(Instant::now),
+                            ),
+                            observed: item.observed.into(),
+                            tags: vec![],
+                            verified: None,
+                            signature: None,
+                            writer: None,
+                            size: None,
+                            modified: None,
+                            metadata_read: false,
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl From<&FileGroup> for GroupSnapshot {
+    fn from(group: &FileGroup) -> Self {
+        Self {
+            root: group.root.clone(),
+            items: group
+                .items()
+                .iter()
+                .map(|item| ItemSnapshot {
+                    path: group.path(item),
+                    observed: item.observed.into(),
+                    removed: item.removed_at().map(DateTime::from),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use file_task::DELETED_RETENTION;
+
+    #[test]
+    fn restore_keeps_recent_ghosts() {
+        let root = PathBuf::from("/root");
+        let mut before = FileGroup::new(
+            root.clone(),
+            None,
+            vec![
+                FileItem::new(PathBuf::from("kept")),
+                FileItem::new(PathBuf::from("gone")),
+                FileItem::new(PathBuf::from("ghost")),
+            ],
+        );
+        before.get_mut(&root.join("ghost")).unwrap().removed =
+            Some(Instant::now() - Duration::from_secs(60));
+        let snapshot = Snapshot {
+            groups: vec![GroupSnapshot::from(&before)],
+        };
+
+        let mut after = vec![FileGroup::new(
+            root.clone(),
+            None,
+            vec![FileItem::new(PathBuf::from("kept"))],
+        )];
+        snapshot.restore(&mut after, DELETED_RETENTION);
+
+        let items = after[0].items();
+        assert_eq!(items.len(), 3);
+        assert!(items[0].removed.is_none());
+        assert_eq!(after[0].path(&items[1]), root.join("gone"));
+        assert!(items[1]
+            .removed
+            .is_some_and(|r| r.elapsed() < Duration::from_secs(5)));
+        assert!(items[2]
+            .removed
+            .is_some_and(|r| r.elapsed() >= Duration::from_secs(59)));
+    }
+}
@@ -0,0 +1,201 @@
+use std::path::Path;
+
+use crate::filesystem::FileGroup;
+
+/// The upper bound of each histogram bucket but the last, which takes everything bigger.
+const BUCKET_LIMITS: [u64; 7] = [
+    1_000,
+    10_000,
+    100_000,
+    1_000_000,
+    10_000_000,
+    100_000_000,
+    1_000_000_000,
+];
+
+/// Labels for the histogram buckets, in order.
+pub const BUCKET_LABELS: [&str; 8] = [
+    "<1K", "1K-10K", "10K-100K", "100K-1M", "1M-10M", "10M-100M", "100M-1G", ">=1G",
+];
+
+/// How many of a group's files have an extension, and how much they take up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Extension {
+    /// With the dot, e.g. ".csv", or "(none)"
+    pub name: String,
+    pub count: usize,
+    pub bytes: u64,
+}
+
+/// How the sizes of a group's present files are spread out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupStats {
+    pub title: String,
+    pub count: usize,
+    pub total: u64,
+    pub min: u64,
+    pub median: u64,
+    pub max: u64,
+    /// How many files fall in each bucket
+    pub buckets: [usize; 8],
+    /// The most common extensions first
+    pub extensions: Vec<Extension>,
+}
+
+impl GroupStats {
+    /// Sum up the sizes of a group's present files, as last read, leaving out anything that
+    /// isn't a file.
+    pub fn of(group: &FileGroup) -> Self {
+        let files = group
+            .items()
+            .iter()
+            .filter(|item| item.removed.is_none())
+            .filter_map(|item| Some((extension(&item.name), item.size?)))
+            .collect::<Vec<_>>();
+        let sizes = files.iter().map(|(_, size)| *size).collect();
+        Self {
+            extensions: breakdown(&files),
+            ..Self::from_sizes(crate::group_title(group).to_string(), sizes)
+        }
+    }
+
+    fn from_sizes(title: String, mut sizes: Vec<u64>) -> Self {
+        sizes.sort_unstable();
+        let mut buckets = [0; 8];
+        for size in &sizes {
+            buckets[BUCKET_LIMITS.partition_point(|limit| size >= limit)] += 1;
+        }
+        Self {
+            title,
+            count: sizes.len(),
+            total: sizes.iter().sum(),
+            min: sizes.first().copied().unwrap_or_default(),
+            median: sizes.get(sizes.len() / 2).copied().unwrap_or_default(),
+            max: sizes.last().copied().unwrap_or_default(),
+            buckets,
+            extensions: vec![],
+        }
+    }
+}
+
+/// A file's extension as it's grouped by, lowercased so ".CSV" and ".csv" count together.
+fn extension(path: &Path) -> String {
+    match path.extension() {
+        Some(extension) => format!(".{}", extension.to_string_lossy().to_lowercase()),
+        None => "(none)".to_string(),
+    }
+}
+
+/// Total up files by extension, the most common first and then the biggest.
+fn breakdown(files: &[(String, u64)]) -> Vec<Extension> {
+    let mut extensions: Vec<Extension> = vec![];
+    for (name, size) in files {
+        match extensions.iter_mut().find(|e| e.name == *name) {
+            Some(extension) => {
+                extension.count += 1;
+                extension.bytes += size;
+            }
+            None => extensions.push(Extension {
+                name: name.clone(),
+                count: 1,
+                bytes: *size,
+            }),
+        }
+    }
+    extensions.sort_by(|a, b| (b.count, b.bytes, &a.name).cmp(&(a.count, a.bytes, &b.name)));
+    extensions
+}
+
+/// A size in bytes in the largest binary unit that keeps it at least 1, e.g. "1.5 MiB".
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::filesystem::FileItem;
+
+    #[test]
+    fn size_distribution() {
+        let stats = GroupStats::from_sizes(
+            "inbox".to_string(),
+            vec![5_000_000, 12, 999, 1_000, 40_000, 2_000_000_000],
+        );
+        assert_eq!(stats.count, 6);
+        assert_eq!(stats.total, 2_005_042_011);
+        assert_eq!(
+            (stats.min, stats.median, stats.max),
+            (12, 40_000, 2_000_000_000)
+        );
+        assert_eq!(stats.buckets, [2, 1, 1, 0, 1, 0, 0, 1]);
+
+        let empty = GroupStats::from_sizes("empty".to_string(), vec![]);
+        assert_eq!((empty.count, empty.median), (0, 0));
+    }
+
+    #[test]
+    fn tracked_sizes() {
+        let mut group = FileGroup::new(
+            PathBuf::from("/in"),
+            None,
+            ["a.csv", "b.csv", "sub"]
+                .into_iter()
+                .map(|name| FileItem::new(PathBuf::from(name)))
+                .collect(),
+        );
+        for (name, size) in [("a.csv", Some(10)), ("b.csv", Some(30)), ("sub", None)] {
+            let item = group.get_mut(&Path::new("/in").join(name)).unwrap();
+            item.set_metadata((size, None));
+        }
+
+        let stats = GroupStats::of(&group);
+        assert_eq!((stats.count, stats.total), (2, 40));
+        assert_eq!(stats.extensions[0].name, ".csv");
+    }
+
+    #[test]
+    fn extension_breakdown() {
+        assert_eq!(extension(Path::new("/in/Report.CSV")), ".csv");
+        assert_eq!(extension(Path::new("/in/data.tar.gz")), ".gz");
+        assert_eq!(extension(Path::new("/in/README")), "(none)");
+
+        let files = [
+            (".csv".to_string(), 100),
+            (".json".to_string(), 5_000),
+            (".csv".to_string(), 300),
+            ("(none)".to_string(), 5_000),
+        ];
+        let counts = breakdown(&files)
+            .into_iter()
+            .map(|e| (e.name, e.count, e.bytes))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            counts,
+            [
+                (".csv".to_string(), 2, 400),
+                ("(none)".to_string(), 1, 5_000),
+                (".json".to_string(), 1, 5_000),
+            ]
+        );
+    }
+
+    #[test]
+    fn human_sizes() {
+        assert_eq!(format_size(999), "999 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(5 * 1024 * 1024 * 1024), "5.0 GiB");
+    }
+}
@@ -0,0 +1,377 @@
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::events::Event;
+use crate::filesystem::{FileGroup, FileItem};
+use crate::AppState;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS items (
+    path TEXT PRIMARY KEY,
+    root TEXT NOT NULL,
+    observed TEXT NOT NULL,
+    removed TEXT
+);
+CREATE TABLE IF NOT EXISTS events (
+    id INTEGER PRIMARY KEY,
+    time TEXT NOT NULL,
+    event TEXT NOT NULL,
+    group_title TEXT,
+    path TEXT,
+    service TEXT,
+    status TEXT,
+    rule TEXT,
+    message TEXT,
+    summary TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS events_by_time ON events (time);
+CREATE TABLE IF NOT EXISTS service_transitions (
+    id INTEGER PRIMARY KEY,
+    time TEXT NOT NULL,
+    service TEXT NOT NULL,
+    active INTEGER NOT NULL,
+    status TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS tags (
+    path TEXT NOT NULL,
+    inode INTEGER NOT NULL,
+    tag TEXT NOT NULL,
+    PRIMARY KEY (path, tag)
+);
+";
+
+/// A SQLite database of every file seen, every event, and every service transition, kept
+/// across runs so history isn't limited to what the current process has watched.
+pub struct Store {
+    conn: Connection,
+    path: PathBuf,
+}
+
+/// An event as recorded in the store.
+#[derive(Debug, PartialEq)]
+pub struct StoredEvent {
+    pub time: DateTime<Utc>,
+    pub summary: String,
+}
+
+/// A change of the service's state as recorded in the store.
+#[derive(Debug, PartialEq)]
+pub struct Transition {
+    pub time: DateTime<Utc>,
+    pub active: bool,
+    pub status: String,
+}
+
+impl Store {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("could not create {}: {}", parent.display(), e))?;
+        }
+        let conn = Connection::open(path)
+            .and_then(|conn| conn.execute_batch(SCHEMA).map(|()| conn))
+            .map_err(|e| format!("could not open database {}: {}", path.display(), e))?;
+        Ok(Self {
+            conn,
+            path: path.to_path_buf(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Record every file currently tracked, e.g. after the initial scan.
+    pub fn record_items(&mut self, groups: &[FileGroup]) -> Result<(), String> {
+        let tx = self.conn.transaction().map_err(|e| e.to_string())?;
+        for group in groups {
+            for item in group.items() {
+                upsert_item(&tx, group, item).map_err(|e| e.to_string())?;
+            }
+        }
+        tx.commit().map_err(|e| e.to_string())
+    }
+
+    /// Give tracked files the tags they were last given, as long as they're still the same file.
+    pub fn load_tags(&self, groups: &mut [FileGroup]) -> Result<(), String> {
+        let mut statement = self
+            .conn
+            .prepare("SELECT tag FROM tags WHERE path = ?1 AND inode = ?2 ORDER BY rowid")
+            .map_err(|e| e.to_string())?;
+        for group in groups {
+            let paths = group
+                .items()
+                .iter()
+                .map(|item| group.path(item))
+                .collect::<Vec<_>>();
+            for path in paths {
+                let Some(inode) = inode(&path) else {
+                    continue;
+                };
+                let tags = statement
+                    .query_map(params![path.to_string_lossy(), inode], |row| row.get(0))
+                    .and_then(|rows| rows.collect::<rusqlite::Result<Vec<String>>>())
+                    .map_err(|e| e.to_string())?;
+                if let Some(item) = group.get_mut(&path) {
+                    item.tags = tags;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Remember a file's tags, replacing any it had.
+    pub fn save_tags(&mut self, path: &Path, tags: &[String]) -> Result<(), String> {
+        write_tags(&self.conn, path, tags).map_err(|e| e.to_string())
+    }
+
+    /// Record the events of one tick, along with the files and service they're about.
+    pub fn update(&mut self, state: &AppState, events: &[Event]) -> Result<(), String> {
+        if events.is_empty() {
+            return Ok(());
+        }
+        let tx = self.conn.transaction().map_err(|e| e.to_string())?;
+        for event in events {
+            let payload = event.payload();
+            tx.execute(
+                "INSERT INTO events
+                    (time, event, group_title, path, service, status, rule, message, summary)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![
+                    payload.time,
+                    payload.event.name(),
+                    payload.group,
+                    payload.path.map(|p| p.to_string_lossy()),
+                    payload.service,
+                    payload.status,
+                    payload.rule,
+                    payload.message,
+                    event.summary(),
+                ],
+            )
+            .map_err(|e| e.to_string())?;
+
+            match event {
+                Event::FileAdded { path, .. } | Event::FileRemoved { path, .. } => {
+                    let tracked = state.file_groups.iter().find_map(|group| {
+                        let item = group.get(path)?;
+                        Some((group, item))
+                    });
+                    if let Some((group, item)) = tracked {
+                        upsert_item(&tx, group, item).map_err(|e| e.to_string())?;
+                        // tags follow a file that was renamed
+                        if matches!(event, Event::FileAdded { .. }) && !item.tags.is_empty() {
+                            write_tags(&tx, path, &item.tags).map_err(|e| e.to_string())?;
+                        }
+                    }
+                }
+                Event::ServiceChanged {
+                    service,
+                    active,
+                    status,
+                } => {
+                    tx.execute(
+                        "INSERT INTO service_transitions (time, service, active, status)
+                         VALUES (?1, ?2, ?3, ?4)",
+                        params![payload.time, service, active, status.trim()],
+                    )
+                    .map_err(|e| e.to_string())?;
+                }
+                Event::Alert { .. } | Event::Action { .. } => {}
+            }
+        }
+        tx.commit().map_err(|e| e.to_string())
+    }
+
+    /// The most recent events, newest first.
+    pub fn recent_events(&self, limit: usize) -> Result<Vec<StoredEvent>, String> {
+        let mut statement = self
+            .conn
+            .prepare("SELECT time, summary FROM events ORDER BY id DESC LIMIT ?1")
+            .map_err(|e| e.to_string())?;
+        let rows = statement
+            .query_map([limit as i64], |row| {
+                Ok(StoredEvent {
+                    time: parse_time(row.get(0)?),
+                    summary: row.get(1)?,
+                })
+            })
+            .map_err(|e| e.to_string())?;
+        rows.collect::<Result<_, _>>().map_err(|e| e.to_string())
+    }
+
+    /// The group and time of each file added since a moment, oldest first.
+    pub fn arrivals(&self, since: DateTime<Utc>) -> Result<Vec<(String, DateTime<Utc>)>, String> {
+        let mut statement = self
+            .conn
+            .prepare(
+                "SELECT group_title, time FROM events
+                 WHERE event = 'added' AND time >= ?1 ORDER BY id",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = statement
+            .query_map([since.to_rfc3339()], |row| {
+                Ok((row.get(0)?, parse_time(row.get(1)?)))
+            })
+            .map_err(|e| e.to_string())?;
+        rows.collect::<Result<_, _>>().map_err(|e| e.to_string())
+    }
+
+    /// When the service last changed state, if it ever has while recorded.
+    pub fn last_transition(&self, service: &str) -> Result<Option<Transition>, String> {
+        self.conn
+            .query_row(
+                "SELECT time, active, status FROM service_transitions
+                 WHERE service = ?1 ORDER BY id DESC LIMIT 1",
+                [service],
+                |row| {
+                    Ok(Transition {
+                        time: parse_time(row.get(0)?),
+                        active: row.get(1)?,
+                        status: row.get(2)?,
+                    })
+                },
+            )
+            .optional()
+            .map_err(|e| e.to_string())
+    }
+}
+
+fn upsert_item(conn: &Connection, group: &FileGroup, item: &FileItem) -> rusqlite::Result<usize> {
+    let observed = DateTime::<Utc>::from(item.observed).to_rfc3339();
+    let removed = item
+        .removed_at()
+        .map(|removed| DateTime::<Utc>::from(removed).to_rfc3339());
+    conn.execute(
+        "INSERT INTO items (path, root, observed, removed) VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT (path) DO UPDATE SET root = ?2, observed = ?3, removed = ?4",
+        params![
+            group.path(item).to_string_lossy(),
+            group.root.to_string_lossy(),
+            observed,
+            removed
+        ],
+    )
+}
+
+fn write_tags(conn: &Connection, path: &Path, tags: &[String]) -> rusqlite::Result<()> {
+    let path_text = path.to_string_lossy();
+    conn.execute("DELETE FROM tags WHERE path = ?1", [&path_text])?;
+    // a file that's gone can't be told apart from a new one later, so it keeps no tags
+    let Some(inode) = inode(path) else {
+        return Ok(());
+    };
+    for tag in tags {
+        conn.execute(
+            "INSERT INTO tags (path, inode, tag) VALUES (?1, ?2, ?3)",
+            params![path_text, inode, tag],
+        )?;
+    }
+    Ok(())
+}
+
+/// What tells a file apart from another later put at the same path.
+fn inode(path: &Path) -> Option<i64> {
+    fs::metadata(path)
+        .ok()
+        .map(|metadata| metadata.ino() as i64)
+}
+
+// times are only ever written by this module, so anything unreadable is treated as very old
+fn parse_time(time: String) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(&time)
+        .map(|time| time.with_timezone(&Utc))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_events_and_transitions() {
+        let mut store = Store::open(Path::new(":memory:")).unwrap();
+        let root = PathBuf::from("/tmp/a");
+        let state = AppState {
+            file_groups: vec![FileGroup::new(
+                root.clone(),
+                None,
+                vec![FileItem::new(PathBuf::from("x"))],
+            )],
+            ..Default::default()
+        };
+        let events = [
+            Event::FileAdded {
+                group: "a".to_string(),
+                path: root.join("x"),
+            },
+            Event::ServiceChanged {
+                service: "web".to_string(),
+                active: true,
+                status: "running\n".to_string(),
+            },
+        ];
+        store.update(&state, &events).unwrap();
+
+        let summaries = store
+            .recent_events(10)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.summary)
+            .collect::<Vec<_>>();
+        assert_eq!(summaries, vec!["service web is running", "a: added x"]);
+
+        let hour = chrono::Duration::hours(1);
+        let arrivals = store.arrivals(Utc::now() - hour).unwrap();
+        assert_eq!(arrivals.len(), 1);
+        assert_eq!(arrivals[0].0, "a");
+        assert!(store.arrivals(Utc::now() + hour).unwrap().is_empty());
+
+        let transition = store.last_transition("web").unwrap().unwrap();
+        assert!(transition.active);
+        assert_eq!(transition.status, "running");
+        assert_eq!(store.last_transition("db").unwrap(), None);
+
+        let items: i64 = store
+            .conn
+            .query_row("SELECT count(*) FROM items", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(items, 1);
+    }
+
+    #[test]
+    fn tags_stay_with_the_same_file() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        fs::write(dir.join("a"), "a").unwrap();
+        fs::write(dir.join("b"), "b").unwrap();
+        let group = || {
+            FileGroup::new(
+                dir.to_path_buf(),
+                None,
+                vec![
+                    FileItem::new(PathBuf::from("a")),
+                    FileItem::new(PathBuf::from("b")),
+                ],
+            )
+        };
+
+        let mut store = Store::open(Path::new(":memory:")).unwrap();
+        let tags = vec!["checked".to_string(), "problem".to_string()];
+        store.save_tags(&dir.join("a"), &tags).unwrap();
+        store.save_tags(&dir.join("b"), &tags).unwrap();
+        // a different file in b's place isn't tagged
+        fs::rename(dir.join("b"), dir.join("old")).unwrap();
+        fs::write(dir.join("b"), "b").unwrap();
+
+        let mut groups = [group()];
+        store.load_tags(&mut groups).unwrap();
+
+        assert_eq!(groups[0].items()[0].tags, tags);
+        assert!(groups[0].items()[1].tags.is_empty());
+    }
+}
@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::mpsc::{channel, Sender};
+use std::thread;
+
+use ::syslog::{Formatter3164, LogFormat, Logger, LoggerBackend, Severity};
+
+use crate::config::{SyslogConfig, SyslogSeverity};
+use crate::events::{Event, EventFilter, EventKind, Receipt, Sink};
+
+/// Sends selected events to the local syslog or a remote one over UDP or TCP, in the
+/// traditional BSD (RFC 3164) format.
+pub struct Syslog {
+    filter: EventFilter,
+    severities: HashMap<EventKind, SyslogSeverity>,
+    target: Target,
+    tx: Sender<(SyslogSeverity, String, Receipt)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Target {
+    Local,
+    Udp(String),
+    Tcp(String),
+}
+
+impl Target {
+    fn parse(server: Option<&str>) -> Result<Self, String> {
+        let Some(server) = server else {
+            return Ok(Self::Local);
+        };
+        match server.split_once("://") {
+            Some(("udp", address)) => Ok(Self::Udp(address.to_string())),
+            Some(("tcp", address)) => Ok(Self::Tcp(address.to_string())),
+            _ => Err(format!(
+                "invalid syslog server \"{}\", expected udp://host:port or tcp://host:port",
+                server
+            )),
+        }
+    }
+
+    fn connect(
+        &self,
+        formatter: Formatter3164,
+    ) -> ::syslog::Result<Logger<LoggerBackend, Formatter3164>> {
+        match self {
+            Self::Local => ::syslog::unix(formatter),
+            Self::Udp(address) => {
+                let server = resolve(address)?;
+                let local = if server.is_ipv4() {
+                    "0.0.0.0:0"
+                } else {
+                    "[::]:0"
+                };
+                ::syslog::udp(formatter, local, server)
+            }
+            Self::Tcp(address) => ::syslog::tcp(formatter, address.as_str()),
+        }
+    }
+}
+
+impl std::fmt::Display for Target {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Local => write!(f, "local"),
+            Self::Udp(address) => write!(f, "udp://{}", address),
+            Self::Tcp(address) => write!(f, "tcp://{}", address),
+        }
+    }
+}
+
+fn resolve(address: &str) -> ::syslog::Result<SocketAddr> {
+    address
+        .to_socket_addrs()
+        .map_err(|e| ::syslog::Error::Initialization(e.into()))?
+        .next()
+        .ok_or_else(|| ::syslog::Error::Initialization("no server address".into()))
+}
+
+impl Syslog {
+    pub fn new(
+        config: &SyslogConfig,
+        diagnostics: Sender<String>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let filter = EventFilter::new(&config.events)?;
+        let severities = EventKind::ALL
+            .iter()
+            .map(|&kind| (kind, config.severity(kind)))
+            .collect();
+        let target = Target::parse(config.server.as_deref())?;
+        let formatter = Formatter3164 {
+            facility: config.facility.0,
+            // the local syslog knows which host it's on
+            hostname: match target {
+                Target::Local => None,
+                _ => Formatter3164::default().hostname,
+            },
+            process: "file_task".to_string(),
+            pid: std::process::id(),
+        };
+        let (tx, rx) = channel::<(SyslogSeverity, String, Receipt)>();
+        let name = target.clone();
+
+        // a remote server may be slow or unreachable, so keep it off the UI thread
+        thread::spawn(move || {
+            let mut logger = None;
+            let mut working = true;
+            for (severity, message, receipt) in rx {
+                let sent = match logger.as_mut() {
+                    Some(logger) => Ok(logger),
+                    None => target
+                        .connect(formatter.clone())
+                        .map(|connected| logger.insert(connected)),
+                }
+                .and_then(|logger| write(logger, &target, severity, &message));
+
+                match sent {
+                    Ok(()) => {
+                        working = true;
+                        receipt.sent();
+                    }
+                    Err(e) => {
+                        // connect again for the next message, but only report once per outage
+                        logger = None;
+                        if working {
+                            let _ = diagnostics.send(format!("syslog {} failed: {}", target, e));
+                        }
+                        working = false;
+                        receipt.failed(e);
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            filter,
+            severities,
+            target: name,
+            tx,
+        })
+    }
+}
+
+fn write(
+    logger: &mut Logger<LoggerBackend, Formatter3164>,
+    target: &Target,
+    severity: SyslogSeverity,
+    message: &str,
+) -> ::syslog::Result<()> {
+    // messages on a stream are separated by newlines
+    let message = match target {
+        Target::Tcp(_) => format!("{}\n", message),
+        _ => message.to_string(),
+    };
+    logger
+        .formatter
+        .format(&mut logger.backend, severity.into(), message)
+}
+
+impl From<SyslogSeverity> for Severity {
+    fn from(severity: SyslogSeverity) -> Self {
+        match severity {
+            SyslogSeverity::Emerg => Severity::LOG_EMERG,
+            SyslogSeverity::Alert => Severity::LOG_ALERT,
+            SyslogSeverity::Crit => Severity::LOG_CRIT,
+            SyslogSeverity::Err => Severity::LOG_ERR,
+            SyslogSeverity::Warning => Severity::LOG_WARNING,
+            SyslogSeverity::Notice => Severity::LOG_NOTICE,
+            SyslogSeverity::Info => Severity::LOG_INFO,
+            SyslogSeverity::Debug => Severity::LOG_DEBUG,
+        }
+    }
+}
+
+impl Sink for Syslog {
+    fn send(&mut self, event: &Event, receipt: Receipt) {
+        if !self.filter.matches(event) {
+            return;
+        }
+        let severity = self.severities[&event.kind()];
+        let message = match event.path() {
+            Some(path) => format!("{} ({})", event.summary(), path.display()),
+            None => event.summary(),
+        };
+        let _ = self.tx.send((severity, message, receipt));
+    }
+
+    fn name(&self) -> String {
+        format!("syslog {}", self.target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_targets() {
+        assert_eq!(Target::parse(None), Ok(Target::Local));
+        assert_eq!(
+            Target::parse(Some("udp://logs:514")),
+            Ok(Target::Udp("logs:514".to_string()))
+        );
+        assert_eq!(
+            Target::parse(Some("tcp://[::1]:601")),
+            Ok(Target::Tcp("[::1]:601".to_string()))
+        );
+        assert!(Target::parse(Some("logs:514")).is_err());
+    }
+}
@@ -0,0 +1,78 @@
+use std::env;
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::process;
+use std::time::{Duration, Instant};
+
+/// Talks to systemd's notification socket when running as a `Type=notify` service.
+pub struct Notifier {
+    socket: UnixDatagram,
+    addr: SocketAddr,
+    watchdog: Option<Duration>,
+    last_ping: Option<Instant>,
+    status: String,
+}
+
+impl Notifier {
+    /// A notifier if systemd asked for notifications, i.e. set `NOTIFY_SOCKET`.
+    pub fn from_env() -> Option<Self> {
+        let path = env::var("NOTIFY_SOCKET").ok()?;
+        let addr = match path.strip_prefix('@') {
+            Some(name) => SocketAddr::from_abstract_name(name).ok()?,
+            None => SocketAddr::from_pathname(&path).ok()?,
+        };
+        let socket = UnixDatagram::unbound().ok()?;
+
+        // the watchdog settings are only meant for us if the pid matches, when it's given
+        let for_us = env::var("WATCHDOG_PID")
+            .ok()
+            .and_then(|pid| pid.parse::<u32>().ok())
+            .is_none_or(|pid| pid == process::id());
+        let watchdog = env::var("WATCHDOG_USEC")
+            .ok()
+            .and_then(|usec| usec.parse().ok())
+            .filter(|_| for_us)
+            .map(Duration::from_micros);
+
+        Some(Self {
+            socket,
+            addr,
+            watchdog,
+            last_ping: None,
+            status: String::new(),
+        })
+    }
+
+    /// How often to call `keep_alive`, if the watchdog is enabled.
+    pub fn keep_alive_interval(&self) -> Option<Duration> {
+        // pinging at half the timeout leaves room for a slow tick
+        self.watchdog.map(|w| w / 2)
+    }
+
+    pub fn ready(&self) {
+        self.send("READY=1");
+    }
+
+    pub fn keep_alive(&mut self) {
+        let Some(interval) = self.keep_alive_interval() else {
+            return;
+        };
+        if self.last_ping.is_none_or(|last| last.elapsed() >= interval) {
+            self.send("WATCHDOG=1");
+            self.last_ping = Some(Instant::now());
+        }
+    }
+
+    /// Update the status shown by `systemctl status`, if it changed.
+    pub fn status(&mut self, status: String) {
+        if status != self.status {
+            self.send(&format!("STATUS={}", status));
+            self.status = status;
+        }
+    }
+
+    fn send(&self, message: &str) {
+        // nothing to be done if systemd isn't listening
+        let _ = self.socket.send_to_addr(message.as_bytes(), &self.addr);
+    }
+}
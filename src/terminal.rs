@@ -1,31 +1,31 @@
-use crossterm::event::{Event, KeyCode};
 use crossterm::terminal::{disable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
 
-use crossterm::{event, execute};
+use crossterm::{cursor, execute};
 
 use std::io;
+use std::panic;
+use std::sync::Once;
 
 use crossterm::terminal::enable_raw_mode;
 
 use std::io::Stdout;
 
-use tui::backend::CrosstermBackend;
+use tui::backend::{Backend, CrosstermBackend};
 
 use tui::Terminal;
 
-use crate::INPUT_POLL;
-
 pub struct TerminalState {
     pub terminal: Terminal<CrosstermBackend<Stdout>>,
 }
 
 impl TerminalState {
-    pub fn init() -> Result<TerminalState, Box<dyn std::error::Error>> {
-        enable_raw_mode()?;
+    pub fn init() -> file_task::Result<TerminalState> {
+        restore_on_panic();
         let mut stdout = io::stdout();
-        execute!(stdout, EnterAlternateScreen)?;
-        let backend = CrosstermBackend::new(stdout);
-        let terminal = Terminal::new(backend)?;
+        let terminal = enable_raw_mode()
+            .and_then(|()| execute!(stdout, EnterAlternateScreen))
+            .and_then(|()| Terminal::new(CrosstermBackend::new(stdout)))
+            .map_err(file_task::Error::Terminal)?;
 
         Ok(TerminalState { terminal })
     }
@@ -33,19 +33,40 @@ impl TerminalState {
 
 impl Drop for TerminalState {
     fn drop(&mut self) {
-        disable_raw_mode().expect("disable raw mode");
-        execute!(self.terminal.backend_mut(), LeaveAlternateScreen).expect("cleanup");
+        // this can run while panicking, so failing here would abort without a message
+        restore();
     }
 }
 
-pub fn should_quit() -> Result<bool, Box<dyn std::error::Error>> {
-    if event::poll(INPUT_POLL)? {
-        if let Event::Key(key) = event::read()? {
-            if let KeyCode::Char('q') = key.code {
-                return Ok(true);
-            }
-        }
+/// Put the terminal back the way it was before `init`. Doing it twice is harmless.
+fn restore() {
+    let _ = disable_raw_mode();
+    let _ = execute!(io::stdout(), LeaveAlternateScreen, cursor::Show);
+}
+
+/// Stop as if Ctrl-Z had been pressed in a normal program, handing the terminal back to the
+/// shell, then take it over again and redraw everything once resumed.
+pub fn suspend<B: Backend>(terminal: &mut Terminal<B>) -> file_task::Result<()> {
+    restore();
+    // SAFETY: raising a signal has no memory safety requirements
+    unsafe {
+        libc::raise(libc::SIGTSTP);
     }
+    enable_raw_mode()
+        .and_then(|()| execute!(io::stdout(), EnterAlternateScreen))
+        .and_then(|()| terminal.clear())
+        .map_err(file_task::Error::Terminal)
+}
 
-    Ok(false)
+/// Restore the terminal before a panic's message is printed, so it ends up readable on the
+/// normal screen rather than lost on the alternate one.
+fn restore_on_panic() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let report = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            restore();
+            report(info);
+        }));
+    });
 }
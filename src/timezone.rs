@@ -0,0 +1,63 @@
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{DateTime, Local, NaiveDateTime, Utc};
+use chrono_tz::Tz;
+use serde::Deserialize;
+
+/// The zone displayed times are shown in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Timezone {
+    #[default]
+    Local,
+    Named(Tz),
+}
+
+impl Timezone {
+    /// The current wall clock time in this zone.
+    pub fn now(&self) -> NaiveDateTime {
+        match self {
+            Self::Local => Local::now().naive_local(),
+            Self::Named(tz) => Utc::now().with_timezone(tz).naive_local(),
+        }
+    }
+
+    /// The wall clock time in this zone at the given moment.
+    pub fn local(&self, time: DateTime<Utc>) -> NaiveDateTime {
+        match self {
+            Self::Local => time.with_timezone(&Local).naive_local(),
+            Self::Named(tz) => time.with_timezone(tz).naive_local(),
+        }
+    }
+}
+
+impl FromStr for Timezone {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("local") {
+            return Ok(Self::Local);
+        }
+        s.parse::<Tz>()
+            .map(Self::Named)
+            .map_err(|_| format!("unknown time zone \"{}\"", s))
+    }
+}
+
+impl TryFrom<String> for Timezone {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for Timezone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Local => write!(f, "local"),
+            Self::Named(tz) => write!(f, "{}", tz.name()),
+        }
+    }
+}
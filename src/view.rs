@@ -0,0 +1,1673 @@
+use std::time::{Duration, Instant, SystemTime};
+
+use chrono::NaiveDateTime;
+use clap::ValueEnum;
+use serde::Deserialize;
+use tui::{
+    backend::Backend,
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph},
+    Frame,
+};
+
+use crate::archive::Peek;
+use crate::events::Delivery;
+use crate::filesystem::{FileGroup, FileItem, Signature};
+use crate::follow::Follow;
+use crate::health::{self, Check};
+use crate::heatmap::Heatmap;
+use crate::highlight::Highlights;
+use crate::holders::Holders;
+use crate::service::{ServiceDetails, ServiceState};
+use crate::settings::SettingsEditor;
+use crate::stats::{format_size, GroupStats, BUCKET_LABELS};
+use crate::timezone::Timezone;
+use crate::{display_name, is_shown, AppState};
+
+/// Rows past the bottom of a file list to build anyway, so they're ready to scroll into view
+const OVERSCAN: usize = 16;
+
+/// Something about each file that can be shown in a column before its name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Column {
+    Size,
+    /// When it was last modified
+    Mtime,
+    /// How long ago it was last modified
+    Age,
+}
+
+impl Column {
+    pub const ALL: &'static [Column] = &[Column::Size, Column::Mtime, Column::Age];
+}
+
+/// A group's files as they're listed: those the tag filter shows, in the group's order.
+pub fn listed<'a>(
+    state: &'a AppState,
+    group: &'a FileGroup,
+) -> impl Iterator<Item = &'a FileItem> + 'a {
+    let filter = state.tag_filter.as_deref();
+    group.sorted().filter(move |item| is_shown(item, filter))
+}
+
+/// How far a group is scrolled to show its selected file with `rows` on screen, moving it from
+/// `offset` as little as it takes.
+fn scroll(offset: usize, selected: Option<usize>, rows: usize) -> usize {
+    match selected {
+        Some(selected) if selected < offset => selected,
+        Some(selected) if rows > 0 && selected >= offset + rows => selected + 1 - rows,
+        _ => offset,
+    }
+}
+
+/// The files of a group that are on screen, or about to be with a little scrolling, going by
+/// the size of the focused group.
+pub fn on_screen<'a>(
+    state: &'a AppState,
+    group: &'a FileGroup,
+) -> impl Iterator<Item = &'a FileItem> + 'a {
+    let selection = state.selections.get(&group.root);
+    let offset = scroll(
+        selection.map_or(0, |selection| selection.offset()),
+        selection.and_then(|selection| selection.selected()),
+        state.page,
+    );
+    listed(state, group)
+        .skip(offset)
+        .take(state.page + OVERSCAN)
+}
+
+/// Put each group in the order chosen for it, which only sorts those whose order has changed.
+pub fn apply_sorts(state: &mut AppState) {
+    for group in state.file_groups.iter_mut() {
+        group.set_sort(state.sorts.get(&group.root).copied().unwrap_or(state.sort));
+    }
+}
+
+pub fn ui<B: Backend>(frame: &mut Frame<B>, state: &mut AppState) {
+    let now = state.timezone.now();
+    draw(frame, state, now);
+}
+
+/// The dashboard as it is at a given time, scrolling each group to its selected file.
+fn draw<B: Backend>(frame: &mut Frame<B>, state: &mut AppState, now: NaiveDateTime) {
+    const STATUS_BAR_HEIGHT: u16 = 1;
+    let screen_area = frame.size();
+    let file_group_space = screen_area.height.saturating_sub(STATUS_BAR_HEIGHT);
+
+    let layout_areas = Layout::default()
+        .constraints([
+            Constraint::Length(file_group_space),  // file list area
+            Constraint::Length(STATUS_BAR_HEIGHT), // status bar
+        ])
+        .split(screen_area);
+
+    match (&state.settings, &state.stats, &state.heatmap, &state.follow) {
+        (Some(settings), ..) => draw_settings(frame, settings, layout_areas[0]),
+        _ if state.show_notifications => draw_notifications(frame, state, layout_areas[0]),
+        (_, Some(stats), _, _) if state.by_extension => {
+            draw_extensions(frame, stats, layout_areas[0])
+        }
+        (_, Some(stats), _, _) => draw_stats(frame, stats, layout_areas[0]),
+        (_, None, Some(heatmaps), _) => draw_heatmaps(frame, heatmaps, layout_areas[0]),
+        (_, None, None, Some(_)) => {
+            let halves = Layout::default()
+                .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .split(layout_areas[0]);
+            draw_groups(frame, state, halves[0]);
+            if let Some(follow) = &state.follow {
+                draw_follow(frame, follow, &state.follow_highlights, halves[1]);
+            }
+        }
+        (_, None, None, None) => draw_groups(frame, state, layout_areas[0]),
+    }
+    if let Some(peek) = &state.peek {
+        draw_peek(frame, peek, layout_areas[0]);
+    }
+    if let Some(holders) = &state.holders {
+        draw_holders(frame, holders, layout_areas[0]);
+    }
+    if state.show_health {
+        draw_health(frame, &state.health, layout_areas[0]);
+    }
+    draw_note_input(frame, state, layout_areas[0]);
+    draw_tag_input(frame, state, layout_areas[0]);
+    if state.show_debug {
+        draw_debug(frame, state, layout_areas[0]);
+    }
+
+    let time = draw_time(now);
+    let service_status = draw_service_status(state);
+    let paused = draw_paused(state);
+    let health = draw_health_summary(state);
+    let filter = draw_filter(state);
+    let lags = draw_lags(state);
+    let low_space = draw_low_space(state);
+    let message = draw_message(state);
+    let content = Line::from(
+        time.into_iter()
+            .chain(service_status)
+            .chain(health)
+            .chain(paused)
+            .chain(filter)
+            .chain(lags)
+            .chain(low_space)
+            .chain(message)
+            .collect::<Vec<_>>(),
+    );
+
+    let bar = Paragraph::new(content).style(Style::default().bg(Color::Blue));
+
+    frame.render_widget(bar, layout_areas[1]);
+}
+
+/// Stack the groups in an area, splitting it evenly between them, showing only files with the
+/// filter's tag if there is one. Each group is scrolled as little as it takes to show its
+/// selected file.
+pub fn draw_groups<B: Backend>(frame: &mut Frame<B>, state: &mut AppState, area: Rect) {
+    apply_sorts(state);
+    let filter = state.tag_filter.as_deref();
+    let columns: &[Column] = if state.show_columns {
+        &state.columns
+    } else {
+        &[]
+    };
+    if state.file_groups.is_empty() {
+        return;
+    }
+    let file_group_count = state.file_groups.len() as u32;
+    let total_group_space = area.height as u32;
+    let per_group_space = total_group_space / file_group_count;
+    let extra_space = total_group_space % file_group_count;
+
+    // divide the file list area up into even parts
+    let constraints = (0..file_group_count)
+        .map(|i| {
+            Constraint::Ratio(
+                // if there's any extra space give it to the first item
+                per_group_space + (if i == 0 { extra_space } else { 0 }),
+                total_group_space,
+            )
+        })
+        .collect::<Vec<_>>();
+    let file_list_areas = Layout::default().constraints(constraints).split(area);
+
+    for (index, rect) in file_list_areas.iter().enumerate() {
+        let group = &state.file_groups[index];
+        let note_rows = group.note.iter().count();
+        let rows = (rect.height.saturating_sub(2) as usize).saturating_sub(note_rows);
+        let focused = index == state.focus % state.file_groups.len();
+        if focused {
+            state.page = rows;
+        }
+        let selection = state.selections.entry(group.root.clone()).or_default();
+        let shown = group
+            .items()
+            .iter()
+            .filter(|item| is_shown(item, filter))
+            .count();
+        if selection
+            .selected()
+            .is_some_and(|selected| selected >= shown)
+        {
+            selection.select(shown.checked_sub(1));
+        }
+        *selection.offset_mut() = scroll(selection.offset(), selection.selected(), rows);
+        let offset = selection.offset();
+        let selected = selection.selected();
+        let note = group.note.as_deref().map(|note| {
+            let style = Style::default()
+                .fg(Color::DarkGray)
+                .add_modifier(Modifier::ITALIC);
+            ListItem::new(note).style(style)
+        });
+        let list_items = note
+            .iter()
+            .cloned()
+            .chain(
+                listed(state, group)
+                    .skip(offset)
+                    // only what fits is drawn, so huge groups don't have to be built in full
+                    // every frame
+                    .take(rows + OVERSCAN)
+                    .map(|item| {
+                        let out_of_sync = state.out_of_sync.contains(&group.path(item));
+                        draw_file_item(
+                            item,
+                            &state.highlights,
+                            out_of_sync,
+                            columns,
+                            &state.timezone,
+                        )
+                    }),
+            )
+            .collect::<Vec<_>>();
+        let mut title = group
+            .title
+            .as_deref()
+            .unwrap_or_else(|| display_name(&group.root))
+            .to_string();
+        if group.hidden > 0 {
+            title = format!("{} ({} more not shown)", title, group.hidden);
+        }
+        let oldest = group
+            .items()
+            .iter()
+            .filter(|item| item.removed.is_none())
+            .filter_map(|item| item.modified)
+            .min();
+        if let Some(oldest) = oldest.filter(|_| columns.contains(&Column::Age)) {
+            title = format!("{} (oldest {})", title, format_age(age(oldest)));
+        }
+        if let Some(order) = group.sort().describe() {
+            title = format!("{} ({})", title, order);
+        }
+        let unmatched = state
+            .out_of_sync
+            .iter()
+            .filter(|path| path.starts_with(&group.root) && group.get(path).is_some())
+            .count();
+        if unmatched > 0 {
+            title = format!("{} ({} out of sync)", title, unmatched);
+        }
+        let unmounted = state.unmounted.get(&group.root);
+        if let Some(unit) = unmounted {
+            title = format!("{} ({} not mounted)", title, unit);
+        }
+        let mut block = Block::default().title(title).borders(Borders::ALL);
+        if state.breached.contains(&group.root) || unmounted.is_some() {
+            block = block.border_style(Style::default().fg(Color::Red));
+        } else if focused && selected.is_some() {
+            block = block.border_style(Style::default().fg(Color::Cyan));
+        }
+        let list = List::new(list_items)
+            .block(block)
+            .style(Style::default())
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        // the list is built from the first file shown, under the note
+        let mut list_state = ListState::default()
+            .with_selected(selected.map(|selected| selected - offset + note_rows));
+        frame.render_stateful_widget(list, *rect, &mut list_state)
+    }
+}
+
+/// The latest lines of a followed file, as many as fit.
+fn draw_follow<B: Backend>(
+    frame: &mut Frame<B>,
+    follow: &Follow,
+    highlights: &Highlights,
+    area: Rect,
+) {
+    let rows = area.height.saturating_sub(2) as usize;
+    let lines = follow
+        .lines
+        .iter()
+        .skip(follow.lines.len().saturating_sub(rows))
+        .map(|line| ListItem::new(line.as_str()).style(highlights.style(line, Style::default())))
+        .collect::<Vec<_>>();
+    let block = Block::default()
+        .title(format!("Following {}", follow.path.display()))
+        .borders(Borders::ALL);
+    frame.render_widget(List::new(lines).block(block), area);
+}
+
+/// Each group's file sizes in a box of its own, stacked like the groups: the totals, then a
+/// histogram with a bar for each range of sizes.
+fn draw_stats<B: Backend>(frame: &mut Frame<B>, stats: &[GroupStats], area: Rect) {
+    if stats.is_empty() {
+        return;
+    }
+    let constraints = vec![Constraint::Ratio(1, stats.len() as u32); stats.len()];
+    let areas = Layout::default().constraints(constraints).split(area);
+    let label_width = BUCKET_LABELS
+        .iter()
+        .map(|l| l.len())
+        .max()
+        .unwrap_or_default();
+    for (group, rect) in stats.iter().zip(areas.iter()) {
+        let most = group
+            .buckets
+            .iter()
+            .copied()
+            .max()
+            .unwrap_or_default()
+            .max(1);
+        // what's left of a row after the borders, the label, and the count
+        let bar_width =
+            (rect.width as usize).saturating_sub(2 + label_width + 1 + 1 + most.to_string().len());
+        let mut lines = vec![
+            ListItem::new(format!(
+                "{} files, {} in all",
+                group.count,
+                format_size(group.total)
+            )),
+            ListItem::new(format!(
+                "min {}, median {}, max {}",
+                format_size(group.min),
+                format_size(group.median),
+                format_size(group.max)
+            )),
+        ];
+        lines.extend(
+            BUCKET_LABELS
+                .iter()
+                .zip(group.buckets)
+                .map(|(label, count)| {
+                    let bar = "█".repeat(count * bar_width / most);
+                    ListItem::new(Line::from(vec![
+                        Span::raw(format!("{:<1$} ", label, label_width)),
+                        Span::styled(bar, Style::default().fg(Color::Cyan)),
+                        Span::raw(format!(" {}", count)),
+                    ]))
+                }),
+        );
+        let block = Block::default()
+            .title(format!("{} sizes", group.title))
+            .borders(Borders::ALL);
+        frame.render_widget(List::new(lines).block(block), *rect);
+    }
+}
+
+/// A table for each group of how many files have each extension and how much they take up.
+fn draw_extensions<B: Backend>(frame: &mut Frame<B>, stats: &[GroupStats], area: Rect) {
+    if stats.is_empty() {
+        return;
+    }
+    let constraints = vec![Constraint::Ratio(1, stats.len() as u32); stats.len()];
+    let areas = Layout::default().constraints(constraints).split(area);
+    for (group, rect) in stats.iter().zip(areas.iter()) {
+        let name_width = group
+            .extensions
+            .iter()
+            .map(|e| e.name.chars().count())
+            .max()
+            .unwrap_or_default();
+        let mut lines = vec![ListItem::new(format!(
+            "{} files, {} in all",
+            group.count,
+            format_size(group.total)
+        ))];
+        lines.extend(group.extensions.iter().map(|extension| {
+            let share = extension.count * 100 / group.count.max(1);
+            ListItem::new(Line::from(vec![
+                Span::styled(
+                    format!("{:<1$}", extension.name, name_width),
+                    Style::default().fg(Color::Cyan),
+                ),
+                Span::raw(format!(
+                    " {:>5} {:>3}% {:>10}",
+                    extension.count,
+                    share,
+                    format_size(extension.bytes)
+                )),
+            ]))
+        }));
+        let block = Block::default()
+            .title(format!("{} extensions", group.title))
+            .borders(Borders::ALL);
+        frame.render_widget(List::new(lines).block(block), *rect);
+    }
+}
+
+/// A grid for each group with a row per day and a column per hour, each cell shaded by how
+/// many files arrived then, like a contribution graph. The latest days are kept if they don't
+/// all fit.
+fn draw_heatmaps<B: Backend>(frame: &mut Frame<B>, heatmaps: &[Heatmap], area: Rect) {
+    // from nothing arriving to the busiest hour
+    const SHADES: [Color; 4] = [
+        Color::Indexed(22),
+        Color::Indexed(28),
+        Color::Indexed(34),
+        Color::Indexed(40),
+    ];
+    if heatmaps.is_empty() {
+        return;
+    }
+    let constraints = vec![Constraint::Ratio(1, heatmaps.len() as u32); heatmaps.len()];
+    let areas = Layout::default().constraints(constraints).split(area);
+    for (heatmap, rect) in heatmaps.iter().zip(areas.iter()) {
+        let most = heatmap.most().max(1);
+        let hours = (0..24)
+            .step_by(3)
+            .map(|hour| format!("{:02}    ", hour))
+            .collect::<String>();
+        let mut lines = vec![ListItem::new(format!("       {}", hours))];
+        let days = heatmap.counts.iter().enumerate().map(|(i, counts)| {
+            let day = heatmap.first_day + chrono::Duration::days(i as i64);
+            let mut line = vec![Span::raw(day.format("%a %d ").to_string())];
+            line.extend(counts.iter().map(|&count| match count {
+                0 => Span::styled("··", Style::default().fg(Color::DarkGray)),
+                _ => {
+                    let shade = ((count * SHADES.len() as u32).div_ceil(most) as usize)
+                        .clamp(1, SHADES.len());
+                    Span::styled("██", Style::default().fg(SHADES[shade - 1]))
+                }
+            }));
+            ListItem::new(Line::from(line))
+        });
+        let rows = (rect.height as usize).saturating_sub(3);
+        lines.extend(days.skip(heatmap.counts.len().saturating_sub(rows)));
+        let block = Block::default()
+            .title(format!(
+                "{} arrivals (busiest hour: {})",
+                heatmap.title,
+                heatmap.most()
+            ))
+            .borders(Borders::ALL);
+        frame.render_widget(List::new(lines).block(block), *rect);
+    }
+}
+
+/// Each setting in the config with its value, and the value being typed for the selected one.
+fn draw_settings<B: Backend>(frame: &mut Frame<B>, settings: &SettingsEditor, area: Rect) {
+    let key_width = settings
+        .values
+        .iter()
+        .map(|(setting, _)| setting.key().len())
+        .max()
+        .unwrap_or_default();
+    let mut items = settings
+        .values
+        .iter()
+        .enumerate()
+        .map(|(i, (setting, value))| {
+            let key = Span::raw(format!("{:1$}  ", setting.key(), key_width));
+            let value = match value.as_str() {
+                "" => Span::styled("(not set)", Style::default().fg(Color::DarkGray)),
+                value => Span::raw(value),
+            };
+            let style = match i == settings.selected {
+                true => Style::default().add_modifier(Modifier::REVERSED),
+                false => Style::default(),
+            };
+            ListItem::new(Line::from(vec![key, value])).style(style)
+        })
+        .collect::<Vec<_>>();
+    items.push(ListItem::new(""));
+    items.push(ListItem::new(Span::styled(
+        settings.setting().description(),
+        Style::default().fg(Color::DarkGray),
+    )));
+    match (&settings.input, &settings.error) {
+        (Some(input), error) => {
+            let mut line = vec![
+                Span::styled("> ", Style::default().fg(Color::Cyan)),
+                Span::raw(format!("{}_", input)),
+            ];
+            if let Some(error) = error {
+                line.push(Span::styled(
+                    format!("  {}", error),
+                    Style::default().fg(Color::Red),
+                ));
+            }
+            items.push(ListItem::new(Line::from(line)));
+        }
+        (None, _) => items.push(ListItem::new(Span::styled(
+            "enter to change, esc to go back",
+            Style::default().fg(Color::DarkGray),
+        ))),
+    }
+    let title = match &settings.profile {
+        Some(profile) => format!(
+            "Settings in {} (profile {})",
+            settings.path.display(),
+            profile
+        ),
+        None => format!("Settings in {}", settings.path.display()),
+    };
+    let block = Block::default().title(title).borders(Borders::ALL);
+    frame.render_widget(List::new(items).block(block), area);
+}
+
+/// The alerts raised, the newest first, each with what every sink did with it.
+fn draw_notifications<B: Backend>(frame: &mut Frame<B>, state: &AppState, area: Rect) {
+    let mut items = state
+        .notifications
+        .iter()
+        .rev()
+        .take(area.height as usize)
+        .map(|notification| {
+            let time = state.timezone.local(notification.time);
+            let mut lines = vec![Line::from(vec![
+                Span::styled(
+                    time.format("%Y-%m-%d %H:%M:%S ").to_string(),
+                    Style::default().fg(Color::DarkGray),
+                ),
+                Span::styled(
+                    format!("{} ", notification.rule),
+                    Style::default().fg(Color::Yellow),
+                ),
+                Span::raw(notification.message.as_str()),
+            ])];
+            let mut deliveries = vec![Span::raw("  ")];
+            for (sink, delivery) in &notification.deliveries {
+                let (outcome, color) = match delivery {
+                    Delivery::Pending => ("sending".to_string(), Color::Cyan),
+                    Delivery::Sent => ("sent".to_string(), Color::Green),
+                    Delivery::Failed(e) => (format!("failed: {}", e), Color::Red),
+                    Delivery::Skipped => ("skipped".to_string(), Color::DarkGray),
+                };
+                deliveries.push(Span::raw(format!("  {} ", sink)));
+                deliveries.push(Span::styled(outcome, Style::default().fg(color)));
+            }
+            if notification.deliveries.is_empty() {
+                deliveries.push(Span::styled(
+                    "no sinks configured",
+                    Style::default().fg(Color::DarkGray),
+                ));
+            }
+            lines.push(Line::from(deliveries));
+            ListItem::new(lines)
+        })
+        .collect::<Vec<_>>();
+    if items.is_empty() {
+        items.push(ListItem::new("no alerts raised yet"));
+    }
+    let block = Block::default()
+        .title(format!("Alerts ({})", state.notifications.len()))
+        .borders(Borders::ALL);
+    frame.render_widget(List::new(items).block(block), area);
+}
+
+/// The contents of an archive in a box over the middle of an area, with each file's size.
+fn draw_peek<B: Backend>(frame: &mut Frame<B>, peek: &Peek, area: Rect) {
+    let size_width = peek
+        .entries
+        .iter()
+        .map(|entry| entry.size.to_string().len())
+        .max()
+        .unwrap_or_default();
+    let entries = peek
+        .entries
+        .iter()
+        .map(|entry| {
+            ListItem::new(Line::from(vec![
+                Span::styled(
+                    format!("{:>1$} ", entry.size, size_width),
+                    Style::default().fg(Color::DarkGray),
+                ),
+                Span::raw(entry.name.as_str()),
+            ]))
+        })
+        .collect::<Vec<_>>();
+    let title = format!(
+        "{}: {} files, {} bytes",
+        display_name(&peek.path),
+        peek.entries.len(),
+        peek.size()
+    );
+    draw_popup(frame, title, entries, area);
+}
+
+/// The processes with a file open in a box over the middle of an area, with how far each has
+/// got through it.
+fn draw_holders<B: Backend>(frame: &mut Frame<B>, holders: &Holders, area: Rect) {
+    let processes = holders
+        .processes
+        .iter()
+        .map(|holder| {
+            ListItem::new(Line::from(vec![
+                Span::styled(
+                    format!("{:>7} ", holder.pid),
+                    Style::default().fg(Color::DarkGray),
+                ),
+                Span::raw(format!("{} ", holder.command)),
+                Span::styled(
+                    format!("{} at byte {}", holder.access.name(), holder.position),
+                    Style::default().fg(Color::Cyan),
+                ),
+            ]))
+        })
+        .collect();
+    let title = format!("{} is open in", display_name(&holders.path));
+    draw_popup(frame, title, processes, area);
+}
+
+/// Each check and whether it's healthy.
+fn draw_health<B: Backend>(frame: &mut Frame<B>, checks: &[Check], area: Rect) {
+    let items = checks
+        .iter()
+        .map(|check| {
+            let (mark, color) = if check.healthy {
+                ("✓", Color::Green)
+            } else {
+                ("✗", Color::Red)
+            };
+            ListItem::new(Line::from(vec![
+                Span::styled(format!("{} ", mark), Style::default().fg(color)),
+                Span::raw(format!("{} ", check.name)),
+                Span::styled(check.detail.as_str(), Style::default().fg(Color::DarkGray)),
+            ]))
+        })
+        .collect();
+    let (healthy, total) = health::tally(checks);
+    draw_popup(
+        frame,
+        format!("Checks ({}/{} healthy)", healthy, total),
+        items,
+        area,
+    );
+}
+
+/// A list in a box over the middle of an area.
+fn draw_popup<B: Backend>(frame: &mut Frame<B>, title: String, items: Vec<ListItem>, area: Rect) {
+    let width = (area.width * 4 / 5).max(area.width.min(20));
+    let height = (area.height * 4 / 5).max(area.height.min(5));
+    let rect = Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    );
+    let block = Block::default()
+        .title(title)
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Cyan));
+    frame.render_widget(Clear, rect);
+    frame.render_widget(List::new(items).block(block), rect);
+}
+
+/// The dashboard's internals in a box in the top right corner of an area.
+fn draw_debug<B: Backend>(frame: &mut Frame<B>, state: &AppState, area: Rect) {
+    let internals = &state.internals;
+    let items = state.file_groups.iter().flat_map(|group| group.items());
+    let removed = items.clone().filter(|item| item.removed.is_some()).count();
+    let rows = [
+        ("backend", internals.backend.clone()),
+        (
+            "changes",
+            format!("{:.1}/s", internals.rate(Instant::now())),
+        ),
+        ("backlog", format!("{} at the last tick", internals.backlog)),
+        ("wakes", format!("{} at once", internals.wakes)),
+        (
+            "draw",
+            format!("{:.1} ms", internals.draw.as_secs_f64() * 1000.0),
+        ),
+        ("groups", state.file_groups.len().to_string()),
+        ("files", format!("{} ({} removed)", items.count(), removed)),
+        (
+            "memory",
+            internals
+                .memory
+                .map_or_else(|| "unknown".to_string(), format_size),
+        ),
+    ];
+    let lines = rows
+        .into_iter()
+        .map(|(name, value)| {
+            Line::from(vec![
+                Span::styled(format!("{:<9}", name), Style::default().fg(Color::Magenta)),
+                Span::raw(value),
+            ])
+        })
+        .collect::<Vec<_>>();
+    let width = 36.min(area.width);
+    let height = (lines.len() as u16 + 2).min(area.height);
+    let rect = Rect::new(area.right() - width, area.y, width, height);
+    let block = Block::default()
+        .title("Debug")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Magenta));
+    frame.render_widget(Clear, rect);
+    frame.render_widget(Paragraph::new(lines).block(block), rect);
+}
+
+/// A one line box along the bottom of an area to type a group's note in.
+fn draw_note_input<B: Backend>(frame: &mut Frame<B>, state: &AppState, area: Rect) {
+    let Some(input) = &state.note_input else {
+        return;
+    };
+    let Some(group) = state.file_groups.get(input.group) else {
+        return;
+    };
+    let title = format!(
+        "Note for {} (enter: save, tab: next group, esc: cancel)",
+        crate::group_title(group)
+    );
+    draw_input(frame, title, &input.text, area);
+}
+
+/// The same box, to type a tag for the selected file in.
+fn draw_tag_input<B: Backend>(frame: &mut Frame<B>, state: &AppState, area: Rect) {
+    let Some(input) = &state.tag_input else {
+        return;
+    };
+    let name = display_name(&input.path);
+    let title = if input.on {
+        format!("Tag {} with (enter: save, esc: cancel)", name)
+    } else {
+        format!("Take away from {} (enter: save, esc: cancel)", name)
+    };
+    draw_input(frame, title, &input.text, area);
+}
+
+fn draw_input<B: Backend>(frame: &mut Frame<B>, title: String, text: &str, area: Rect) {
+    const HEIGHT: u16 = 3;
+    let height = HEIGHT.min(area.height);
+    let rect = Rect::new(area.x, area.bottom() - height, area.width, height);
+    let block = Block::default()
+        .title(title)
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Yellow));
+    frame.render_widget(Clear, rect);
+    frame.render_widget(Paragraph::new(text).block(block), rect);
+    if height == HEIGHT {
+        let column = (text.chars().count() as u16).min(rect.width.saturating_sub(3));
+        frame.set_cursor(rect.x + 1 + column, rect.y + 1);
+    }
+}
+
+pub fn draw_time<'a>(now: NaiveDateTime) -> Vec<Span<'a>> {
+    let time = vec![
+        Span::styled("[", Style::default().fg(Color::Cyan)),
+        Span::styled(now.format("%H:%M").to_string(), Style::default()),
+        Span::styled("]", Style::default().fg(Color::Cyan)),
+    ];
+    time
+}
+
+/// Each service's status, named when there's more than one.
+pub fn draw_service_status(state: &AppState) -> Vec<Span<'_>> {
+    let named = state.services.len() > 1;
+    state
+        .services
+        .iter()
+        .flat_map(|service| {
+            let (active, status_desc): (bool, &str) = match service {
+                ServiceState::Details(ServiceDetails { active, status, .. }) => (*active, status),
+                ServiceState::Unknown(_) => (false, "----"),
+            };
+            let status_style = if active {
+                Style::default().fg(Color::Green)
+            } else {
+                Style::default().bg(Color::Red)
+            };
+            let name = named.then(|| Span::raw(format!("{}: ", service.name())));
+            std::iter::once(Span::styled("[", Style::default().fg(Color::Cyan)))
+                .chain(name)
+                .chain([
+                    Span::styled(
+                        status_desc,
+                        Style::default()
+                            .add_modifier(Modifier::BOLD)
+                            .patch(status_style),
+                    ),
+                    Span::styled("]", Style::default().fg(Color::Cyan)),
+                ])
+        })
+        .collect()
+}
+
+pub fn draw_paused<'a>(state: &AppState) -> Vec<Span<'a>> {
+    if state.paused {
+        vec![
+            Span::styled("[", Style::default().fg(Color::Cyan)),
+            Span::styled("paused", Style::default().fg(Color::Yellow)),
+            Span::styled("]", Style::default().fg(Color::Cyan)),
+        ]
+    } else {
+        vec![]
+    }
+}
+
+fn draw_filter(state: &AppState) -> Vec<Span<'_>> {
+    match &state.tag_filter {
+        Some(tag) => vec![
+            Span::styled("[", Style::default().fg(Color::Cyan)),
+            Span::styled(format!("tag: {}", tag), Style::default().fg(tag_color(tag))),
+            Span::styled("]", Style::default().fg(Color::Cyan)),
+        ],
+        None => vec![],
+    }
+}
+
+/// How far behind each compared mirror is, in the status bar.
+fn draw_lags(state: &AppState) -> Vec<Span<'_>> {
+    state
+        .lags
+        .iter()
+        .flat_map(|lag| {
+            let status = if lag.pending == 0 {
+                Span::styled("in sync", Style::default().fg(Color::Green))
+            } else {
+                Span::styled(
+                    format!("{} waiting, {}", lag.pending, format_age(lag.oldest)),
+                    Style::default().fg(Color::Yellow),
+                )
+            };
+            [
+                Span::styled("[", Style::default().fg(Color::Cyan)),
+                Span::raw(format!("{}→{} ", lag.source, lag.mirror)),
+                status,
+                Span::styled("]", Style::default().fg(Color::Cyan)),
+            ]
+        })
+        .collect()
+}
+
+/// How many checks are healthy, in the status bar, when there's more than the services to check.
+fn draw_health_summary(state: &AppState) -> Vec<Span<'_>> {
+    let others = state.health.len().saturating_sub(state.services.len());
+    if others == 0 {
+        return vec![];
+    }
+    let (healthy, total) = health::tally(&state.health);
+    let (mark, color) = if healthy == total {
+        ("✓", Color::Green)
+    } else {
+        ("✗", Color::Red)
+    };
+    vec![
+        Span::styled("[", Style::default().fg(Color::Cyan)),
+        Span::raw(format!("checks {}/{} ", healthy, total)),
+        Span::styled(mark, Style::default().fg(color)),
+        Span::styled("]", Style::default().fg(Color::Cyan)),
+    ]
+}
+
+/// The filesystems running out of room, in the status bar.
+fn draw_low_space(state: &AppState) -> Vec<Span<'_>> {
+    state
+        .low_space
+        .iter()
+        .flat_map(|(group, low)| {
+            [
+                Span::styled("[", Style::default().fg(Color::Cyan)),
+                Span::styled(
+                    format!("{}: {}", group, low),
+                    Style::default().bg(Color::Red),
+                ),
+                Span::styled("]", Style::default().fg(Color::Cyan)),
+            ]
+        })
+        .collect()
+}
+
+/// A duration in its largest whole unit, like "12m", short enough for the status bar.
+fn format_age(age: Duration) -> String {
+    match age.as_secs() {
+        secs @ 0..60 => format!("{}s", secs),
+        secs @ 60..3600 => format!("{}m", secs / 60),
+        secs @ 3600..86400 => format!("{}h", secs / 3600),
+        secs => format!("{}d", secs / 86400),
+    }
+}
+
+fn draw_message(state: &AppState) -> Vec<Span<'_>> {
+    match &state.message {
+        Some(message) => vec![Span::raw(" "), Span::raw(message.as_str())],
+        None => vec![],
+    }
+}
+
+fn draw_file_item<'a>(
+    file: &'a FileItem,
+    highlights: &Highlights,
+    out_of_sync: bool,
+    columns: &[Column],
+    timezone: &Timezone,
+) -> ListItem<'a> {
+    let color = if file.removed.is_none() {
+        Color::Green
+    } else {
+        Color::LightBlue
+    };
+    let name = file.shown_name();
+    let style = highlights.style(name, Style::default().fg(color));
+    let mut line = draw_columns(file, columns, timezone);
+    line.push(Span::styled(name, style));
+    if out_of_sync {
+        line.push(Span::styled(" ≠", Style::default().fg(Color::Yellow)));
+    }
+    match file.verified {
+        Some(true) => line.push(Span::styled(" ✓", Style::default().fg(Color::Green))),
+        Some(false) => line.push(Span::styled(" ✗", Style::default().fg(Color::Red))),
+        None => {}
+    }
+    if let Some(signature) = file.signature {
+        let color = match signature {
+            Signature::Signed => Color::Green,
+            Signature::Unsigned => Color::Yellow,
+            Signature::Invalid => Color::Red,
+        };
+        line.push(Span::raw(" "));
+        line.push(Span::styled(signature.name(), Style::default().fg(color)));
+    }
+    if let Some(writer) = &file.writer {
+        let uid = writer
+            .uid
+            .map(|uid| format!(" uid {}", uid))
+            .unwrap_or_default();
+        line.push(Span::styled(
+            format!(" by {}[{}]{}", writer.command, writer.pid, uid),
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+    for tag in &file.tags {
+        line.push(Span::raw(" "));
+        line.push(Span::styled(
+            tag.as_str(),
+            Style::default().fg(Color::Black).bg(tag_color(tag)),
+        ));
+    }
+    ListItem::new(Line::from(line))
+}
+
+/// A file's columns, each padded to line up with the same column of the files around it.
+fn draw_columns<'a>(file: &FileItem, columns: &[Column], timezone: &Timezone) -> Vec<Span<'a>> {
+    let unknown = || "-".to_string();
+    columns
+        .iter()
+        .map(|column| {
+            let text = match column {
+                Column::Size => format!("{:>10}", file.size.map_or_else(unknown, format_size)),
+                Column::Mtime => format!(
+                    "{:>11}",
+                    file.modified.map_or_else(unknown, |modified| {
+                        timezone
+                            .local(modified.into())
+                            .format("%m-%d %H:%M")
+                            .to_string()
+                    })
+                ),
+                Column::Age => format!(
+                    "{:>4}",
+                    file.modified
+                        .map_or_else(unknown, |modified| format_age(age(modified)))
+                ),
+            };
+            Span::styled(text + " ", Style::default().fg(Color::DarkGray))
+        })
+        .collect()
+}
+
+/// How long ago a file was modified, or nothing for a clock that's behind it.
+fn age(modified: SystemTime) -> Duration {
+    modified.elapsed().unwrap_or_default()
+}
+
+/// A color for a tag's badge, the same for a tag wherever it's shown.
+fn tag_color(tag: &str) -> Color {
+    const COLORS: &[Color] = &[
+        Color::Magenta,
+        Color::Yellow,
+        Color::Cyan,
+        Color::LightRed,
+        Color::LightGreen,
+        Color::LightMagenta,
+    ];
+    let hash = tag.bytes().fold(0usize, |hash, b| {
+        hash.wrapping_mul(31).wrapping_add(b.into())
+    });
+    COLORS[hash % COLORS.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+    use std::env;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use chrono::{DateTime, NaiveDate};
+    use tui::backend::TestBackend;
+    use tui::buffer::Buffer;
+    use tui::Terminal;
+
+    use super::*;
+    use crate::app::NoteInput;
+    use crate::archive::Entry;
+    use crate::compare::Lag;
+    use crate::debug::Internals;
+    use crate::events::Event;
+    use crate::filesystem::{Sort, Writer};
+    use crate::holders::{Access, Holder};
+    use crate::settings::Setting;
+    use crate::stats::Extension;
+
+    /// Render the dashboard at 09:41 and compare its text with `tests/ui/<name>.txt`, rewriting
+    /// the snapshot instead when `UPDATE_SNAPSHOTS` is set. The buffer is returned so styles,
+    /// which the snapshot doesn't keep, can be checked too.
+    fn assert_snapshot(name: &str, state: &mut AppState, width: u16, height: u16) -> Buffer {
+        let now = NaiveDate::from_ymd_opt(2024, 1, 1)
+            .and_then(|d| d.and_hms_opt(9, 41, 0))
+            .unwrap();
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        terminal.draw(|f| draw(f, state, now)).unwrap();
+
+        let buffer = terminal.backend().buffer();
+        let rendered = buffer
+            .content()
+            .chunks(buffer.area.width.max(1) as usize)
+            .map(|row| {
+                row.iter()
+                    .map(|cell| cell.symbol.as_str())
+                    .collect::<String>()
+            })
+            .map(|line| line + "\n")
+            .collect::<String>();
+
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/ui")
+            .join(format!("{}.txt", name));
+        if env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, &rendered).unwrap();
+        }
+        let expected = fs::read_to_string(&path).unwrap_or_else(|e| {
+            panic!(
+                "{}: {} (set UPDATE_SNAPSHOTS=1 to create it)",
+                path.display(),
+                e
+            )
+        });
+        assert_eq!(rendered, expected, "{} differs from the snapshot", name);
+        buffer.clone()
+    }
+
+    fn group(root: &str, title: Option<&str>, names: &[&str]) -> FileGroup {
+        let items = names
+            .iter()
+            .map(|name| FileItem::new(PathBuf::from(name)))
+            .collect();
+        FileGroup::new(PathBuf::from(root), title.map(str::to_string), items)
+    }
+
+    fn removed(mut group: FileGroup, name: &str) -> FileGroup {
+        let path = group.root.join(name);
+        group.get_mut(&path).unwrap().removed = Some(Instant::now());
+        group
+    }
+
+    #[test]
+    fn multiple_groups() {
+        let mut downloads = group("/home/me/Downloads", None, &["report.pdf", "photo.jpg"]);
+        downloads.hidden = 3;
+        let mut state = AppState {
+            file_groups: vec![
+                downloads,
+                group("/srv/inbox", Some("Inbox"), &["a.txt", "b.txt", "c.txt"]),
+            ],
+            ..AppState::default()
+        };
+        assert_snapshot("multiple_groups", &mut state, 40, 12);
+    }
+
+    #[test]
+    fn removed_items() {
+        let mut state = AppState {
+            file_groups: vec![removed(
+                group("/srv/inbox", None, &["kept.txt", "gone.txt"]),
+                "gone.txt",
+            )],
+            paused: true,
+            message: Some("exported".to_string()),
+            ..AppState::default()
+        };
+        let buffer = assert_snapshot("removed_items", &mut state, 40, 6);
+        assert_eq!(buffer.get(1, 1).fg, Color::Green);
+        assert_eq!(buffer.get(1, 2).fg, Color::LightBlue);
+    }
+
+    #[test]
+    fn service_down() {
+        let mut state = AppState {
+            file_groups: vec![group("/srv/inbox", None, &["a.txt"])],
+            services: vec![ServiceState::Details(ServiceDetails::new(
+                "web".to_string(),
+                false,
+                "failed".to_string(),
+            ))],
+            ..AppState::default()
+        };
+        let buffer = assert_snapshot("service_down", &mut state, 30, 5);
+        assert_eq!(buffer.get(8, 4).bg, Color::Red);
+    }
+
+    #[test]
+    fn several_services() {
+        let mut state = AppState {
+            file_groups: vec![group("/srv/inbox", None, &["a.txt"])],
+            services: vec![
+                ServiceState::Details(ServiceDetails::new(
+                    "fetch".to_string(),
+                    true,
+                    "running".to_string(),
+                )),
+                ServiceState::Details(ServiceDetails::new(
+                    "upload".to_string(),
+                    false,
+                    "failed".to_string(),
+                )),
+            ],
+            ..AppState::default()
+        };
+        let buffer = assert_snapshot("several_services", &mut state, 50, 5);
+        assert_eq!(buffer.get(15, 4).fg, Color::Green);
+        assert_eq!(buffer.get(34, 4).bg, Color::Red);
+    }
+
+    #[test]
+    fn tagged_files() {
+        let mut inbox = group("/srv/inbox", None, &["a.txt", "b.txt", "c.txt"]);
+        for (name, tag) in [
+            ("a.txt", "checked"),
+            ("b.txt", "problem"),
+            ("b.txt", "checked"),
+        ] {
+            let path = inbox.root.join(name);
+            inbox.get_mut(&path).unwrap().tags.push(tag.to_string());
+        }
+        let mut state = AppState {
+            file_groups: vec![inbox],
+            ..AppState::default()
+        };
+        let buffer = assert_snapshot("tagged_files", &mut state, 40, 6);
+        assert_eq!(buffer.get(7, 1).bg, tag_color("checked"));
+
+        state.tag_filter = Some("problem".to_string());
+        assert_snapshot("tagged_files_filtered", &mut state, 40, 6);
+    }
+
+    #[test]
+    fn notes() {
+        let mut quiet = group("/srv/b", Some("Customer B"), &["a.txt"]);
+        quiet.note = Some("Paused until Monday".to_string());
+        let mut state = AppState {
+            file_groups: vec![group("/srv/a", Some("Customer A"), &[]), quiet],
+            ..AppState::default()
+        };
+        assert_snapshot("notes", &mut state, 40, 9);
+
+        state.note_input = Some(NoteInput {
+            group: 0,
+            text: "Back on".to_string(),
+        });
+        assert_snapshot("notes_editing", &mut state, 40, 9);
+    }
+
+    #[test]
+    fn scrolled_to_selection() {
+        let names = ["a", "b", "c", "d", "e", "f", "g", "h"];
+        let mut inbox = group("/srv/inbox", None, &names);
+        inbox.note = Some("Nightly drop".to_string());
+        let mut state = AppState {
+            file_groups: vec![inbox, group("/srv/outbox", None, &["x"])],
+            selections: HashMap::from([(
+                PathBuf::from("/srv/inbox"),
+                ListState::default().with_selected(Some(5)),
+            )]),
+            ..AppState::default()
+        };
+        let buffer = assert_snapshot("scrolled_to_selection", &mut state, 30, 13);
+        assert!(buffer.get(1, 4).modifier.contains(Modifier::REVERSED));
+        assert_eq!(buffer.get(0, 0).fg, Color::Cyan);
+        assert_eq!(state.page, 3);
+
+        // scrolling back up only as far as the selection
+        state
+            .selections
+            .get_mut(Path::new("/srv/inbox"))
+            .unwrap()
+            .select(Some(2));
+        assert_snapshot("scrolled_back", &mut state, 30, 13);
+    }
+
+    #[test]
+    fn file_columns() {
+        let mut inbox = group("/srv/inbox", None, &["a.csv", "b.csv", "c.csv"]);
+        let now = SystemTime::now();
+        for (name, size, age) in [
+            ("a.csv", 512, 90),
+            ("b.csv", 3 * 1024 * 1024, 2 * 3600 + 60),
+        ] {
+            let item = inbox
+                .get_mut(Path::new("/srv/inbox").join(name).as_path())
+                .unwrap();
+            item.set_metadata((Some(size), Some(now - Duration::from_secs(age))));
+        }
+        let mut state = AppState {
+            file_groups: vec![inbox],
+            columns: vec![Column::Size, Column::Age],
+            show_columns: true,
+            timezone: "UTC".parse().unwrap(),
+            ..AppState::default()
+        };
+        let buffer = assert_snapshot("file_columns", &mut state, 40, 6);
+        assert_eq!(buffer.get(1, 1).fg, Color::DarkGray);
+
+        // hidden again, the names are back at the edge
+        state.show_columns = false;
+        assert_snapshot("file_columns_hidden", &mut state, 40, 6);
+    }
+
+    #[test]
+    fn mtime_column() {
+        let mut inbox = group("/srv/inbox", None, &["a.csv"]);
+        let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        inbox
+            .get_mut(Path::new("/srv/inbox/a.csv"))
+            .unwrap()
+            .set_metadata((None, Some(modified)));
+        let mut state = AppState {
+            file_groups: vec![inbox],
+            columns: vec![Column::Mtime],
+            show_columns: true,
+            timezone: "UTC".parse().unwrap(),
+            ..AppState::default()
+        };
+        assert_snapshot("mtime_column", &mut state, 30, 4);
+    }
+
+    #[test]
+    fn sorted_files() {
+        let mut inbox = group("/srv/inbox", None, &["b.csv", "c.csv", "a.csv", "d.csv"]);
+        for (name, size, age) in [("a.csv", 30, 300), ("b.csv", 10, 60), ("c.csv", 20, 120)] {
+            let item = inbox
+                .get_mut(Path::new("/srv/inbox").join(name).as_path())
+                .unwrap();
+            item.set_metadata((
+                Some(size),
+                Some(SystemTime::now() - Duration::from_secs(age)),
+            ));
+        }
+        let outbox = group("/srv/outbox", None, &["z.csv", "y.csv"]);
+        let mut state = AppState {
+            file_groups: vec![inbox, outbox],
+            sort: Sort::Name,
+            sorts: HashMap::from([(PathBuf::from("/srv/inbox"), Sort::Size)]),
+            ..AppState::default()
+        };
+        assert_snapshot("sorted_files", &mut state, 30, 13);
+
+        // newest first, with d.csv's time unknown
+        state.sorts.insert(PathBuf::from("/srv/inbox"), Sort::Mtime);
+        assert_snapshot("sorted_files_newest", &mut state, 30, 13);
+    }
+
+    #[test]
+    fn highlighted_names() {
+        let mut state = AppState {
+            file_groups: vec![removed(
+                group("/srv/inbox", None, &["ok.txt", "FAIL.txt", "FAIL.log"]),
+                "FAIL.log",
+            )],
+            highlights: Highlights::new([("FAIL", "bold red")]).unwrap(),
+            ..AppState::default()
+        };
+        let buffer = assert_snapshot("highlighted_names", &mut state, 30, 6);
+        assert_eq!(buffer.get(1, 1).fg, Color::Green);
+        for row in [2, 3] {
+            assert_eq!(buffer.get(1, row).fg, Color::Red);
+            assert!(buffer.get(1, row).modifier.contains(Modifier::BOLD));
+        }
+    }
+
+    #[test]
+    fn followed_file() {
+        let log = tempfile::NamedTempFile::new().unwrap();
+        fs::write(log.path(), "starting\nERROR: disk full\nretrying\nok\n").unwrap();
+        let mut follow = Follow::new(log.path().to_path_buf()).unwrap();
+        // shown under a name that's the same on every run
+        follow.path = PathBuf::from("/var/log/app.log");
+        let mut state = AppState {
+            file_groups: vec![group("/var/log", None, &["app.log"])],
+            follow: Some(follow),
+            follow_highlights: Highlights::new([("ERROR", "red")]).unwrap(),
+            ..AppState::default()
+        };
+        let buffer = assert_snapshot("followed_file", &mut state, 30, 11);
+        assert_eq!(buffer.get(1, 6).fg, Color::Red);
+        assert_eq!(buffer.get(1, 7).fg, Color::Reset);
+    }
+
+    #[test]
+    fn verification_badges() {
+        let item = |name: &str, verified, signature| FileItem {
+            verified,
+            signature,
+            ..FileItem::new(PathBuf::from(name))
+        };
+        let inbox = FileGroup::new(
+            PathBuf::from("/srv/inbox"),
+            None,
+            vec![
+                item("good.iso", Some(true), Some(Signature::Signed)),
+                item("bad.iso", Some(false), None),
+                item("new.iso", None, Some(Signature::Unsigned)),
+            ],
+        );
+        let mut state = AppState {
+            file_groups: vec![inbox],
+            ..AppState::default()
+        };
+        let buffer = assert_snapshot("verification_badges", &mut state, 30, 6);
+        assert_eq!(buffer.get(10, 1).fg, Color::Green);
+        assert_eq!(buffer.get(9, 2).fg, Color::Red);
+        assert_eq!(buffer.get(12, 1).fg, Color::Green);
+        assert_eq!(buffer.get(9, 3).fg, Color::Yellow);
+    }
+
+    #[test]
+    fn file_writers() {
+        let written = FileItem {
+            writer: Some(Writer {
+                pid: 1440,
+                uid: Some(1001),
+                command: "rsync".to_string(),
+            }),
+            ..FileItem::new(PathBuf::from("feed.csv"))
+        };
+        let inbox = FileGroup::new(
+            PathBuf::from("/srv/inbox"),
+            None,
+            vec![written, FileItem::new(PathBuf::from("old.csv"))],
+        );
+        let mut state = AppState {
+            file_groups: vec![inbox],
+            ..AppState::default()
+        };
+        let buffer = assert_snapshot("file_writers", &mut state, 36, 5);
+        assert_eq!(buffer.get(9, 1).fg, Color::DarkGray);
+    }
+
+    #[test]
+    fn archive_contents() {
+        let entry = |name: &str, size| Entry {
+            name: name.to_string(),
+            size,
+        };
+        let mut state = AppState {
+            file_groups: vec![group("/srv/inbox", None, &["delivery.zip"])],
+            peek: Some(Peek {
+                path: PathBuf::from("/srv/inbox/delivery.zip"),
+                entries: vec![entry("report.csv", 1200), entry("images/logo.png", 34)],
+            }),
+            ..AppState::default()
+        };
+        assert_snapshot("archive_contents", &mut state, 40, 8);
+    }
+
+    #[test]
+    fn open_file_holders() {
+        let mut state = AppState {
+            file_groups: vec![group("/srv/inbox", None, &["feed.csv"])],
+            holders: Some(Holders {
+                path: PathBuf::from("/srv/inbox/feed.csv"),
+                processes: vec![
+                    Holder {
+                        pid: 812,
+                        command: "loader".to_string(),
+                        access: Access::Read,
+                        position: 4096,
+                    },
+                    Holder {
+                        pid: 1440,
+                        command: "rsync".to_string(),
+                        access: Access::Write,
+                        position: 81920,
+                    },
+                ],
+            }),
+            ..AppState::default()
+        };
+        assert_snapshot("open_file_holders", &mut state, 50, 8);
+    }
+
+    #[test]
+    fn settings_editor() {
+        let mut values = Setting::ALL
+            .iter()
+            .map(|setting| (*setting, String::new()))
+            .collect::<Vec<_>>();
+        values[0].1 = "/srv/inbox, /srv/outbox".to_string();
+        values[7].1 = "500".to_string();
+        let mut state = AppState {
+            settings: Some(SettingsEditor {
+                path: PathBuf::from("/etc/file_task.toml"),
+                profile: None,
+                values,
+                selected: 7,
+                input: Some("50x".to_string()),
+                error: Some("50x isn't a number of files".to_string()),
+            }),
+            ..AppState::default()
+        };
+        let buffer = assert_snapshot("settings_editor", &mut state, 70, 20);
+        assert!(buffer.get(1, 8).modifier.contains(Modifier::REVERSED));
+        assert_eq!(buffer.get(9, 16).fg, Color::Red);
+    }
+
+    #[test]
+    fn health_checks() {
+        let nginx = ServiceState::Details(ServiceDetails::new(
+            "nginx".to_string(),
+            true,
+            "active (running)".to_string(),
+        ));
+        let mut state = AppState {
+            file_groups: vec![group("/srv/inbox", None, &["a"])],
+            health: vec![
+                health::service(&nginx),
+                Check::new("count inbox".to_string(), true, "1 files".to_string()),
+                Check::new(
+                    "space inbox".to_string(),
+                    false,
+                    "4% space free".to_string(),
+                ),
+            ],
+            services: vec![nginx],
+            show_health: true,
+            ..AppState::default()
+        };
+        let buffer = assert_snapshot("health_checks", &mut state, 60, 10);
+        assert_eq!(buffer.get(7, 4).fg, Color::Red);
+    }
+
+    #[test]
+    fn debug_overlay() {
+        let mut internals = Internals::new("local");
+        internals.backlog = 3;
+        internals.wakes = 2;
+        internals.draw = Duration::from_micros(1250);
+        internals.memory = Some(14 * 1024 * 1024);
+        let mut inbox = group("/srv/inbox", None, &["a", "b"]);
+        inbox.get_mut(Path::new("/srv/inbox/a")).unwrap().removed = Some(Instant::now());
+        let mut state = AppState {
+            file_groups: vec![inbox],
+            show_debug: true,
+            internals,
+            ..AppState::default()
+        };
+        let buffer = assert_snapshot("debug_overlay", &mut state, 60, 12);
+        assert_eq!(buffer.get(59, 0).fg, Color::Magenta);
+    }
+
+    #[test]
+    fn alert_history() {
+        let mut state = AppState {
+            file_groups: vec![group("/srv/inbox", None, &["a"])],
+            timezone: "UTC".parse().unwrap(),
+            show_notifications: true,
+            ..AppState::default()
+        };
+        let sinks = || vec!["webhook".to_string(), "email".to_string()];
+        let at = |time| DateTime::parse_from_rfc3339(time).unwrap().into();
+        let alert = |message: &str| Event::Alert {
+            rule: "count".to_string(),
+            path: None,
+            message: message.to_string(),
+        };
+        let first = state.notifications.raise(
+            &alert("inbox has 12 files"),
+            sinks(),
+            at("2024-01-01T09:12:00Z"),
+        );
+        state
+            .notifications
+            .deliver(first.unwrap(), 0, Delivery::Sent);
+        state
+            .notifications
+            .deliver(first.unwrap(), 1, Delivery::Failed("refused".to_string()));
+        state.notifications.raise(
+            &alert("inbox has 15 files"),
+            sinks(),
+            at("2024-01-01T09:40:00Z"),
+        );
+        let buffer = assert_snapshot("alert_history", &mut state, 70, 8);
+        assert_eq!(buffer.get(13, 4).fg, Color::Green);
+        assert_eq!(buffer.get(26, 4).fg, Color::Red);
+    }
+
+    #[test]
+    fn size_stats() {
+        let mut state = AppState {
+            file_groups: vec![group("/srv/inbox", None, &["a"])],
+            stats: Some(vec![GroupStats {
+                title: "inbox".to_string(),
+                count: 7,
+                total: 5_300_000,
+                min: 200,
+                median: 4_000,
+                max: 5_000_000,
+                buckets: [1, 4, 1, 0, 1, 0, 0, 0],
+                extensions: vec![],
+            }]),
+            ..AppState::default()
+        };
+        assert_snapshot("size_stats", &mut state, 40, 13);
+    }
+
+    #[test]
+    fn extension_breakdown() {
+        let extension = |name: &str, count, bytes| Extension {
+            name: name.to_string(),
+            count,
+            bytes,
+        };
+        let mut state = AppState {
+            file_groups: vec![group("/srv/inbox", None, &["a"])],
+            stats: Some(vec![GroupStats {
+                title: "inbox".to_string(),
+                count: 7,
+                total: 5_300_000,
+                min: 200,
+                median: 4_000,
+                max: 5_000_000,
+                buckets: [1, 4, 1, 0, 1, 0, 0, 0],
+                extensions: vec![
+                    extension(".csv", 5, 290_000),
+                    extension(".xlsx", 1, 5_000_000),
+                    extension("(none)", 1, 10_000),
+                ],
+            }]),
+            by_extension: true,
+            ..AppState::default()
+        };
+        assert_snapshot("extension_breakdown", &mut state, 40, 8);
+    }
+
+    #[test]
+    fn arrival_heatmap() {
+        let mut counts = vec![[0; 24]; 4];
+        counts[0][2] = 4;
+        counts[1][3] = 1;
+        counts[2][3] = 2;
+        counts[3][4] = 3;
+        let mut state = AppState {
+            file_groups: vec![group("/srv/nightly", None, &["a"])],
+            heatmap: Some(vec![Heatmap {
+                title: "nightly".to_string(),
+                first_day: NaiveDate::from_ymd_opt(2024, 5, 11).unwrap(),
+                counts,
+            }]),
+            ..AppState::default()
+        };
+        let buffer = assert_snapshot("arrival_heatmap", &mut state, 57, 8);
+        assert_eq!(buffer.get(12, 2).fg, Color::Indexed(40));
+        assert_eq!(buffer.get(14, 3).fg, Color::Indexed(22));
+        assert_eq!(buffer.get(8, 2).fg, Color::DarkGray);
+    }
+
+    #[test]
+    fn breached_border() {
+        let mut state = AppState {
+            file_groups: vec![group("/spool", None, &["a"]), group("/in", None, &["b"])],
+            breached: HashSet::from([PathBuf::from("/spool")]),
+            ..AppState::default()
+        };
+        let buffer = assert_snapshot("breached_border", &mut state, 20, 7);
+        assert_eq!(buffer.get(0, 0).fg, Color::Red);
+        assert_eq!(buffer.get(0, 3).fg, Color::Reset);
+    }
+
+    #[test]
+    fn unmounted_group() {
+        let mut state = AppState {
+            file_groups: vec![group("/srv/data/reports", Some("Reports"), &[])],
+            unmounted: HashMap::from([(
+                PathBuf::from("/srv/data/reports"),
+                "srv-data.mount".to_string(),
+            )]),
+            ..AppState::default()
+        };
+        let buffer = assert_snapshot("unmounted_group", &mut state, 44, 5);
+        assert_eq!(buffer.get(0, 0).fg, Color::Red);
+    }
+
+    #[test]
+    fn out_of_sync_files() {
+        let mut state = AppState {
+            file_groups: vec![
+                group("/srv/outgoing", None, &["a.csv", "b.csv"]),
+                group("/mnt/mirror", None, &["a.csv"]),
+            ],
+            out_of_sync: HashSet::from([PathBuf::from("/srv/outgoing/b.csv")]),
+            ..AppState::default()
+        };
+        assert_snapshot("out_of_sync_files", &mut state, 32, 8);
+    }
+
+    #[test]
+    fn mirror_lag_status() {
+        let lag = |source: &str, pending, oldest| Lag {
+            source: source.to_string(),
+            mirror: "backup".to_string(),
+            pending,
+            oldest: Duration::from_secs(oldest),
+        };
+        let mut state = AppState {
+            lags: vec![lag("outgoing", 3, 754), lag("archive", 0, 0)],
+            ..AppState::default()
+        };
+        assert_snapshot("mirror_lag_status", &mut state, 64, 2);
+
+        let mut state = AppState {
+            low_space: vec![("ingest".to_string(), "3% inodes free".to_string())],
+            ..AppState::default()
+        };
+        assert_snapshot("low_space_status", &mut state, 40, 2);
+        assert_eq!(format_age(Duration::from_secs(59)), "59s");
+        assert_eq!(format_age(Duration::from_secs(7200)), "2h");
+        assert_eq!(format_age(Duration::from_secs(200_000)), "2d");
+    }
+
+    #[test]
+    fn tiny_terminal() {
+        let mut state = AppState {
+            file_groups: vec![
+                group("/a", None, &["one"]),
+                group("/b", None, &["two"]),
+                group("/c", None, &["three"]),
+            ],
+            ..AppState::default()
+        };
+        assert_snapshot("tiny_terminal", &mut state, 12, 3);
+        assert_snapshot("empty_terminal", &mut state, 0, 0);
+        assert_snapshot("no_groups", &mut AppState::default(), 12, 2);
+    }
+}
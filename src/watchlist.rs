@@ -0,0 +1,191 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::cli::WatchArgs;
+use crate::filesystem::WatchPath;
+
+/// A file listing paths to watch, one per line, with optional per-path options:
+///
+/// ```text
+/// # comments and blank lines are ignored
+/// /mnt/x9f3/incoming  title="Customer A inbox"
+/// /srv/drop  recursive include=*.csv exclude=*.tmp
+/// relative/to/this/file
+/// ```
+#[derive(Debug)]
+pub struct Watchlist {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl Watchlist {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            modified: None,
+        }
+    }
+
+    pub fn read(&mut self) -> Result<Vec<WatchPath>, String> {
+        self.modified = self.current_modified();
+        let source = fs::read_to_string(&self.path)
+            .map_err(|e| format!("could not read {}: {}", self.path.display(), e))?;
+        let base = self.path.parent().unwrap_or(Path::new(""));
+        parse(&source, base).map_err(|e| format!("{}:{}", self.path.display(), e))
+    }
+
+    /// Whether the file has changed since it was last read.
+    pub fn changed(&self) -> bool {
+        self.current_modified() != self.modified
+    }
+
+    fn current_modified(&self) -> Option<SystemTime> {
+        fs::metadata(&self.path).and_then(|m| m.modified()).ok()
+    }
+}
+
+/// All the paths to watch from the command line, including any watch list.
+pub fn watch_paths(args: &WatchArgs) -> Result<Vec<WatchPath>, Box<dyn std::error::Error>> {
+    let listed = match &args.watchlist {
+        Some(watchlist) => Watchlist::new(watchlist.clone()).read()?,
+        None => vec![],
+    };
+    Ok(with_listed(args, listed))
+}
+
+/// The paths given directly followed by those from the watch list, which --recursive, --include
+/// and --exclude apply to as well.
+pub fn with_listed(args: &WatchArgs, listed: Vec<WatchPath>) -> Vec<WatchPath> {
+    let mut paths = argument_paths(args);
+    paths.extend(listed.into_iter().map(|path| WatchPath {
+        recursive: path.recursive || args.recursive,
+        include: [args.include.as_slice(), &path.include].concat(),
+        exclude: [args.exclude.as_slice(), &path.exclude].concat(),
+        ..path
+    }));
+    paths
+}
+
+/// The paths to watch given directly (or from the config), without any watch list.
+fn argument_paths(args: &WatchArgs) -> Vec<WatchPath> {
+    args.paths
+        .iter()
+        .map(|path| WatchPath {
+            path: path.clone(),
+            title: args.titles.get(path).cloned(),
+            note: args.notes.get(path).cloned(),
+            recursive: args.recursive || args.recursive_paths.contains(path),
+            include: globs(&args.include, &args.path_include, path),
+            exclude: globs(&args.exclude, &args.path_exclude, path),
+        })
+        .collect()
+}
+
+/// The globs given for every path followed by those for this one.
+pub fn globs(all: &[String], each: &HashMap<PathBuf, Vec<String>>, path: &Path) -> Vec<String> {
+    let own = each.get(path).map(Vec::as_slice).unwrap_or_default();
+    [all, own].concat()
+}
+
+fn parse(source: &str, base: &Path) -> Result<Vec<WatchPath>, String> {
+    let mut paths = vec![];
+
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let mut tokens = tokenize(line)
+            .map_err(|e| format!("{}: {}", line_number, e))?
+            .into_iter()
+            .take_while(|t| !t.starts_with('#'));
+
+        let Some(path) = tokens.next() else {
+            continue;
+        };
+        let mut watch = WatchPath::from(base.join(path));
+
+        for option in tokens {
+            match option.split_once('=') {
+                Some(("title", title)) => watch.title = Some(title.to_string()),
+                Some(("note", note)) => watch.note = Some(note.to_string()),
+                Some(("include", glob)) => watch.include.push(glob.to_string()),
+                Some(("exclude", glob)) => watch.exclude.push(glob.to_string()),
+                None if option == "recursive" => watch.recursive = true,
+                _ => return Err(format!("{}: unknown option \"{}\"", line_number, option)),
+            }
+        }
+
+        paths.push(watch);
+    }
+
+    Ok(paths)
+}
+
+/// Split a line on whitespace, treating double-quoted sections as part of a single token.
+pub fn tokenize(line: &str) -> Result<Vec<String>, String> {
+    let mut tokens = vec![];
+    let mut current: Option<String> = None;
+    let mut quoted = false;
+
+    for c in line.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                current.get_or_insert_with(String::new);
+            }
+            c if c.is_whitespace() && !quoted => tokens.extend(current.take()),
+            c => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    if quoted {
+        return Err("unterminated quote".to_string());
+    }
+    tokens.extend(current);
+
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_lines() {
+        let source =
+            "# header\n\n/a title=\"Customer A inbox\" note=quiet recursive exclude=*.tmp # note\n  rel\n";
+        let paths = parse(source, Path::new("/lists")).unwrap();
+        assert_eq!(
+            paths,
+            vec![
+                WatchPath {
+                    path: PathBuf::from("/a"),
+                    title: Some("Customer A inbox".to_string()),
+                    note: Some("quiet".to_string()),
+                    recursive: true,
+                    include: vec![],
+                    exclude: vec!["*.tmp".to_string()],
+                },
+                WatchPath::from(PathBuf::from("/lists/rel")),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_quoted_path() {
+        let paths = parse("\"/with space\"", Path::new("/")).unwrap();
+        assert_eq!(paths[0].path, PathBuf::from("/with space"));
+    }
+
+    #[test]
+    fn parse_errors_have_line_numbers() {
+        assert_eq!(
+            parse("/a\n/b nope=1", Path::new("/")),
+            Err("2: unknown option \"nope=1\"".to_string())
+        );
+        assert_eq!(
+            parse("\"/a", Path::new("/")),
+            Err("1: unterminated quote".to_string())
+        );
+    }
+}
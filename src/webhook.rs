@@ -0,0 +1,179 @@
+use std::sync::mpsc::{channel, Sender};
+use std::thread;
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use ureq::Agent;
+
+use crate::config::{WebhookConfig, WebhookFormat};
+use crate::events::{Event, EventFilter, EventKind, Receipt, Sink};
+
+/// POSTs selected events as JSON to a URL.
+pub struct Webhook {
+    filter: EventFilter,
+    format: WebhookFormat,
+    url: String,
+    tx: Sender<(String, Receipt)>,
+}
+
+impl Webhook {
+    pub fn new(
+        config: &WebhookConfig,
+        diagnostics: Sender<String>,
+    ) -> Result<Self, globset::Error> {
+        let filter = EventFilter::new(&config.events)?;
+        let (tx, rx) = channel::<(String, Receipt)>();
+
+        let agent: Agent = Agent::config_builder()
+            .timeout_global(Some(Duration::from_secs(config.timeout_secs)))
+            .build()
+            .into();
+        let url = config.url.clone();
+        let retries = config.retries;
+
+        // delivery may be slow or need retrying, so keep it off the UI thread
+        thread::spawn(move || {
+            for (body, receipt) in rx {
+                let mut attempt = 0;
+                loop {
+                    match post(&agent, &url, &body) {
+                        Ok(()) => receipt.sent(),
+                        Err(e) if attempt >= retries => {
+                            let _ = diagnostics.send(format!("webhook {} failed: {}", url, e));
+                            receipt.failed(e);
+                        }
+                        Err(_) => {
+                            thread::sleep(Duration::from_secs(1 << attempt.min(6)));
+                            attempt += 1;
+                            continue;
+                        }
+                    }
+                    break;
+                }
+            }
+        });
+
+        Ok(Self {
+            filter,
+            format: config.format,
+            url: config.url.clone(),
+            tx,
+        })
+    }
+}
+
+fn post(agent: &Agent, url: &str, body: &str) -> Result<(), ureq::Error> {
+    agent
+        .post(url)
+        .content_type("application/json")
+        .send(body)
+        .map(|_| ())
+}
+
+impl Sink for Webhook {
+    fn send(&mut self, event: &Event, receipt: Receipt) {
+        if !self.filter.matches(event) {
+            return;
+        }
+        let body = match self.format {
+            WebhookFormat::Json => json!(event.payload()),
+            WebhookFormat::Slack => slack_message(event),
+            WebhookFormat::Discord => discord_message(event),
+        };
+        let _ = self.tx.send((body.to_string(), receipt));
+    }
+
+    fn name(&self) -> String {
+        format!("webhook {}", self.url)
+    }
+}
+
+/// The details worth showing alongside an event's summary, as label and value.
+fn fields(event: &Event) -> Vec<(&'static str, String)> {
+    let payload = event.payload();
+    [
+        ("Group", payload.group.map(str::to_string)),
+        ("Path", payload.path.map(|p| p.display().to_string())),
+        ("Service", payload.service.map(str::to_string)),
+        ("State", payload.status.map(str::to_string)),
+        ("Rule", payload.rule.map(str::to_string)),
+    ]
+    .into_iter()
+    .filter_map(|(label, value)| Some((label, value?)))
+    .collect()
+}
+
+fn slack_message(event: &Event) -> Value {
+    let summary = event.summary();
+    let fields = fields(event)
+        .into_iter()
+        .map(
+            |(label, value)| json!({ "type": "mrkdwn", "text": format!("*{}*\n{}", label, value) }),
+        )
+        .collect::<Vec<_>>();
+
+    let mut blocks = vec![json!({
+        "type": "section",
+        "text": { "type": "mrkdwn", "text": format!("*{}*", summary) },
+    })];
+    if !fields.is_empty() {
+        blocks.push(json!({ "type": "section", "fields": fields }));
+    }
+    // `text` is what notifications and clients without block support show
+    json!({ "text": summary, "blocks": blocks })
+}
+
+fn discord_message(event: &Event) -> Value {
+    let color = match event.kind() {
+        EventKind::Added | EventKind::ServiceUp => 0x2eb86b,
+        EventKind::Removed => 0x95a5a6,
+        EventKind::ServiceDown | EventKind::Alert => 0xe74c3c,
+        EventKind::Action => 0x3498db,
+    };
+    let fields = fields(event)
+        .into_iter()
+        .map(|(label, value)| json!({ "name": label, "value": value, "inline": true }))
+        .collect::<Vec<_>>();
+
+    json!({
+        "embeds": [{
+            "title": event.summary(),
+            "color": color,
+            "fields": fields,
+            "timestamp": event.payload().time,
+        }],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn added() -> Event {
+        Event::FileAdded {
+            group: "Inbox".to_string(),
+            path: PathBuf::from("/in/a.pdf"),
+        }
+    }
+
+    #[test]
+    fn slack_blocks() {
+        let message = slack_message(&added());
+        assert_eq!(message["text"], "Inbox: added a.pdf");
+        assert_eq!(
+            message["blocks"][1]["fields"][1]["text"],
+            "*Path*\n/in/a.pdf"
+        );
+    }
+
+    #[test]
+    fn discord_embed() {
+        let message = discord_message(&added());
+        let embed = &message["embeds"][0];
+        assert_eq!(embed["title"], "Inbox: added a.pdf");
+        assert_eq!(embed["fields"][0]["name"], "Group");
+        assert_eq!(embed["fields"][0]["value"], "Inbox");
+    }
+}
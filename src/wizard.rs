@@ -0,0 +1,414 @@
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+
+use crossterm::event::{self, Event as TermEvent, KeyCode, KeyEvent, KeyModifiers};
+use toml_edit::{Array, DocumentMut};
+use tui::{
+    backend::Backend,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, Paragraph},
+    Frame,
+};
+
+use crate::service::service_exists;
+use crate::terminal::TerminalState;
+
+/// The parts of the setup, in order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+    Directories,
+    Service,
+    Retention,
+    Confirm,
+}
+
+/// Where the setup goes after a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Continue,
+    Cancel,
+    Done,
+}
+
+/// Asks what to watch on the first run, building the config to start from.
+#[derive(Debug)]
+struct Wizard {
+    step: Step,
+    /// The directory being browsed
+    dir: PathBuf,
+    /// Its subdirectories, after the parent
+    entries: Vec<PathBuf>,
+    selected: usize,
+    /// The directories picked to watch, in the order they were picked
+    chosen: Vec<PathBuf>,
+    service: String,
+    max_items: String,
+    /// What's wrong with the step's answer
+    error: Option<String>,
+}
+
+/// Whether to run the setup: only when started in a terminal with nothing at all saying what to
+/// watch.
+pub fn wanted() -> bool {
+    std::env::args_os().len() == 1
+        && std::env::var_os("FILE_TASK_PATHS").is_none()
+        && io::stdin().is_terminal()
+        && io::stdout().is_terminal()
+}
+
+/// Walk through the first settings and write them to a new config, returning whether it was
+/// written or the setup was cancelled.
+pub fn run(path: &Path) -> Result<bool, Box<dyn std::error::Error>> {
+    let start = std::env::current_dir()?;
+    let mut wizard = Wizard::new(start);
+    let mut state = TerminalState::init()?;
+    loop {
+        state.terminal.draw(|f| draw(f, &wizard, path))?;
+        let TermEvent::Key(key) = event::read()? else {
+            continue;
+        };
+        match wizard.key(key) {
+            Outcome::Continue => {}
+            Outcome::Cancel => return Ok(false),
+            Outcome::Done => break,
+        }
+    }
+    drop(state);
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, wizard.config())
+        .map_err(|e| format!("could not write config {}: {}", path.display(), e))?;
+    println!("wrote {}", path.display());
+    Ok(true)
+}
+
+impl Wizard {
+    fn new(dir: PathBuf) -> Self {
+        let mut wizard = Self {
+            step: Step::Directories,
+            dir: PathBuf::new(),
+            entries: vec![],
+            selected: 0,
+            chosen: vec![],
+            service: String::new(),
+            max_items: String::new(),
+            error: None,
+        };
+        wizard.browse(dir);
+        wizard
+    }
+
+    /// Show a directory's subdirectories, leaving out hidden ones.
+    fn browse(&mut self, dir: PathBuf) {
+        let mut entries = fs::read_dir(&dir)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .map(|entry| entry.path())
+                    .filter(|path| path.is_dir())
+                    .filter(|path| !crate::display_name(path).starts_with('.'))
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        entries.sort();
+        if let Some(parent) = dir.parent() {
+            entries.insert(0, parent.to_path_buf());
+        }
+        self.dir = dir;
+        self.entries = entries;
+        self.selected = 0;
+    }
+
+    /// The highlighted directory, unless it's the parent.
+    fn highlighted(&self) -> Option<&PathBuf> {
+        let is_parent = self.dir.parent().is_some() && self.selected == 0;
+        self.entries.get(self.selected).filter(|_| !is_parent)
+    }
+
+    fn key(&mut self, key: KeyEvent) -> Outcome {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return Outcome::Cancel;
+        }
+        let back = match self.step {
+            Step::Directories => return self.browse_key(key.code),
+            Step::Service => Step::Directories,
+            Step::Retention => Step::Service,
+            Step::Confirm => Step::Retention,
+        };
+        if key.code == KeyCode::Esc {
+            self.step = back;
+            self.error = None;
+            return Outcome::Continue;
+        }
+        let input = match self.step {
+            Step::Service => &mut self.service,
+            Step::Retention => &mut self.max_items,
+            _ => {
+                return match key.code {
+                    KeyCode::Enter => Outcome::Done,
+                    _ => Outcome::Continue,
+                }
+            }
+        };
+        match key.code {
+            KeyCode::Char(c) => input.push(c),
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Enter => match self.check() {
+                Ok(next) => {
+                    self.step = next;
+                    self.error = None;
+                }
+                Err(e) => self.error = Some(e),
+            },
+            _ => {}
+        }
+        Outcome::Continue
+    }
+
+    fn browse_key(&mut self, code: KeyCode) -> Outcome {
+        match code {
+            KeyCode::Esc => return Outcome::Cancel,
+            KeyCode::Up | KeyCode::Char('k') => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.selected = (self.selected + 1).min(self.entries.len().saturating_sub(1))
+            }
+            KeyCode::Enter | KeyCode::Right | KeyCode::Char('l') => {
+                if let Some(dir) = self.entries.get(self.selected).cloned() {
+                    self.browse(dir);
+                }
+            }
+            KeyCode::Backspace | KeyCode::Left | KeyCode::Char('h') => {
+                if let Some(parent) = self.dir.parent().map(Path::to_path_buf) {
+                    self.browse(parent);
+                }
+            }
+            KeyCode::Char(' ') => {
+                let dir = self
+                    .highlighted()
+                    .cloned()
+                    .unwrap_or_else(|| self.dir.clone());
+                match self.chosen.iter().position(|chosen| *chosen == dir) {
+                    Some(i) => {
+                        self.chosen.remove(i);
+                    }
+                    None => self.chosen.push(dir),
+                }
+                self.error = None;
+            }
+            KeyCode::Tab => match self.chosen.is_empty() {
+                true => self.error = Some("pick at least one directory with space".to_string()),
+                false => {
+                    self.step = Step::Service;
+                    self.error = None;
+                }
+            },
+            _ => {}
+        }
+        Outcome::Continue
+    }
+
+    /// Check the answer to a step, giving the step after it.
+    fn check(&self) -> Result<Step, String> {
+        match self.step {
+            Step::Service => {
+                let service = self.service.trim();
+                if !service.is_empty() && service_exists(service) == Ok(false) {
+                    return Err(format!("service {} does not exist", service));
+                }
+                Ok(Step::Retention)
+            }
+            Step::Retention => {
+                let max_items = self.max_items.trim();
+                if !max_items.is_empty() && max_items.parse::<usize>().is_err() {
+                    return Err(format!("{} isn't a number of files", max_items));
+                }
+                Ok(Step::Confirm)
+            }
+            step => Ok(step),
+        }
+    }
+
+    /// The config the answers make.
+    fn config(&self) -> String {
+        let mut doc = DocumentMut::new();
+        let mut paths = Array::new();
+        for dir in &self.chosen {
+            paths.push(dir.to_string_lossy().into_owned());
+        }
+        doc["paths"] = toml_edit::value(paths);
+        let service = self.service.trim();
+        if !service.is_empty() {
+            doc["service"] = toml_edit::value(service);
+        }
+        if let Ok(max_items) = self.max_items.trim().parse::<i64>() {
+            doc["max_items"] = toml_edit::value(max_items);
+        }
+        doc.to_string()
+    }
+}
+
+fn draw<B: Backend>(frame: &mut Frame<B>, wizard: &Wizard, path: &Path) {
+    let areas = Layout::default()
+        .constraints([Constraint::Min(3), Constraint::Length(3)])
+        .split(frame.size());
+    let (title, help) = match wizard.step {
+        Step::Directories => (
+            format!("Directories to watch, in {}", wizard.dir.display()),
+            "space: pick or drop  enter: open  backspace: up  tab: next  esc: quit",
+        ),
+        Step::Service => (
+            "Systemd unit to monitor alongside them (optional)".to_string(),
+            "enter: next  esc: back",
+        ),
+        Step::Retention => (
+            "Most files to keep tracking in each group (optional)".to_string(),
+            "enter: next  esc: back",
+        ),
+        Step::Confirm => (
+            format!("Write {} and start watching?", path.display()),
+            "enter: write  esc: back",
+        ),
+    };
+    let block = Block::default().title(title).borders(Borders::ALL);
+    match wizard.step {
+        Step::Directories => {
+            let halves = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+                .split(areas[0]);
+            draw_browser(frame, wizard, block, halves[0]);
+            let chosen = wizard
+                .chosen
+                .iter()
+                .map(|dir| ListItem::new(dir.display().to_string()))
+                .collect::<Vec<_>>();
+            let picked = Block::default().title("Picked").borders(Borders::ALL);
+            frame.render_widget(List::new(chosen).block(picked), halves[1]);
+        }
+        Step::Service | Step::Retention => {
+            let input = match wizard.step {
+                Step::Service => &wizard.service,
+                _ => &wizard.max_items,
+            };
+            let text = Paragraph::new(format!("> {}_", input)).block(block);
+            frame.render_widget(text, areas[0]);
+        }
+        Step::Confirm => {
+            let text = Paragraph::new(wizard.config()).block(block);
+            frame.render_widget(text, areas[0]);
+        }
+    }
+    let footer = match &wizard.error {
+        Some(error) => Line::from(Span::styled(
+            error.as_str(),
+            Style::default().fg(Color::Red),
+        )),
+        None => Line::from(Span::styled(help, Style::default().fg(Color::DarkGray))),
+    };
+    let footer = Paragraph::new(footer).block(Block::default().borders(Borders::ALL));
+    frame.render_widget(footer, areas[1]);
+}
+
+/// The subdirectories of the directory being browsed, marking the picked ones.
+fn draw_browser<B: Backend>(frame: &mut Frame<B>, wizard: &Wizard, block: Block, area: Rect) {
+    let has_parent = wizard.dir.parent().is_some();
+    let entries = wizard
+        .entries
+        .iter()
+        .enumerate()
+        .map(|(i, dir)| {
+            let name = match i {
+                0 if has_parent => "..".to_string(),
+                _ => format!("{}/", crate::display_name(dir)),
+            };
+            let mark = match wizard.chosen.contains(dir) {
+                true => "[x] ",
+                false => "[ ] ",
+            };
+            let mut style = Style::default();
+            if i == wizard.selected {
+                style = style.add_modifier(Modifier::REVERSED);
+            }
+            ListItem::new(format!("{}{}", mark, name)).style(style)
+        })
+        .collect::<Vec<_>>();
+    // keep the highlighted directory in view
+    let rows = area.height.saturating_sub(2) as usize;
+    let skip = (wizard.selected + 1).saturating_sub(rows);
+    let entries = entries.into_iter().skip(skip).collect::<Vec<_>>();
+    frame.render_widget(List::new(entries).block(block), area);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(wizard: &mut Wizard, codes: &[KeyCode]) -> Outcome {
+        codes.iter().fold(Outcome::Continue, |_, code| {
+            wizard.key(KeyEvent::new(*code, KeyModifiers::NONE))
+        })
+    }
+
+    #[test]
+    fn pick_directories_and_settings() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        for sub in ["inbox", "outbox", ".cache"] {
+            fs::create_dir_all(dir.join(sub)).unwrap();
+        }
+        fs::write(dir.join("notes.txt"), "").unwrap();
+
+        let mut wizard = Wizard::new(dir.to_path_buf());
+        assert_eq!(
+            wizard.entries,
+            [
+                dir.parent().unwrap(),
+                &dir.join("inbox"),
+                &dir.join("outbox")
+            ]
+        );
+
+        press(&mut wizard, &[KeyCode::Tab]);
+        assert_eq!(wizard.step, Step::Directories);
+        assert!(wizard.error.is_some());
+
+        // pick inbox from here, then outbox from inside it
+        press(
+            &mut wizard,
+            &[KeyCode::Down, KeyCode::Char(' '), KeyCode::Down],
+        );
+        press(
+            &mut wizard,
+            &[KeyCode::Enter, KeyCode::Char(' '), KeyCode::Tab],
+        );
+        assert_eq!(wizard.chosen, [dir.join("inbox"), dir.join("outbox")]);
+        assert_eq!(wizard.step, Step::Service);
+
+        press(
+            &mut wizard,
+            &[KeyCode::Enter, KeyCode::Char('x'), KeyCode::Enter],
+        );
+        assert_eq!(wizard.step, Step::Retention);
+        assert!(wizard.error.is_some());
+        press(
+            &mut wizard,
+            &[KeyCode::Backspace, KeyCode::Char('9'), KeyCode::Enter],
+        );
+        assert_eq!(wizard.step, Step::Confirm);
+        assert_eq!(
+            wizard.config(),
+            format!(
+                "paths = [\"{}\", \"{}\"]\nmax_items = 9\n",
+                dir.join("inbox").display(),
+                dir.join("outbox").display()
+            )
+        );
+        assert_eq!(press(&mut wizard, &[KeyCode::Enter]), Outcome::Done);
+    }
+}
@@ -0,0 +1,257 @@
+//! Watching real directories, from the first scan through to the changes the platform's file
+//! events report. Renames are reported as one move by inotify, but as a separate removal and
+//! addition by FSEvents, so what's asserted holds either way unless marked otherwise.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use file_task::clock::SystemClock;
+use file_task::filesystem::{
+    get_initial_state, update_file_items, watch_groups, FileChange, FileGroup, SourceKind,
+    WatchPath, WatchSource,
+};
+use file_task::DELETED_RETENTION;
+use tempfile::TempDir;
+
+/// Long enough for the debouncer to pass on changes, even on a slow machine.
+const SETTLE_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Scratch directories for one test, removed when it's done.
+struct Scratch(TempDir);
+
+impl Scratch {
+    fn new(name: &str, dirs: &[&str]) -> Self {
+        let root = tempfile::Builder::new()
+            .prefix(&format!("file_task-watcher-{}", name))
+            .tempdir()
+            .unwrap();
+        for dir in dirs {
+            fs::create_dir_all(root.path().join(dir)).unwrap();
+        }
+        Self(root)
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.0.path().join(name)
+    }
+}
+
+/// The watched groups along with the source feeding them changes.
+struct Watch {
+    groups: Vec<FileGroup>,
+    rx: Receiver<FileChange>,
+    _source: Box<dyn WatchSource>,
+}
+
+impl Watch {
+    fn start(paths: &[PathBuf]) -> Self {
+        Self::start_paths(paths.iter().cloned().map(WatchPath::from).collect())
+    }
+
+    fn start_paths(paths: Vec<WatchPath>) -> Self {
+        let (tx, rx) = channel();
+        let mut source = SourceKind::default().create(tx, channel().0).unwrap();
+        let groups = get_initial_state(paths, &*source).unwrap();
+        watch_groups(&groups, &mut *source).unwrap();
+        Self {
+            groups,
+            rx,
+            _source: source,
+        }
+    }
+
+    /// Apply changes as they come in until the groups look as expected, panicking if they never
+    /// do.
+    fn settle(&mut self, done: impl Fn(&[FileGroup]) -> bool) {
+        let start = Instant::now();
+        loop {
+            update_file_items(&self.rx, &mut self.groups, &SystemClock, DELETED_RETENTION);
+            if done(&self.groups) {
+                return;
+            }
+            assert!(
+                start.elapsed() < SETTLE_TIMEOUT,
+                "changes never settled: {:#?}",
+                self.groups
+            );
+            thread::sleep(Duration::from_millis(100));
+        }
+    }
+}
+
+/// Names in a group that are there now, in order.
+fn present(group: &FileGroup) -> Vec<&Path> {
+    let mut names = group
+        .items()
+        .iter()
+        .filter(|item| item.removed.is_none())
+        .map(|item| &*item.name)
+        .collect::<Vec<_>>();
+    names.sort();
+    names
+}
+
+/// Names in a group that are still shown after being removed.
+fn removed(group: &FileGroup) -> Vec<&Path> {
+    let mut names = group
+        .items()
+        .iter()
+        .filter(|item| item.removed.is_some())
+        .map(|item| &*item.name)
+        .collect::<Vec<_>>();
+    names.sort();
+    names
+}
+
+fn names(names: &[&'static str]) -> Vec<&'static Path> {
+    names.iter().copied().map(Path::new).collect()
+}
+
+#[test]
+fn initial_scan() {
+    let scratch = Scratch::new("scan", &["in"]);
+    fs::write(scratch.path("in/a"), "a").unwrap();
+    fs::write(scratch.path("in/b"), "b").unwrap();
+    fs::create_dir(scratch.path("in/sub")).unwrap();
+
+    let watch = Watch::start(&[scratch.path("in")]);
+
+    assert_eq!(
+        watch.groups[0].root,
+        scratch.path("in").canonicalize().unwrap()
+    );
+    assert_eq!(present(&watch.groups[0]), names(&["a", "b", "sub"]));
+}
+
+#[test]
+fn create_and_delete() {
+    let scratch = Scratch::new("create", &["in"]);
+    fs::write(scratch.path("in/kept"), "").unwrap();
+    fs::write(scratch.path("in/doomed"), "").unwrap();
+    let mut watch = Watch::start(&[scratch.path("in")]);
+
+    fs::write(scratch.path("in/new"), "").unwrap();
+    fs::remove_file(scratch.path("in/doomed")).unwrap();
+    watch.settle(|groups| present(&groups[0]) == names(&["kept", "new"]));
+
+    // a deleted file is still shown, as removed, until it expires
+    assert_eq!(removed(&watch.groups[0]), names(&["doomed"]));
+}
+
+#[test]
+fn rename_within_group() {
+    let scratch = Scratch::new("rename", &["in"]);
+    fs::write(scratch.path("in/before"), "").unwrap();
+    let mut watch = Watch::start(&[scratch.path("in")]);
+
+    fs::rename(scratch.path("in/before"), scratch.path("in/after")).unwrap();
+    watch.settle(|groups| present(&groups[0]) == names(&["after"]));
+
+    // inotify pairs up the two halves of the rename, so the item is renamed in place
+    #[cfg(target_os = "linux")]
+    assert!(removed(&watch.groups[0]).is_empty());
+}
+
+#[test]
+fn rename_over_existing() {
+    let scratch = Scratch::new("overwrite", &["in"]);
+    fs::write(scratch.path("in/draft"), "new").unwrap();
+    fs::write(scratch.path("in/final"), "old").unwrap();
+    let mut watch = Watch::start(&[scratch.path("in")]);
+
+    fs::rename(scratch.path("in/draft"), scratch.path("in/final")).unwrap();
+    watch.settle(|groups| present(&groups[0]) == names(&["final"]));
+
+    // only ever one item per name
+    let finals = watch.groups[0]
+        .items()
+        .iter()
+        .filter(|item| &*item.name == Path::new("final"))
+        .count();
+    assert_eq!(finals, 1);
+}
+
+#[test]
+fn move_between_groups() {
+    let scratch = Scratch::new("between", &["from", "to"]);
+    fs::write(scratch.path("from/a"), "").unwrap();
+    let mut watch = Watch::start(&[scratch.path("from"), scratch.path("to")]);
+
+    fs::rename(scratch.path("from/a"), scratch.path("to/a")).unwrap();
+    watch.settle(|groups| present(&groups[0]).is_empty() && present(&groups[1]) == names(&["a"]));
+
+    // moved straight across rather than left behind as removed
+    #[cfg(target_os = "linux")]
+    assert!(watch.groups[0].items().is_empty());
+}
+
+#[test]
+fn move_out_of_and_into_tracking() {
+    let scratch = Scratch::new("outside", &["in", "elsewhere"]);
+    fs::write(scratch.path("in/leaving"), "").unwrap();
+    fs::write(scratch.path("elsewhere/arriving"), "").unwrap();
+    let mut watch = Watch::start(&[scratch.path("in")]);
+
+    fs::rename(
+        scratch.path("in/leaving"),
+        scratch.path("elsewhere/leaving"),
+    )
+    .unwrap();
+    fs::rename(
+        scratch.path("elsewhere/arriving"),
+        scratch.path("in/arriving"),
+    )
+    .unwrap();
+    watch.settle(|groups| {
+        present(&groups[0]) == names(&["arriving"]) && removed(&groups[0]) == names(&["leaving"])
+    });
+}
+
+#[test]
+fn modified_contents() {
+    let scratch = Scratch::new("modify", &["in"]);
+    fs::write(scratch.path("in/log"), "one\n").unwrap();
+    let mut watch = Watch::start(&[scratch.path("in")]);
+
+    fs::write(scratch.path("in/log"), "one\ntwo\n").unwrap();
+    let log = watch.groups[0].root.join("log");
+    let start = Instant::now();
+    loop {
+        let changes = update_file_items(
+            &watch.rx,
+            &mut watch.groups,
+            &SystemClock,
+            DELETED_RETENTION,
+        );
+        if changes.contains(&FileChange::Modified(log.clone())) {
+            break;
+        }
+        assert!(start.elapsed() < SETTLE_TIMEOUT, "never reported modified");
+        thread::sleep(Duration::from_millis(100));
+    }
+    // what's tracked stays the same
+    assert_eq!(present(&watch.groups[0]), names(&["log"]));
+}
+
+#[test]
+fn recursive_subdirectories() {
+    let scratch = Scratch::new("recursive", &["in/2024-06-01"]);
+    fs::write(scratch.path("in/top"), "").unwrap();
+    fs::write(scratch.path("in/2024-06-01/a"), "").unwrap();
+    let mut watch = Watch::start_paths(vec![WatchPath {
+        recursive: true,
+        ..WatchPath::from(scratch.path("in"))
+    }]);
+    assert_eq!(present(&watch.groups[0]), names(&["2024-06-01/a", "top"]));
+
+    // filled straight away, before the new directory can be watched
+    fs::create_dir(scratch.path("in/2024-06-02")).unwrap();
+    fs::write(scratch.path("in/2024-06-02/b"), "").unwrap();
+    watch.settle(|groups| present(&groups[0]) == names(&["2024-06-01/a", "2024-06-02/b", "top"]));
+
+    fs::remove_dir_all(scratch.path("in/2024-06-01")).unwrap();
+    watch.settle(|groups| removed(&groups[0]) == names(&["2024-06-01/a"]));
+}